@@ -0,0 +1,43 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use libipld::cbor::bytes::Bytes;
+use libipld::cbor::shared::SharedIpld;
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
+use libipld::{ipld, Ipld};
+
+fn bench_bytes(c: &mut Criterion) {
+    let block = ipld!({ "name": "blob", "data": vec![0u8; 10 * 1024 * 1024] });
+    let bytes = Bytes::from(DagCborCodec.encode(&block).unwrap());
+
+    c.bench_function("decode 10mb bytes field", |b| {
+        b.iter(|| {
+            black_box(DagCborCodec.decode::<Ipld>(&bytes).unwrap());
+        });
+    });
+    c.bench_function("decode 10mb bytes field shared", |b| {
+        b.iter(|| {
+            black_box(SharedIpld::decode(&bytes).unwrap());
+        });
+    });
+
+    let ipld: Ipld = DagCborCodec.decode(&bytes).unwrap();
+    c.bench_function("clone 10mb bytes field", |b| {
+        b.iter(|| {
+            black_box(ipld.clone());
+        });
+    });
+    let shared = SharedIpld::decode(&bytes).unwrap();
+    c.bench_function("clone 10mb bytes field shared", |b| {
+        b.iter(|| {
+            black_box(shared.clone());
+        });
+    });
+}
+
+criterion_group! {
+    name = bytes;
+    config = Criterion::default();
+    targets = bench_bytes
+}
+
+criterion_main!(bytes);
@@ -1,8 +1,62 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use libipld::cbor::borrowed::IpldRef;
+use libipld::cbor::decode::{read_ipld, read_ipld_with, skip_value, BytesVisitor, DecodeOptions};
+use libipld::cbor::encode::{write_ipld, EncodeOptions};
 use libipld::cbor::DagCborCodec;
 use libipld::cid::Cid;
 use libipld::codec::Codec;
+use libipld::error::Result;
+use libipld::multihash::{Code, MultihashDigest};
 use libipld::{ipld, Ipld};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the allocations, so that benchmarks can report them.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Returns the number of allocations `f` makes.
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+/// Replaces byte strings with their length, reusing a single buffer for their payload.
+#[derive(Default)]
+struct Scratch(Vec<u8>);
+
+impl BytesVisitor for Scratch {
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+
+    fn visit_bytes(&mut self) -> Result<Ipld> {
+        Ok(Ipld::Integer(black_box(&self.0).len() as i128))
+    }
+}
 
 fn bench_codec(c: &mut Criterion) {
     c.bench_function("roundtrip", |b| {
@@ -23,12 +77,176 @@ fn bench_codec(c: &mut Criterion) {
             }
         });
     });
+
+    let large = ipld!({
+        "bytes": vec![0u8; 1024 * 1024],
+        "list": vec![Ipld::String("hello".to_string()); 10_000],
+    });
+    let bytes = DagCborCodec.encode(&large).unwrap();
+    c.bench_function("decode large", |b| {
+        b.iter(|| {
+            let ipld: Ipld = DagCborCodec.decode(&bytes).unwrap();
+            black_box(ipld);
+        });
+    });
+    c.bench_function("skip large", |b| {
+        b.iter(|| {
+            skip_value(&mut black_box(&bytes[..])).unwrap();
+        });
+    });
+
+    let blobs = Ipld::List(vec![Ipld::Bytes(vec![0u8; 64 * 1024]); 64]);
+    let bytes = DagCborCodec.encode(&blobs).unwrap();
+    c.bench_function("decode blobs", |b| {
+        b.iter(|| {
+            let ipld: Ipld = DagCborCodec.decode(&bytes).unwrap();
+            black_box(ipld);
+        });
+    });
+    c.bench_function("decode blobs borrowed", |b| {
+        b.iter(|| {
+            let ipld = IpldRef::decode(&bytes).unwrap();
+            black_box(ipld);
+        });
+    });
+    let mut scratch = Scratch::default();
+    let opts = DecodeOptions::default();
+    c.bench_function("decode blobs scratch", |b| {
+        b.iter(|| {
+            let ipld = read_ipld_with(&mut Cursor::new(&bytes), &opts, &mut scratch).unwrap();
+            black_box(ipld);
+        });
+    });
+    println!(
+        "decode blobs: {} allocations per block, {} with a scratch buffer",
+        allocations(|| {
+            black_box(DagCborCodec.decode::<Ipld>(&bytes).unwrap());
+        }),
+        allocations(|| {
+            black_box(read_ipld_with(&mut Cursor::new(&bytes), &opts, &mut scratch).unwrap());
+        }),
+    );
+}
+
+fn bench_batch(c: &mut Criterion) {
+    let blocks: Vec<Vec<u8>> = (0..50_000)
+        .map(|i| {
+            let ipld = ipld!({ "index": i, "name": "block", "tags": ["a", "b", "c"] });
+            DagCborCodec.encode(&ipld).unwrap()
+        })
+        .collect();
+    c.bench_function("decode 50k blocks", |b| {
+        b.iter(|| {
+            let ipld: Vec<Result<Ipld>> = blocks
+                .iter()
+                .map(|block| DagCborCodec.decode(block))
+                .collect();
+            black_box(ipld);
+        });
+    });
+    // Only runs in parallel with the `parallel` feature enabled.
+    c.bench_function("decode 50k blocks batch", |b| {
+        b.iter(|| {
+            black_box(DagCborCodec.decode_batch(&blocks));
+        });
+    });
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let nodes: Vec<Ipld> = (0..100_000)
+        .map(|i| ipld!({ "index": i, "name": "node", "tags": ["a", "b"] }))
+        .collect();
+    c.bench_function("encode 100k nodes", |b| {
+        b.iter(|| {
+            for node in &nodes {
+                black_box(DagCborCodec.encode(node).unwrap());
+            }
+        });
+    });
+    // The codec adds nothing to writing with the default options directly.
+    c.bench_function("encode 100k nodes write_ipld", |b| {
+        let opts = EncodeOptions::default();
+        b.iter(|| {
+            for node in &nodes {
+                let mut buf = Vec::with_capacity(u16::MAX as usize);
+                write_ipld(&mut buf, node, &opts).unwrap();
+                black_box(buf);
+            }
+        });
+    });
+    let mut buf = Vec::new();
+    c.bench_function("encode 100k nodes into buffer", |b| {
+        b.iter(|| {
+            for node in &nodes {
+                buf.clear();
+                DagCborCodec.encode_into(node, &mut buf).unwrap();
+                black_box(&buf);
+            }
+        });
+    });
+    c.bench_function("encode and hash 100k nodes", |b| {
+        b.iter(|| {
+            for node in &nodes {
+                let bytes = DagCborCodec.encode(node).unwrap();
+                black_box((Cid::new_v1(0x71, Code::Sha2_256.digest(&bytes)), bytes));
+            }
+        });
+    });
+    // Only runs in parallel with the `parallel` feature enabled.
+    c.bench_function("encode and hash 100k nodes batch", |b| {
+        b.iter(|| {
+            black_box(DagCborCodec.encode_batch(Code::Sha2_256, &nodes));
+        });
+    });
+    println!(
+        "encode 100k nodes: {} allocations, {} into a reused buffer",
+        allocations(|| {
+            for node in &nodes {
+                black_box(DagCborCodec.encode(node).unwrap());
+            }
+        }),
+        allocations(|| {
+            for node in &nodes {
+                buf.clear();
+                DagCborCodec.encode_into(node, &mut buf).unwrap();
+                black_box(&buf);
+            }
+        }),
+    );
+}
+
+fn bench_file(c: &mut Criterion) {
+    // About 10 MB of small items.
+    let block = Ipld::List(vec![
+        ipld!({ "index": 1_000_000, "name": "block", "bytes": vec![0u8; 32] });
+        150_000
+    ]);
+    let bytes = DagCborCodec.encode(&block).unwrap();
+    let path = std::env::temp_dir().join("libipld-cbor-bench-10mb");
+    File::create(&path).unwrap().write_all(&bytes).unwrap();
+
+    let opts = DecodeOptions::default();
+    let mut file = File::open(&path).unwrap();
+    c.bench_function("decode 10mb file", |b| {
+        b.iter(|| {
+            file.seek(SeekFrom::Start(0)).unwrap();
+            black_box(read_ipld(&mut file, &opts).unwrap());
+        });
+    });
+    let mut file = BufReader::new(File::open(&path).unwrap());
+    c.bench_function("decode 10mb file buffered", |b| {
+        b.iter(|| {
+            file.seek(SeekFrom::Start(0)).unwrap();
+            black_box(read_ipld(&mut file, &opts).unwrap());
+        });
+    });
+    std::fs::remove_file(&path).unwrap();
 }
 
 criterion_group! {
     name = codec;
     config = Criterion::default();
-    targets = bench_codec
+    targets = bench_codec, bench_batch, bench_encode, bench_file
 }
 
 criterion_main!(codec);
@@ -0,0 +1,185 @@
+//! Conversions between `Ipld` and `serde_cbor::Value`.
+//!
+//! Links are tag 42 byte strings holding the binary CID prefixed by a zero byte, like in
+//! DAG-CBOR. Other tags have no `Ipld` representation, they are an error by default, or dropped
+//! in favour of the value they tag with [`UnknownTags::Ignore`]. Map keys must be strings.
+//! Null, booleans, integers, floats, byte strings, strings and arrays map directly.
+use alloc::{boxed::Box, vec::Vec};
+use core::convert::TryFrom;
+
+use serde_cbor::Value;
+
+use crate::cid::Cid;
+use crate::error::NotIpld;
+use crate::ipld::Ipld;
+
+/// The tag of links.
+const LINK_TAG: u64 = 42;
+
+/// What to do with tags other than the link tag.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownTags {
+    /// Fail with [`NotIpld`].
+    #[default]
+    Error,
+    /// Convert the tagged value as if it wasn't tagged.
+    Ignore,
+}
+
+/// Converts a `serde_cbor::Value` to `Ipld`.
+pub fn from_value(value: Value, unknown_tags: UnknownTags) -> Result<Ipld, NotIpld> {
+    Ok(match value {
+        Value::Null => Ipld::Null,
+        Value::Bool(bool) => Ipld::Bool(bool),
+        Value::Integer(int) => Ipld::Integer(int),
+        Value::Float(float) => Ipld::Float(float),
+        Value::Bytes(bytes) => Ipld::Bytes(bytes),
+        Value::Text(string) => Ipld::String(string),
+        Value::Array(array) => Ipld::List(
+            array
+                .into_iter()
+                .map(|value| from_value(value, unknown_tags))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Map(map) => Ipld::Map(
+            map.into_iter()
+                .map(|(key, value)| match key {
+                    Value::Text(key) => Ok((key, from_value(value, unknown_tags)?)),
+                    key => Err(NotIpld(key)),
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Tag(LINK_TAG, value) => match link(&value) {
+            Some(cid) => Ipld::Link(cid),
+            None => return Err(NotIpld(Value::Tag(LINK_TAG, value))),
+        },
+        Value::Tag(_, value) if unknown_tags == UnknownTags::Ignore => {
+            from_value(*value, unknown_tags)?
+        }
+        value => return Err(NotIpld(value)),
+    })
+}
+
+/// Returns the CID of the value of a link tag.
+fn link(value: &Value) -> Option<Cid> {
+    match value {
+        Value::Bytes(bytes) => match bytes.split_first() {
+            Some((0, cid)) => Cid::try_from(cid).ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Fails with [`NotIpld`] on tags other than links and on map keys that aren't strings. Use
+/// [`from_value`] to ignore such tags.
+impl TryFrom<Value> for Ipld {
+    type Error = NotIpld;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        from_value(value, UnknownTags::Error)
+    }
+}
+
+impl From<Ipld> for Value {
+    fn from(ipld: Ipld) -> Self {
+        match ipld {
+            Ipld::Null => Self::Null,
+            Ipld::Bool(bool) => Self::Bool(bool),
+            Ipld::Integer(int) => Self::Integer(int),
+            Ipld::Float(float) => Self::Float(float),
+            Ipld::String(string) => Self::Text(string),
+            Ipld::Bytes(bytes) => Self::Bytes(bytes),
+            Ipld::List(list) => Self::Array(list.into_iter().map(Self::from).collect()),
+            Ipld::Map(map) => Self::Map(
+                map.into_iter()
+                    .map(|(key, value)| (Self::Text(key), Self::from(value)))
+                    .collect(),
+            ),
+            Ipld::Link(cid) => {
+                let mut bytes = Vec::from([0]);
+                bytes.extend(cid.to_bytes());
+                Self::Tag(LINK_TAG, Box::new(Self::Bytes(bytes)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{
+        collections::BTreeMap,
+        string::{String, ToString},
+        vec,
+    };
+    use multihash::{Code, MultihashDigest};
+
+    fn map<const N: usize>(entries: [(Value, Value); N]) -> Value {
+        Value::Map(BTreeMap::from(entries))
+    }
+
+    fn text(s: &str) -> Value {
+        Value::Text(String::from(s))
+    }
+
+    #[test]
+    fn roundtrip() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(b"cbor value"));
+        let mut link = vec![0];
+        link.extend(cid.to_bytes());
+        let value = map([
+            (text("null"), Value::Null),
+            (text("bool"), Value::Bool(true)),
+            (
+                text("numbers"),
+                Value::Array(vec![Value::Integer(-1), Value::Float(1.5)]),
+            ),
+            (text("bytes"), Value::Bytes(vec![1, 2, 3])),
+            (text("link"), Value::Tag(42, Box::new(Value::Bytes(link)))),
+            (text("nested"), map([(text("list"), Value::Array(vec![]))])),
+        ]);
+        let ipld = Ipld::try_from(value.clone()).unwrap();
+        assert_eq!(ipld.get("link").unwrap(), &Ipld::Link(cid));
+        assert_eq!(
+            ipld.get("numbers").unwrap().get(1).unwrap(),
+            &Ipld::Float(1.5)
+        );
+        assert_eq!(Value::from(ipld), value);
+    }
+
+    #[test]
+    fn tags() {
+        let tagged = Value::Tag(1, Box::new(Value::Integer(1_600_000_000)));
+        let err = Ipld::try_from(Value::Array(vec![tagged.clone()]))
+            .expect_err("should have failed on an unknown tag");
+        assert_eq!(err.0, tagged);
+        assert_eq!(
+            from_value(tagged, UnknownTags::Ignore).unwrap(),
+            Ipld::Integer(1_600_000_000)
+        );
+
+        // Tag 42 is never ignored, it has to be a valid link.
+        for invalid in [
+            Value::Bytes(vec![]),
+            Value::Bytes(vec![1, 2, 3]),
+            Value::Bytes(vec![0, 1, 2, 3]),
+            text("link"),
+        ] {
+            from_value(Value::Tag(42, Box::new(invalid)), UnknownTags::Ignore)
+                .expect_err("should have failed on an invalid link");
+        }
+    }
+
+    #[test]
+    fn non_string_keys() {
+        for key in [Value::Integer(1), Value::Bytes(vec![1]), Value::Null] {
+            let err = Ipld::try_from(map([(key.clone(), Value::Null)]))
+                .expect_err("should have failed on a key that isn't a string");
+            assert_eq!(err.0, key);
+        }
+        let ipld = Ipld::try_from(map([(text("1"), Value::Null)])).unwrap();
+        assert_eq!(ipld.get("1").unwrap(), &Ipld::Null);
+        assert_eq!(ipld.get("1".to_string()).unwrap(), &Ipld::Null);
+    }
+}
@@ -0,0 +1,99 @@
+//! String forms of CIDs.
+//!
+//! CIDv1 are written in lowercase base32 and CIDv0 in base58btc, the only base they have. This is
+//! the form DAG-JSON links and paths use. Any multibase is accepted when parsing.
+use crate::cid::{Cid, Version};
+use crate::error::{Result, UnknownMultibase};
+use multibase::Base;
+
+/// Returns the string form of a CID, lowercase base32 for CIDv1 and base58btc for CIDv0.
+pub fn cid_to_string_base32(cid: &Cid) -> String {
+    match cid.version() {
+        Version::V0 => Base::Base58Btc.encode(cid.hash().to_bytes()),
+        Version::V1 => multibase::encode(Base::Base32Lower, cid.to_bytes()),
+    }
+}
+
+/// Parses a CID from a string in any multibase, or a CIDv0 in base58btc without a multibase
+/// prefix.
+///
+/// Unlike `Cid::try_from(&str)` the whole string has to be the CID. Unknown multibase prefixes
+/// are reported as [`UnknownMultibase`].
+pub fn parse_cid_any_base(s: &str) -> Result<Cid> {
+    let bytes = if Version::is_v0_str(s) {
+        Base::Base58Btc.decode(s)?
+    } else {
+        match multibase::decode(s) {
+            Ok((_, bytes)) => bytes,
+            Err(multibase::Error::UnknownBase(prefix)) => {
+                return Err(UnknownMultibase(prefix).into())
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+    Ok(Cid::try_from(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use multihash::{Code, Multihash, MultihashDigest};
+
+    /// A sha2-256 multihash, CIDv0 only support those.
+    fn digest() -> Multihash {
+        Code::Sha2_256.digest(b"cid string")
+    }
+
+    #[test]
+    fn roundtrip() {
+        let digest = digest();
+        let v1 = Cid::new_v1(0x71, digest);
+        let v0 = Cid::new_v0(digest).unwrap();
+
+        let s = cid_to_string_base32(&v1);
+        assert!(s.starts_with("bafy"));
+        assert_eq!(s, v1.to_string());
+        assert_eq!(parse_cid_any_base(&s).unwrap(), v1);
+        let s = cid_to_string_base32(&v0);
+        assert!(s.starts_with("Qm"));
+        assert_eq!(s, v0.to_string());
+        assert_eq!(parse_cid_any_base(&s).unwrap(), v0);
+
+        for base in [
+            Base::Base32Upper,
+            Base::Base36Lower,
+            Base::Base58Btc,
+            Base::Base64,
+            Base::Base64Url,
+            Base::Base16Lower,
+        ] {
+            let s = v1.to_string_of_base(base).unwrap();
+            assert_eq!(parse_cid_any_base(&s).unwrap(), v1, "{}", s);
+        }
+        // A CIDv0 with a multibase prefix.
+        let s = multibase::encode(Base::Base58Btc, v0.to_bytes());
+        assert_eq!(parse_cid_any_base(&s).unwrap(), v0);
+    }
+
+    #[test]
+    fn invalid() {
+        let v1 = Cid::new_v1(0x71, digest());
+        let s = cid_to_string_base32(&v1);
+        let err = parse_cid_any_base(&format!("!{}", &s[1..]))
+            .expect_err("should have failed on an unknown multibase")
+            .downcast::<UnknownMultibase>()
+            .unwrap();
+        assert_eq!(err.0, '!');
+
+        for s in [
+            "",
+            "b",
+            "bafy",
+            &s[..s.len() - 1],
+            &format!("/ipfs/{}", s),
+            &format!("{}/a", s),
+        ] {
+            parse_cid_any_base(s).expect_err(&format!("should have failed to parse {:?}", s));
+        }
+    }
+}
@@ -1,9 +1,9 @@
 //! `Ipld` codecs.
-use alloc::{format, string::String, vec::Vec};
-use core::{convert::TryFrom, ops::Deref};
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use core::convert::TryFrom;
 
 use crate::cid::Cid;
-use crate::error::{Result, UnsupportedCodec};
+use crate::error::{BufferTooSmall, Result, UnsupportedCodec};
 use crate::io::{Cursor, Read, Seek, Write};
 use crate::ipld::Ipld;
 
@@ -18,6 +18,33 @@ pub trait Codec:
         Ok(buf)
     }
 
+    /// Encodes an encodable type, appending it to `buf`.
+    ///
+    /// Reusing `buf` saves allocating a new buffer on every call. To write straight into a file
+    /// or socket, use [`Encode::encode`], which takes any writer.
+    fn encode_into<T: Encode<Self> + ?Sized>(&self, obj: &T, buf: &mut Vec<u8>) -> Result<()> {
+        obj.encode(*self, buf)
+    }
+
+    /// Encodes an encodable type into the start of `buf`, e.g. a fixed-size buffer without
+    /// allocating, and returns the number of bytes written.
+    ///
+    /// Returns [`BufferTooSmall`] if the encoding doesn't fit, the contents of `buf` are
+    /// unspecified then.
+    fn encode_to_slice<T: Encode<Self> + ?Sized>(&self, obj: &T, buf: &mut [u8]) -> Result<usize> {
+        let len = buf.len();
+        let mut w = &mut buf[..];
+        if let Err(err) = obj.encode(*self, &mut w) {
+            // Tells running out of space apart from values that can't be encoded at all.
+            let needed = obj.encoded_len(*self)?;
+            if needed > len {
+                return Err(anyhow::Error::msg(BufferTooSmall { needed, len }));
+            }
+            return Err(err);
+        }
+        Ok(len - w.len())
+    }
+
     /// Decodes a decodable type.
     fn decode<T: Decode<Self>>(&self, bytes: &[u8]) -> Result<T> {
         T::decode(*self, &mut Cursor::new(bytes))
@@ -33,6 +60,52 @@ pub trait Codec:
     }
 }
 
+/// A codec with a single multicodec code.
+///
+/// Codecs that pick their code at runtime, like `IpldCodec`, only implement [`Codec`].
+pub trait FixedCodec: Codec {
+    /// The multicodec code, which is also what converting the codec into a `u64` returns.
+    const CODE: u64;
+}
+
+/// The multicodec codes of the codecs defined by IPLD, whether or not their crates are used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum Multicodec {
+    /// Raw binary.
+    Raw = 0x55,
+    /// DAG-CBOR.
+    DagCbor = 0x71,
+    /// DAG-JSON.
+    DagJson = 0x0129,
+    /// DAG-PB.
+    DagPb = 0x70,
+}
+
+impl Multicodec {
+    /// Returns the code.
+    pub const fn code(self) -> u64 {
+        self as u64
+    }
+}
+
+impl TryFrom<u64> for Multicodec {
+    type Error = UnsupportedCodec;
+
+    fn try_from(code: u64) -> core::result::Result<Self, Self::Error> {
+        [Self::Raw, Self::DagCbor, Self::DagJson, Self::DagPb]
+            .into_iter()
+            .find(|codec| codec.code() == code)
+            .ok_or(UnsupportedCodec(code))
+    }
+}
+
+impl From<Multicodec> for u64 {
+    fn from(codec: Multicodec) -> Self {
+        codec.code()
+    }
+}
+
 /// Encode trait.
 ///
 /// This trait is generic over a codec, so that different codecs can be implemented for the same
@@ -43,11 +116,103 @@ pub trait Encode<C: Codec> {
     /// It takes a specific codec as parameter, so that the [`Encode`] can be generic over an enum
     /// that contains multiple codecs.
     fn encode<W: Write>(&self, c: C, w: &mut W) -> Result<()>;
+
+    /// Encodes into a `impl Write` like [`Encode::encode`], and returns the number of bytes
+    /// written.
+    ///
+    /// This is the size of the encoded item also when writing to a stream that can't be measured
+    /// afterwards, like a socket. The default implementation counts the bytes on their way to
+    /// `w`, so it's exact for every implementation of [`Encode::encode`].
+    fn encode_counted<W: Write>(&self, c: C, w: &mut W) -> Result<usize> {
+        let mut w = Counted { w, count: 0 };
+        self.encode(c, &mut w)?;
+        Ok(w.count)
+    }
+
+    /// Returns the number of bytes [`Encode::encode`] writes, e.g. to allocate a buffer of the
+    /// right size or to check a size limit up front.
+    ///
+    /// The default implementation encodes into a sink that only counts the bytes. Codecs can
+    /// compute it without encoding instead.
+    fn encoded_len(&self, c: C) -> Result<usize> {
+        let mut w = CountingWriter::new();
+        self.encode(c, &mut w)?;
+        Ok(w.count())
+    }
+}
+
+/// A writer that discards the bytes and only counts them.
+///
+/// Encoding into it measures the encoded size of types that can't compute it without encoding.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountingWriter(usize);
+
+impl CountingWriter {
+    /// Creates a new writer with a count of zero.
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn count(&self) -> usize {
+        self.0
+    }
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> crate::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> crate::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A writer that counts the bytes it passes on.
+struct Counted<'a, W> {
+    w: &'a mut W,
+    count: usize,
+}
+
+impl<W: Write> Write for Counted<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> crate::io::Result<usize> {
+        let n = self.w.write(buf)?;
+        self.count += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> crate::io::Result<()> {
+        self.w.flush()
+    }
 }
 
-impl<C: Codec, T: Encode<C>> Encode<C> for &T {
+impl<C: Codec, T: Encode<C> + ?Sized> Encode<C> for &T {
     fn encode<W: Write>(&self, c: C, w: &mut W) -> Result<()> {
-        self.deref().encode(c, w)
+        (**self).encode(c, w)
+    }
+
+    fn encoded_len(&self, c: C) -> Result<usize> {
+        (**self).encoded_len(c)
+    }
+
+    fn encode_counted<W: Write>(&self, c: C, w: &mut W) -> Result<usize> {
+        (**self).encode_counted(c, w)
+    }
+}
+
+impl<C: Codec, T: Encode<C>> Encode<C> for Box<T> {
+    fn encode<W: Write>(&self, c: C, w: &mut W) -> Result<()> {
+        (**self).encode(c, w)
+    }
+
+    fn encoded_len(&self, c: C) -> Result<usize> {
+        (**self).encoded_len(c)
+    }
+
+    fn encode_counted<W: Write>(&self, c: C, w: &mut W) -> Result<usize> {
+        (**self).encode_counted(c, w)
     }
 }
 
@@ -63,6 +228,12 @@ pub trait Decode<C: Codec>: Sized {
     fn decode<R: Read + Seek>(c: C, r: &mut R) -> Result<Self>;
 }
 
+impl<C: Codec, T: Decode<C>> Decode<C> for Box<T> {
+    fn decode<R: Read + Seek>(c: C, r: &mut R) -> Result<Self> {
+        Ok(Box::new(T::decode(c, r)?))
+    }
+}
+
 /// References trait.
 ///
 /// This trait is generic over a codec, so that different codecs can be implemented for the same
@@ -158,4 +329,26 @@ mod tests {
         let ipld: Ipld = CodecImpl.decode(&bytes).unwrap();
         assert_eq!(ipld, Ipld::Null);
     }
+
+    #[test]
+    fn test_multicodec() {
+        for codec in [
+            Multicodec::Raw,
+            Multicodec::DagCbor,
+            Multicodec::DagJson,
+            Multicodec::DagPb,
+        ] {
+            assert_eq!(Multicodec::try_from(u64::from(codec)).unwrap(), codec);
+        }
+        assert_eq!(Multicodec::DagJson.code(), 0x0129);
+        let err = Multicodec::try_from(0x300000).unwrap_err();
+        assert_eq!(err.0, 0x300000);
+    }
+
+    #[test]
+    fn test_encode_counted() {
+        let mut buf = vec![1, 2, 3];
+        assert_eq!(Ipld::Null.encode_counted(CodecImpl, &mut buf).unwrap(), 1);
+        assert_eq!(buf, [1, 2, 3, 0]);
+    }
 }
@@ -1,10 +1,9 @@
 //! Conversion to and from ipld.
 use crate::cid::Cid;
-use crate::ipld::Ipld;
+use crate::ipld::{Ipld, IpldMap};
 use alloc::{
     borrow::ToOwned,
     boxed::Box,
-    collections::BTreeMap,
     string::{String, ToString},
     vec::Vec,
 };
@@ -59,6 +58,6 @@ derive_to_ipld!(Bytes, Box<[u8]>, into_vec);
 derive_to_ipld!(Bytes, Vec<u8>, into);
 derive_to_ipld!(Bytes, &[u8], to_vec);
 derive_to_ipld!(List, Vec<Ipld>, into);
-derive_to_ipld!(Map, BTreeMap<String, Ipld>, to_owned);
+derive_to_ipld!(Map, IpldMap, to_owned);
 derive_to_ipld_generic!(Link, Cid, clone);
 derive_to_ipld_generic!(Link, &Cid, to_owned);
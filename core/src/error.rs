@@ -14,6 +14,31 @@ use thiserror::Error;
 #[cfg_attr(feature = "std", derive(Error), error("Block size {0} exceeds 1MiB."))]
 pub struct BlockTooLarge(pub usize);
 
+/// The encoding doesn't fit into the buffer it is written to.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "std",
+    derive(Error),
+    error("Encoding needs {needed} bytes, but the buffer only has {len}.")
+)]
+pub struct BufferTooSmall {
+    /// The length of the encoding.
+    pub needed: usize,
+    /// The length of the buffer.
+    pub len: usize,
+}
+
+#[cfg(not(feature = "std"))]
+impl core::fmt::Display for BufferTooSmall {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "Encoding needs {} bytes, but the buffer only has {}.",
+            self.needed, self.len
+        )
+    }
+}
+
 /// The codec is unsupported.
 #[derive(Clone, Copy, Debug)]
 #[cfg_attr(feature = "std", derive(Error), error("Unsupported codec {0:?}."))]
@@ -24,6 +49,15 @@ pub struct UnsupportedCodec(pub u64);
 #[cfg_attr(feature = "std", derive(Error), error("Unsupported multihash {0:?}."))]
 pub struct UnsupportedMultihash(pub u64);
 
+/// The multibase prefix of a CID string is unknown.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "std",
+    derive(Error),
+    error("Unknown multibase prefix {0:?}.")
+)]
+pub struct UnknownMultibase(pub char);
+
 /// Hash does not match the CID.
 #[derive(Clone, Debug)]
 #[cfg_attr(
@@ -38,6 +72,18 @@ pub struct InvalidMultihash(pub Vec<u8>);
 #[cfg_attr(feature = "std", derive(Error), error("Failed to retrieve block {0}."))]
 pub struct BlockNotFound(pub Cid);
 
+/// The value can't be represented as JSON.
+#[cfg(feature = "serde_json")]
+#[derive(Clone, Debug, Error)]
+#[error("{0:?} can't be represented as JSON.")]
+pub struct NotJson(pub Ipld);
+
+/// The cbor value can't be represented as `Ipld`.
+#[cfg(feature = "serde_cbor")]
+#[derive(Clone, Debug, Error)]
+#[error("{0:?} can't be represented as Ipld.")]
+pub struct NotIpld(pub serde_cbor::Value);
+
 /// Error during Serde operations.
 #[cfg(feature = "serde-codec")]
 #[derive(Clone, Debug)]
@@ -2,7 +2,6 @@
 use alloc::{
     borrow::ToOwned,
     boxed::Box,
-    collections::BTreeMap,
     string::{String, ToString},
     vec,
     vec::Vec,
@@ -12,6 +11,21 @@ use core::fmt;
 use crate::cid::Cid;
 use crate::error::TypeError;
 
+/// The map type of [`Ipld::Map`].
+///
+/// Keys are kept in sorted order by default. With the `indexmap` feature enabled, they are kept in
+/// the order they were inserted, which for decoded data is the order of the encoded block.
+/// Encoders write maps in canonical order in both cases.
+#[cfg(not(feature = "indexmap"))]
+pub type IpldMap = alloc::collections::BTreeMap<String, Ipld>;
+/// The map type of [`Ipld::Map`].
+///
+/// Keys are kept in sorted order by default. With the `indexmap` feature enabled, they are kept in
+/// the order they were inserted, which for decoded data is the order of the encoded block.
+/// Encoders write maps in canonical order in both cases.
+#[cfg(feature = "indexmap")]
+pub type IpldMap = indexmap::IndexMap<String, Ipld>;
+
 /// Ipld
 #[derive(Clone, PartialEq)]
 pub enum Ipld {
@@ -30,7 +44,7 @@ pub enum Ipld {
     /// Represents a list.
     List(Vec<Ipld>),
     /// Represents a map of strings.
-    Map(BTreeMap<String, Ipld>),
+    Map(IpldMap),
     /// Represents a map of integers.
     Link(Cid),
 }
@@ -111,9 +125,9 @@ impl Ipld {
                 }
             }),
             Ipld::Map(ref mut m) => match index {
-                IpldIndex::Map(ref key) => Some(m.remove(key)),
-                IpldIndex::MapRef(key) => Some(m.remove(key)),
-                IpldIndex::List(i) => Some(m.remove(&i.to_string())),
+                IpldIndex::Map(ref key) => Some(remove(m, key)),
+                IpldIndex::MapRef(key) => Some(remove(m, key)),
+                IpldIndex::List(i) => Some(remove(m, &i.to_string())),
             },
             _ => None,
         };
@@ -159,6 +173,14 @@ impl Ipld {
     }
 }
 
+/// Removes an entry, keeping the order of the remaining ones.
+fn remove(map: &mut IpldMap, key: &str) -> Option<Ipld> {
+    #[cfg(not(feature = "indexmap"))]
+    return map.remove(key);
+    #[cfg(feature = "indexmap")]
+    return map.shift_remove(key);
+}
+
 /// Ipld iterator.
 pub struct IpldIter<'a> {
     stack: Vec<Box<dyn Iterator<Item = &'a Ipld> + 'a>>,
@@ -259,7 +281,7 @@ mod tests {
         assert_eq!(ipld.clone().take(1).unwrap(), Ipld::Integer(1));
         assert_eq!(ipld.take(2).unwrap(), Ipld::Integer(2));
 
-        let mut map = BTreeMap::new();
+        let mut map = IpldMap::new();
         map.insert("a".to_string(), Ipld::Integer(0));
         map.insert("b".to_string(), Ipld::Integer(1));
         map.insert("c".to_string(), Ipld::Integer(2));
@@ -274,7 +296,7 @@ mod tests {
         assert_eq!(ipld.get(1).unwrap(), &Ipld::Integer(1));
         assert_eq!(ipld.get(2).unwrap(), &Ipld::Integer(2));
 
-        let mut map = BTreeMap::new();
+        let mut map = IpldMap::new();
         map.insert("a".to_string(), Ipld::Integer(0));
         map.insert("b".to_string(), Ipld::Integer(1));
         map.insert("c".to_string(), Ipld::Integer(2));
@@ -0,0 +1,140 @@
+//! Conversions between `Ipld` and `serde_json::Value`.
+use core::convert::TryFrom;
+
+use serde_json::{Number, Value};
+
+use crate::error::NotJson;
+use crate::ipld::Ipld;
+
+/// JSON numbers that fit an `i64` or `u64` become integers, all other numbers floats. Strings,
+/// lists and maps convert one to one. When parsing JSON with duplicate keys, serde_json keeps the
+/// last value, a `Value` never has duplicate keys.
+impl From<Value> for Ipld {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(bool) => Self::Bool(bool),
+            Value::Number(number) => {
+                if let Some(int) = number.as_i64() {
+                    Self::Integer(int.into())
+                } else if let Some(int) = number.as_u64() {
+                    Self::Integer(int.into())
+                } else {
+                    Self::Float(number.as_f64().expect("numbers are integers or floats"))
+                }
+            }
+            Value::String(string) => Self::String(string),
+            Value::Array(array) => Self::List(array.into_iter().map(Self::from).collect()),
+            Value::Object(object) => Self::Map(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (key, Self::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Fails with [`NotJson`] on bytes and links, which JSON has no type for, on integers outside of
+/// `i64::MIN..=u64::MAX` and on floats that aren't finite. Use the DAG-JSON codec to represent
+/// bytes and links.
+impl TryFrom<Ipld> for Value {
+    type Error = NotJson;
+
+    fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+        Ok(match ipld {
+            Ipld::Null => Self::Null,
+            Ipld::Bool(bool) => Self::Bool(bool),
+            Ipld::Integer(int) => {
+                if let Ok(int) = i64::try_from(int) {
+                    Self::Number(int.into())
+                } else if let Ok(int) = u64::try_from(int) {
+                    Self::Number(int.into())
+                } else {
+                    return Err(NotJson(ipld));
+                }
+            }
+            Ipld::Float(float) => match Number::from_f64(float) {
+                Some(number) => Self::Number(number),
+                None => return Err(NotJson(ipld)),
+            },
+            Ipld::String(string) => Self::String(string),
+            Ipld::List(list) => Self::Array(
+                list.into_iter()
+                    .map(Self::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Ipld::Map(map) => Self::Object(
+                map.into_iter()
+                    .map(|(key, value)| Ok((key, Self::try_from(value)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Ipld::Bytes(_) | Ipld::Link(_) => return Err(NotJson(ipld)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cid::Cid;
+    use crate::ipld::IpldMap;
+    use multihash::{Code, MultihashDigest};
+    use serde_json::json;
+
+    #[test]
+    fn roundtrip() {
+        let value = json!({
+            "null": null,
+            "bool": false,
+            "ints": [0, -1, i64::MIN, i64::MAX, u64::MAX],
+            "floats": [0.5, -1.5, 1e300],
+            "string": "string",
+            "nested": { "list": [[], {}] },
+        });
+        let ipld = Ipld::from(value.clone());
+        assert_eq!(
+            ipld.get("ints").unwrap().get(4).unwrap(),
+            &Ipld::Integer(u64::MAX.into())
+        );
+        assert_eq!(
+            ipld.get("floats").unwrap().get(0).unwrap(),
+            &Ipld::Float(0.5)
+        );
+        assert_eq!(Value::try_from(ipld).unwrap(), value);
+    }
+
+    #[test]
+    fn integral_floats() {
+        // Whether a number is integral depends on how it was written, not on its value.
+        assert_eq!(Ipld::from(json!(1.0)), Ipld::Float(1.0));
+        assert_eq!(Ipld::from(json!(1)), Ipld::Integer(1));
+        assert_eq!(Value::try_from(Ipld::Float(1.0)).unwrap(), json!(1.0));
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let value: Value = serde_json::from_str(r#"{"a": 1, "a": 2}"#).unwrap();
+        let mut map = IpldMap::new();
+        map.insert("a".into(), Ipld::Integer(2));
+        assert_eq!(Ipld::from(value), Ipld::Map(map));
+    }
+
+    #[test]
+    fn not_json() {
+        let cid = Cid::new_v1(0x55, Code::Blake3_256.digest(b"json"));
+        let invalid = [
+            Ipld::Bytes(vec![1, 2, 3]),
+            Ipld::Link(cid),
+            Ipld::Integer(i128::from(u64::MAX) + 1),
+            Ipld::Integer(i128::from(i64::MIN) - 1),
+            Ipld::Float(f64::NAN),
+            Ipld::Float(f64::INFINITY),
+        ];
+        for ipld in invalid {
+            let err = Value::try_from(Ipld::List(vec![Ipld::Null, ipld.clone()]))
+                .expect_err("should have failed on a value JSON can't represent");
+            assert_eq!(format!("{:?}", err.0), format!("{:?}", ipld));
+        }
+    }
+}
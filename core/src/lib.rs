@@ -1,4 +1,17 @@
 //! Core ipld types used by ipld codecs.
+//!
+//! # Generating random `Ipld`
+//!
+//! Two features generate random `Ipld` values, one per property testing crate:
+//!
+//! - `arb` implements `quickcheck::Arbitrary`, for existing quickcheck tests. The size of the
+//!   tree follows the size of quickcheck's `Gen`, there are no other knobs. Any value can come
+//!   up, including numbers that codecs like DAG-CBOR can't encode.
+//! - `proptest` implements `proptest::arbitrary::Arbitrary`, see the `prop` module. `IpldParams`
+//!   bounds the depth and collection length and toggles links. By default it only generates
+//!   values that every codec can round trip. The property tests of the codecs use this one.
+//!
+//! Neither implements the `arbitrary` crate's `Arbitrary`.
 #![deny(missing_docs)]
 #![deny(warnings)]
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -10,6 +23,8 @@ pub mod convert;
 pub mod error;
 pub mod ipld;
 pub mod link;
+#[cfg(feature = "proptest")]
+pub mod prop;
 pub mod raw;
 pub mod raw_value;
 #[cfg(feature = "serde-codec")]
@@ -17,6 +32,12 @@ pub mod serde;
 
 #[cfg(feature = "arb")]
 mod arb;
+#[cfg(feature = "serde_cbor")]
+pub mod cbor_value;
+#[cfg(feature = "std")]
+pub mod cid_string;
+#[cfg(feature = "serde_json")]
+mod json;
 
 pub use cid;
 #[cfg(feature = "std")]
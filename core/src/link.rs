@@ -42,10 +42,7 @@ impl<T> fmt::Display for Link<T> {
 
 impl<T> Clone for Link<T> {
     fn clone(&self) -> Self {
-        Self {
-            cid: self.cid,
-            _marker: self._marker,
-        }
+        *self
     }
 }
 
@@ -61,7 +58,7 @@ impl<T> Eq for Link<T> {}
 
 impl<T> PartialOrd for Link<T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cid.cmp(other.cid()))
+        Some(self.cmp(other))
     }
 }
 
@@ -0,0 +1,157 @@
+//! Random `Ipld` for property tests with [`proptest`](mod@proptest).
+//!
+//! # Example
+//!
+//! ```
+//! use libipld_core::ipld::Ipld;
+//! use libipld_core::prop::IpldParams;
+//! use proptest::prelude::*;
+//! use proptest::test_runner::TestRunner;
+//!
+//! let params = IpldParams {
+//!     max_depth: 2,
+//!     links: false,
+//!     ..Default::default()
+//! };
+//! TestRunner::default()
+//!     .run(&any_with::<Ipld>(params), |ipld| {
+//!         prop_assert!(!ipld.iter().any(|ipld| matches!(ipld, Ipld::Link(_))));
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//! ```
+use crate::cid::Cid;
+use crate::ipld::Ipld;
+use crate::multihash::MultihashGeneric;
+use proptest::collection::{btree_map, vec};
+use proptest::num::f64;
+use proptest::prelude::*;
+use proptest::strategy::Union;
+
+/// Parameters for generating `Ipld` with [`any_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct IpldParams {
+    /// The maximum number of nested lists and maps. Defaults to 4.
+    pub max_depth: u32,
+    /// The maximum number of items of a list or entries of a map. Defaults to 8.
+    pub max_collection_len: usize,
+    /// Generate links, with a random sha2-256 digest. Defaults to true.
+    pub links: bool,
+    /// Also generate NaN, infinities and integers beyond 64 bit, which codecs like DAG-CBOR
+    /// can't encode. Defaults to false, so that all values can be round tripped.
+    pub any_numbers: bool,
+}
+
+impl Default for IpldParams {
+    fn default() -> Self {
+        Self {
+            max_depth: 4,
+            max_collection_len: 8,
+            links: true,
+            any_numbers: false,
+        }
+    }
+}
+
+/// Generates links to DAG-CBOR, DAG-JSON, DAG-PB and raw blocks, and CIDv0.
+fn link() -> impl Strategy<Value = Cid> {
+    let codec = prop_oneof![Just(0x55u64), Just(0x70), Just(0x71), Just(0x0129)];
+    (any::<bool>(), codec, any::<[u8; 32]>()).prop_map(|(v0, codec, digest)| {
+        let hash =
+            MultihashGeneric::<64>::wrap(0x12, &digest).expect("digest fits into a multihash");
+        if v0 {
+            Cid::new_v0(hash).expect("sha2-256 is a valid CIDv0 hash")
+        } else {
+            Cid::new_v1(codec, hash)
+        }
+    })
+}
+
+impl Arbitrary for Ipld {
+    type Parameters = IpldParams;
+    type Strategy = BoxedStrategy<Ipld>;
+
+    fn arbitrary_with(params: IpldParams) -> Self::Strategy {
+        let integer = if params.any_numbers {
+            any::<i128>().boxed()
+        } else {
+            prop_oneof![
+                any::<i64>().prop_map(i128::from),
+                any::<u64>().prop_map(i128::from),
+                any::<u64>().prop_map(|n| -1 - i128::from(n)),
+            ]
+            .boxed()
+        };
+        let float = if params.any_numbers {
+            any::<f64>().boxed()
+        } else {
+            (f64::POSITIVE | f64::NEGATIVE | f64::NORMAL | f64::SUBNORMAL | f64::ZERO).boxed()
+        };
+        let mut leaves = vec![
+            Just(Ipld::Null).boxed(),
+            any::<bool>().prop_map(Ipld::Bool).boxed(),
+            integer.prop_map(Ipld::Integer).boxed(),
+            float.prop_map(Ipld::Float).boxed(),
+            any::<String>().prop_map(Ipld::String).boxed(),
+            vec(any::<u8>(), 0..64).prop_map(Ipld::Bytes).boxed(),
+        ];
+        if params.links {
+            leaves.push(link().prop_map(Ipld::Link).boxed());
+        }
+
+        let len = params.max_collection_len;
+        let size = params.max_depth.saturating_mul(len as u32);
+        Union::new(leaves)
+            .prop_recursive(params.max_depth, size, len as u32, move |inner| {
+                prop_oneof![
+                    vec(inner.clone(), 0..=len).prop_map(Ipld::List),
+                    btree_map(any::<String>(), inner, 0..=len)
+                        .prop_map(|map| Ipld::Map(map.into_iter().collect())),
+                ]
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::test_runner::TestRunner;
+
+    /// Returns the number of nested lists and maps.
+    fn depth(ipld: &Ipld) -> u32 {
+        match ipld {
+            Ipld::List(list) => 1 + list.iter().map(depth).max().unwrap_or(0),
+            Ipld::Map(map) => 1 + map.values().map(depth).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn params() {
+        let params = IpldParams {
+            max_depth: 3,
+            max_collection_len: 5,
+            links: false,
+            any_numbers: false,
+        };
+        TestRunner::default()
+            .run(&any_with::<Ipld>(params), |ipld| {
+                prop_assert!(depth(&ipld) <= 3);
+                for ipld in ipld.iter() {
+                    match ipld {
+                        Ipld::List(list) => prop_assert!(list.len() <= 5),
+                        Ipld::Map(map) => prop_assert!(map.len() <= 5),
+                        Ipld::Link(_) => prop_assert!(false, "unexpected link"),
+                        Ipld::Integer(i) => {
+                            prop_assert!(*i >= -(u64::MAX as i128) - 1 && *i <= u64::MAX as i128)
+                        }
+                        Ipld::Float(f) => prop_assert!(f.is_finite()),
+                        _ => {}
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+}
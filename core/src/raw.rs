@@ -3,7 +3,7 @@ use alloc::{boxed::Box, vec, vec::Vec};
 use core::{convert::TryFrom, iter::Extend};
 
 use crate::cid::Cid;
-use crate::codec::{Codec, Decode, Encode, References};
+use crate::codec::{Codec, Decode, Encode, FixedCodec, Multicodec, References};
 use crate::error::{Result, UnsupportedCodec};
 use crate::io::{Read, Seek, Write};
 use crate::ipld::Ipld;
@@ -14,9 +14,13 @@ pub struct RawCodec;
 
 impl Codec for RawCodec {}
 
+impl FixedCodec for RawCodec {
+    const CODE: u64 = Multicodec::Raw.code();
+}
+
 impl From<RawCodec> for u64 {
     fn from(_: RawCodec) -> Self {
-        0x55
+        RawCodec::CODE
     }
 }
 
@@ -105,4 +109,13 @@ mod tests {
         let ipld2: Ipld = RawCodec.decode(&bytes).unwrap();
         assert_eq!(ipld, ipld2);
     }
+
+    #[test]
+    fn test_raw_codec_only_bytes() {
+        RawCodec
+            .encode(&Ipld::String("not bytes".into()))
+            .expect_err("should have failed to encode a string")
+            .downcast::<crate::error::TypeError>()
+            .unwrap();
+    }
 }
@@ -1,4 +1,4 @@
-use alloc::{borrow::ToOwned, collections::BTreeMap, format, string::String, vec::Vec};
+use alloc::{borrow::ToOwned, format, string::String, vec::Vec};
 use core::{convert::TryFrom, fmt};
 
 use cid::serde::{BytesToCidVisitor, CID_SERDE_PRIVATE_IDENTIFIER};
@@ -9,17 +9,15 @@ use serde::{
 };
 
 use crate::error::SerdeError;
-use crate::ipld::Ipld;
+use crate::ipld::{Ipld, IpldMap};
 
 /// Deserialize instances of [`crate::ipld::Ipld`].
 ///
 /// # Example
 ///
 /// ```
-/// use std::collections::BTreeMap;
-///
 /// use serde::Deserialize;
-/// use libipld_core::ipld::Ipld;
+/// use libipld_core::ipld::{Ipld, IpldMap};
 /// use libipld_core::serde::from_ipld;
 ///
 /// #[derive(Deserialize)]
@@ -31,7 +29,7 @@ use crate::ipld::Ipld;
 /// }
 ///
 /// let ipld = Ipld::Map({
-///     BTreeMap::from([
+///     IpldMap::from([
 ///         ("name".into(), Ipld::String("Hello World!".into())),
 ///         ("age".into(), Ipld::Integer(52)),
 ///         (
@@ -161,7 +159,7 @@ impl<'de> de::Deserialize<'de> for Ipld {
             where
                 V: de::MapAccess<'de>,
             {
-                let mut values = BTreeMap::new();
+                let mut values = IpldMap::new();
 
                 while let Some((key, value)) = visitor.next_entry()? {
                     values.insert(key, value);
@@ -545,7 +543,7 @@ impl<'de> de::Deserializer<'de> for Ipld {
     }
 }
 
-fn visit_map<'de, V>(map: BTreeMap<String, Ipld>, visitor: V) -> Result<V::Value, SerdeError>
+fn visit_map<'de, V>(map: IpldMap, visitor: V) -> Result<V::Value, SerdeError>
 where
     V: de::Visitor<'de>,
 {
@@ -564,12 +562,12 @@ where
 // Heavily based on
 // https://github.com/serde-rs/json/blob/95f67a09399d546d9ecadeb747a845a77ff309b2/src/value/de.rs#L601
 struct MapDeserializer {
-    iter: <BTreeMap<String, Ipld> as IntoIterator>::IntoIter,
+    iter: <IpldMap as IntoIterator>::IntoIter,
     value: Option<Ipld>,
 }
 
 impl MapDeserializer {
-    fn new(map: BTreeMap<String, Ipld>) -> Self {
+    fn new(map: IpldMap) -> Self {
         Self {
             iter: map.into_iter(),
             value: None,
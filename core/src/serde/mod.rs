@@ -10,7 +10,6 @@ pub use ser::{to_ipld, Serializer};
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
     use std::convert::TryFrom;
     use std::fmt;
 
@@ -19,7 +18,7 @@ mod tests {
     use serde::{de::DeserializeOwned, Deserialize, Serialize};
     use serde_test::{assert_tokens, Token};
 
-    use crate::ipld::Ipld;
+    use crate::ipld::{Ipld, IpldMap};
     use crate::serde::{from_ipld, to_ipld};
 
     /// Utility for testing (de)serialization of [`Ipld`].
@@ -99,7 +98,7 @@ mod tests {
         let person = Person::default();
 
         let expected_ipld = Ipld::Map({
-            BTreeMap::from([
+            IpldMap::from([
                 ("name".into(), Ipld::String("Hello World!".into())),
                 ("age".into(), Ipld::Integer(52)),
                 (
@@ -2,7 +2,6 @@
 // https://github.com/serde-rs/json/blob/95f67a09399d546d9ecadeb747a845a77ff309b2/src/value/ser.rs
 use alloc::{
     borrow::ToOwned,
-    collections::BTreeMap,
     format,
     string::{String, ToString},
     vec::Vec,
@@ -14,7 +13,7 @@ use cid::Cid;
 use serde::ser;
 
 use crate::error::SerdeError;
-use crate::ipld::Ipld;
+use crate::ipld::{Ipld, IpldMap};
 
 /// Serialize into instances of [`crate::ipld::Ipld`].
 ///
@@ -237,7 +236,7 @@ impl serde::Serializer for Serializer {
     where
         T: ser::Serialize,
     {
-        let values = BTreeMap::from([(variant.to_owned(), value.serialize(self)?)]);
+        let values = IpldMap::from([(variant.to_owned(), value.serialize(self)?)]);
         Ok(Self::Ok::Map(values))
     }
 
@@ -287,7 +286,7 @@ impl serde::Serializer for Serializer {
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
         Ok(SerializeMap {
-            map: BTreeMap::new(),
+            map: IpldMap::new(),
             next_key: None,
         })
     }
@@ -309,7 +308,7 @@ impl serde::Serializer for Serializer {
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
         Ok(SerializeStructVariant {
             name: String::from(variant),
-            map: BTreeMap::new(),
+            map: IpldMap::new(),
         })
     }
 
@@ -329,13 +328,13 @@ pub struct SerializeTupleVariant {
 }
 
 pub struct SerializeMap {
-    map: BTreeMap<String, Ipld>,
+    map: IpldMap,
     next_key: Option<String>,
 }
 
 pub struct SerializeStructVariant {
     name: String,
-    map: BTreeMap<String, Ipld>,
+    map: IpldMap,
 }
 
 impl ser::SerializeSeq for SerializeVec {
@@ -400,7 +399,7 @@ impl ser::SerializeTupleVariant for SerializeTupleVariant {
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        let map = BTreeMap::from([(self.name, Self::Ok::List(self.vec))]);
+        let map = IpldMap::from([(self.name, Self::Ok::List(self.vec))]);
         Ok(Self::Ok::Map(map))
     }
 }
@@ -478,7 +477,7 @@ impl ser::SerializeStructVariant for SerializeStructVariant {
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        let mut object = BTreeMap::new();
+        let mut object = IpldMap::new();
 
         object.insert(self.name, Self::Ok::Map(self.map));
 
@@ -1,14 +1,11 @@
 #![cfg(feature = "serde-codec")]
 
-extern crate alloc;
-
-use alloc::collections::BTreeMap;
 use core::convert::TryFrom;
 
 use serde_test::{assert_de_tokens, Token};
 
 use libipld_core::cid::{serde::CID_SERDE_PRIVATE_IDENTIFIER, Cid};
-use libipld_core::ipld::Ipld;
+use libipld_core::ipld::{Ipld, IpldMap};
 
 #[test]
 fn ipld_deserialize_null() {
@@ -106,7 +103,7 @@ fn ipld_deserialize_list() {
 
 #[test]
 fn ipld_deserialize_map() {
-    let ipld = Ipld::Map(BTreeMap::from([
+    let ipld = Ipld::Map(IpldMap::from([
         ("hello".to_string(), Ipld::Bool(true)),
         ("world!".to_string(), Ipld::Bool(false)),
     ]));
@@ -10,7 +10,7 @@ use serde_bytes::ByteBuf;
 use serde_json::json;
 
 use libipld_core::cid::Cid;
-use libipld_core::ipld::Ipld;
+use libipld_core::ipld::{Ipld, IpldMap};
 
 /// This function is to test that all IPLD kinds except the given one errors, when trying to
 /// deserialize to the given Rust type.
@@ -40,7 +40,7 @@ where
         assert!(T::deserialize(Ipld::List(vec![Ipld::Integer(22), Ipld::Bool(false)])).is_err());
     }
     if !matches!(except, Ipld::Map(_)) {
-        assert!(T::deserialize(Ipld::Map(BTreeMap::from([
+        assert!(T::deserialize(Ipld::Map(IpldMap::from([
             ("hello".into(), Ipld::Null),
             ("world!".into(), Ipld::Float(7.4))
         ])))
@@ -435,7 +435,7 @@ fn ipld_deserializer_tuple_struct_errors() {
 #[test]
 fn ipld_deserializer_map() {
     let map = BTreeMap::from([("hello".to_string(), true), ("world!".to_string(), false)]);
-    let ipld = Ipld::Map(BTreeMap::from([
+    let ipld = Ipld::Map(IpldMap::from([
         ("hello".to_string(), Ipld::Bool(true)),
         ("world!".to_string(), Ipld::Bool(false)),
     ]));
@@ -543,7 +543,7 @@ fn ipld_deserializer_option() {
     assert!(
         <Option<u8>>::deserialize(Ipld::List(vec![Ipld::Integer(22), Ipld::Bool(false)])).is_err()
     );
-    assert!(<Option<u8>>::deserialize(Ipld::Map(BTreeMap::from([
+    assert!(<Option<u8>>::deserialize(Ipld::Map(IpldMap::from([
         ("hello".into(), Ipld::Null),
         ("world!".into(), Ipld::Float(7.4))
     ])))
@@ -575,15 +575,15 @@ fn ipld_deserializer_enum() {
     assert_eq!(deserialized_one, enum_one);
 
     let enum_two = MyEnum::Two(4);
-    let ipld_two = Ipld::Map(BTreeMap::from([("Two".into(), Ipld::Integer(4))]));
+    let ipld_two = Ipld::Map(IpldMap::from([("Two".into(), Ipld::Integer(4))]));
     error_except(enum_two.clone(), &ipld_two);
     let deserialized_two = MyEnum::deserialize(ipld_two).unwrap();
     assert_eq!(deserialized_two, enum_two);
 
     let enum_three = MyEnum::Three { value: true };
-    let ipld_three = Ipld::Map(BTreeMap::from([(
+    let ipld_three = Ipld::Map(IpldMap::from([(
         "Three".into(),
-        Ipld::Map(BTreeMap::from([("value".into(), Ipld::Bool(true))])),
+        Ipld::Map(IpldMap::from([("value".into(), Ipld::Bool(true))])),
     )]));
     error_except(enum_three.clone(), &ipld_three);
     let deserialized_three = MyEnum::deserialize(ipld_three).unwrap();
@@ -599,7 +599,7 @@ fn ipld_deserializer_enum_tuple_variant_errors() {
 
     let tuple_variant = MyEnum::Two(17, false);
 
-    let ipld_not_enough = Ipld::Map(BTreeMap::from([(
+    let ipld_not_enough = Ipld::Map(IpldMap::from([(
         "Two".into(),
         Ipld::List(vec![Ipld::Integer(17)]),
     )]));
@@ -607,7 +607,7 @@ fn ipld_deserializer_enum_tuple_variant_errors() {
     let error_not_enough = MyEnum::deserialize(ipld_not_enough);
     assert!(error_not_enough.is_err());
 
-    let ipld_too_many = Ipld::Map(BTreeMap::from([(
+    let ipld_too_many = Ipld::Map(IpldMap::from([(
         "Two".into(),
         Ipld::List(vec![Ipld::Integer(17), Ipld::Bool(false), Ipld::Null]),
     )]));
@@ -615,7 +615,7 @@ fn ipld_deserializer_enum_tuple_variant_errors() {
     let error_too_many = MyEnum::deserialize(ipld_too_many);
     assert!(error_too_many.is_err());
 
-    let ipld_not_matching = Ipld::Map(BTreeMap::from([(
+    let ipld_not_matching = Ipld::Map(IpldMap::from([(
         "Two".into(),
         Ipld::List(vec![Ipld::Bool(false), Ipld::Integer(17)]),
     )]));
@@ -636,7 +636,7 @@ fn ipld_deserializer_struct() {
         hello: 91,
         world: false,
     };
-    let ipld = Ipld::Map(BTreeMap::from([
+    let ipld = Ipld::Map(IpldMap::from([
         ("hello".into(), Ipld::Integer(my_struct.hello.into())),
         ("world".into(), Ipld::Bool(my_struct.world)),
     ]));
@@ -659,7 +659,7 @@ fn ipld_deserializer_struct_errors() {
         world: false,
     };
 
-    let ipld_missing = Ipld::Map(BTreeMap::from([(
+    let ipld_missing = Ipld::Map(IpldMap::from([(
         "hello".into(),
         Ipld::Integer(my_struct.hello.into()),
     )]));
@@ -667,7 +667,7 @@ fn ipld_deserializer_struct_errors() {
     let error_missing = MyStruct::deserialize(ipld_missing);
     assert!(error_missing.is_err());
 
-    let ipld_wrong = Ipld::Map(BTreeMap::from([(
+    let ipld_wrong = Ipld::Map(IpldMap::from([(
         "wrong".into(),
         Ipld::Integer(my_struct.hello.into()),
     )]));
@@ -691,7 +691,7 @@ fn ipld_deserializer_ipld() {
 #[test]
 fn ipld_deserializer_serde_json_value() {
     let json_value = json!({ "hello": true, "world": "it is" });
-    let ipld = Ipld::Map(BTreeMap::from([
+    let ipld = Ipld::Map(IpldMap::from([
         ("hello".into(), Ipld::Bool(true)),
         ("world".into(), Ipld::String("it is".into())),
     ]));
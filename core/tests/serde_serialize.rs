@@ -1,14 +1,11 @@
 #![cfg(feature = "serde-codec")]
 
-extern crate alloc;
-
-use alloc::collections::BTreeMap;
 use core::convert::TryFrom;
 
 use serde_test::{assert_ser_tokens, Token};
 
 use libipld_core::cid::{serde::CID_SERDE_PRIVATE_IDENTIFIER, Cid};
-use libipld_core::ipld::Ipld;
+use libipld_core::ipld::{Ipld, IpldMap};
 
 #[test]
 fn ipld_serialize_null() {
@@ -71,7 +68,7 @@ fn ipld_serialize_list() {
 
 #[test]
 fn ipld_serialize_map() {
-    let ipld = Ipld::Map(BTreeMap::from([
+    let ipld = Ipld::Map(IpldMap::from([
         ("hello".to_string(), Ipld::Bool(true)),
         ("world!".to_string(), Ipld::Bool(false)),
     ]));
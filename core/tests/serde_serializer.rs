@@ -9,7 +9,7 @@ use serde::{ser, Serialize};
 use serde_bytes::ByteBuf;
 
 use libipld_core::cid::Cid;
-use libipld_core::ipld::Ipld;
+use libipld_core::ipld::{Ipld, IpldMap};
 use libipld_core::serde::to_ipld;
 
 fn assert_serialized<T>(input: T, ipld: Ipld)
@@ -176,7 +176,7 @@ fn ipld_serializer_tuple_struct() {
 #[test]
 fn ipld_serializer_map() {
     let map = BTreeMap::from([("hello".to_string(), true), ("world!".to_string(), false)]);
-    let ipld = Ipld::Map(BTreeMap::from([
+    let ipld = Ipld::Map(IpldMap::from([
         ("hello".to_string(), Ipld::Bool(true)),
         ("world!".to_string(), Ipld::Bool(false)),
     ]));
@@ -237,13 +237,13 @@ fn ipld_serializer_enum() {
     assert_serialized(enum_one, ipld_one);
 
     let enum_two = MyEnum::Two(4);
-    let ipld_two = Ipld::Map(BTreeMap::from([("Two".into(), Ipld::Integer(4))]));
+    let ipld_two = Ipld::Map(IpldMap::from([("Two".into(), Ipld::Integer(4))]));
     assert_serialized(enum_two, ipld_two);
 
     let enum_three = MyEnum::Three { value: true };
-    let ipld_three = Ipld::Map(BTreeMap::from([(
+    let ipld_three = Ipld::Map(IpldMap::from([(
         "Three".into(),
-        Ipld::Map(BTreeMap::from([("value".into(), Ipld::Bool(true))])),
+        Ipld::Map(IpldMap::from([("value".into(), Ipld::Bool(true))])),
     )]));
     assert_serialized(enum_three, ipld_three);
 }
@@ -260,7 +260,7 @@ fn ipld_serializer_struct() {
         hello: 91,
         world: false,
     };
-    let ipld = Ipld::Map(BTreeMap::from([
+    let ipld = Ipld::Map(IpldMap::from([
         ("hello".into(), Ipld::Integer(my_struct.hello.into())),
         ("world".into(), Ipld::Bool(my_struct.world)),
     ]));
@@ -2,6 +2,7 @@
 //! renamed in the `Cargo.toml` file.
 use ipld::DagCbor;
 
+#[allow(dead_code)]
 #[derive(Clone, DagCbor, Debug, Default, PartialEq)]
 struct NamedStruct {
     boolean: bool,
@@ -11,6 +11,7 @@ mod kw {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct Attrs<A> {
     pub paren: syn::token::Paren,
     pub attrs: Punctuated<A, syn::token::Comma>,
@@ -26,6 +27,7 @@ impl<A: Parse> Parse for Attrs<A> {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct Attr<K, V> {
     pub key: K,
     pub eq: syn::token::Eq,
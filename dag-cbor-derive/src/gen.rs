@@ -47,6 +47,7 @@ pub fn gen_decode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
                 use #libipld::cbor::error::{LengthOutOfRange, MissingKey, UnexpectedCode, UnexpectedKey};
                 use #libipld::codec::Decode;
                 use #libipld::error::Result;
+                use #libipld::raw_value::IgnoredAny;
                 use std::io::SeekFrom;
                 #body
             }
@@ -264,7 +265,7 @@ fn gen_decode_struct(s: &Struct) -> TokenStream {
                             match key.as_str() {
                                 #(#key => { #binding = Some(Decode::decode(c, r)?); })*
                                 _ => {
-                                    Decode::decode(c, r)?;
+                                    let _: IgnoredAny = Decode::decode(c, r)?;
                                 }
                             }
                         }
@@ -1,5 +1,6 @@
+use libipld::cbor::error::UnexpectedKey;
 use libipld::cbor::DagCborCodec;
-use libipld::codec::assert_roundtrip;
+use libipld::codec::{assert_roundtrip, Codec};
 use libipld::{ipld, DagCbor};
 
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
@@ -28,3 +29,22 @@ fn enum_string() {
     assert_roundtrip(DagCborCodec, &EnumString::Variant, &ipld!("test"));
     assert_roundtrip(DagCborCodec, &EnumString::Other, &ipld!("Other"));
 }
+
+#[test]
+fn enum_unknown_variant() {
+    let bytes = DagCborCodec.encode(&ipld!(2)).unwrap();
+    let err = DagCborCodec
+        .decode::<EnumInt>(&bytes)
+        .expect_err("should have failed to decode an unknown variant")
+        .downcast::<UnexpectedKey>()
+        .unwrap();
+    assert_eq!(err.key, "2");
+
+    let bytes = DagCborCodec.encode(&ipld!("Missing")).unwrap();
+    let err = DagCborCodec
+        .decode::<EnumString>(&bytes)
+        .expect_err("should have failed to decode an unknown variant")
+        .downcast::<UnexpectedKey>()
+        .unwrap();
+    assert_eq!(err.key, "Missing");
+}
@@ -1,6 +1,7 @@
+use libipld::cbor::error::UnexpectedKey;
 use libipld::cbor::DagCborCodec;
-use libipld::codec::assert_roundtrip;
-use libipld::{ipld, DagCbor};
+use libipld::codec::{assert_roundtrip, Codec, Decode};
+use libipld::{ipld, DagCbor, Ipld};
 
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
 #[ipld(repr = "keyed")]
@@ -94,3 +95,45 @@ fn union_int_tuple() {
         &ipld!([4, { "boolean": true }]),
     );
 }
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+pub enum Shape {
+    Empty,
+    #[ipld(repr = "value")]
+    Circle(f64),
+    Rect {
+        width: u32,
+        height: u32,
+    },
+}
+
+#[test]
+fn union_keyed_shapes() {
+    assert_roundtrip(DagCborCodec, &Shape::Empty, &ipld!({ "Empty": null }));
+    assert_roundtrip(DagCborCodec, &Shape::Circle(1.5), &ipld!({ "Circle": 1.5 }));
+    assert_roundtrip(
+        DagCborCodec,
+        &Shape::Rect {
+            width: 1,
+            height: 2,
+        },
+        &ipld!({ "Rect": { "width": 1, "height": 2 } }),
+    );
+}
+
+#[test]
+fn union_unknown_variant() {
+    fn unknown_key<T: Decode<DagCborCodec> + std::fmt::Debug>(ipld: Ipld) -> UnexpectedKey {
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        DagCborCodec
+            .decode::<T>(&bytes)
+            .expect_err("should have failed to decode an unknown variant")
+            .downcast::<UnexpectedKey>()
+            .unwrap()
+    }
+
+    let err = unknown_key::<Shape>(ipld!({ "Triangle": null }));
+    assert_eq!(err.key, "Triangle");
+    assert!(err.ty.ends_with("Shape"));
+    assert_eq!(unknown_key::<IntTuple>(ipld!([5, null])).key, "5");
+}
@@ -0,0 +1,31 @@
+//! Writes a list of 10 million integers without holding the list in memory.
+use libipld_cbor::encode::Encoder;
+use libipld_core::error::Result;
+use std::io::{self, Write};
+
+/// A writer that discards the bytes and only counts them.
+struct Counter(u64);
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    const LEN: u64 = 10_000_000;
+    let mut encoder = Encoder::new(Counter(0));
+    encoder.begin_list(Some(LEN))?;
+    for i in 0..LEN {
+        encoder.push(&i)?;
+    }
+    encoder.end()?;
+    let counter = encoder.finish()?;
+    println!("Encoded {} integers into {} bytes.", LEN, counter.0);
+    Ok(())
+}
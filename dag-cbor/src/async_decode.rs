@@ -0,0 +1,950 @@
+//! Async CBOR decoder.
+//!
+//! Mirrors the sync decoder for types that are read from an `AsyncRead`, e.g. a network stream,
+//! without buffering the whole block first. As an `AsyncRead` can't seek, every item is decoded
+//! from a major that was read already. Errors don't have a [`Location`](crate::error::Location)
+//! attached. `Ipld` is decoded with the given [`DecodeOptions`] by [`read_ipld`].
+use crate::cbor::{KeyOrder, Major, MajorKind, BREAK, F16, F32, F64, FALSE, NULL, TRUE, UNDEFINED};
+use crate::decode::{self, DecodeOptions};
+use crate::error::{
+    DuplicateKey, FloatNotF64, IndefiniteLength, InvalidCidPrefix, InvalidLinkType,
+    LengthOutOfRange, MaxCollectionLenExceeded, MaxDepthExceeded, MaxStringLenExceeded,
+    NonFiniteFloat, NumberOutOfRange, Undefined, UnexpectedCode, UnexpectedEof, UnknownTag,
+    UnorderedKeys, UnsupportedSimpleValue,
+};
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use futures_lite::io::{AsyncRead, AsyncReadExt};
+use libipld_core::cid::Cid;
+use libipld_core::error::Result;
+use libipld_core::ipld::{Ipld, IpldMap};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+
+/// A boxed future returned by [`AsyncDecode`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Async version of `Decode<DagCborCodec>`.
+///
+/// The futures are boxed, so that nested types like `Ipld` can be decoded recursively.
+pub trait AsyncDecode: Sized + Send {
+    /// Decodes the item whose major has already been read.
+    fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+        major: Major,
+        r: &'a mut R,
+    ) -> BoxFuture<'a, Self>;
+}
+
+/// Decodes an item from an `impl AsyncRead`.
+pub async fn decode<T: AsyncDecode, R: AsyncRead + Unpin + Send>(r: &mut R) -> Result<T> {
+    let major = read_major(r).await?;
+    T::decode_major(major, r).await
+}
+
+/// Fills `buf` from an async byte stream. Running out of bytes is reported as [`UnexpectedEof`].
+async fn fill<R: AsyncRead + Unpin>(r: &mut R, buf: &mut [u8]) -> Result<()> {
+    let mut got = 0;
+    while got < buf.len() {
+        match r.read(&mut buf[got..]).await {
+            Ok(0) => {
+                return Err(UnexpectedEof {
+                    expected: buf.len() as u64,
+                    got: got as u64,
+                }
+                .into())
+            }
+            Ok(n) => got += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Reads `N` bytes from an async byte stream.
+async fn read_array<R: AsyncRead + Unpin, const N: usize>(r: &mut R) -> Result<[u8; N]> {
+    let mut buf = [0; N];
+    fill(r, &mut buf).await?;
+    Ok(buf)
+}
+
+/// Reads a u8 from an async byte stream.
+pub async fn read_u8<R: AsyncRead + Unpin>(r: &mut R) -> Result<u8> {
+    Ok(read_array::<_, 1>(r).await?[0])
+}
+
+/// Reads a u16 from an async byte stream.
+pub async fn read_u16<R: AsyncRead + Unpin>(r: &mut R) -> Result<u16> {
+    Ok(u16::from_be_bytes(read_array(r).await?))
+}
+
+/// Reads a u32 from an async byte stream.
+pub async fn read_u32<R: AsyncRead + Unpin>(r: &mut R) -> Result<u32> {
+    Ok(u32::from_be_bytes(read_array(r).await?))
+}
+
+/// Reads a u64 from an async byte stream.
+pub async fn read_u64<R: AsyncRead + Unpin>(r: &mut R) -> Result<u64> {
+    Ok(u64::from_be_bytes(read_array(r).await?))
+}
+
+/// Reads a half-precision float from an async byte stream and widens it to a f64.
+pub async fn read_f16<R: AsyncRead + Unpin>(r: &mut R) -> Result<f64> {
+    decode::read_f16(&mut &read_array::<_, 2>(r).await?[..])
+}
+
+/// Reads a f32 from an async byte stream.
+pub async fn read_f32<R: AsyncRead + Unpin>(r: &mut R) -> Result<f32> {
+    Ok(f32::from_be_bytes(read_array(r).await?))
+}
+
+/// Reads a f64 from an async byte stream.
+pub async fn read_f64<R: AsyncRead + Unpin>(r: &mut R) -> Result<f64> {
+    Ok(f64::from_be_bytes(read_array(r).await?))
+}
+
+/// Reads `len` number of bytes from an async byte stream.
+pub async fn read_bytes<R: AsyncRead + Unpin>(r: &mut R, len: u64) -> Result<Vec<u8>> {
+    let len = usize::try_from(len).map_err(|_| LengthOutOfRange::new::<usize>())?;
+    // Limit up-front allocations to 16KiB as the length is user controlled.
+    let mut buf = Vec::with_capacity(len.min(16 * 1024));
+    (&mut *r).take(len as u64).read_to_end(&mut buf).await?;
+    if buf.len() != len {
+        return Err(UnexpectedEof {
+            expected: len as u64,
+            got: buf.len() as u64,
+        }
+        .into());
+    }
+    Ok(buf)
+}
+
+/// Reads `len` number of bytes from an async byte stream and converts them to a string.
+pub async fn read_str<R: AsyncRead + Unpin>(r: &mut R, len: u64) -> Result<String> {
+    let bytes = read_bytes(r, len).await?;
+    decode::utf8_string(bytes)
+}
+
+/// Reads and validates a major "byte" from an async byte stream, see [`decode::read_major`].
+pub async fn read_major<R: AsyncRead + Unpin>(r: &mut R) -> Result<Major> {
+    let byte = read_u8(r).await?;
+    match byte {
+        0xe0..=0xf3 => Err(UnsupportedSimpleValue(byte & 0x1f).into()),
+        0xf8 => Err(UnsupportedSimpleValue(read_u8(r).await?).into()),
+        _ => Ok(Major::try_from(byte)?),
+    }
+}
+
+/// Reads the uint argument to the given major type, see [`decode::read_uint`].
+pub async fn read_uint<R: AsyncRead + Unpin>(r: &mut R, major: Major) -> Result<u64> {
+    let mut buf = [0; 8];
+    let buf = &mut buf[..major.len() as usize];
+    fill(r, buf).await?;
+    decode::read_uint(&mut &buf[..], major)
+}
+
+/// Reads the integer argument of the given major type, see [`decode::read_int`].
+pub async fn read_int<R: AsyncRead + Unpin>(r: &mut R, major: Major, strict: bool) -> Result<u64> {
+    let mut buf = [0; 8];
+    let buf = &mut buf[..major.len() as usize];
+    fill(r, buf).await?;
+    decode::read_int(&mut &buf[..], major, strict)
+}
+
+/// Reads the tag number of the given tag major, see [`decode::read_tag`].
+pub async fn read_tag<R: AsyncRead + Unpin>(r: &mut R, major: Major, strict: bool) -> Result<u64> {
+    let mut buf = [0; 8];
+    let buf = &mut buf[..major.len() as usize];
+    fill(r, buf).await?;
+    decode::read_tag(&mut &buf[..], major, strict)
+}
+
+/// Reads the chunks of an indefinite-length string of the given major type until the "break"
+/// stop code and concatenates them. Fails if they are longer than `max_len` in total.
+async fn read_chunks<R: AsyncRead + Unpin>(
+    r: &mut R,
+    kind: MajorKind,
+    max_len: usize,
+) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    loop {
+        let major = read_major(r).await?;
+        if major == BREAK {
+            return Ok(buf);
+        }
+        if major.kind() != kind || major.is_indefinite() {
+            return Err(UnexpectedCode::new::<Vec<u8>>(major.into()).into());
+        }
+        let len = read_uint(r, major).await?;
+        let total = usize::try_from(len)
+            .ok()
+            .and_then(|len| len.checked_add(buf.len()))
+            .ok_or_else(LengthOutOfRange::new::<Vec<u8>>)?;
+        if total > max_len {
+            return Err(MaxStringLenExceeded(max_len).into());
+        }
+        buf.extend(read_bytes(r, len).await?);
+    }
+}
+
+/// Reads the payload of a byte string whose major has already been read.
+pub async fn read_byte_string<R: AsyncRead + Unpin>(r: &mut R, major: Major) -> Result<Vec<u8>> {
+    if major.is_indefinite() {
+        read_chunks(r, MajorKind::ByteString, usize::MAX).await
+    } else {
+        let len = read_uint(r, major).await?;
+        read_bytes(r, len).await
+    }
+}
+
+/// Reads the payload of a text string whose major has already been read.
+pub async fn read_text_string<R: AsyncRead + Unpin>(r: &mut R, major: Major) -> Result<String> {
+    if major.is_indefinite() {
+        let bytes = read_chunks(r, MajorKind::TextString, usize::MAX).await?;
+        decode::utf8_string(bytes)
+    } else {
+        let len = read_uint(r, major).await?;
+        read_str(r, len).await
+    }
+}
+
+/// Reads a cid from an async byte stream.
+pub async fn read_link<R: AsyncRead + Unpin>(r: &mut R) -> Result<Cid> {
+    let major = read_major(r).await?;
+    if major.kind() != MajorKind::ByteString {
+        return Err(InvalidLinkType(major.into()).into());
+    }
+    let len = read_uint(r, major).await?;
+    if len < 1 {
+        return Err(LengthOutOfRange::new::<Cid>().into());
+    }
+    let bytes = read_bytes(r, len).await?;
+    // skip the first byte per
+    // https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#links
+    if bytes[0] != 0 {
+        return Err(InvalidCidPrefix(bytes[0]).into());
+    }
+    let mut cid_bytes = &bytes[1..];
+    let cid = Cid::read_bytes(&mut cid_bytes)?;
+    // Make sure we've read the entire CID.
+    if !cid_bytes.is_empty() {
+        return Err(LengthOutOfRange::new::<Cid>().into());
+    }
+    Ok(cid)
+}
+
+/// Reads the major of the next item of a list or map. Returns `None` once all items were read.
+/// `remaining` is the number of items that are left, or `None` for an indefinite-length list or
+/// map.
+async fn read_next<R: AsyncRead + Unpin>(
+    r: &mut R,
+    remaining: &mut Option<u64>,
+) -> Result<Option<Major>> {
+    match remaining {
+        Some(0) => Ok(None),
+        Some(len) => {
+            *len -= 1;
+            Ok(Some(read_major(r).await?))
+        }
+        None => match read_major(r).await? {
+            BREAK => Ok(None),
+            major => Ok(Some(major)),
+        },
+    }
+}
+
+/// Returns the number of items of a list or map, or `None` if it is of indefinite length.
+async fn read_len<R: AsyncRead + Unpin>(r: &mut R, major: Major) -> Result<Option<u64>> {
+    if major.is_indefinite() {
+        Ok(None)
+    } else {
+        Ok(Some(read_uint(r, major).await?))
+    }
+}
+
+impl AsyncDecode for bool {
+    fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+        major: Major,
+        _: &'a mut R,
+    ) -> BoxFuture<'a, Self> {
+        Box::pin(async move {
+            Ok(match major {
+                FALSE => false,
+                TRUE => true,
+                m => return Err(UnexpectedCode::new::<Self>(m.into()).into()),
+            })
+        })
+    }
+}
+
+macro_rules! impl_num {
+    (unsigned $($t:ty),*) => {
+        $(
+            impl AsyncDecode for $t {
+                fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+                    major: Major,
+                    r: &'a mut R,
+                ) -> BoxFuture<'a, Self> {
+                    Box::pin(async move {
+                        match major.kind() {
+                            MajorKind::UnsignedInt => {}
+                            MajorKind::NegativeInt => {
+                                // Still validate the encoding of the number.
+                                read_int(r, major, false).await?;
+                                return Err(NumberOutOfRange::new::<Self>().into());
+                            }
+                            _ => return Err(UnexpectedCode::new::<Self>(major.into()).into()),
+                        }
+                        let value = read_int(r, major, false).await?;
+                        Self::try_from(value).map_err(|_| NumberOutOfRange::new::<Self>().into())
+                    })
+                }
+            }
+        )*
+    };
+    (signed $($t:ty),*) => {
+        $(
+            impl AsyncDecode for $t {
+                fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+                    major: Major,
+                    r: &'a mut R,
+                ) -> BoxFuture<'a, Self> {
+                    Box::pin(async move {
+                        match major.kind() {
+                            MajorKind::UnsignedInt | MajorKind::NegativeInt => (),
+                            _ => return Err(UnexpectedCode::new::<Self>(major.into()).into()),
+                        };
+                        let value = read_int(r, major, false).await?;
+                        let mut value = Self::try_from(value)
+                            .map_err(|_| NumberOutOfRange::new::<Self>())?;
+                        if major.kind() == MajorKind::NegativeInt {
+                            // This is guaranteed to not overflow.
+                            value = -1 - value;
+                        }
+                        Ok(value)
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_num!(unsigned u8, u16, u32, u64, u128);
+impl_num!(signed i8, i16, i32, i64, i128);
+
+impl AsyncDecode for f32 {
+    fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+        major: Major,
+        r: &'a mut R,
+    ) -> BoxFuture<'a, Self> {
+        Box::pin(async move {
+            let num = match major {
+                // Every half-precision float is exactly representable as a f32.
+                F16 => read_f16(r).await? as Self,
+                F32 => read_f32(r).await?,
+                F64 => {
+                    let num = read_f64(r).await?;
+                    let converted = num as Self;
+                    if f64::from(converted) != num {
+                        return Err(NumberOutOfRange::new::<Self>().into());
+                    }
+                    converted
+                }
+                m => return Err(UnexpectedCode::new::<Self>(m.into()).into()),
+            };
+            if !num.is_finite() {
+                return Err(NumberOutOfRange::new::<Self>().into());
+            }
+            Ok(num)
+        })
+    }
+}
+
+impl AsyncDecode for f64 {
+    fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+        major: Major,
+        r: &'a mut R,
+    ) -> BoxFuture<'a, Self> {
+        Box::pin(async move {
+            let num = match major {
+                F16 => read_f16(r).await?,
+                F32 => read_f32(r).await?.into(),
+                F64 => read_f64(r).await?,
+                m => return Err(UnexpectedCode::new::<Self>(m.into()).into()),
+            };
+            if !num.is_finite() {
+                return Err(NumberOutOfRange::new::<Self>().into());
+            }
+            Ok(num)
+        })
+    }
+}
+
+impl AsyncDecode for String {
+    fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+        major: Major,
+        r: &'a mut R,
+    ) -> BoxFuture<'a, Self> {
+        Box::pin(async move {
+            if major.kind() != MajorKind::TextString {
+                return Err(UnexpectedCode::new::<Self>(major.into()).into());
+            }
+            read_text_string(r, major).await
+        })
+    }
+}
+
+impl AsyncDecode for Cid {
+    fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+        major: Major,
+        r: &'a mut R,
+    ) -> BoxFuture<'a, Self> {
+        Box::pin(async move {
+            if major.kind() != MajorKind::Tag {
+                return Err(UnexpectedCode::new::<Self>(major.into()).into());
+            }
+            match read_tag(r, major, false).await? {
+                42 => read_link(r).await,
+                tag => Err(UnknownTag(tag).into()),
+            }
+        })
+    }
+}
+
+impl<T: AsyncDecode> AsyncDecode for Option<T> {
+    fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+        major: Major,
+        r: &'a mut R,
+    ) -> BoxFuture<'a, Self> {
+        Box::pin(async move {
+            match major {
+                NULL => Ok(None),
+                UNDEFINED => Err(Undefined.into()),
+                _ => Ok(Some(T::decode_major(major, r).await?)),
+            }
+        })
+    }
+}
+
+impl<T: AsyncDecode> AsyncDecode for Vec<T> {
+    fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+        major: Major,
+        r: &'a mut R,
+    ) -> BoxFuture<'a, Self> {
+        Box::pin(async move {
+            if major.kind() != MajorKind::Array {
+                return Err(UnexpectedCode::new::<Self>(major.into()).into());
+            }
+            let mut remaining = read_len(r, major).await?;
+            let len = usize::try_from(remaining.unwrap_or_default())
+                .map_err(|_| LengthOutOfRange::new::<usize>())?;
+            // Limit up-front allocations to 16KiB as the length is user controlled.
+            let max_alloc = (16 * 1024) / std::mem::size_of::<T>().max(1);
+            let mut list = Vec::with_capacity(len.min(max_alloc));
+            while let Some(major) = read_next(r, &mut remaining).await? {
+                list.push(T::decode_major(major, r).await?);
+            }
+            Ok(list)
+        })
+    }
+}
+
+/// If a key occurs more than once, the last value wins.
+impl<K: AsyncDecode + Ord, T: AsyncDecode> AsyncDecode for BTreeMap<K, T> {
+    fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+        major: Major,
+        r: &'a mut R,
+    ) -> BoxFuture<'a, Self> {
+        Box::pin(async move {
+            if major.kind() != MajorKind::Map {
+                return Err(UnexpectedCode::new::<Self>(major.into()).into());
+            }
+            let mut remaining = read_len(r, major).await?;
+            let mut map = BTreeMap::new();
+            while let Some(major) = read_next(r, &mut remaining).await? {
+                let key = K::decode_major(major, r).await?;
+                let value = decode(r).await?;
+                map.insert(key, value);
+            }
+            Ok(map)
+        })
+    }
+}
+
+/// Accepts the same input as decoding `Ipld` with the default options.
+impl AsyncDecode for Ipld {
+    fn decode_major<'a, R: AsyncRead + Unpin + Send>(
+        major: Major,
+        r: &'a mut R,
+    ) -> BoxFuture<'a, Self> {
+        Box::pin(async move {
+            let opts = DecodeOptions::default();
+            read_ipld_nested(major, r, &opts, 0).await
+        })
+    }
+}
+
+/// Reads `Ipld` from an async byte stream using the given options, see [`decode::read_ipld`].
+pub async fn read_ipld<R: AsyncRead + Unpin + Send>(
+    r: &mut R,
+    opts: &DecodeOptions,
+) -> Result<Ipld> {
+    let major = read_major(r).await?;
+    read_ipld_nested(major, r, opts, 0).await
+}
+
+/// Reads `Ipld` that is nested in `depth` lists or maps.
+fn read_ipld_nested<'a, R: AsyncRead + Unpin + Send>(
+    major: Major,
+    r: &'a mut R,
+    opts: &'a DecodeOptions,
+    depth: usize,
+) -> BoxFuture<'a, Ipld> {
+    Box::pin(async move {
+        if opts.strict && major.is_indefinite() && major.kind() != MajorKind::Other {
+            return Err(IndefiniteLength.into());
+        }
+        let ipld = match major.kind() {
+            MajorKind::UnsignedInt => Ipld::Integer(read_int(r, major, opts.strict).await? as i128),
+            MajorKind::NegativeInt => {
+                Ipld::Integer(-1 - read_int(r, major, opts.strict).await? as i128)
+            }
+            MajorKind::ByteString => Ipld::Bytes(read_ipld_string(r, major, opts).await?),
+            MajorKind::TextString => {
+                let bytes = read_ipld_string(r, major, opts).await?;
+                if opts.lossy_utf8 && !opts.strict {
+                    Ipld::String(String::from_utf8_lossy(&bytes).into_owned())
+                } else {
+                    Ipld::String(decode::utf8_string(bytes)?)
+                }
+            }
+            MajorKind::Array | MajorKind::Map if depth >= opts.max_depth => {
+                return Err(MaxDepthExceeded(opts.max_depth).into());
+            }
+            MajorKind::Array => {
+                let mut remaining = read_ipld_len(r, major, opts).await?;
+                let mut list = Vec::new();
+                while let Some(major) = read_next(r, &mut remaining).await? {
+                    if list.len() >= opts.max_collection_len {
+                        return Err(MaxCollectionLenExceeded(opts.max_collection_len).into());
+                    }
+                    list.push(read_ipld_nested(major, r, opts, depth + 1).await?);
+                }
+                Ipld::List(list)
+            }
+            MajorKind::Map => {
+                let mut remaining = read_ipld_len(r, major, opts).await?;
+                let mut map = IpldMap::new();
+                let mut prev: Option<String> = None;
+                let mut entries = 0;
+                while let Some(major) = read_next(r, &mut remaining).await? {
+                    if entries >= opts.max_collection_len {
+                        return Err(MaxCollectionLenExceeded(opts.max_collection_len).into());
+                    }
+                    entries += 1;
+                    let key = read_ipld_key(major, r, &map, &mut prev, opts).await?;
+                    let major = read_major(r).await?;
+                    let value = read_ipld_nested(major, r, opts, depth + 1).await?;
+                    map.insert(key, value);
+                }
+                Ipld::Map(map)
+            }
+            MajorKind::Tag => match read_tag(r, major, opts.strict).await? {
+                42 => Ipld::Link(read_link(r).await?),
+                // Bignums aren't part of the DAG-CBOR data model.
+                2 if !opts.strict => Ipld::Integer(read_bignum(r, opts).await?),
+                3 if !opts.strict => Ipld::Integer(-1 - read_bignum(r, opts).await?),
+                _ if opts.allow_unknown_tags && !opts.strict => {
+                    if depth >= opts.max_depth {
+                        return Err(MaxDepthExceeded(opts.max_depth).into());
+                    }
+                    let major = read_major(r).await?;
+                    read_ipld_nested(major, r, opts, depth + 1).await?
+                }
+                value => return Err(UnknownTag(value).into()),
+            },
+            MajorKind::Other => match major {
+                FALSE => Ipld::Bool(false),
+                TRUE => Ipld::Bool(true),
+                NULL => Ipld::Null,
+                UNDEFINED if opts.allow_undefined && !opts.strict => Ipld::Null,
+                UNDEFINED => return Err(Undefined.into()),
+                F16 | F32 | F64 => {
+                    let num = match major {
+                        F16 => read_f16(r).await?,
+                        F32 => read_f32(r).await? as f64,
+                        _ => read_f64(r).await?,
+                    };
+                    if opts.strict {
+                        if !num.is_finite() {
+                            return Err(NonFiniteFloat(num).into());
+                        }
+                        if major != F64 {
+                            return Err(FloatNotF64.into());
+                        }
+                    }
+                    Ipld::Float(num)
+                }
+                m => return Err(UnexpectedCode::new::<Ipld>(m.into()).into()),
+            },
+        };
+        Ok(ipld)
+    })
+}
+
+/// Reads the payload of a byte string or text string whose major has already been read, checking
+/// its length before reading it.
+async fn read_ipld_string<R: AsyncRead + Unpin>(
+    r: &mut R,
+    major: Major,
+    opts: &DecodeOptions,
+) -> Result<Vec<u8>> {
+    let max_len = match major.kind() {
+        MajorKind::ByteString => opts.max_bytes_len,
+        _ => opts.max_string_len,
+    };
+    if major.is_indefinite() {
+        return read_chunks(r, major.kind(), max_len).await;
+    }
+    let len = read_uint(r, major).await?;
+    if len > max_len as u64 {
+        return Err(MaxStringLenExceeded(max_len).into());
+    }
+    read_bytes(r, len).await
+}
+
+/// Like [`read_len`], but checks the length of a definite-length list or map against the maximum
+/// collection length. Indefinite-length ones are checked while reading their items.
+async fn read_ipld_len<R: AsyncRead + Unpin>(
+    r: &mut R,
+    major: Major,
+    opts: &DecodeOptions,
+) -> Result<Option<u64>> {
+    match read_len(r, major).await? {
+        Some(len) if len > opts.max_collection_len as u64 => {
+            Err(MaxCollectionLenExceeded(opts.max_collection_len).into())
+        }
+        len => Ok(len),
+    }
+}
+
+/// Reads a map key whose major has already been read and checks it against the keys that were
+/// read before.
+async fn read_ipld_key<R: AsyncRead + Unpin>(
+    major: Major,
+    r: &mut R,
+    map: &IpldMap,
+    prev: &mut Option<String>,
+    opts: &DecodeOptions,
+) -> Result<String> {
+    if major.kind() != MajorKind::TextString {
+        return Err(UnexpectedCode::new::<String>(major.into()).into());
+    }
+    if opts.strict && major.is_indefinite() {
+        return Err(IndefiniteLength.into());
+    }
+    let key = decode::utf8_string(read_ipld_string(r, major, opts).await?)?;
+    if opts.strict && opts.key_order != KeyOrder::Insertion {
+        if let Some(prev) = prev {
+            match opts.key_order.compare(prev, &key) {
+                Some(Ordering::Less) => {}
+                Some(Ordering::Equal) => return Err(DuplicateKey(key).into()),
+                _ => return Err(UnorderedKeys(key).into()),
+            }
+        }
+        *prev = Some(key.clone());
+    }
+    if (opts.strict || !opts.allow_duplicate_keys) && map.contains_key(&key) {
+        return Err(DuplicateKey(key).into());
+    }
+    Ok(key)
+}
+
+/// Reads the byte string of a bignum (tag 2 or 3) and returns its unsigned value. Its length is
+/// checked against the maximum length of byte strings.
+async fn read_bignum<R: AsyncRead + Unpin>(r: &mut R, opts: &DecodeOptions) -> Result<i128> {
+    let major = read_major(r).await?;
+    if major.kind() != MajorKind::ByteString {
+        return Err(UnexpectedCode::new::<i128>(major.into()).into());
+    }
+    decode::bignum_from_bytes(&read_ipld_string(r, major, opts).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DagCborCodec;
+    use futures_lite::future::block_on;
+    use libipld_core::codec::Codec;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_macro::ipld;
+    use std::io;
+    use std::task::{Context, Poll};
+
+    /// A reader that yields a single byte at a time and is pending before every byte.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        pending: bool,
+    }
+
+    impl<'a> Trickle<'a> {
+        fn new(bytes: &'a [u8]) -> Self {
+            Self {
+                bytes,
+                pending: true,
+            }
+        }
+    }
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            if self.pending {
+                self.pending = false;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.pending = true;
+            match (self.bytes.split_first(), buf.first_mut()) {
+                (Some((byte, rest)), Some(out)) => {
+                    *out = *byte;
+                    self.bytes = rest;
+                    Poll::Ready(Ok(1))
+                }
+                _ => Poll::Ready(Ok(0)),
+            }
+        }
+    }
+
+    fn decode_trickle<T: AsyncDecode>(bytes: &[u8]) -> Result<T> {
+        block_on(decode(&mut Trickle::new(bytes)))
+    }
+
+    #[test]
+    fn ipld() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(&b"cid"[..]));
+        let ipld = ipld!({
+          "number": -300,
+          "list": [true, null, 1.5, u64::MAX],
+          "bytes": vec![0, 1, 2, 3],
+          "map": { "string": "hello", "link": cid },
+        });
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        assert_eq!(decode_trickle::<Ipld>(&bytes).unwrap(), ipld);
+
+        // Indefinite-length items and bignums.
+        let bytes = [
+            0x9f, // Start indefinite-length list
+            0x7f, 0x61, 0x61, 0x61, 0x62, 0xff, // "ab"
+            0xc3, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // -2^64 - 1
+            0xff, // "break"
+        ];
+        assert_eq!(
+            decode_trickle::<Ipld>(&bytes).unwrap(),
+            ipld!(["ab", -(u64::MAX as i128) - 2])
+        );
+    }
+
+    #[test]
+    fn typed() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(&b"cid"[..]));
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), vec![Some(cid), None]);
+        let bytes = DagCborCodec.encode(&map).unwrap();
+        assert_eq!(
+            decode_trickle::<BTreeMap<String, Vec<Option<Cid>>>>(&bytes).unwrap(),
+            map
+        );
+
+        let bytes = DagCborCodec.encode(&-1i64).unwrap();
+        assert_eq!(decode_trickle::<i8>(&bytes).unwrap(), -1);
+        decode_trickle::<u8>(&bytes)
+            .expect_err("should have failed to decode a negative number")
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+        assert_eq!(decode_trickle::<f32>(&[0xf9, 0x3e, 0x00]).unwrap(), 1.5);
+        assert!(decode_trickle::<bool>(&[0xf5]).unwrap());
+        decode_trickle::<Option<bool>>(&[0xf7])
+            .expect_err("should have failed to decode undefined")
+            .downcast::<Undefined>()
+            .unwrap();
+
+        // A link tag with a two byte head.
+        let mut bytes = DagCborCodec.encode(&cid).unwrap();
+        bytes.splice(..2, [0xd9, 0x00, 0x2a]);
+        assert_eq!(decode_trickle::<Cid>(&bytes).unwrap(), cid);
+        assert_eq!(decode_trickle::<Ipld>(&bytes).unwrap(), Ipld::Link(cid));
+    }
+
+    #[test]
+    fn errors() {
+        decode_trickle::<String>(&[0x65, 0x68, 0x65])
+            .expect_err("should have failed to decode a truncated string")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        decode_trickle::<Vec<u8>>(&[0x82, 0x01])
+            .expect_err("should have failed to decode a truncated list")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        // Like the sync decoder, integers may have a longer head than needed, lengths may not.
+        assert_eq!(decode_trickle::<u64>(&[0x18, 0x01]).unwrap(), 1);
+        assert_eq!(
+            decode_trickle::<Ipld>(&[0x39, 0x00, 0x01]).unwrap(),
+            Ipld::Integer(-2)
+        );
+        decode_trickle::<String>(&[0x78, 0x01, 0x61])
+            .expect_err("should have failed to decode a non-minimal length")
+            .downcast::<crate::error::NumberNotMinimal>()
+            .unwrap();
+        decode_trickle::<Ipld>(&[0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02])
+            .expect_err("should have failed to decode duplicate keys")
+            .downcast::<DuplicateKey>()
+            .unwrap();
+        block_on(decode::<Ipld, _>(&mut &vec![0x81; 10_000][..]))
+            .expect_err("should have failed to decode deeply nested lists")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+    }
+
+    fn read_trickle(bytes: &[u8], opts: &DecodeOptions) -> Result<Ipld> {
+        block_on(read_ipld(&mut Trickle::new(bytes), opts))
+    }
+
+    #[test]
+    fn options() {
+        let opts = DecodeOptions {
+            max_depth: 1,
+            ..Default::default()
+        };
+        assert_eq!(read_trickle(&[0x81, 0x01], &opts).unwrap(), ipld!([1]));
+        read_trickle(&[0x81, 0x81, 0x01], &opts)
+            .expect_err("should have failed to decode nested lists")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+        read_trickle(&[0xa1, 0x61, 0x61, 0xa0], &opts)
+            .expect_err("should have failed to decode nested maps")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+
+        let opts = DecodeOptions {
+            max_string_len: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            read_trickle(&[0x62, 0x61, 0x62], &opts).unwrap(),
+            ipld!("ab")
+        );
+        let err = read_trickle(&[0x63, 0x61, 0x62, 0x63], &opts)
+            .expect_err("should have failed to decode a long string")
+            .downcast::<MaxStringLenExceeded>()
+            .unwrap();
+        assert_eq!(err.0, 2);
+        read_trickle(&[0x7f, 0x62, 0x61, 0x62, 0x61, 0x63, 0xff], &opts)
+            .expect_err("should have failed to decode long string chunks")
+            .downcast::<MaxStringLenExceeded>()
+            .unwrap();
+        read_trickle(&[0xa1, 0x63, 0x61, 0x62, 0x63, 0x01], &opts)
+            .expect_err("should have failed to decode a long map key")
+            .downcast::<MaxStringLenExceeded>()
+            .unwrap();
+        // Byte strings have their own limit.
+        assert_eq!(
+            read_trickle(&[0x43, 0x01, 0x02, 0x03], &opts).unwrap(),
+            Ipld::Bytes(vec![1, 2, 3])
+        );
+
+        let opts = DecodeOptions {
+            max_bytes_len: 2,
+            ..Default::default()
+        };
+        // The length is checked before reading the payload, which isn't there.
+        read_trickle(
+            &[0x5b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            &opts,
+        )
+        .expect_err("should have failed to decode long bytes")
+        .downcast::<MaxStringLenExceeded>()
+        .unwrap();
+        read_trickle(&[0x5f, 0x42, 0x01, 0x02, 0x41, 0x03, 0xff], &opts)
+            .expect_err("should have failed to decode long byte chunks")
+            .downcast::<MaxStringLenExceeded>()
+            .unwrap();
+        read_trickle(&[0xc2, 0x43, 0x01, 0x00, 0x00], &opts)
+            .expect_err("should have failed to decode a long bignum")
+            .downcast::<MaxStringLenExceeded>()
+            .unwrap();
+
+        let opts = DecodeOptions {
+            max_collection_len: 1,
+            ..Default::default()
+        };
+        assert_eq!(read_trickle(&[0x81, 0x01], &opts).unwrap(), ipld!([1]));
+        read_trickle(&[0x82, 0x01, 0x02], &opts)
+            .expect_err("should have failed to decode a long list")
+            .downcast::<MaxCollectionLenExceeded>()
+            .unwrap();
+        read_trickle(&[0x9f, 0x01, 0x02, 0xff], &opts)
+            .expect_err("should have failed to decode a long indefinite-length list")
+            .downcast::<MaxCollectionLenExceeded>()
+            .unwrap();
+        read_trickle(&[0xa2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x02], &opts)
+            .expect_err("should have failed to decode a long map")
+            .downcast::<MaxCollectionLenExceeded>()
+            .unwrap();
+        read_trickle(&[0xbf, 0x61, 0x61, 0x01, 0x61, 0x62, 0x02, 0xff], &opts)
+            .expect_err("should have failed to decode a long indefinite-length map")
+            .downcast::<MaxCollectionLenExceeded>()
+            .unwrap();
+    }
+
+    #[test]
+    fn strict() {
+        let strict = |bytes: &[u8]| read_trickle(bytes, &DecodeOptions::strict());
+        strict(&[0x18, 0x01])
+            .expect_err("should have failed to decode a non-minimal integer")
+            .downcast::<crate::error::NumberNotMinimal>()
+            .unwrap();
+        strict(&[0x9f, 0xff])
+            .expect_err("should have failed to decode an indefinite-length list")
+            .downcast::<IndefiniteLength>()
+            .unwrap();
+        strict(&[0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02])
+            .expect_err("should have failed to decode unordered keys")
+            .downcast::<UnorderedKeys>()
+            .unwrap();
+        strict(&[0xc2, 0x41, 0x01])
+            .expect_err("should have failed to decode a bignum")
+            .downcast::<UnknownTag>()
+            .unwrap();
+        strict(&[0xf9, 0x3e, 0x00])
+            .expect_err("should have failed to decode a half-precision float")
+            .downcast::<FloatNotF64>()
+            .unwrap();
+
+        let lenient = DecodeOptions {
+            allow_duplicate_keys: true,
+            allow_unknown_tags: true,
+            allow_undefined: true,
+            lossy_utf8: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            read_trickle(&[0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02], &lenient).unwrap(),
+            ipld!({ "a": 2 })
+        );
+        assert_eq!(read_trickle(&[0xc1, 0x01], &lenient).unwrap(), ipld!(1));
+        assert_eq!(read_trickle(&[0xf7], &lenient).unwrap(), Ipld::Null);
+        assert_eq!(
+            read_trickle(&[0x61, 0xff], &lenient).unwrap(),
+            ipld!("\u{fffd}")
+        );
+        read_trickle(&[0xf7], &DecodeOptions::default())
+            .expect_err("should have failed to decode undefined")
+            .downcast::<Undefined>()
+            .unwrap();
+    }
+}
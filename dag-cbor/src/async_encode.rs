@@ -0,0 +1,357 @@
+//! Async CBOR encoder.
+//!
+//! Mirrors the sync encoder for types that are written to an `AsyncWrite`, e.g. a network stream,
+//! without buffering the whole block first. The bytes are computed by the sync encoder, only
+//! writing them is async, hence both produce the same output.
+use crate::async_decode::BoxFuture;
+use crate::cbor::{ByteBuf, KeyOrder, MajorKind, FALSE, NULL, TRUE};
+use crate::encode::{self, float_head, int_head, ordered_entries, EncodeOptions};
+use crate::error::NumberOutOfRange;
+use futures_lite::io::{AsyncWrite, AsyncWriteExt};
+use libipld_core::cid::Cid;
+use libipld_core::error::Result;
+use libipld_core::ipld::Ipld;
+use std::collections::BTreeMap;
+
+/// Async version of `Encode<DagCborCodec>`.
+///
+/// The futures are boxed, so that nested types like `Ipld` can be encoded recursively.
+pub trait AsyncEncode: Send + Sync {
+    /// Encodes the item.
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()>;
+}
+
+/// Encodes an item into an `impl AsyncWrite`.
+///
+/// The writer isn't flushed, the same as for the sync encoder.
+pub async fn encode<T, W>(w: &mut W, value: &T) -> Result<()>
+where
+    T: AsyncEncode + ?Sized,
+    W: AsyncWrite + Unpin + Send,
+{
+    value.encode(w).await
+}
+
+/// Writes all of `buf` to an async byte stream.
+async fn write_all<W: AsyncWrite + Unpin>(w: &mut W, buf: &[u8]) -> Result<()> {
+    w.write_all(buf).await?;
+    Ok(())
+}
+
+/// Writes a null byte to an async byte stream.
+pub async fn write_null<W: AsyncWrite + Unpin>(w: &mut W) -> Result<()> {
+    write_all(w, &[NULL.into()]).await
+}
+
+/// Writes a u64 to an async byte stream.
+pub async fn write_u64<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    major: MajorKind,
+    value: u64,
+) -> Result<()> {
+    write_all(w, &encode::head(major, value)).await
+}
+
+/// Writes a tag to an async byte stream.
+pub async fn write_tag<W: AsyncWrite + Unpin>(w: &mut W, tag: u64) -> Result<()> {
+    write_u64(w, MajorKind::Tag, tag).await
+}
+
+impl AsyncEncode for bool {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        let buf = if *self { [TRUE.into()] } else { [FALSE.into()] };
+        Box::pin(async move { write_all(w, &buf).await })
+    }
+}
+
+macro_rules! impl_num {
+    (unsigned $($t:ty),*) => {
+        $(
+            impl AsyncEncode for $t {
+                fn encode<'a, W: AsyncWrite + Unpin + Send>(
+                    &'a self,
+                    w: &'a mut W,
+                ) -> BoxFuture<'a, ()> {
+                    Box::pin(async move {
+                        write_u64(w, MajorKind::UnsignedInt, (*self).into()).await
+                    })
+                }
+            }
+        )*
+    };
+    (signed $($t:ty),*) => {
+        $(
+            impl AsyncEncode for $t {
+                fn encode<'a, W: AsyncWrite + Unpin + Send>(
+                    &'a self,
+                    w: &'a mut W,
+                ) -> BoxFuture<'a, ()> {
+                    Box::pin(async move {
+                        write_all(w, &int_head((*self).into())?).await
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_num!(unsigned u8, u16, u32, u64);
+impl_num!(signed i8, i16, i32, i64, i128);
+
+impl AsyncEncode for u128 {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let value = u64::try_from(*self).map_err(|_| NumberOutOfRange::new::<u128>())?;
+            write_u64(w, MajorKind::UnsignedInt, value).await
+        })
+    }
+}
+
+impl AsyncEncode for f32 {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        // IPLD maximally encodes floats.
+        Box::pin(async move {
+            write_all(w, &float_head((*self).into(), &EncodeOptions::default())?).await
+        })
+    }
+}
+
+impl AsyncEncode for f64 {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        Box::pin(async move { write_all(w, &float_head(*self, &EncodeOptions::default())?).await })
+    }
+}
+
+impl AsyncEncode for [u8] {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            write_u64(w, MajorKind::ByteString, self.len() as u64).await?;
+            write_all(w, self).await
+        })
+    }
+}
+
+impl AsyncEncode for ByteBuf {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        self[..].encode(w)
+    }
+}
+
+impl AsyncEncode for str {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            write_u64(w, MajorKind::TextString, self.len() as u64).await?;
+            write_all(w, self.as_bytes()).await
+        })
+    }
+}
+
+impl AsyncEncode for String {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        self.as_str().encode(w)
+    }
+}
+
+impl AsyncEncode for Cid {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            write_tag(w, 42).await?;
+            // insert zero byte per https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#links
+            let buf = self.to_bytes();
+            write_u64(w, MajorKind::ByteString, buf.len() as u64 + 1).await?;
+            write_all(w, &[0]).await?;
+            write_all(w, &buf).await
+        })
+    }
+}
+
+/// `None` is written as null and `Some` as the value itself, like the sync encoder does.
+impl<T: AsyncEncode> AsyncEncode for Option<T> {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        match self {
+            Some(value) => value.encode(w),
+            None => Box::pin(write_null(w)),
+        }
+    }
+}
+
+impl<T: AsyncEncode> AsyncEncode for Vec<T> {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            write_u64(w, MajorKind::Array, self.len() as u64).await?;
+            for value in self {
+                value.encode(w).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<T: AsyncEncode> AsyncEncode for BTreeMap<String, T> {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let entries = ordered_entries(self, KeyOrder::DagCbor);
+            write_u64(w, MajorKind::Map, entries.len() as u64).await?;
+            for (key, value) in entries {
+                key.encode(w).await?;
+                value.encode(w).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl AsyncEncode for Ipld {
+    fn encode<'a, W: AsyncWrite + Unpin + Send>(&'a self, w: &'a mut W) -> BoxFuture<'a, ()> {
+        match self {
+            Self::Null => Box::pin(write_null(w)),
+            Self::Bool(b) => b.encode(w),
+            Self::Integer(i) => i.encode(w),
+            Self::Float(f) => f.encode(w),
+            Self::Bytes(b) => b[..].encode(w),
+            Self::String(s) => s.encode(w),
+            Self::List(l) => l.encode(w),
+            Self::Map(m) => Box::pin(async move {
+                let entries = ordered_entries(m, KeyOrder::DagCbor);
+                write_u64(w, MajorKind::Map, entries.len() as u64).await?;
+                for (key, value) in entries {
+                    key.encode(w).await?;
+                    value.encode(w).await?;
+                }
+                Ok(())
+            }),
+            Self::Link(cid) => cid.encode(w),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::NonFiniteFloat;
+    use crate::DagCborCodec;
+    use futures_lite::future::block_on;
+    use libipld_core::codec::{Codec, Encode};
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_macro::ipld;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// A writer that accepts up to three bytes at a time and is pending before every write.
+    #[derive(Default)]
+    struct Trickle {
+        bytes: Vec<u8>,
+        pending: bool,
+    }
+
+    impl AsyncWrite for Trickle {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if !self.pending {
+                self.pending = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.pending = false;
+            let n = buf.len().min(3);
+            self.bytes.extend_from_slice(&buf[..n]);
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn encode_trickle<T: AsyncEncode + ?Sized>(value: &T) -> Result<Vec<u8>> {
+        let mut w = Trickle::default();
+        block_on(encode(&mut w, value))?;
+        Ok(w.bytes)
+    }
+
+    /// Checks that the async encoder writes the same bytes as the sync one.
+    fn assert_same<T: AsyncEncode + Encode<DagCborCodec>>(value: &T) {
+        assert_eq!(
+            encode_trickle(value).unwrap(),
+            DagCborCodec.encode(value).unwrap()
+        );
+    }
+
+    #[test]
+    fn ipld() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(&b"cid"[..]));
+        assert_same(&ipld!({
+          "number": -300,
+          "list": [true, null, 1.5, u64::MAX, -(u64::MAX as i128) - 1],
+          "bytes": vec![0, 1, 2, 3],
+          "map": { "string": "hello", "link": cid, "longer key": {} },
+        }));
+    }
+
+    #[test]
+    fn typed() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(&b"cid"[..]));
+        let mut map = BTreeMap::new();
+        map.insert("bb".to_string(), vec![Some(cid), None]);
+        map.insert("c".to_string(), vec![]);
+        assert_same(&map);
+
+        for n in [
+            0u64,
+            23,
+            24,
+            255,
+            256,
+            65535,
+            65536,
+            u32::MAX as u64,
+            u64::MAX,
+        ] {
+            assert_same(&n);
+            assert_same(&(-(n as i128) - 1));
+        }
+        assert_same(&u8::MAX);
+        assert_same(&i8::MIN);
+        assert_same(&i16::MIN);
+        assert_same(&i32::MIN);
+        assert_same(&i64::MIN);
+        assert_same(&u128::from(u64::MAX));
+        assert_same(&0.5f32);
+        assert_same(&-1.1f64);
+        assert_same(&false);
+        assert_same(&"hello".to_string());
+        assert_same(&ByteBuf(vec![1, 2, 3]));
+        assert_eq!(
+            encode_trickle("hello").unwrap(),
+            [0x65, b'h', b'e', b'l', b'l', b'o']
+        );
+    }
+
+    #[test]
+    fn errors() {
+        encode_trickle(&f64::NAN)
+            .expect_err("should have failed to encode NaN")
+            .downcast::<NonFiniteFloat>()
+            .unwrap();
+        encode_trickle(&ipld!([Ipld::Float(f64::INFINITY)]))
+            .expect_err("should have failed to encode an infinite float")
+            .downcast::<NonFiniteFloat>()
+            .unwrap();
+        encode_trickle(&Ipld::Integer(i128::MIN))
+            .expect_err("should have failed to encode a large integer")
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+        encode_trickle(&u128::MAX)
+            .expect_err("should have failed to encode a large integer")
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+    }
+}
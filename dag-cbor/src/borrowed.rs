@@ -0,0 +1,310 @@
+//! Zero-copy decoding into borrowed `Ipld`.
+use crate::cbor::{Major, MajorKind, BREAK, F16, F32, F64, FALSE, NULL, TRUE};
+use crate::decode::{
+    locate, read_bignum, read_break, read_f16, read_f32, read_f64, read_len, read_link, read_major,
+    read_next, read_tag, read_uint, under, utf8_str, DecodeOptions, SliceDecoder,
+};
+use crate::error::{
+    DuplicateKey, IndefiniteLength, LengthOutOfRange, MaxDepthExceeded, TrailingBytes,
+    UnexpectedCode, UnexpectedEof, UnknownTag,
+};
+use core::convert::TryFrom;
+use libipld_core::cid::Cid;
+use libipld_core::error::{Result, TypeError, TypeErrorType};
+use libipld_core::ipld::{Ipld, IpldIndex};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+/// Borrowed version of [`Ipld`].
+///
+/// Strings and byte strings point into the decoded bytes instead of being copied, which makes
+/// inspecting a few fields of a large block cheap. Indefinite-length strings consist of several
+/// chunks and therefore can't be borrowed, decoding them fails with [`IndefiniteLength`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum IpldRef<'a> {
+    /// Represents the absence of a value or the value undefined.
+    Null,
+    /// Represents a boolean value.
+    Bool(bool),
+    /// Represents an integer.
+    Integer(i128),
+    /// Represents a floating point value.
+    Float(f64),
+    /// Represents an UTF-8 string.
+    String(&'a str),
+    /// Represents a sequence of bytes.
+    Bytes(&'a [u8]),
+    /// Represents a list.
+    List(Vec<IpldRef<'a>>),
+    /// Represents a map of strings.
+    Map(BTreeMap<&'a str, IpldRef<'a>>),
+    /// Represents a link.
+    Link(Cid),
+}
+
+impl<'a> IpldRef<'a> {
+    /// Decodes a single item that has to span all of `bytes`.
+    ///
+    /// Accepts the same input as decoding `Ipld` with the default options, apart from
+    /// indefinite-length strings.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self> {
+        let mut decoder = SliceDecoder::new(bytes);
+        let ipld = decoder.decode_ref()?;
+        if !decoder.is_empty() {
+            return Err(TrailingBytes(decoder.remaining().len()).into());
+        }
+        Ok(ipld)
+    }
+
+    /// Indexes into a list or map.
+    pub fn get<'b, T: Into<IpldIndex<'b>>>(&self, index: T) -> Result<&Self, TypeError> {
+        let index = index.into();
+        let ipld = match self {
+            Self::List(l) => match index {
+                IpldIndex::List(i) => Some(i),
+                IpldIndex::Map(ref key) => key.parse().ok(),
+                IpldIndex::MapRef(key) => key.parse().ok(),
+            }
+            .map(|i| l.get(i)),
+            Self::Map(m) => match index {
+                IpldIndex::Map(ref key) => Some(m.get(key.as_str())),
+                IpldIndex::MapRef(key) => Some(m.get(key)),
+                IpldIndex::List(i) => Some(m.get(i.to_string().as_str())),
+            },
+            _ => None,
+        };
+        ipld.unwrap_or_default()
+            .ok_or_else(|| TypeError::new(index, self))
+    }
+
+    /// Follows the segments of a path, e.g. the ones of `libipld::Path::iter`.
+    pub fn get_path<'b, I: IntoIterator<Item = &'b str>>(
+        &self,
+        path: I,
+    ) -> Result<&Self, TypeError> {
+        path.into_iter()
+            .try_fold(self, |ipld, segment| ipld.get(segment))
+    }
+
+    /// Copies the borrowed data into an owned [`Ipld`].
+    pub fn to_owned(&self) -> Ipld {
+        match self {
+            Self::Null => Ipld::Null,
+            Self::Bool(b) => Ipld::Bool(*b),
+            Self::Integer(i) => Ipld::Integer(*i),
+            Self::Float(f) => Ipld::Float(*f),
+            Self::String(s) => Ipld::String(s.to_string()),
+            Self::Bytes(b) => Ipld::Bytes(b.to_vec()),
+            Self::List(l) => Ipld::List(l.iter().map(Self::to_owned).collect()),
+            Self::Map(m) => Ipld::Map(
+                m.iter()
+                    .map(|(key, value)| (key.to_string(), value.to_owned()))
+                    .collect(),
+            ),
+            Self::Link(cid) => Ipld::Link(*cid),
+        }
+    }
+}
+
+impl From<&IpldRef<'_>> for TypeErrorType {
+    fn from(ipld: &IpldRef<'_>) -> Self {
+        match ipld {
+            IpldRef::Null => Self::Null,
+            IpldRef::Bool(_) => Self::Bool,
+            IpldRef::Integer(_) => Self::Integer,
+            IpldRef::Float(_) => Self::Float,
+            IpldRef::String(_) => Self::String,
+            IpldRef::Bytes(_) => Self::Bytes,
+            IpldRef::List(_) => Self::List,
+            IpldRef::Map(_) => Self::Map,
+            IpldRef::Link(_) => Self::Link,
+        }
+    }
+}
+
+/// Reads borrowed `Ipld` that is nested in `depth` lists or maps. Errors have the location of the
+/// item that failed to decode attached.
+pub(crate) fn read_ipld_ref<'a>(r: &mut Cursor<&'a [u8]>, depth: usize) -> Result<IpldRef<'a>> {
+    let offset = r.position();
+    read_item(r, depth).map_err(|err| locate(err, offset))
+}
+
+fn read_item<'a>(r: &mut Cursor<&'a [u8]>, depth: usize) -> Result<IpldRef<'a>> {
+    let max_depth = DecodeOptions::default().max_depth;
+    let major = read_major(r)?;
+    let ipld = match major.kind() {
+        MajorKind::UnsignedInt => IpldRef::Integer(read_uint(r, major)? as i128),
+        MajorKind::NegativeInt => IpldRef::Integer(-1 - read_uint(r, major)? as i128),
+        MajorKind::ByteString => IpldRef::Bytes(read_borrowed(r, major)?),
+        MajorKind::TextString => IpldRef::String(utf8_str(read_borrowed(r, major)?)?),
+        MajorKind::Array | MajorKind::Map if depth >= max_depth => {
+            return Err(MaxDepthExceeded(max_depth).into());
+        }
+        MajorKind::Array => {
+            let mut remaining = read_len(r, major)?;
+            let mut list = Vec::new();
+            while read_next(r, &mut remaining)? {
+                let value = read_ipld_ref(r, depth + 1).map_err(|err| under(err, &list.len()))?;
+                list.push(value);
+            }
+            IpldRef::List(list)
+        }
+        MajorKind::Map => {
+            let mut remaining = read_len(r, major)?;
+            let mut map = BTreeMap::new();
+            while read_next(r, &mut remaining)? {
+                let offset = r.position();
+                let key =
+                    read_key(r, remaining.is_none(), &map).map_err(|err| locate(err, offset))?;
+                let value = read_ipld_ref(r, depth + 1).map_err(|err| under(err, &key))?;
+                map.insert(key, value);
+            }
+            IpldRef::Map(map)
+        }
+        MajorKind::Tag => match read_tag(r, major, false)? {
+            42 => IpldRef::Link(read_link(r)?),
+            2 => IpldRef::Integer(read_bignum(r)?),
+            3 => IpldRef::Integer(-1 - read_bignum(r)?),
+            value => return Err(UnknownTag(value).into()),
+        },
+        MajorKind::Other => match major {
+            FALSE => IpldRef::Bool(false),
+            TRUE => IpldRef::Bool(true),
+            NULL => IpldRef::Null,
+            F16 => IpldRef::Float(read_f16(r)?),
+            F32 => IpldRef::Float(read_f32(r)? as f64),
+            F64 => IpldRef::Float(read_f64(r)?),
+            m => return Err(UnexpectedCode::new::<IpldRef>(m.into()).into()),
+        },
+    };
+    Ok(ipld)
+}
+
+/// Reads a map key and checks that it wasn't read before.
+fn read_key<'a>(
+    r: &mut Cursor<&'a [u8]>,
+    indefinite: bool,
+    map: &BTreeMap<&'a str, IpldRef<'a>>,
+) -> Result<&'a str> {
+    let major = read_major(r)?;
+    if major.kind() != MajorKind::TextString {
+        return Err(UnexpectedCode::new::<&str>(major.into()).into());
+    }
+    let key = utf8_str(read_borrowed(r, major)?)?;
+    // A key without a value.
+    if indefinite && read_break(r)? {
+        return Err(UnexpectedCode::new::<IpldRef>(BREAK.into()).into());
+    }
+    if map.contains_key(key) {
+        return Err(DuplicateKey(key.to_string()).into());
+    }
+    Ok(key)
+}
+
+/// Returns the payload of a definite-length string without copying it.
+fn read_borrowed<'a>(r: &mut Cursor<&'a [u8]>, major: Major) -> Result<&'a [u8]> {
+    if major.is_indefinite() {
+        return Err(IndefiniteLength.into());
+    }
+    let len = read_uint(r, major)?;
+    let bytes: &'a [u8] = r.get_ref();
+    let start = usize::try_from(r.position()).map_err(|_| LengthOutOfRange::new::<usize>())?;
+    let rest = bytes.get(start..).unwrap_or_default();
+    let len = usize::try_from(len).map_err(|_| LengthOutOfRange::new::<usize>())?;
+    if len > rest.len() {
+        return Err(UnexpectedEof {
+            expected: len as u64,
+            got: rest.len() as u64,
+        }
+        .into());
+    }
+    r.set_position((start + len) as u64);
+    Ok(&rest[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{InvalidUtf8, Location};
+    use crate::DagCborCodec;
+    use libipld_core::codec::Codec;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_macro::ipld;
+
+    #[test]
+    fn roundtrip() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(&b"cid"[..]));
+        let ipld = ipld!({
+          "number": -1,
+          "list": [true, null, 1.5],
+          "bytes": vec![0, 1, 2, 3],
+          "map": { "string": "hello", "link": cid },
+        });
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        let ipld_ref = IpldRef::decode(&bytes).unwrap();
+        assert_eq!(ipld_ref.to_owned(), ipld);
+
+        // The strings point into the input.
+        let range = bytes.as_ptr_range();
+        match ipld_ref.get("bytes").unwrap() {
+            IpldRef::Bytes(b) => assert!(range.contains(&b.as_ptr())),
+            other => panic!("unexpected {:?}", other),
+        }
+        match ipld_ref.get_path(["map", "string"]).unwrap() {
+            IpldRef::String(s) => {
+                assert_eq!(*s, "hello");
+                assert!(range.contains(&s.as_ptr()));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(
+            ipld_ref.get_path("list/2".split('/')).unwrap(),
+            &IpldRef::Float(1.5)
+        );
+        assert_eq!(ipld_ref.get_path(None).unwrap(), &ipld_ref);
+        ipld_ref
+            .get_path(["map", "missing"])
+            .expect_err("should have failed to follow a missing key");
+        ipld_ref
+            .get_path(["number", "0"])
+            .expect_err("should have failed to index into an integer");
+    }
+
+    #[test]
+    fn slice_decoder() {
+        let bytes = [0x42, 0x01, 0x02, 0x61, 0x61];
+        let mut decoder = SliceDecoder::new(&bytes);
+        assert_eq!(decoder.decode_ref().unwrap(), IpldRef::Bytes(&[1, 2]));
+        assert_eq!(decoder.decode_ref().unwrap(), IpldRef::String("a"));
+        assert!(decoder.is_empty());
+
+        IpldRef::decode(&bytes)
+            .expect_err("should have failed to decode trailing bytes")
+            .downcast::<TrailingBytes>()
+            .unwrap();
+    }
+
+    #[test]
+    fn errors() {
+        let err = IpldRef::decode(&[0x82, 0x01, 0x63, 0x61])
+            .expect_err("should have failed to decode a truncated string");
+        assert_eq!(err.downcast_ref::<Location>().unwrap().path, vec!["1"]);
+        err.downcast::<UnexpectedEof>().unwrap();
+        IpldRef::decode(&[0x5f, 0x41, 0x01, 0xff])
+            .expect_err("should have failed to borrow an indefinite-length string")
+            .downcast::<IndefiniteLength>()
+            .unwrap();
+        IpldRef::decode(&[0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02])
+            .expect_err("should have failed to decode duplicate keys")
+            .downcast::<DuplicateKey>()
+            .unwrap();
+        IpldRef::decode(&[0x62, 0xff, 0xfe])
+            .expect_err("should have failed to decode invalid UTF-8")
+            .downcast::<InvalidUtf8>()
+            .unwrap();
+        IpldRef::decode(&vec![0x81; 10_000])
+            .expect_err("should have failed to decode deeply nested lists")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+//! Read-ahead buffering for decoding from arbitrary readers.
+use libipld_core::error::Result;
+use std::io::{Read, Seek, SeekFrom};
+
+/// The size of the read-ahead buffer.
+const CAPACITY: usize = 4096;
+
+/// The size of the first read-ahead. Small items are often decoded from the middle of a larger
+/// stream, so the read-ahead starts small and doubles with every refill.
+const INITIAL_WINDOW: usize = 64;
+
+/// Buffers a reader while decoding, so that headers and small payloads are read from memory
+/// instead of one read call each.
+///
+/// The stream position is tracked without asking the underlying reader, which for a `File` would
+/// be a syscall per decoded item. Call [`Buffered::finish`] once done, which moves the underlying
+/// reader back to the end of what was decoded.
+pub(crate) struct Buffered<'a, R: Read + Seek> {
+    inner: &'a mut R,
+    /// Grows with the read-ahead window, so that decoding a small item stays cheap.
+    buf: Vec<u8>,
+    pos: usize,
+    filled: usize,
+    /// The position of the underlying reader, which is at the end of the buffered bytes.
+    offset: u64,
+    window: usize,
+}
+
+impl<'a, R: Read + Seek> Buffered<'a, R> {
+    /// Buffers `inner`, starting at its current position.
+    pub(crate) fn new(inner: &'a mut R) -> Result<Self> {
+        let offset = inner.stream_position()?;
+        Ok(Self {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+            filled: 0,
+            offset,
+            window: INITIAL_WINDOW,
+        })
+    }
+
+    /// Returns the position of the next byte that will be read.
+    fn position(&self) -> u64 {
+        self.offset - (self.filled - self.pos) as u64
+    }
+
+    /// Moves the underlying reader to the position of the next unread byte.
+    pub(crate) fn finish(self) -> Result<()> {
+        if self.pos != self.filled {
+            let position = self.position();
+            self.inner.seek(SeekFrom::Start(position))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, R: Read + Seek> Read for Buffered<'a, R> {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.filled {
+            // Large payloads bypass the buffer.
+            if out.len() >= self.window {
+                let n = self.inner.read(out)?;
+                self.offset += n as u64;
+                return Ok(n);
+            }
+            if self.buf.len() < self.window {
+                self.buf.resize(self.window, 0);
+            }
+            let n = self.inner.read(&mut self.buf[..self.window])?;
+            self.offset += n as u64;
+            self.pos = 0;
+            self.filled = n;
+            self.window = (self.window * 2).min(CAPACITY);
+        }
+        let n = out.len().min(self.filled - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for Buffered<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(target) => Some(target),
+            SeekFrom::Current(delta) => self.position().checked_add_signed(delta),
+            SeekFrom::End(_) => None,
+        };
+        let start = self.offset - self.filled as u64;
+        match target {
+            Some(target) if target >= start && target <= self.offset => {
+                self.pos = (target - start) as usize;
+                Ok(target)
+            }
+            _ => {
+                // Relative seeks are relative to the logical position, not the underlying one.
+                let pos = match (pos, target) {
+                    (SeekFrom::Current(_), Some(target)) => SeekFrom::Start(target),
+                    _ => pos,
+                };
+                self.offset = self.inner.seek(pos)?;
+                self.pos = 0;
+                self.filled = 0;
+                Ok(self.offset)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_and_seek() -> Result<()> {
+        let bytes: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        let mut cursor = Cursor::new(&bytes);
+        cursor.set_position(10);
+        let mut r = Buffered::new(&mut cursor)?;
+
+        let mut buf = [0; 4];
+        r.read_exact(&mut buf)?;
+        assert_eq!(buf, [10, 11, 12, 13]);
+        assert_eq!(r.stream_position()?, 14);
+        // Within the buffer.
+        r.seek(SeekFrom::Current(-2))?;
+        r.read_exact(&mut buf)?;
+        assert_eq!(buf, [12, 13, 14, 15]);
+        // Past the buffer.
+        r.seek(SeekFrom::Start(5000))?;
+        assert_eq!(r.stream_position()?, 5000);
+        r.read_exact(&mut buf)?;
+        assert_eq!(buf, [136, 137, 138, 139]);
+        // Large reads bypass the buffer.
+        let mut large = vec![0; 1000];
+        r.read_exact(&mut large)?;
+        assert_eq!(large[..], bytes[5004..6004]);
+        assert_eq!(r.stream_position()?, 6004);
+
+        r.finish()?;
+        assert_eq!(cursor.position(), 6004);
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 //! CBOR helper types for encoding and decoding.
+use std::cmp::Ordering;
 use std::convert::TryFrom;
+use std::ops::{Deref, DerefMut};
 
 use crate::error::UnexpectedCode;
 use libipld_core::ipld::Ipld;
@@ -15,12 +17,16 @@ pub const FALSE: Major = Major::new(MajorKind::Other, 20);
 pub const TRUE: Major = Major::new(MajorKind::Other, 21);
 /// The constant NULL.
 pub const NULL: Major = Major::new(MajorKind::Other, 22);
+/// The constant UNDEFINED.
+pub const UNDEFINED: Major = Major::new(MajorKind::Other, 23);
 /// The major "byte" indicating that a 16 bit float follows.
 pub const F16: Major = Major::new(MajorKind::Other, 25);
 /// The major "byte" indicating that a 32 bit float follows.
 pub const F32: Major = Major::new(MajorKind::Other, 26);
 /// The major "byte" indicating that a 64 bit float follows.
 pub const F64: Major = Major::new(MajorKind::Other, 27);
+/// The "break" stop code terminating an indefinite-length item.
+pub const BREAK: Major = Major::new(MajorKind::Other, 31);
 
 impl Major {
     const fn new(kind: MajorKind, info: u8) -> Self {
@@ -40,6 +46,12 @@ impl Major {
         self.0 & 0x1f
     }
 
+    /// Returns true if the additional info marks an indefinite-length item.
+    #[inline(always)]
+    pub const fn is_indefinite(self) -> bool {
+        self.info() == 31
+    }
+
     /// Interprets the additioanl info as a number of additional bytes that should be consumed.
     #[inline(always)]
     #[allow(clippy::len_without_is_empty)]
@@ -66,14 +78,21 @@ impl TryFrom<u8> for Major {
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         // We don't allow any major types with additional info 28-31 inclusive.
         // Or the bitmask 0b00011100 = 28.
-        if value & 28 == 28 {
-            return Err(UnexpectedCode::new::<Ipld>(value));
+        // The exceptions are indefinite-length strings, arrays and maps and the "break" stop code.
+        if value == u8::from(BREAK) {
+            return Ok(BREAK);
+        } else if value & 28 == 28 {
+            return match value {
+                // Indefinite-length byte strings, text strings, arrays and maps.
+                0x5f | 0x7f | 0x9f | 0xbf => Ok(Major(value)),
+                _ => Err(UnexpectedCode::new::<Ipld>(value)),
+            };
         } else if (value >> 5) == MajorKind::Other as u8 {
             match value & 0x1f {
-                // False, True, Null. TODO: Allow undefined?
-                20 | 21 | 22 => (),
+                // False, True, Null and Undefined.
+                20..=23 => (),
                 // Floats. TODO: forbid f16 & f32?
-                25 | 26 | 27 => (),
+                25..=27 => (),
                 // Everything is forbidden.
                 _ => {
                     return Err(UnexpectedCode::new::<Ipld>(value));
@@ -106,3 +125,89 @@ pub enum MajorKind {
     /// Other (major type 7).
     Other = 7,
 }
+
+/// The order of map keys, see [`EncodeOptions`](crate::encode::EncodeOptions) and
+/// [`DecodeOptions`](crate::decode::DecodeOptions).
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum KeyOrder {
+    /// Shorter keys first, keys of the same length in bytewise order, as required by DAG-CBOR.
+    ///
+    /// This is the canonical order of RFC 7049. For text string keys it is also the deterministic
+    /// order of RFC 8949, which compares the encoded keys, and those start with their length.
+    #[default]
+    DagCbor,
+    /// Bytewise order of the keys regardless of their length, e.g. `"aa"` before `"b"`.
+    Bytewise,
+    /// The order in which the map yields its entries, i.e. the order of insertion with the
+    /// `indexmap` feature. Keys aren't compared.
+    Insertion,
+}
+
+impl KeyOrder {
+    /// Compares two keys, or returns `None` if the order doesn't compare keys.
+    pub fn compare(self, a: &str, b: &str) -> Option<Ordering> {
+        match self {
+            Self::DagCbor => Some(
+                a.len()
+                    .cmp(&b.len())
+                    .then_with(|| a.as_bytes().cmp(b.as_bytes())),
+            ),
+            Self::Bytewise => Some(a.as_bytes().cmp(b.as_bytes())),
+            Self::Insertion => None,
+        }
+    }
+}
+
+/// A byte buffer that is encoded as a cbor byte string.
+///
+/// `Vec<u8>` is encoded as a list of integers like any other `Vec<T>`. Wrap it into a `ByteBuf`
+/// to encode and decode it as a byte string instead.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ByteBuf(pub Vec<u8>);
+
+impl Deref for ByteBuf {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ByteBuf {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<Vec<u8>> for ByteBuf {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<ByteBuf> for Vec<u8> {
+    fn from(bytes: ByteBuf) -> Self {
+        bytes.0
+    }
+}
+
+/// A borrowed slice that is encoded as a cbor list, like a `Vec<T>`.
+///
+/// `[u8]` is encoded as a byte string, hence `[T]` can't be encoded as a list in general. Wrap a
+/// slice into a `List` to encode it without copying it into a `Vec<T>` first.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct List<'a, T>(pub &'a [T]);
+
+impl<'a, T> Deref for List<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl<'a, T> From<&'a [T]> for List<'a, T> {
+    fn from(values: &'a [T]) -> Self {
+        Self(values)
+    }
+}
@@ -1,78 +1,225 @@
 //! CBOR decoder
-use crate::cbor::{Major, MajorKind, F32, F64, FALSE, NULL, TRUE};
+use crate::borrowed::{read_ipld_ref, IpldRef};
+use crate::buffered::Buffered;
+use crate::cbor::{
+    ByteBuf, KeyOrder, Major, MajorKind, BREAK, F16, F32, F64, FALSE, NULL, TRUE, UNDEFINED,
+};
 use crate::error::{
-    InvalidCidPrefix, LengthOutOfRange, NumberNotMinimal, NumberOutOfRange, UnexpectedCode,
-    UnexpectedEof, UnknownTag,
+    DuplicateKey, FloatNotF64, IndefiniteLength, InvalidCidPrefix, InvalidLinkType, InvalidUtf8,
+    LengthMismatch, LengthOutOfRange, Location, MaxCollectionLenExceeded, MaxDepthExceeded,
+    MaxStringLenExceeded, NonFiniteFloat, NumberNotMinimal, NumberOutOfRange, TrailingBytes,
+    Undefined, UnexpectedCode, UnexpectedEof, UnknownTag, UnorderedKeys, UnsupportedSimpleValue,
 };
 use crate::DagCborCodec as DagCbor;
 use byteorder::{BigEndian, ByteOrder};
+use core::cmp::Ordering;
 use core::convert::TryFrom;
 use libipld_core::codec::{Decode, References};
-use libipld_core::error::Result;
-use libipld_core::ipld::Ipld;
+use libipld_core::error::{Error, Result};
+use libipld_core::ipld::{Ipld, IpldMap};
 use libipld_core::{cid::Cid, raw_value::SkipOne};
-use std::collections::BTreeMap;
-use std::io::{Read, Seek, SeekFrom};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::rc::Rc;
 use std::sync::Arc;
 
+/// Fills `buf` from a byte stream. Running out of bytes is reported as [`UnexpectedEof`], so that
+/// a truncated input can be told apart from other IO errors.
+fn fill<R: Read>(r: &mut R, buf: &mut [u8]) -> Result<()> {
+    let mut got = 0;
+    while got < buf.len() {
+        match r.read(&mut buf[got..]) {
+            Ok(0) => {
+                return Err(UnexpectedEof {
+                    expected: buf.len() as u64,
+                    got: got as u64,
+                }
+                .into())
+            }
+            Ok(n) => got += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
 /// Reads a u8 from a byte stream.
 pub fn read_u8<R: Read>(r: &mut R) -> Result<u8> {
     let mut buf = [0; 1];
-    r.read_exact(&mut buf)?;
+    fill(r, &mut buf)?;
     Ok(buf[0])
 }
 
 /// Reads a u16 from a byte stream.
 pub fn read_u16<R: Read>(r: &mut R) -> Result<u16> {
     let mut buf = [0; 2];
-    r.read_exact(&mut buf)?;
+    fill(r, &mut buf)?;
     Ok(BigEndian::read_u16(&buf))
 }
 
 /// Reads a u32 from a byte stream.
 pub fn read_u32<R: Read>(r: &mut R) -> Result<u32> {
     let mut buf = [0; 4];
-    r.read_exact(&mut buf)?;
+    fill(r, &mut buf)?;
     Ok(BigEndian::read_u32(&buf))
 }
 
 /// Reads a u64 from a byte stream.
 pub fn read_u64<R: Read>(r: &mut R) -> Result<u64> {
     let mut buf = [0; 8];
-    r.read_exact(&mut buf)?;
+    fill(r, &mut buf)?;
     Ok(BigEndian::read_u64(&buf))
 }
 
+/// Reads a half-precision float from a byte stream and widens it to a f64.
+pub fn read_f16<R: Read>(r: &mut R) -> Result<f64> {
+    let bits = read_u16(r)?;
+    let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exp = i32::from((bits >> 10) & 0x1f);
+    let mant = f64::from(bits & 0x3ff);
+    let num = match exp {
+        // Zero and subnormals.
+        0 => mant * 2f64.powi(-24),
+        // Infinity and NaN.
+        31 if mant == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mant + 1024.0) * 2f64.powi(exp - 25),
+    };
+    Ok(sign * num)
+}
+
 /// Reads a f32 from a byte stream.
 pub fn read_f32<R: Read>(r: &mut R) -> Result<f32> {
     let mut buf = [0; 4];
-    r.read_exact(&mut buf)?;
+    fill(r, &mut buf)?;
     Ok(BigEndian::read_f32(&buf))
 }
 
 /// Reads a f64 from a byte stream.
 pub fn read_f64<R: Read>(r: &mut R) -> Result<f64> {
     let mut buf = [0; 8];
-    r.read_exact(&mut buf)?;
+    fill(r, &mut buf)?;
     Ok(BigEndian::read_f64(&buf))
 }
 
 /// Reads `len` number of bytes from a byte stream.
 pub fn read_bytes<R: Read>(r: &mut R, len: u64) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    append_bytes(r, len, &mut buf)?;
+    Ok(buf)
+}
+
+/// Reads `len` number of bytes from a byte stream into `buf`, replacing its contents.
+///
+/// The allocation of `buf` is reused, hence reading many byte strings into the same buffer only
+/// allocates when a byte string is longer than any before.
+pub fn read_bytes_into<R: Read>(r: &mut R, len: u64, buf: &mut Vec<u8>) -> Result<()> {
+    buf.clear();
+    append_bytes(r, len, buf)
+}
+
+/// Reads `len` number of bytes from a byte stream and appends them to `buf`.
+fn append_bytes<R: Read>(r: &mut R, len: u64, buf: &mut Vec<u8>) -> Result<()> {
     let len = usize::try_from(len).map_err(|_| LengthOutOfRange::new::<usize>())?;
     // Limit up-front allocations to 16KiB as the length is user controlled.
-    let mut buf = Vec::with_capacity(len.min(16 * 1024));
-    r.take(len as u64).read_to_end(&mut buf)?;
-    if buf.len() != len {
-        return Err(UnexpectedEof.into());
+    buf.reserve(len.min(16 * 1024));
+    let start = buf.len();
+    r.take(len as u64).read_to_end(buf)?;
+    let got = buf.len() - start;
+    if got != len {
+        return Err(UnexpectedEof {
+            expected: len as u64,
+            got: got as u64,
+        }
+        .into());
     }
+    Ok(())
+}
+
+/// Reads the chunks of an indefinite-length string of the given major type until the "break"
+/// stop code and concatenates them.
+///
+/// Every chunk must be a definite-length string of the same major type.
+pub fn read_chunks<R: Read>(r: &mut R, kind: MajorKind) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    read_chunks_into(r, kind, usize::MAX, &mut buf)?;
     Ok(buf)
 }
 
+/// Like [`read_chunks`], but appends to `buf` and fails with [`MaxStringLenExceeded`] once the
+/// concatenated chunks are longer than `max_len`.
+fn read_chunks_into<R: Read>(
+    r: &mut R,
+    kind: MajorKind,
+    max_len: usize,
+    buf: &mut Vec<u8>,
+) -> Result<()> {
+    loop {
+        let major = read_major(r)?;
+        if major == BREAK {
+            return Ok(());
+        }
+        if major.kind() != kind || major.is_indefinite() {
+            return Err(UnexpectedCode::new::<Vec<u8>>(major.into()).into());
+        }
+        let len = read_uint(r, major)?;
+        // The total length needs to fit into memory, just like a definite-length string.
+        let total = usize::try_from(len)
+            .ok()
+            .and_then(|len| len.checked_add(buf.len()))
+            .ok_or_else(LengthOutOfRange::new::<Vec<u8>>)?;
+        if total > max_len {
+            return Err(MaxStringLenExceeded(max_len).into());
+        }
+        append_bytes(r, len, buf)?;
+    }
+}
+
+/// Reads the payload of a byte string whose major has already been read. Both definite and
+/// indefinite-length byte strings are supported.
+pub fn read_byte_string<R: Read>(r: &mut R, major: Major) -> Result<Vec<u8>> {
+    if major.is_indefinite() {
+        read_chunks(r, MajorKind::ByteString)
+    } else {
+        let len = read_uint(r, major)?;
+        read_bytes(r, len)
+    }
+}
+
 /// Reads `len` number of bytes from a byte stream and converts them to a string.
 pub fn read_str<R: Read>(r: &mut R, len: u64) -> Result<String> {
     let bytes = read_bytes(r, len)?;
-    Ok(String::from_utf8(bytes)?)
+    utf8_string(bytes)
+}
+
+/// Converts the payload of a text string into a string, invalid UTF-8 is reported as
+/// [`InvalidUtf8`].
+pub(crate) fn utf8_string(bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes).map_err(|err| InvalidUtf8::from(err.utf8_error()).into())
+}
+
+/// Like [`utf8_string`], but borrows the payload.
+pub(crate) fn utf8_str(bytes: &[u8]) -> Result<&str> {
+    std::str::from_utf8(bytes).map_err(|err| InvalidUtf8::from(err).into())
+}
+
+/// Reads the payload of a text string whose major has already been read. Both definite and
+/// indefinite-length text strings are supported.
+///
+/// The UTF-8 validation of an indefinite-length text string happens on the concatenated chunks,
+/// hence a code point split across two chunks is accepted.
+pub fn read_text_string<R: Read>(r: &mut R, major: Major) -> Result<String> {
+    if major.is_indefinite() {
+        let bytes = read_chunks(r, MajorKind::TextString)?;
+        utf8_string(bytes)
+    } else {
+        let len = read_uint(r, major)?;
+        read_str(r, len)
+    }
 }
 
 /// Reads a list of any type that implements `TryReadCbor` from a stream of cbor encoded bytes.
@@ -81,7 +228,8 @@ pub fn read_list<R: Read + Seek, T: Decode<DagCbor>>(r: &mut R, len: u64) -> Res
     // Limit up-front allocations to 16KiB as the length is user controlled.
     //
     // Can't make this "const" because the generic, but it _should_ be known at compile time.
-    let max_alloc = (16 * 1024) / std::mem::size_of::<T>();
+    // Zero sized types don't allocate, hence they are treated as single bytes.
+    let max_alloc = (16 * 1024) / std::mem::size_of::<T>().max(1);
 
     let mut list: Vec<T> = Vec::with_capacity(len.min(max_alloc));
     for _ in 0..len {
@@ -90,7 +238,38 @@ pub fn read_list<R: Read + Seek, T: Decode<DagCbor>>(r: &mut R, len: u64) -> Res
     Ok(list)
 }
 
+/// Reads an indefinite-length list of any type that implements `TryReadCbor` from a stream of
+/// cbor encoded bytes. Items are read until the "break" stop code.
+///
+/// As the length isn't known up front, items are counted while reading and more than `max_len`
+/// of them are an error.
+pub fn read_list_il<R: Read + Seek, T: Decode<DagCbor>>(
+    r: &mut R,
+    max_len: usize,
+) -> Result<Vec<T>> {
+    let mut list: Vec<T> = Vec::new();
+    while !read_break(r)? {
+        if list.len() >= max_len {
+            return Err(MaxCollectionLenExceeded(max_len).into());
+        }
+        list.push(T::decode(DagCbor, r)?);
+    }
+    Ok(list)
+}
+
+/// Consumes the "break" stop code if it is the next byte. Otherwise the stream is left untouched.
+pub(crate) fn read_break<R: Read + Seek>(r: &mut R) -> Result<bool> {
+    if read_u8(r)? == u8::from(BREAK) {
+        Ok(true)
+    } else {
+        r.seek(SeekFrom::Current(-1))?;
+        Ok(false)
+    }
+}
+
 /// Reads a map of any type that implements `TryReadCbor` from a stream of cbor encoded bytes.
+///
+/// If a key occurs more than once, the last value wins.
 pub fn read_map<R: Read + Seek, K: Decode<DagCbor> + Ord, T: Decode<DagCbor>>(
     r: &mut R,
     len: u64,
@@ -105,18 +284,70 @@ pub fn read_map<R: Read + Seek, K: Decode<DagCbor> + Ord, T: Decode<DagCbor>>(
     Ok(map)
 }
 
+/// Reads an indefinite-length map of any type that implements `TryReadCbor` from a stream of cbor
+/// encoded bytes. Key/value pairs are read until the "break" stop code.
+///
+/// Entries are counted while reading and more than `max_len` of them are an error.
+pub fn read_map_il<R: Read + Seek, K: Decode<DagCbor> + Ord, T: Decode<DagCbor>>(
+    r: &mut R,
+    max_len: usize,
+) -> Result<BTreeMap<K, T>> {
+    let mut map: BTreeMap<K, T> = BTreeMap::new();
+    let mut entries = 0;
+    while !read_break(r)? {
+        if entries >= max_len {
+            return Err(MaxCollectionLenExceeded(max_len).into());
+        }
+        entries += 1;
+        let key = K::decode(DagCbor, r)?;
+        // A key without a value.
+        if read_break(r)? {
+            return Err(UnexpectedCode::new::<BTreeMap<K, T>>(BREAK.into()).into());
+        }
+        let value = T::decode(DagCbor, r)?;
+        map.insert(key, value);
+    }
+    Ok(map)
+}
+
+/// Reads the key/value pairs of a map whose major has already been read and passes each of them to
+/// `insert`. Both definite and indefinite-length maps are supported.
+///
+/// This allows decoding into any map container.
+pub fn read_map_into<R, K, T, F>(r: &mut R, major: Major, mut insert: F) -> Result<()>
+where
+    R: Read + Seek,
+    K: Decode<DagCbor>,
+    T: Decode<DagCbor>,
+    F: FnMut(K, T),
+{
+    let mut remaining = read_len(r, major)?;
+    while read_next(r, &mut remaining)? {
+        let key = K::decode(DagCbor, r)?;
+        // A key without a value.
+        if remaining.is_none() && read_break(r)? {
+            return Err(UnexpectedCode::new::<(K, T)>(BREAK.into()).into());
+        }
+        let value = T::decode(DagCbor, r)?;
+        insert(key, value);
+    }
+    Ok(())
+}
+
 /// Reads a cid from a stream of cbor encoded bytes.
-pub fn read_link<R: Read + Seek>(r: &mut R) -> Result<Cid> {
+pub fn read_link<R: Read>(r: &mut R) -> Result<Cid> {
     let major = read_major(r)?;
     if major.kind() != MajorKind::ByteString {
-        return Err(UnexpectedCode::new::<Cid>(major.into()).into());
+        return Err(InvalidLinkType(major.into()).into());
     }
     let len = read_uint(r, major)?;
     if len < 1 {
         return Err(LengthOutOfRange::new::<Cid>().into());
     }
 
-    let mut r = r.take(len);
+    // Read the whole byte string first, so that a truncated CID is reported as such.
+    let bytes = read_bytes(r, len)?;
+    let mut r = &bytes[..];
 
     // skip the first byte per
     // https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#links
@@ -125,11 +356,10 @@ pub fn read_link<R: Read + Seek>(r: &mut R) -> Result<Cid> {
         return Err(InvalidCidPrefix(prefix).into());
     }
 
-    // Read the CID. No need to limit the size, the CID will do this for us.
     let cid = Cid::read_bytes(&mut r)?;
 
     // Make sure we've read the entire CID.
-    if r.read(&mut [0u8][..])? != 0 {
+    if !r.is_empty() {
         return Err(LengthOutOfRange::new::<Cid>().into());
     }
 
@@ -137,14 +367,55 @@ pub fn read_link<R: Read + Seek>(r: &mut R) -> Result<Cid> {
 }
 
 /// Read a and validate major "byte". This includes both the major type and the additional info.
+///
+/// Unsupported simple values are reported as [`UnsupportedSimpleValue`]. The payload byte of a
+/// simple value that has one is consumed, so that the stream is positioned at the next item.
 pub fn read_major<R: Read>(r: &mut R) -> Result<Major> {
-    Ok(Major::try_from(read_u8(r)?)?)
+    let byte = read_u8(r)?;
+    match byte {
+        0xe0..=0xf3 => Err(UnsupportedSimpleValue(byte & 0x1f).into()),
+        0xf8 => Err(UnsupportedSimpleValue(read_u8(r)?).into()),
+        _ => Ok(Major::try_from(byte)?),
+    }
 }
 
 /// Read the uint argument to the given major type. This function errors if:
 /// 1. The major type doesn't expect an integer argument.
 /// 2. The integer argument is not "minimally" encoded per the IPLD spec.
 pub fn read_uint<R: Read>(r: &mut R, major: Major) -> Result<u64> {
+    match read_argument(r, major)? {
+        (value, true) => Ok(value),
+        (_, false) => Err(NumberNotMinimal.into()),
+    }
+}
+
+/// Reads the integer argument of the given major type, e.g. the value of an integer.
+///
+/// Like [`read_uint`], but a longer head than needed, e.g. `0x19 0x00 0x05` for 5, is accepted
+/// unless `strict` is set. Whether the value fits into a type is up to the caller.
+pub fn read_int<R: Read>(r: &mut R, major: Major, strict: bool) -> Result<u64> {
+    match read_argument(r, major)? {
+        (value, true) => Ok(value),
+        (value, false) if !strict => Ok(value),
+        (_, false) => Err(NumberNotMinimal.into()),
+    }
+}
+
+/// Reads the tag number of the given tag major.
+///
+/// Like [`read_uint`], but some encoders write the link tag 42 with a longer head than needed,
+/// e.g. `0xd9 0x00 0x2a`. Such a head is accepted unless `strict` is set, other tag numbers still
+/// need to be minimally encoded.
+pub fn read_tag<R: Read>(r: &mut R, major: Major, strict: bool) -> Result<u64> {
+    match read_argument(r, major)? {
+        (value, true) => Ok(value),
+        (42, false) if !strict => Ok(42),
+        (_, false) => Err(NumberNotMinimal.into()),
+    }
+}
+
+/// Reads the argument to the given major type, and returns whether it is minimally encoded.
+fn read_argument<R: Read>(r: &mut R, major: Major) -> Result<(u64, bool)> {
     const MAX_SHORT: u64 = 23;
     const MAX_1BYTE: u64 = u8::MAX as u64;
     const MAX_2BYTE: u64 = u16::MAX as u64;
@@ -152,26 +423,15 @@ pub fn read_uint<R: Read>(r: &mut R, major: Major) -> Result<u64> {
     if major.kind() == MajorKind::Other {
         return Err(UnexpectedCode::new::<u64>(major.into()).into());
     }
-    match major.info() {
-        value @ 0..=23 => Ok(value as u64),
-        24 => match read_u8(r)? as u64 {
-            0..=MAX_SHORT => Err(NumberNotMinimal.into()),
-            value => Ok(value),
-        },
-        25 => match read_u16(r)? as u64 {
-            0..=MAX_1BYTE => Err(NumberNotMinimal.into()),
-            value => Ok(value),
-        },
-        26 => match read_u32(r)? as u64 {
-            0..=MAX_2BYTE => Err(NumberNotMinimal.into()),
-            value => Ok(value),
-        },
-        27 => match read_u64(r)? as u64 {
-            0..=MAX_4BYTE => Err(NumberNotMinimal.into()),
-            value => Ok(value),
-        },
-        _ => Err(UnexpectedCode::new::<u64>(major.into()).into()),
-    }
+    let (value, max_shorter) = match major.info() {
+        value @ 0..=23 => return Ok((value as u64, true)),
+        24 => (read_u8(r)? as u64, MAX_SHORT),
+        25 => (read_u16(r)? as u64, MAX_1BYTE),
+        26 => (read_u32(r)? as u64, MAX_2BYTE),
+        27 => (read_u64(r)?, MAX_4BYTE),
+        _ => return Err(UnexpectedCode::new::<u64>(major.into()).into()),
+    };
+    Ok((value, value > max_shorter))
 }
 
 impl Decode<DagCbor> for bool {
@@ -190,10 +450,16 @@ macro_rules! impl_num {
             impl Decode<DagCbor> for $t {
                 fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
                     let major = read_major(r)?;
-                    if major.kind() != MajorKind::UnsignedInt {
-                        return Err(UnexpectedCode::new::<Self>(major.into()).into());
+                    match major.kind() {
+                        MajorKind::UnsignedInt => {}
+                        MajorKind::NegativeInt => {
+                            // Still validate the encoding of the number.
+                            read_int(r, major, false)?;
+                            return Err(NumberOutOfRange::new::<Self>().into());
+                        }
+                        _ => return Err(UnexpectedCode::new::<Self>(major.into()).into()),
                     }
-                    let value = read_uint(r, major)?;
+                    let value = read_int(r, major, false)?;
                     Self::try_from(value).map_err(|_| NumberOutOfRange::new::<Self>().into())
                 }
             }
@@ -204,12 +470,11 @@ macro_rules! impl_num {
             impl Decode<DagCbor> for $t {
                 fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
                     let major = read_major(r)?;
-                    let value = read_uint(r, major)?;
                     match major.kind() {
                         MajorKind::UnsignedInt | MajorKind::NegativeInt => (),
                         _ => return Err(UnexpectedCode::new::<Self>(major.into()).into()),
                     };
-
+                    let value = read_int(r, major, false)?;
                     let mut value = Self::try_from(value)
                         .map_err(|_| NumberOutOfRange::new::<Self>())?;
                     if major.kind() == MajorKind::NegativeInt {
@@ -228,9 +493,10 @@ impl_num!(signed i8, i16, i32, i64, i128);
 
 impl Decode<DagCbor> for f32 {
     fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
-        // TODO: We don't accept f16
-        // TODO: By IPLD spec, we shouldn't accept f32 either...
+        // TODO: By IPLD spec, we shouldn't accept f16 or f32 either...
         let num = match read_major(r)? {
+            // Every half-precision float is exactly representable as a f32.
+            F16 => read_f16(r)? as Self,
             F32 => read_f32(r)?,
             F64 => {
                 let num = read_f64(r)?;
@@ -251,9 +517,9 @@ impl Decode<DagCbor> for f32 {
 
 impl Decode<DagCbor> for f64 {
     fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
-        // TODO: We don't accept f16
-        // TODO: By IPLD spec, we shouldn't accept f32 either...
+        // TODO: By IPLD spec, we shouldn't accept f16 or f32 either...
         let num = match read_major(r)? {
+            F16 => read_f16(r)?,
             F32 => read_f32(r)?.into(),
             F64 => read_f64(r)?,
             m => return Err(UnexpectedCode::new::<Self>(m.into()).into()),
@@ -272,8 +538,36 @@ impl Decode<DagCbor> for String {
         if major.kind() != MajorKind::TextString {
             return Err(UnexpectedCode::new::<Self>(major.into()).into());
         }
-        let len = read_uint(r, major)?;
-        read_str(r, len)
+        read_text_string(r, major)
+    }
+}
+
+/// Always decodes into an owned string.
+impl Decode<DagCbor> for Cow<'static, str> {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        Ok(Cow::Owned(String::decode(c, r)?))
+    }
+}
+
+/// Decodes a byte string, always into owned bytes.
+impl Decode<DagCbor> for Cow<'static, [u8]> {
+    fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
+        let major = read_major(r)?;
+        if major.kind() != MajorKind::ByteString {
+            return Err(UnexpectedCode::new::<Self>(major.into()).into());
+        }
+        Ok(Cow::Owned(read_byte_string(r, major)?))
+    }
+}
+
+impl Decode<DagCbor> for char {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        let string = String::decode(c, r)?;
+        let mut chars = string.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Ok(ch),
+            _ => Err(LengthMismatch::new::<Self>(1, string.chars().count() as u64).into()),
+        }
     }
 }
 
@@ -281,7 +575,7 @@ impl Decode<DagCbor> for Cid {
     fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
         let major = read_major(r)?;
         if major.kind() == MajorKind::Tag {
-            match read_uint(r, major)? {
+            match read_tag(r, major, false)? {
                 42 => read_link(r),
                 tag => Err(UnknownTag(tag).into()),
             }
@@ -297,8 +591,30 @@ impl Decode<DagCbor> for Box<[u8]> {
         if major.kind() != MajorKind::ByteString {
             return Err(UnexpectedCode::new::<Self>(major.into()).into());
         }
-        let len = read_uint(r, major)?;
-        Ok(read_bytes(r, len)?.into_boxed_slice())
+        Ok(read_byte_string(r, major)?.into_boxed_slice())
+    }
+}
+
+impl Decode<DagCbor> for ByteBuf {
+    fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
+        let major = read_major(r)?;
+        if major.kind() != MajorKind::ByteString {
+            return Err(UnexpectedCode::new::<Self>(major.into()).into());
+        }
+        Ok(Self(read_byte_string(r, major)?))
+    }
+}
+
+/// Decodes a byte string into a buffer that is handed over to `Bytes` without copying. To share the
+/// buffer of the input instead, see [`SharedIpld`](crate::shared::SharedIpld).
+#[cfg(feature = "bytes")]
+impl Decode<DagCbor> for bytes::Bytes {
+    fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
+        let major = read_major(r)?;
+        if major.kind() != MajorKind::ByteString {
+            return Err(UnexpectedCode::new::<Self>(major.into()).into());
+        }
+        Ok(read_byte_string(r, major)?.into())
     }
 }
 
@@ -306,6 +622,7 @@ impl<T: Decode<DagCbor>> Decode<DagCbor> for Option<T> {
     fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
         let result = match read_major(r)? {
             NULL => None,
+            UNDEFINED => return Err(Undefined.into()),
             _ => {
                 r.seek(SeekFrom::Current(-1))?;
                 Some(T::decode(c, r)?)
@@ -321,211 +638,787 @@ impl<T: Decode<DagCbor>> Decode<DagCbor> for Vec<T> {
         if major.kind() != MajorKind::Array {
             return Err(UnexpectedCode::new::<Self>(major.into()).into());
         }
+        if major.is_indefinite() {
+            return read_list_il(r, DecodeOptions::default().max_collection_len);
+        }
         let len = read_uint(r, major)?;
         read_list(r, len)
     }
 }
 
+/// Arrays are decoded from lists of exactly `N` items, just like a `Vec<T>`.
+impl<T: Decode<DagCbor>, const N: usize> Decode<DagCbor> for [T; N] {
+    fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
+        let major = read_major(r)?;
+        if major.kind() != MajorKind::Array {
+            return Err(UnexpectedCode::new::<Self>(major.into()).into());
+        }
+        let len = read_uint(r, major)?;
+        if len != N as u64 {
+            return Err(LengthMismatch::new::<Self>(N as u64, len).into());
+        }
+        let list: Vec<T> = read_list(r, len)?;
+        // The length was checked above.
+        Ok(list
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("list has length {}", N)))
+    }
+}
+
 impl<K: Decode<DagCbor> + Ord, T: Decode<DagCbor>> Decode<DagCbor> for BTreeMap<K, T> {
     fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
         let major = read_major(r)?;
         if major.kind() != MajorKind::Map {
             return Err(UnexpectedCode::new::<Self>(major.into()).into());
         }
+        if major.is_indefinite() {
+            return read_map_il(r, DecodeOptions::default().max_collection_len);
+        }
 
         let len = read_uint(r, major)?;
         read_map(r, len)
     }
 }
 
-impl Decode<DagCbor> for Ipld {
+/// If a key occurs more than once, the last value wins.
+impl<K, T, S> Decode<DagCbor> for HashMap<K, T, S>
+where
+    K: Decode<DagCbor> + Eq + Hash,
+    T: Decode<DagCbor>,
+    S: BuildHasher + Default,
+{
     fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
         let major = read_major(r)?;
-        let ipld = match major.kind() {
-            MajorKind::UnsignedInt => Self::Integer(read_uint(r, major)? as i128),
-            MajorKind::NegativeInt => Self::Integer(-1 - read_uint(r, major)? as i128),
-            MajorKind::ByteString => {
-                let len = read_uint(r, major)?;
-                Self::Bytes(read_bytes(r, len)?)
-            }
-            MajorKind::TextString => {
-                let len = read_uint(r, major)?;
-                Self::String(read_str(r, len)?)
-            }
-            MajorKind::Array => {
-                let len = read_uint(r, major)?;
-                Self::List(read_list(r, len)?)
-            }
-            MajorKind::Map => {
-                let len = read_uint(r, major)?;
-                Self::Map(read_map(r, len)?)
+        if major.kind() != MajorKind::Map {
+            return Err(UnexpectedCode::new::<Self>(major.into()).into());
+        }
+        let mut map = HashMap::default();
+        read_map_into(r, major, |key, value| {
+            map.insert(key, value);
+        })?;
+        Ok(map)
+    }
+}
+
+/// Decodes items from a byte slice while keeping track of the position.
+///
+/// Errors have a [`Location`] attached, which contains the offset of the item that failed to
+/// decode. When decoding `Ipld` this is the innermost item, otherwise the item itself.
+///
+/// # Example
+///
+/// ```
+/// use libipld_cbor::decode::SliceDecoder;
+///
+/// // Two integers back to back.
+/// let mut decoder = SliceDecoder::new(&[0x01, 0x18, 0x2a]);
+/// assert_eq!(decoder.decode::<u64>().unwrap(), 1);
+/// assert_eq!(decoder.position(), 1);
+/// assert_eq!(decoder.decode::<u64>().unwrap(), 42);
+/// assert!(decoder.is_empty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct SliceDecoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SliceDecoder<'a> {
+    /// Creates a new decoder, starting at the beginning of `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Returns the number of bytes consumed so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the bytes that haven't been consumed yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    /// Returns true if all bytes were consumed.
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    /// Decodes the next item. On error the position isn't advanced.
+    pub fn decode<T: Decode<DagCbor>>(&mut self) -> Result<T> {
+        let mut r = Cursor::new(self.remaining());
+        let result = T::decode(DagCbor, &mut r);
+        self.advance(result, r.position())
+    }
+
+    /// Decodes the next item without copying its strings and byte strings. On error the position
+    /// isn't advanced.
+    pub fn decode_ref(&mut self) -> Result<IpldRef<'a>> {
+        let mut r = Cursor::new(self.remaining());
+        let result = read_ipld_ref(&mut r, 0);
+        self.advance(result, r.position())
+    }
+
+    fn advance<T>(&mut self, result: Result<T>, len: u64) -> Result<T> {
+        match result {
+            Ok(value) => {
+                self.pos += len as usize;
+                Ok(value)
             }
-            MajorKind::Tag => {
-                let value = read_uint(r, major)?;
-                if value == 42 {
-                    Self::Link(read_link(r)?)
+            Err(mut err) => {
+                // The location of errors of nested items is relative to the start of the item.
+                if let Some(location) = err.downcast_mut::<Location>() {
+                    location.offset += self.pos as u64;
+                    Err(err)
                 } else {
-                    return Err(UnknownTag(value).into());
+                    Err(locate(err, self.pos as u64))
                 }
             }
-            MajorKind::Other => match major {
-                FALSE => Self::Bool(false),
-                TRUE => Self::Bool(true),
-                NULL => Self::Null,
-                F32 => Self::Float(read_f32(r)? as f64),
-                F64 => Self::Float(read_f64(r)?),
-                m => return Err(UnexpectedCode::new::<Self>(m.into()).into()),
-            },
-        };
-        Ok(ipld)
+        }
     }
 }
 
-impl References<DagCbor> for Ipld {
-    fn references<R: Read + Seek, E: Extend<Cid>>(
-        _: DagCbor,
-        r: &mut R,
-        set: &mut E,
-    ) -> Result<()> {
-        let mut remaining: u64 = 1;
-        while remaining > 0 {
-            remaining -= 1;
-            let major = read_major(r)?;
-            match major.kind() {
-                MajorKind::UnsignedInt | MajorKind::NegativeInt | MajorKind::Other => {
-                    // TODO: validate ints & floats?
-                    r.seek(SeekFrom::Current(major.len() as i64))?;
-                }
-                MajorKind::ByteString | MajorKind::TextString => {
-                    // TODO: validate utf8?
-                    // We could just reject this case, but we can't just play it fast and loose and
-                    // wrap. We might as well just try to seek (and likely fail).
-                    let mut offset = read_uint(r, major)?;
-                    while offset > i64::MAX as u64 {
-                        r.seek(SeekFrom::Current(i64::MAX))?;
-                        offset -= i64::MAX as u64;
-                    }
-                    r.seek(SeekFrom::Current(offset as i64))?;
-                }
-                MajorKind::Array => {
-                    remaining = remaining
-                        .checked_add(read_uint(r, major)?)
-                        .ok_or_else(LengthOutOfRange::new::<Self>)?;
-                }
-                MajorKind::Map => {
-                    // TODO: consider using a checked "monad" type to simplify.
-                    let items = read_uint(r, major)?
-                        .checked_mul(2)
-                        .ok_or_else(LengthOutOfRange::new::<Self>)?;
-                    remaining = remaining
-                        .checked_add(items)
-                        .ok_or_else(LengthOutOfRange::new::<Self>)?;
-                }
-                MajorKind::Tag => match read_uint(r, major)? {
-                    42 => set.extend(std::iter::once(read_link(r)?)),
-                    _ => {
-                        remaining = remaining
-                            .checked_add(1)
-                            .ok_or_else(LengthOutOfRange::new::<Self>)?;
-                    }
-                },
-            };
+/// Decodes a single item from the start of `bytes`. Returns the item together with the number of
+/// bytes it occupied.
+///
+/// The count covers the whole item, including tags and nested items, so that `bytes[len..]` is
+/// the input that follows it.
+pub fn decode_prefix<T: Decode<DagCbor>>(bytes: &[u8]) -> Result<(T, usize)> {
+    let mut decoder = SliceDecoder::new(bytes);
+    let value = decoder.decode()?;
+    Ok((value, decoder.position()))
+}
+
+/// Decodes items that are concatenated back to back in a byte stream, also known as a CBOR
+/// sequence.
+///
+/// The iterator ends when the stream ends between two items. A stream that ends within an item
+/// yields [`UnexpectedEof`] instead. After an error no more items are decoded.
+///
+/// # Example
+///
+/// ```
+/// use libipld_cbor::decode::SeqDecoder;
+/// use std::io::Cursor;
+///
+/// // Three integers back to back.
+/// let items = SeqDecoder::<_, u64>::new(Cursor::new([0x01, 0x02, 0x18, 0x2a]));
+/// assert_eq!(items.collect::<Result<Vec<_>, _>>().unwrap(), vec![1, 2, 42]);
+/// ```
+pub struct SeqDecoder<R, T> {
+    r: R,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<R: Read + Seek, T: Decode<DagCbor>> SeqDecoder<R, T> {
+    /// Creates a new decoder, starting at the current position of `r`.
+    pub fn new(r: R) -> Self {
+        Self {
+            r,
+            done: false,
+            _marker: PhantomData,
         }
-        Ok(())
     }
-}
 
-impl<T: Decode<DagCbor>> Decode<DagCbor> for Arc<T> {
-    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
-        Ok(Arc::new(T::decode(c, r)?))
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.r
     }
-}
 
-impl Decode<DagCbor> for () {
-    fn decode<R: Read + Seek>(_c: DagCbor, r: &mut R) -> Result<Self> {
-        let major = read_u8(r)?;
-        match major {
-            0x80 => {}
-            _ => {
-                return Err(UnexpectedCode::new::<Self>(major).into());
+    /// Returns whether another item starts, without consuming any of it.
+    fn has_next(&mut self) -> Result<bool> {
+        let mut buf = [0; 1];
+        loop {
+            match self.r.read(&mut buf) {
+                Ok(0) => return Ok(false),
+                Ok(_) => break,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
             }
-        };
-        Ok(())
+        }
+        self.r.seek(SeekFrom::Current(-1))?;
+        Ok(true)
     }
 }
 
-impl<A: Decode<DagCbor>> Decode<DagCbor> for (A,) {
-    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
-        let major = read_u8(r)?;
-        let result = match major {
-            0x81 => (A::decode(c, r)?,),
-            _ => {
-                return Err(UnexpectedCode::new::<Self>(major).into());
+impl<R: Read + Seek, T: Decode<DagCbor>> Iterator for SeqDecoder<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = match self.has_next() {
+            Ok(true) => T::decode(DagCbor, &mut self.r),
+            Ok(false) => {
+                self.done = true;
+                return None;
             }
+            Err(err) => Err(err),
         };
-        Ok(result)
+        self.done = result.is_err();
+        Some(result)
     }
 }
 
-impl<A: Decode<DagCbor>, B: Decode<DagCbor>> Decode<DagCbor> for (A, B) {
-    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
-        let major = read_u8(r)?;
-        let result = match major {
-            0x82 => (A::decode(c, r)?, B::decode(c, r)?),
-            _ => {
-                return Err(UnexpectedCode::new::<Self>(major).into());
-            }
-        };
-        Ok(result)
-    }
+/// Decodes `Ipld` items from input that arrives in chunks, e.g. from a network connection.
+///
+/// Chunks are buffered with [`StreamDecoder::feed`], and [`StreamDecoder::poll_item`] returns an
+/// item once all of it has arrived. Running out of input within an item isn't an error, the item
+/// is decoded again from its start on the next poll.
+///
+/// # Example
+///
+/// ```
+/// use libipld_cbor::decode::StreamDecoder;
+/// use libipld_core::ipld::Ipld;
+///
+/// let mut decoder = StreamDecoder::new();
+/// // A list of two integers, split in the middle.
+/// decoder.feed(&[0x82, 0x01]);
+/// assert_eq!(decoder.poll_item().unwrap(), None);
+/// decoder.feed(&[0x02]);
+/// let list = Ipld::List(vec![Ipld::Integer(1), Ipld::Integer(2)]);
+/// assert_eq!(decoder.poll_item().unwrap(), Some(list));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct StreamDecoder {
+    buf: Vec<u8>,
+    /// The start of the next item within `buf`.
+    pos: usize,
+    opts: DecodeOptions,
 }
 
-impl<A: Decode<DagCbor>, B: Decode<DagCbor>, C: Decode<DagCbor>> Decode<DagCbor> for (A, B, C) {
-    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
-        let major = read_u8(r)?;
-        let result = match major {
-            0x83 => (A::decode(c, r)?, B::decode(c, r)?, C::decode(c, r)?),
-            _ => {
-                return Err(UnexpectedCode::new::<Self>(major).into());
-            }
-        };
-        Ok(result)
+impl StreamDecoder {
+    /// Creates a new decoder with the default options.
+    pub fn new() -> Self {
+        Self::default()
     }
-}
 
-impl<A: Decode<DagCbor>, B: Decode<DagCbor>, C: Decode<DagCbor>, D: Decode<DagCbor>> Decode<DagCbor>
-    for (A, B, C, D)
-{
-    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
-        let major = read_u8(r)?;
-        let result = match major {
-            0x84 => (
-                A::decode(c, r)?,
-                B::decode(c, r)?,
-                C::decode(c, r)?,
-                D::decode(c, r)?,
-            ),
-            _ => {
-                return Err(UnexpectedCode::new::<Self>(major).into());
-            }
-        };
-        Ok(result)
+    /// Creates a new decoder with the given options.
+    pub fn with_options(opts: DecodeOptions) -> Self {
+        Self {
+            opts,
+            ..Self::default()
+        }
     }
-}
 
-impl SkipOne for DagCbor {
-    fn skip<R: Read + Seek>(&self, r: &mut R) -> Result<()> {
+    /// Appends a chunk of input.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        // Drop the items that were already decoded before the buffer grows.
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Decodes the next item. Returns `None` if the input ends before the item does, so that
+    /// more needs to be fed first.
+    ///
+    /// An error means that the input is malformed, polling again returns the same error.
+    pub fn poll_item(&mut self) -> Result<Option<Ipld>> {
+        if self.pos == self.buf.len() {
+            return Ok(None);
+        }
+        let mut r = Cursor::new(&self.buf[self.pos..]);
+        match read_ipld_unbuffered(&mut r, &self.opts) {
+            Ok(ipld) => {
+                self.pos += r.position() as usize;
+                Ok(Some(ipld))
+            }
+            Err(err) if err.is::<UnexpectedEof>() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the input that wasn't decoded yet.
+    pub fn remaining(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+}
+
+/// Decodes a single item that has to span all of `bytes`.
+///
+/// Returns [`TrailingBytes`] if any input remains after the item.
+pub fn decode<T: Decode<DagCbor>>(bytes: &[u8]) -> Result<T> {
+    let (value, len) = decode_prefix(bytes)?;
+    if len < bytes.len() {
+        return Err(TrailingBytes(bytes.len() - len).into());
+    }
+    Ok(value)
+}
+
+/// Options for decoding `Ipld`.
+///
+/// The default options are lenient and accept any valid cbor the decoder understands, even if
+/// re-encoding it doesn't reproduce the same bytes. The only exception are duplicate map keys, as
+/// they would make two different encodings decode to the same map.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DecodeOptions {
+    /// Only accept canonical DAG-CBOR. This rejects indefinite-length items, bignums, integers
+    /// with a longer head than needed, NaN and infinite floats, floats that aren't encoded as 64
+    /// bit and map keys that are duplicated or not in canonical order.
+    pub strict: bool,
+    /// Accept duplicate map keys, the last value wins. Ignored in strict mode.
+    pub allow_duplicate_keys: bool,
+    /// The maximum number of nested lists and maps. Defaults to 128.
+    pub max_depth: usize,
+    /// The maximum length of a text string in bytes, including map keys. Unlimited by default.
+    pub max_string_len: usize,
+    /// The maximum length of a byte string. Unlimited by default.
+    pub max_bytes_len: usize,
+    /// The maximum number of items of a list or entries of a map. Unlimited by default.
+    pub max_collection_len: usize,
+    /// Decode the item inside of an unknown tag as if the tag wasn't there. Nested tags count
+    /// towards the maximum depth. Ignored in strict mode.
+    pub allow_unknown_tags: bool,
+    /// Replace invalid UTF-8 in string values with U+FFFD instead of failing. Map keys are still
+    /// validated. Ignored in strict mode.
+    pub lossy_utf8: bool,
+    /// Decode undefined as null instead of failing. Ignored in strict mode.
+    pub allow_undefined: bool,
+    /// The order of map keys required in strict mode. Defaults to DAG-CBOR's. With
+    /// [`KeyOrder::Insertion`] keys can be in any order, but duplicates are still rejected.
+    pub key_order: KeyOrder,
+}
+
+impl DecodeOptions {
+    /// Options that only accept canonical DAG-CBOR.
+    pub const fn strict() -> Self {
+        Self {
+            strict: true,
+            allow_duplicate_keys: false,
+            max_depth: 128,
+            max_string_len: usize::MAX,
+            max_bytes_len: usize::MAX,
+            max_collection_len: usize::MAX,
+            allow_unknown_tags: false,
+            lossy_utf8: false,
+            allow_undefined: false,
+            key_order: KeyOrder::DagCbor,
+        }
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            allow_duplicate_keys: false,
+            max_depth: 128,
+            max_string_len: usize::MAX,
+            max_bytes_len: usize::MAX,
+            max_collection_len: usize::MAX,
+            allow_unknown_tags: false,
+            lossy_utf8: false,
+            allow_undefined: false,
+            key_order: KeyOrder::DagCbor,
+        }
+    }
+}
+
+/// Reads `Ipld` from a stream of cbor encoded bytes using the given options.
+///
+/// This is what `Ipld::decode` does with the default options. Typed decoders always use the
+/// default options.
+///
+/// Unlike `Ipld::decode`, the reader is buffered while decoding, which makes decoding from
+/// unbuffered readers like a `File` much faster. Afterwards the reader is positioned right after
+/// the decoded item.
+pub fn read_ipld<R: Read + Seek>(r: &mut R, opts: &DecodeOptions) -> Result<Ipld> {
+    read_ipld_with(r, opts, &mut OwnedBytes::default())
+}
+
+/// Like [`read_ipld`], but without buffering the reader, for readers that are in memory already.
+pub(crate) fn read_ipld_unbuffered<R: Read + Seek>(
+    r: &mut R,
+    opts: &DecodeOptions,
+) -> Result<Ipld> {
+    read_ipld_nested(r, opts, 0, &mut OwnedBytes::default())
+}
+
+/// Decides what the byte strings within `Ipld` decode to, see [`read_ipld_with`].
+pub trait BytesVisitor {
+    /// The buffer the payload of the next byte string is read into.
+    fn buffer(&mut self) -> &mut Vec<u8>;
+
+    /// Called once the payload of a byte string was read into the buffer, returns the `Ipld` the
+    /// byte string decodes to. The buffer is cleared before the next byte string is read into it.
+    fn visit_bytes(&mut self) -> Result<Ipld>;
+}
+
+/// Decodes byte strings into `Ipld::Bytes`, which is what [`read_ipld`] does.
+#[derive(Default)]
+struct OwnedBytes(Vec<u8>);
+
+impl BytesVisitor for OwnedBytes {
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+
+    fn visit_bytes(&mut self) -> Result<Ipld> {
+        Ok(Ipld::Bytes(std::mem::take(&mut self.0)))
+    }
+}
+
+/// Like [`read_ipld`], but hands the payload of every byte string to `visitor` instead of
+/// allocating a `Vec` for it.
+///
+/// This allows a visitor to process large byte strings in a scratch buffer that is reused across
+/// byte strings and decodes, and to replace them with a smaller `Ipld`, like their length or
+/// hash.
+pub fn read_ipld_with<R: Read + Seek, V: BytesVisitor>(
+    r: &mut R,
+    opts: &DecodeOptions,
+    visitor: &mut V,
+) -> Result<Ipld> {
+    let mut r = Buffered::new(r)?;
+    let ipld = read_ipld_nested(&mut r, opts, 0, visitor);
+    r.finish()?;
+    ipld
+}
+
+/// Reads `Ipld` that is nested in `depth` lists or maps. Errors have the [`Location`] of the item
+/// that failed to decode attached.
+fn read_ipld_nested<R: Read + Seek, V: BytesVisitor>(
+    r: &mut R,
+    opts: &DecodeOptions,
+    depth: usize,
+    visitor: &mut V,
+) -> Result<Ipld> {
+    let offset = r.stream_position()?;
+    read_ipld_item(r, opts, depth, visitor).map_err(|err| locate(err, offset))
+}
+
+fn read_ipld_item<R: Read + Seek, V: BytesVisitor>(
+    r: &mut R,
+    opts: &DecodeOptions,
+    depth: usize,
+    visitor: &mut V,
+) -> Result<Ipld> {
+    let major = read_major(r)?;
+    if opts.strict && major.is_indefinite() && major.kind() != MajorKind::Other {
+        return Err(IndefiniteLength.into());
+    }
+    let ipld = match major.kind() {
+        MajorKind::UnsignedInt => Ipld::Integer(read_int(r, major, opts.strict)? as i128),
+        MajorKind::NegativeInt => Ipld::Integer(-1 - read_int(r, major, opts.strict)? as i128),
+        MajorKind::ByteString => {
+            let buf = visitor.buffer();
+            buf.clear();
+            read_ipld_string(r, major, opts, buf)?;
+            visitor.visit_bytes()?
+        }
+        MajorKind::TextString => {
+            let mut bytes = Vec::new();
+            read_ipld_string(r, major, opts, &mut bytes)?;
+            if opts.lossy_utf8 && !opts.strict {
+                Ipld::String(String::from_utf8_lossy(&bytes).into_owned())
+            } else {
+                Ipld::String(utf8_string(bytes)?)
+            }
+        }
+        MajorKind::Array | MajorKind::Map if depth >= opts.max_depth => {
+            return Err(MaxDepthExceeded(opts.max_depth).into());
+        }
+        MajorKind::Array => Ipld::List(read_ipld_list(r, major, opts, depth + 1, visitor)?),
+        MajorKind::Map => Ipld::Map(read_ipld_map(r, major, opts, depth + 1, visitor)?),
+        MajorKind::Tag => match read_tag(r, major, opts.strict)? {
+            42 => Ipld::Link(read_link(r)?),
+            // Bignums aren't part of the DAG-CBOR data model.
+            2 if !opts.strict => Ipld::Integer(read_ipld_bignum(r, opts)?),
+            3 if !opts.strict => Ipld::Integer(-1 - read_ipld_bignum(r, opts)?),
+            _ if opts.allow_unknown_tags && !opts.strict => {
+                if depth >= opts.max_depth {
+                    return Err(MaxDepthExceeded(opts.max_depth).into());
+                }
+                read_ipld_item(r, opts, depth + 1, visitor)?
+            }
+            value => return Err(UnknownTag(value).into()),
+        },
+        MajorKind::Other => match major {
+            FALSE => Ipld::Bool(false),
+            TRUE => Ipld::Bool(true),
+            NULL => Ipld::Null,
+            UNDEFINED if opts.allow_undefined && !opts.strict => Ipld::Null,
+            UNDEFINED => return Err(Undefined.into()),
+            F16 | F32 | F64 => {
+                let num = match major {
+                    F16 => read_f16(r)?,
+                    F32 => read_f32(r)? as f64,
+                    _ => read_f64(r)?,
+                };
+                if opts.strict {
+                    if !num.is_finite() {
+                        return Err(NonFiniteFloat(num).into());
+                    }
+                    if major != F64 {
+                        return Err(FloatNotF64.into());
+                    }
+                }
+                Ipld::Float(num)
+            }
+            m => return Err(UnexpectedCode::new::<Ipld>(m.into()).into()),
+        },
+    };
+    Ok(ipld)
+}
+
+/// Reads the payload of a byte string or text string whose major has already been read, checking
+/// its length before reading it.
+fn read_ipld_string<R: Read>(
+    r: &mut R,
+    major: Major,
+    opts: &DecodeOptions,
+    buf: &mut Vec<u8>,
+) -> Result<()> {
+    let max_len = match major.kind() {
+        MajorKind::ByteString => opts.max_bytes_len,
+        _ => opts.max_string_len,
+    };
+    if major.is_indefinite() {
+        return read_chunks_into(r, major.kind(), max_len, buf);
+    }
+    let len = read_uint(r, major)?;
+    if len > max_len as u64 {
+        return Err(MaxStringLenExceeded(max_len).into());
+    }
+    append_bytes(r, len, buf)
+}
+
+/// Reads the byte string of a bignum (tag 2 or 3) and returns its unsigned value.
+pub fn read_bignum<R: Read>(r: &mut R) -> Result<i128> {
+    let major = read_major(r)?;
+    if major.kind() != MajorKind::ByteString {
+        return Err(UnexpectedCode::new::<i128>(major.into()).into());
+    }
+    bignum_from_bytes(&read_byte_string(r, major)?)
+}
+
+/// Like [`read_bignum`], but checks the length of the byte string against the maximum length
+/// before reading it.
+fn read_ipld_bignum<R: Read>(r: &mut R, opts: &DecodeOptions) -> Result<i128> {
+    let major = read_major(r)?;
+    if major.kind() != MajorKind::ByteString {
+        return Err(UnexpectedCode::new::<i128>(major.into()).into());
+    }
+    let mut bytes = Vec::new();
+    read_ipld_string(r, major, opts, &mut bytes)?;
+    bignum_from_bytes(&bytes)
+}
+
+/// Converts the big-endian bytes of a bignum into its unsigned value.
+pub(crate) fn bignum_from_bytes(bytes: &[u8]) -> Result<i128> {
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    let bytes = &bytes[start..];
+    if bytes.len() > 16 {
+        return Err(LengthOutOfRange::new::<i128>().into());
+    }
+    let mut buf = [0; 16];
+    buf[16 - bytes.len()..].copy_from_slice(bytes);
+    let value = u128::from_be_bytes(buf);
+    i128::try_from(value).map_err(|_| NumberOutOfRange::new::<i128>().into())
+}
+
+/// Returns the number of items of a list or map, or `None` if it is of indefinite length.
+pub(crate) fn read_len<R: Read>(r: &mut R, major: Major) -> Result<Option<u64>> {
+    if major.is_indefinite() {
+        Ok(None)
+    } else {
+        Ok(Some(read_uint(r, major)?))
+    }
+}
+
+/// Returns whether another item of a list or map follows. `remaining` is the number of items
+/// that are left, or `None` for an indefinite-length list or map.
+pub(crate) fn read_next<R: Read + Seek>(r: &mut R, remaining: &mut Option<u64>) -> Result<bool> {
+    match remaining {
+        Some(0) => Ok(false),
+        Some(len) => {
+            *len -= 1;
+            Ok(true)
+        }
+        None => Ok(!read_break(r)?),
+    }
+}
+
+/// Like [`read_len`], but checks the length of a definite-length list or map against the maximum
+/// collection length. Indefinite-length ones are checked while reading their items.
+fn read_ipld_len<R: Read>(r: &mut R, major: Major, opts: &DecodeOptions) -> Result<Option<u64>> {
+    let len = read_len(r, major)?;
+    match len {
+        Some(len) if len > opts.max_collection_len as u64 => {
+            Err(MaxCollectionLenExceeded(opts.max_collection_len).into())
+        }
+        len => Ok(len),
+    }
+}
+
+fn read_ipld_list<R: Read + Seek, V: BytesVisitor>(
+    r: &mut R,
+    major: Major,
+    opts: &DecodeOptions,
+    depth: usize,
+    visitor: &mut V,
+) -> Result<Vec<Ipld>> {
+    let mut remaining = read_ipld_len(r, major, opts)?;
+    let len = match remaining {
+        Some(len) => usize::try_from(len).map_err(|_| LengthOutOfRange::new::<usize>())?,
+        None => 0,
+    };
+    // Limit up-front allocations to 16KiB as the length is user controlled.
+    let max_alloc = (16 * 1024) / std::mem::size_of::<Ipld>();
+    let mut list = Vec::with_capacity(len.min(max_alloc));
+    while read_next(r, &mut remaining)? {
+        if list.len() >= opts.max_collection_len {
+            return Err(MaxCollectionLenExceeded(opts.max_collection_len).into());
+        }
+        let value =
+            read_ipld_nested(r, opts, depth, visitor).map_err(|err| under(err, &list.len()))?;
+        list.push(value);
+    }
+    Ok(list)
+}
+
+fn read_ipld_map<R: Read + Seek, V: BytesVisitor>(
+    r: &mut R,
+    major: Major,
+    opts: &DecodeOptions,
+    depth: usize,
+    visitor: &mut V,
+) -> Result<IpldMap> {
+    let mut remaining = read_ipld_len(r, major, opts)?;
+    let mut map = IpldMap::new();
+    let mut prev: Option<String> = None;
+    let mut entries = 0;
+    while read_next(r, &mut remaining)? {
+        if entries >= opts.max_collection_len {
+            return Err(MaxCollectionLenExceeded(opts.max_collection_len).into());
+        }
+        entries += 1;
+        let offset = r.stream_position()?;
+        let key = read_ipld_key(r, remaining.is_none(), &map, &mut prev, opts)
+            .map_err(|err| locate(err, offset))?;
+        let value = read_ipld_nested(r, opts, depth, visitor).map_err(|err| under(err, &key))?;
+        map.insert(key, value);
+    }
+    Ok(map)
+}
+
+/// Reads a map key and checks it against the keys that were read before.
+fn read_ipld_key<R: Read + Seek>(
+    r: &mut R,
+    indefinite: bool,
+    map: &IpldMap,
+    prev: &mut Option<String>,
+    opts: &DecodeOptions,
+) -> Result<String> {
+    let major = read_major(r)?;
+    if major.kind() != MajorKind::TextString {
+        return Err(UnexpectedCode::new::<String>(major.into()).into());
+    }
+    if opts.strict && major.is_indefinite() {
+        return Err(IndefiniteLength.into());
+    }
+    let mut bytes = Vec::new();
+    read_ipld_string(r, major, opts, &mut bytes)?;
+    let key = utf8_string(bytes)?;
+    // A key without a value.
+    if indefinite && read_break(r)? {
+        return Err(UnexpectedCode::new::<Ipld>(BREAK.into()).into());
+    }
+    if opts.strict && opts.key_order != KeyOrder::Insertion {
+        if let Some(prev) = prev {
+            match opts.key_order.compare(prev, &key) {
+                Some(Ordering::Less) => {}
+                Some(Ordering::Equal) => return Err(DuplicateKey(key).into()),
+                _ => return Err(UnorderedKeys(key).into()),
+            }
+        }
+        *prev = Some(key.clone());
+    }
+    if (opts.strict || !opts.allow_duplicate_keys) && map.contains_key(&key) {
+        return Err(DuplicateKey(key).into());
+    }
+    Ok(key)
+}
+
+/// Attaches the location of the item at `offset` to an error, unless a more precise one is
+/// attached already.
+pub(crate) fn locate(err: Error, offset: u64) -> Error {
+    if err.downcast_ref::<Location>().is_some() {
+        err
+    } else {
+        err.context(Location {
+            offset,
+            path: Vec::new(),
+        })
+    }
+}
+
+/// Prepends the map key or list index that contains the failed item to its location.
+pub(crate) fn under(mut err: Error, segment: &dyn ToString) -> Error {
+    if let Some(location) = err.downcast_mut::<Location>() {
+        location.path.insert(0, segment.to_string());
+    }
+    err
+}
+
+impl Decode<DagCbor> for Ipld {
+    fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
+        // Not buffered, as this is also used for nested items by typed decoders.
+        read_ipld_unbuffered(r, &DecodeOptions::default())
+    }
+}
+
+impl References<DagCbor> for Ipld {
+    fn references<R: Read + Seek, E: Extend<Cid>>(
+        _: DagCbor,
+        r: &mut R,
+        set: &mut E,
+    ) -> Result<()> {
         let mut remaining: u64 = 1;
-        while remaining > 0 {
-            remaining -= 1;
-            let major = read_major(r)?;
+        // The open indefinite-length lists and maps, innermost last. Each holds the items that
+        // remained when it was opened and whether it's a map.
+        let mut open: Vec<(u64, bool)> = Vec::new();
+        loop {
+            let major = if remaining > 0 {
+                remaining -= 1;
+                let major = read_major(r)?;
+                if major == BREAK {
+                    return Err(UnexpectedCode::new::<Self>(major.into()).into());
+                }
+                major
+            } else if let Some(&(outer, is_map)) = open.last() {
+                let major = read_major(r)?;
+                if major == BREAK {
+                    open.pop();
+                    remaining = outer;
+                    continue;
+                }
+                if is_map {
+                    // The value of the entry.
+                    remaining = 1;
+                }
+                major
+            } else {
+                break;
+            };
             match major.kind() {
                 MajorKind::UnsignedInt | MajorKind::NegativeInt | MajorKind::Other => {
-                    // TODO: validate?
-                    // minimal integer, valid float, etc?
+                    // TODO: validate ints & floats?
                     r.seek(SeekFrom::Current(major.len() as i64))?;
                 }
+                MajorKind::ByteString | MajorKind::TextString if major.is_indefinite() => {
+                    skip_chunks(r, major.kind())?;
+                }
                 MajorKind::ByteString | MajorKind::TextString => {
+                    // TODO: validate utf8?
                     // We could just reject this case, but we can't just play it fast and loose and
                     // wrap. We might as well just try to seek (and likely fail).
                     let mut offset = read_uint(r, major)?;
@@ -533,9 +1426,12 @@ impl SkipOne for DagCbor {
                         r.seek(SeekFrom::Current(i64::MAX))?;
                         offset -= i64::MAX as u64;
                     }
-                    // TODO: validate utf8?
                     r.seek(SeekFrom::Current(offset as i64))?;
                 }
+                MajorKind::Array | MajorKind::Map if major.is_indefinite() => {
+                    open.push((remaining, major.kind() == MajorKind::Map));
+                    remaining = 0;
+                }
                 MajorKind::Array => {
                     remaining = remaining
                         .checked_add(read_uint(r, major)?)
@@ -550,40 +1446,1432 @@ impl SkipOne for DagCbor {
                         .checked_add(items)
                         .ok_or_else(LengthOutOfRange::new::<Self>)?;
                 }
-                MajorKind::Tag => {
-                    // TODO: validate tag?
-                    r.seek(SeekFrom::Current(major.len() as i64))?;
-                    remaining = remaining
-                        .checked_add(1)
-                        .ok_or_else(LengthOutOfRange::new::<Self>)?;
-                }
+                MajorKind::Tag => match read_tag(r, major, false)? {
+                    42 => set.extend(std::iter::once(read_link(r)?)),
+                    _ => {
+                        remaining = remaining
+                            .checked_add(1)
+                            .ok_or_else(LengthOutOfRange::new::<Self>)?;
+                    }
+                },
             };
         }
         Ok(())
     }
-}
+}
+
+impl<T: Decode<DagCbor>> Decode<DagCbor> for Rc<T> {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        Ok(Rc::new(T::decode(c, r)?))
+    }
+}
+
+impl<T: Decode<DagCbor>> Decode<DagCbor> for Arc<T> {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        Ok(Arc::new(T::decode(c, r)?))
+    }
+}
+
+impl Decode<DagCbor> for () {
+    fn decode<R: Read + Seek>(_c: DagCbor, r: &mut R) -> Result<Self> {
+        match read_major(r)? {
+            NULL => Ok(()),
+            // Like `Option`, as typed decoders don't allow undefined.
+            UNDEFINED => Err(Undefined.into()),
+            major => Err(UnexpectedCode::new::<Self>(major.into()).into()),
+        }
+    }
+}
+
+macro_rules! impl_tuple {
+    ($len:expr, $($t:ident),+) => {
+        impl<$($t: Decode<DagCbor>),+> Decode<DagCbor> for ($($t,)+) {
+            fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+                let major = read_major(r)?;
+                if major.kind() != MajorKind::Array {
+                    return Err(UnexpectedCode::new::<Self>(major.into()).into());
+                }
+                let len = read_uint(r, major)?;
+                if len != $len {
+                    return Err(LengthMismatch::new::<Self>($len, len).into());
+                }
+                Ok(($($t::decode(c, r)?,)+))
+            }
+        }
+    };
+}
+
+impl_tuple!(1, A);
+impl_tuple!(2, A, B);
+impl_tuple!(3, A, B, C);
+impl_tuple!(4, A, B, C, D);
+impl_tuple!(5, A, B, C, D, E);
+impl_tuple!(6, A, B, C, D, E, F);
+impl_tuple!(7, A, B, C, D, E, F, G);
+impl_tuple!(8, A, B, C, D, E, F, G, H);
+
+/// Skips a single item without decoding it, nothing is allocated while doing so.
+///
+/// Nested lists, maps and tags are limited to the same depth as when decoding `Ipld` with the
+/// default options.
+pub fn skip_value<R: Read>(r: &mut R) -> Result<()> {
+    skip_next(r, 0, DecodeOptions::default().max_depth)
+}
+
+/// Skips the next item, which is nested in `depth` lists, maps or tags.
+fn skip_next<R: Read>(r: &mut R, depth: usize, max_depth: usize) -> Result<()> {
+    let major = read_major(r)?;
+    skip_major(r, major, depth, max_depth)
+}
+
+/// Skips the remainder of an item whose major has already been read.
+fn skip_major<R: Read>(r: &mut R, major: Major, depth: usize, max_depth: usize) -> Result<()> {
+    match major.kind() {
+        MajorKind::UnsignedInt | MajorKind::NegativeInt => {
+            // TODO: validate?
+            read_uint(r, major)?;
+        }
+        MajorKind::ByteString | MajorKind::TextString if major.is_indefinite() => {
+            skip_chunks(r, major.kind())?;
+        }
+        MajorKind::ByteString | MajorKind::TextString => {
+            // TODO: validate utf8?
+            let len = read_uint(r, major)?;
+            skip_bytes(r, len)?;
+        }
+        MajorKind::Array | MajorKind::Map | MajorKind::Tag if depth >= max_depth => {
+            return Err(MaxDepthExceeded(max_depth).into());
+        }
+        MajorKind::Array if major.is_indefinite() => loop {
+            let item = read_major(r)?;
+            if item == BREAK {
+                break;
+            }
+            skip_major(r, item, depth + 1, max_depth)?;
+        },
+        MajorKind::Array => {
+            for _ in 0..read_uint(r, major)? {
+                skip_next(r, depth + 1, max_depth)?;
+            }
+        }
+        MajorKind::Map if major.is_indefinite() => loop {
+            let key = read_major(r)?;
+            if key == BREAK {
+                break;
+            }
+            skip_major(r, key, depth + 1, max_depth)?;
+            let value = read_major(r)?;
+            // A key without a value.
+            if value == BREAK {
+                return Err(UnexpectedCode::new::<DagCbor>(value.into()).into());
+            }
+            skip_major(r, value, depth + 1, max_depth)?;
+        },
+        MajorKind::Map => {
+            for _ in 0..read_uint(r, major)? {
+                skip_next(r, depth + 1, max_depth)?;
+                skip_next(r, depth + 1, max_depth)?;
+            }
+        }
+        MajorKind::Tag => {
+            // TODO: validate tag?
+            read_tag(r, major, false)?;
+            skip_next(r, depth + 1, max_depth)?;
+        }
+        MajorKind::Other => match major {
+            FALSE | TRUE | NULL => {}
+            F16 | F32 | F64 => skip_bytes(r, major.len().into())?,
+            m => return Err(UnexpectedCode::new::<DagCbor>(m.into()).into()),
+        },
+    };
+    Ok(())
+}
+
+/// Skips `len` number of bytes of a byte stream.
+/// Skips the chunks of an indefinite-length string of the given major type until the "break" stop
+/// code.
+fn skip_chunks<R: Read>(r: &mut R, kind: MajorKind) -> Result<()> {
+    loop {
+        let chunk = read_major(r)?;
+        if chunk == BREAK {
+            return Ok(());
+        }
+        if chunk.kind() != kind || chunk.is_indefinite() {
+            return Err(UnexpectedCode::new::<DagCbor>(chunk.into()).into());
+        }
+        let len = read_uint(r, chunk)?;
+        skip_bytes(r, len)?;
+    }
+}
+
+fn skip_bytes<R: Read>(r: &mut R, len: u64) -> Result<()> {
+    let got = std::io::copy(&mut r.take(len), &mut std::io::sink())?;
+    if got != len {
+        return Err(UnexpectedEof { expected: len, got }.into());
+    }
+    Ok(())
+}
+
+impl SkipOne for DagCbor {
+    fn skip<R: Read + Seek>(&self, r: &mut R) -> Result<()> {
+        skip_value(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::UnexpectedEof, DagCborCodec};
+    use libipld_core::codec::{Codec, Encode};
+    use libipld_macro::ipld;
+
+    #[test]
+    fn negative_int_boundaries() -> Result<()> {
+        /// Encodes the negative integer `-1 - n`.
+        fn neg(n: u64) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            crate::encode::write_u64(&mut bytes, MajorKind::NegativeInt, n).unwrap();
+            bytes
+        }
+        fn out_of_range<T: Decode<DagCbor> + std::fmt::Debug>(bytes: &[u8]) {
+            DagCborCodec
+                .decode::<T>(bytes)
+                .expect_err("should have failed to decode an out of range number")
+                .downcast::<NumberOutOfRange>()
+                .unwrap();
+        }
+
+        assert_eq!(DagCborCodec.decode::<i8>(&neg(127))?, i8::MIN);
+        out_of_range::<i8>(&neg(128));
+        assert_eq!(DagCborCodec.decode::<i16>(&neg(32767))?, i16::MIN);
+        out_of_range::<i16>(&neg(32768));
+        assert_eq!(DagCborCodec.decode::<i32>(&neg(i32::MAX as u64))?, i32::MIN);
+        out_of_range::<i32>(&neg(i32::MAX as u64 + 1));
+        assert_eq!(DagCborCodec.decode::<i64>(&neg(i64::MAX as u64))?, i64::MIN);
+        out_of_range::<i64>(&neg(i64::MAX as u64 + 1));
+        out_of_range::<i64>(&neg(u64::MAX));
+        assert_eq!(
+            DagCborCodec.decode::<i128>(&neg(u64::MAX))?,
+            -(u64::MAX as i128) - 1
+        );
+
+        assert_eq!(
+            DagCborCodec.decode::<Ipld>(&neg(i64::MAX as u64 + 1))?,
+            Ipld::Integer(i64::MIN as i128 - 1)
+        );
+        // -2^64
+        assert_eq!(
+            DagCborCodec.decode::<Ipld>(&neg(u64::MAX))?,
+            Ipld::Integer(-(1 << 64))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn int_boundaries() -> Result<()> {
+        fn check<T>(min: i128, max: i128)
+        where
+            T: Decode<DagCbor> + TryFrom<i128> + PartialEq + std::fmt::Debug,
+        {
+            for value in [min, max] {
+                let bytes = DagCborCodec.encode(&Ipld::Integer(value)).unwrap();
+                let expected = T::try_from(value).ok().unwrap();
+                assert_eq!(DagCborCodec.decode::<T>(&bytes).unwrap(), expected);
+            }
+            for value in [min - 1, max + 1] {
+                let bytes = DagCborCodec.encode(&Ipld::Integer(value)).unwrap();
+                DagCborCodec
+                    .decode::<T>(&bytes)
+                    .expect_err("should have failed to decode an out of range number")
+                    .downcast::<NumberOutOfRange>()
+                    .unwrap();
+            }
+        }
+        check::<u8>(0, u8::MAX.into());
+        check::<u16>(0, u16::MAX.into());
+        check::<u32>(0, u32::MAX.into());
+        check::<i8>(i8::MIN.into(), i8::MAX.into());
+        check::<i16>(i16::MIN.into(), i16::MAX.into());
+        check::<i32>(i32::MIN.into(), i32::MAX.into());
+        check::<i64>(i64::MIN.into(), i64::MAX.into());
+        assert_eq!(
+            DagCborCodec.decode::<u64>(&DagCborCodec.encode(&u64::MAX)?)?,
+            u64::MAX
+        );
+        DagCborCodec
+            .decode::<u64>(&DagCborCodec.encode(&-1i8)?)
+            .expect_err("should have failed to decode a negative number")
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+
+        // Wider headers are allowed as long as the value fits, like other CBOR decoders do.
+        assert_eq!(DagCborCodec.decode::<u8>(&[0x19, 0x00, 0x05])?, 5);
+        assert_eq!(
+            DagCborCodec.decode::<u8>(&[0x1b, 0, 0, 0, 0, 0, 0, 0, 0xff])?,
+            255
+        );
+        assert_eq!(DagCborCodec.decode::<i8>(&[0x39, 0x00, 0x7f])?, -128);
+        assert_eq!(
+            DagCborCodec.decode::<u16>(&[0x1a, 0, 0, 0xff, 0xff])?,
+            u16::MAX
+        );
+        assert_eq!(
+            DagCborCodec.decode::<Ipld>(&[0x18, 0x05])?,
+            Ipld::Integer(5)
+        );
+        for bytes in [&[0x19, 0x01, 0x00][..], &[0x1a, 0, 0, 0x01, 0x00]] {
+            DagCborCodec
+                .decode::<u8>(bytes)
+                .expect_err("should have failed to decode 256 into a u8")
+                .downcast::<NumberOutOfRange>()
+                .unwrap();
+        }
+        DagCborCodec
+            .decode::<i8>(&[0x39, 0x00, 0x80])
+            .expect_err("should have failed to decode -129 into an i8")
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+        // Only strict mode requires the shortest head.
+        read_ipld(
+            &mut Cursor::new([0x19, 0x00, 0x05]),
+            &DecodeOptions::strict(),
+        )
+        .expect_err("should have failed to decode a non-minimal number")
+        .downcast::<NumberNotMinimal>()
+        .unwrap();
+        DagCborCodec
+            .decode::<i8>(&[0x38, 0xc8])
+            .expect_err("should have failed to decode -201")
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn int128() -> Result<()> {
+        for value in [
+            0,
+            u64::MAX as i128 - 1,
+            u64::MAX as i128,
+            i64::MAX as i128,
+            i64::MAX as i128 + 1,
+            i64::MIN as i128,
+            i64::MIN as i128 - 1,
+            -(u64::MAX as i128) - 1,
+        ] {
+            let bytes = DagCborCodec.encode(&value)?;
+            assert_eq!(DagCborCodec.decode::<i128>(&bytes)?, value);
+            assert_eq!(DagCborCodec.decode::<Ipld>(&bytes)?, Ipld::Integer(value));
+        }
+        for value in [u64::MAX as i128 + 1, -(u64::MAX as i128) - 2] {
+            DagCborCodec
+                .encode(&value)
+                .expect_err("should have failed to encode a number larger than 64 bit")
+                .downcast::<NumberOutOfRange>()
+                .unwrap();
+        }
+
+        for value in [0, i64::MAX as u128 + 1, u64::MAX as u128] {
+            let bytes = DagCborCodec.encode(&value)?;
+            assert_eq!(DagCborCodec.decode::<u128>(&bytes)?, value);
+        }
+        DagCborCodec
+            .encode(&(u64::MAX as u128 + 1))
+            .expect_err("should have failed to encode a number larger than 64 bit")
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+        DagCborCodec
+            .decode::<u128>(&DagCborCodec.encode(&-1i128)?)
+            .expect_err("should have failed to decode a negative number")
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn f16() {
+        // Test vectors from RFC 7049 Appendix A.
+        let vectors: [(&[u8], f64); 9] = [
+            (&[0xf9, 0x00, 0x00], 0.0),
+            (&[0xf9, 0x80, 0x00], -0.0),
+            (&[0xf9, 0x3c, 0x00], 1.0),
+            (&[0xf9, 0x3e, 0x00], 1.5),
+            (&[0xf9, 0x7b, 0xff], 65504.0),
+            (&[0xf9, 0x00, 0x01], 5.960464477539063e-8),
+            (&[0xf9, 0x04, 0x00], 0.00006103515625),
+            (&[0xf9, 0xc4, 0x00], -4.0),
+            (&[0xf9, 0x7c, 0x00], f64::INFINITY),
+        ];
+        for (bytes, expected) in vectors.iter() {
+            let ipld = DagCborCodec.decode::<Ipld>(bytes).unwrap();
+            match ipld {
+                Ipld::Float(num) => {
+                    assert_eq!(num, *expected);
+                    assert_eq!(num.is_sign_negative(), expected.is_sign_negative());
+                }
+                _ => panic!("expected a float, got {:?}", ipld),
+            }
+        }
+
+        let ipld = DagCborCodec.decode::<Ipld>(&[0xf9, 0xfc, 0x00]).unwrap();
+        assert_eq!(ipld, Ipld::Float(f64::NEG_INFINITY));
+        match DagCborCodec.decode::<Ipld>(&[0xf9, 0x7e, 0x00]).unwrap() {
+            Ipld::Float(num) => assert!(num.is_nan()),
+            ipld => panic!("expected a float, got {:?}", ipld),
+        }
+
+        assert_eq!(
+            DagCborCodec.decode::<f64>(&[0xf9, 0x3e, 0x00]).unwrap(),
+            1.5
+        );
+        assert_eq!(
+            DagCborCodec.decode::<f32>(&[0xf9, 0x7b, 0xff]).unwrap(),
+            65504.0
+        );
+        DagCborCodec
+            .decode::<f64>(&[0xf9, 0x7c, 0x00])
+            .expect_err("should have failed to decode infinity")
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+    }
+
+    #[test]
+    fn il_map() {
+        let bytes = [
+            0xBF, // Start indefinite-length map
+            0x63, // First key, UTF-8 string length 3
+            0x46, 0x75, 0x6e, // "Fun"
+            0xF5, // First value, true
+            0x63, // Second key, UTF-8 string length 3
+            0x41, 0x6d, 0x74, // "Amt"
+            0x21, // Second value, -2
+            0xFF, // "break"
+        ];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        let mut expected = IpldMap::new();
+        expected.insert("Fun".to_string(), Ipld::Bool(true));
+        expected.insert("Amt".to_string(), Ipld::Integer(-2));
+        assert_eq!(ipld, Ipld::Map(expected));
+
+        let bytes = [0xbf, 0xff];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        assert_eq!(ipld, Ipld::Map(IpldMap::new()));
+        let map = DagCborCodec.decode::<BTreeMap<String, u8>>(&bytes).unwrap();
+        assert!(map.is_empty());
+
+        let bytes = [
+            0xbf, // Start indefinite-length map
+            0x61, 0x61, // "a"
+            0xa1, 0x61, 0x62, 0x01, // {"b": 1}
+            0x61, 0x63, // "c"
+            0xbf, 0x61, 0x64, 0x02, 0xff, // {"d": 2}
+            0xff, // "break"
+        ];
+        let map = DagCborCodec
+            .decode::<BTreeMap<String, BTreeMap<String, u8>>>(&bytes)
+            .unwrap();
+        assert_eq!(map["a"]["b"], 1);
+        assert_eq!(map["c"]["d"], 2);
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let bytes = [
+            0xa3, // Map of length 3
+            0x61, 0x61, 0x01, // "a": 1
+            0x61, 0x62, 0x02, // "b": 2
+            0x61, 0x61, 0x03, // "a": 3
+        ];
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a duplicate key")
+            .downcast::<DuplicateKey>()
+            .unwrap();
+        assert_eq!(err.0, "a");
+
+        let opts = DecodeOptions {
+            allow_duplicate_keys: true,
+            ..Default::default()
+        };
+        let ipld = read_ipld(&mut Cursor::new(&bytes), &opts).unwrap();
+        let mut expected = IpldMap::new();
+        expected.insert("a".to_string(), Ipld::Integer(3));
+        expected.insert("b".to_string(), Ipld::Integer(2));
+        assert_eq!(ipld, Ipld::Map(expected));
+
+        // Also in indefinite-length maps.
+        let bytes = [0xbf, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02, 0xff];
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a duplicate key")
+            .downcast::<DuplicateKey>()
+            .unwrap();
+        let ipld = read_ipld(&mut Cursor::new(&bytes), &opts).unwrap();
+        assert_eq!(ipld.get("a").unwrap(), &Ipld::Integer(2));
+    }
+
+    #[test]
+    fn strict_non_finite_floats() {
+        let strict = |bytes: &[u8]| read_ipld(&mut Cursor::new(bytes), &DecodeOptions::strict());
+        for bytes in [
+            &[0xfa, 0x7f, 0xc0, 0x00, 0x00][..],
+            &[0xfa, 0x7f, 0x80, 0x00, 0x00][..],
+            &[0xfa, 0xff, 0x80, 0x00, 0x00][..],
+            &[0xfb, 0x7f, 0xf8, 0, 0, 0, 0, 0, 0][..],
+            &[0xfb, 0x7f, 0xf0, 0, 0, 0, 0, 0, 0][..],
+            &[0xfb, 0xff, 0xf0, 0, 0, 0, 0, 0, 0][..],
+        ] {
+            // The lenient mode accepts them.
+            match DagCborCodec.decode::<Ipld>(bytes).unwrap() {
+                Ipld::Float(num) => assert!(!num.is_finite()),
+                ipld => panic!("expected a float, got {:?}", ipld),
+            }
+            strict(bytes)
+                .expect_err("should have rejected a non-finite float")
+                .downcast::<NonFiniteFloat>()
+                .unwrap();
+        }
+
+        // They can't be encoded either.
+        for num in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            DagCborCodec
+                .encode(&Ipld::Float(num))
+                .expect_err("should have failed to encode a non-finite float");
+            DagCborCodec
+                .encode(&(num as f32))
+                .expect_err("should have failed to encode a non-finite float");
+        }
+    }
+
+    #[test]
+    fn strict_key_order() {
+        let strict = |bytes: &[u8]| read_ipld(&mut Cursor::new(bytes), &DecodeOptions::strict());
+
+        // Sorted length-first.
+        let bytes = [0xa2, 0x61, 0x62, 0x01, 0x62, 0x61, 0x61, 0x02];
+        let ipld = strict(&bytes).unwrap();
+        assert_eq!(DagCborCodec.encode(&ipld).unwrap(), bytes);
+
+        // Sorted lexicographically, but not length-first. Re-encoding would reorder the keys.
+        let bytes = [0xa2, 0x62, 0x61, 0x61, 0x01, 0x61, 0x62, 0x02];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        assert_ne!(DagCborCodec.encode(&ipld).unwrap(), bytes);
+        let err = strict(&bytes)
+            .expect_err("should have rejected keys that aren't sorted length-first")
+            .downcast::<UnorderedKeys>()
+            .unwrap();
+        assert_eq!(err.0, "b");
+    }
+
+    #[test]
+    fn max_depth() {
+        let mut bytes = vec![0x81; 10_000];
+        bytes.push(0x01);
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode deeply nested lists")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+        assert_eq!(err.0, 128);
+
+        let mut bytes = [0xa1, 0x60].repeat(10_000);
+        bytes.push(0x01);
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode deeply nested maps")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+
+        let opts = DecodeOptions {
+            max_depth: 2,
+            ..Default::default()
+        };
+        let ipld = read_ipld(&mut Cursor::new(&[0x81, 0x81, 0x01]), &opts).unwrap();
+        assert_eq!(ipld, Ipld::List(vec![Ipld::List(vec![Ipld::Integer(1)])]));
+        read_ipld(&mut Cursor::new(&[0x81, 0x81, 0x81, 0x01]), &opts)
+            .expect_err("should have failed to decode three nested lists")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+    }
+
+    #[test]
+    fn limits() {
+        let opts = DecodeOptions {
+            max_string_len: 3,
+            max_bytes_len: 4,
+            max_collection_len: 2,
+            ..Default::default()
+        };
+        let decode = |bytes: &[u8]| DagCborCodec.decode_with(bytes, &opts);
+
+        assert_eq!(
+            decode(&[0x63, 0x61, 0x62, 0x63]).unwrap(),
+            Ipld::String("abc".into())
+        );
+        assert_eq!(
+            decode(&[0x44, 0x01, 0x02, 0x03, 0x04]).unwrap(),
+            Ipld::Bytes(vec![1, 2, 3, 4])
+        );
+        for (bytes, max_len) in [
+            (&[0x64, 0x61, 0x62, 0x63, 0x64][..], 3),
+            (&[0x45, 0x01, 0x02, 0x03, 0x04, 0x05][..], 4),
+            // The length is checked before reading the string.
+            (&[0x5a, 0xff, 0xff, 0xff, 0xff][..], 4),
+            (&[0x7a, 0xff, 0xff, 0xff, 0xff][..], 3),
+            // Indefinite-length strings are checked while reading the chunks.
+            (
+                &[0x5f, 0x42, 0x01, 0x02, 0x43, 0x03, 0x04, 0x05, 0xff][..],
+                4,
+            ),
+            // Strings within lists and maps, including map keys.
+            (&[0x81, 0x64, 0x61, 0x62, 0x63, 0x64][..], 3),
+            (
+                &[0xa1, 0x61, 0x61, 0x45, 0x01, 0x02, 0x03, 0x04, 0x05][..],
+                4,
+            ),
+            (&[0xa1, 0x64, 0x61, 0x62, 0x63, 0x64, 0x01][..], 3),
+        ] {
+            let err = decode(bytes)
+                .expect_err("should have failed to decode a string that is too long")
+                .downcast::<MaxStringLenExceeded>()
+                .unwrap();
+            assert_eq!(err.0, max_len);
+        }
+
+        // A 3 GiB byte string fails without reading its payload.
+        let mut r = Cursor::new([0x5a, 0xc0, 0x00, 0x00, 0x00, 0x01, 0x02]);
+        read_ipld(&mut r, &opts)
+            .expect_err("should have failed to decode a huge byte string")
+            .downcast::<MaxStringLenExceeded>()
+            .unwrap();
+        assert_eq!(r.position(), 5);
+
+        assert_eq!(
+            decode(&[0xa2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x02]).unwrap(),
+            DagCborCodec
+                .decode::<Ipld>(&[0xa2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x02])
+                .unwrap()
+        );
+        for bytes in [
+            &[0x83, 0x01, 0x02, 0x03][..],
+            &[0x9a, 0xff, 0xff, 0xff, 0xff][..],
+            &[0x9f, 0x01, 0x02, 0x03, 0xff][..],
+            &[0xa3, 0x60, 0x01, 0x61, 0x61, 0x02, 0x61, 0x62, 0x03][..],
+            &[0xbf, 0x60, 0x01, 0x61, 0x61, 0x02, 0x61, 0x62, 0x03, 0xff][..],
+        ] {
+            let err = decode(bytes)
+                .expect_err("should have failed to decode a collection that is too long")
+                .downcast::<MaxCollectionLenExceeded>()
+                .unwrap();
+            assert_eq!(err.0, 2);
+        }
+    }
+
+    #[test]
+    fn unknown_tags() {
+        // Tag 1 (epoch-based date/time) around an integer.
+        let bytes = [0x82, 0xc1, 0x1a, 0x5f, 0x5e, 0x10, 0x00, 0x01];
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode an unknown tag")
+            .downcast::<UnknownTag>()
+            .unwrap();
+        assert_eq!(err.0, 1);
+
+        let opts = DecodeOptions {
+            allow_unknown_tags: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            DagCborCodec.decode_with(&bytes, &opts).unwrap(),
+            Ipld::List(vec![Ipld::Integer(1_600_000_000), Ipld::Integer(1)])
+        );
+        DagCborCodec
+            .decode_with(&vec![0xc1; 10_000], &opts)
+            .expect_err("should have failed to decode deeply nested tags")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+
+        // Strict mode ignores the option.
+        let opts = DecodeOptions {
+            allow_unknown_tags: true,
+            ..DecodeOptions::strict()
+        };
+        DagCborCodec
+            .decode_with(&bytes, &opts)
+            .expect_err("should have failed to decode an unknown tag")
+            .downcast::<UnknownTag>()
+            .unwrap();
+    }
+
+    #[test]
+    fn tag_heads() {
+        let opts = DecodeOptions {
+            allow_unknown_tags: true,
+            ..Default::default()
+        };
+        let cases: [(&[u8], u64); 5] = [
+            (&[0xc0], 0),
+            (&[0xd7], 23),
+            (&[0xd9, 0x01, 0x00], 256),
+            (&[0xda, 0x00, 0x01, 0x00, 0x00], 65_536),
+            (
+                &[0xdb, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
+                1 << 32,
+            ),
+        ];
+        for (head, tag) in cases {
+            let mut bytes = head.to_vec();
+            bytes.push(0x05);
+            let err = DagCborCodec
+                .decode::<Ipld>(&bytes)
+                .expect_err("should have failed to decode an unknown tag")
+                .downcast::<UnknownTag>()
+                .unwrap();
+            assert_eq!(err.0, tag);
+            assert_eq!(
+                DagCborCodec.decode_with(&bytes, &opts).unwrap(),
+                Ipld::Integer(5)
+            );
+        }
+
+        // Tag 0 (date/time string).
+        let mut bytes = vec![0xc0, 0x74];
+        bytes.extend_from_slice(b"2013-03-21T20:04:00Z");
+        assert_eq!(
+            DagCborCodec.decode_with(&bytes, &opts).unwrap(),
+            Ipld::String("2013-03-21T20:04:00Z".into())
+        );
+
+        // Tag numbers need to be minimally encoded as well.
+        DagCborCodec
+            .decode_with(&[0xd8, 0x01, 0x05], &opts)
+            .expect_err("should have failed to decode a non-minimal tag")
+            .downcast::<NumberNotMinimal>()
+            .unwrap();
+    }
+
+    #[test]
+    fn invalid_utf8() -> Result<()> {
+        // An overlong encoding of `/`.
+        let overlong = [0x62, 0xc0, 0xaf];
+        // A continuation byte without a leading byte.
+        let continuation = [0x63, 0x61, 0x80, 0x62];
+
+        let err = DagCborCodec
+            .decode::<String>(&overlong)
+            .expect_err("should have failed to decode an overlong encoding")
+            .downcast::<InvalidUtf8>()
+            .unwrap();
+        assert_eq!(err.offset, 0);
+        let err = DagCborCodec
+            .decode::<Ipld>(&continuation)
+            .expect_err("should have failed to decode a bare continuation byte");
+        assert_eq!(err.downcast_ref::<InvalidUtf8>().unwrap().offset, 1);
+        assert_eq!(err.downcast_ref::<Location>().unwrap().offset, 0);
+
+        let lossy = DecodeOptions {
+            lossy_utf8: true,
+            ..Default::default()
+        };
+        let ipld = DagCborCodec.decode_with(&overlong, &lossy)?;
+        assert_eq!(ipld, Ipld::String("\u{fffd}\u{fffd}".into()));
+        let ipld = DagCborCodec.decode_with(&continuation, &lossy)?;
+        assert_eq!(ipld, Ipld::String("a\u{fffd}b".into()));
+        // Map keys are always validated.
+        DagCborCodec
+            .decode_with(&[0xa1, 0x61, 0x80, 0x01], &lossy)
+            .expect_err("should have failed to decode an invalid key")
+            .downcast::<InvalidUtf8>()
+            .unwrap();
+
+        let strict = DecodeOptions {
+            lossy_utf8: true,
+            ..DecodeOptions::strict()
+        };
+        DagCborCodec
+            .decode_with(&continuation, &strict)
+            .expect_err("should have failed to decode invalid UTF-8 in strict mode")
+            .downcast::<InvalidUtf8>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn seq_decoder() -> Result<()> {
+        let mut bytes = Vec::new();
+        for ipld in [
+            Ipld::Integer(1),
+            Ipld::List(vec![Ipld::String("a".into()), Ipld::Null]),
+            Ipld::Bool(true),
+        ] {
+            bytes.extend(DagCborCodec.encode(&ipld)?);
+        }
+        let items = SeqDecoder::<_, Ipld>::new(Cursor::new(&bytes)).collect::<Result<Vec<_>>>()?;
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[1].get(0)?, &Ipld::String("a".into()));
+        assert!(SeqDecoder::<_, Ipld>::new(Cursor::new(&[]))
+            .next()
+            .is_none());
+
+        // Cut within the second item.
+        let mut items = SeqDecoder::<_, Ipld>::new(Cursor::new(&bytes[..3]));
+        assert_eq!(items.next().unwrap()?, Ipld::Integer(1));
+        items
+            .next()
+            .unwrap()
+            .expect_err("should have failed to decode a truncated item")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        assert!(items.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn stream_decoder() -> Result<()> {
+        // About 1 KB, with nested items, strings and byte strings.
+        let ipld = Ipld::List(vec![
+            ipld!({ "name": "item", "bytes": vec![7u8; 20], "list": [1, -2, 1.5] });
+            25
+        ]);
+        let bytes = DagCborCodec.encode(&ipld)?;
+        assert!(bytes.len() > 1000);
+
+        let mut decoder = StreamDecoder::new();
+        let (last, init) = bytes.split_last().unwrap();
+        for byte in init {
+            decoder.feed(&[*byte]);
+            assert_eq!(decoder.poll_item()?, None);
+        }
+        decoder.feed(&[*last]);
+        assert_eq!(decoder.poll_item()?, Some(ipld));
+        assert_eq!(decoder.poll_item()?, None);
+        assert!(decoder.remaining().is_empty());
+
+        // Several items in one chunk, and one that is cut off.
+        decoder.feed(&[0x01, 0x62, 0x61, 0x62, 0x43, 0x01]);
+        assert_eq!(decoder.poll_item()?, Some(Ipld::Integer(1)));
+        assert_eq!(decoder.poll_item()?, Some(Ipld::String("ab".into())));
+        assert_eq!(decoder.poll_item()?, None);
+        assert_eq!(decoder.remaining(), [0x43, 0x01]);
+        decoder.feed(&[0x02, 0x03]);
+        assert_eq!(decoder.poll_item()?, Some(Ipld::Bytes(vec![1, 2, 3])));
+        // An indefinite-length list waits for its break.
+        decoder.feed(&[0x9f, 0x01]);
+        assert_eq!(decoder.poll_item()?, None);
+        decoder.feed(&[0xff]);
+        assert_eq!(
+            decoder.poll_item()?,
+            Some(Ipld::List(vec![Ipld::Integer(1)]))
+        );
+
+        // Malformed input is an error, not a request for more.
+        decoder.feed(&[0x82, 0x1c]);
+        decoder
+            .poll_item()
+            .expect_err("should have failed to decode a reserved additional info");
+        let mut decoder = StreamDecoder::with_options(DecodeOptions::strict());
+        decoder.feed(&[0x18, 0x01]);
+        decoder
+            .poll_item()
+            .expect_err("should have failed to decode a non-minimal number")
+            .downcast::<NumberNotMinimal>()?;
+        Ok(())
+    }
+
+    #[test]
+    fn read_ipld_position() -> Result<()> {
+        // Long enough to need several refills of the read-ahead.
+        let ipld = Ipld::List(vec![Ipld::String("hello".into()); 1000]);
+        let mut bytes = DagCborCodec.encode(&ipld)?;
+        let len = bytes.len() as u64;
+        bytes.push(0x01);
+        let mut r = Cursor::new(&bytes);
+        assert_eq!(read_ipld(&mut r, &Default::default())?, ipld);
+        assert_eq!(r.position(), len);
+        assert_eq!(u64::decode(DagCbor, &mut r)?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn bytes_into() -> Result<()> {
+        let mut buf = Vec::with_capacity(16);
+        let ptr = buf.as_ptr();
+        read_bytes_into(&mut &[1, 2, 3][..], 3, &mut buf)?;
+        assert_eq!(buf, [1, 2, 3]);
+        read_bytes_into(&mut &[4, 5][..], 2, &mut buf)?;
+        assert_eq!(buf, [4, 5]);
+        assert_eq!(buf.as_ptr(), ptr);
+        let err = read_bytes_into(&mut &[6][..], 2, &mut buf)
+            .expect_err("should have failed to read truncated bytes")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        assert_eq!((err.expected, err.got), (2, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn bytes_visitor() -> Result<()> {
+        // Replaces byte strings with their length.
+        struct Lengths(Vec<u8>);
+
+        impl BytesVisitor for Lengths {
+            fn buffer(&mut self) -> &mut Vec<u8> {
+                &mut self.0
+            }
+
+            fn visit_bytes(&mut self) -> Result<Ipld> {
+                Ok(Ipld::Integer(self.0.len() as i128))
+            }
+        }
+
+        let ipld = Ipld::List(vec![
+            Ipld::Bytes(vec![1; 100]),
+            Ipld::String("a".into()),
+            Ipld::Bytes(vec![2; 10]),
+        ]);
+        let bytes = DagCborCodec.encode(&ipld)?;
+        let mut visitor = Lengths(Vec::new());
+        let decoded = read_ipld_with(&mut Cursor::new(&bytes), &Default::default(), &mut visitor)?;
+        let expected = Ipld::List(vec![
+            Ipld::Integer(100),
+            Ipld::String("a".into()),
+            Ipld::Integer(10),
+        ]);
+        assert_eq!(decoded, expected);
+        // The scratch buffer is reused across decodes.
+        let ptr = visitor.0.as_ptr();
+        read_ipld_with(&mut Cursor::new(&bytes), &Default::default(), &mut visitor)?;
+        assert_eq!(visitor.0.as_ptr(), ptr);
+
+        // Indefinite-length byte strings are concatenated into the buffer.
+        let bytes = [0x5f, 0x41, 0x01, 0x42, 0x02, 0x03, 0xff];
+        let decoded = read_ipld_with(&mut Cursor::new(&bytes), &Default::default(), &mut visitor)?;
+        assert_eq!(decoded, Ipld::Integer(3));
+        assert_eq!(visitor.0, [1, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn undefined() -> Result<()> {
+        let bytes = [0x82, 0xf6, 0xf7];
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode undefined");
+        assert_eq!(err.downcast_ref::<Location>().unwrap().path, ["1"]);
+        err.downcast::<Undefined>().unwrap();
+        DagCborCodec
+            .decode::<Option<u64>>(&[0xf7])
+            .expect_err("should have failed to decode undefined as an option")
+            .downcast::<Undefined>()
+            .unwrap();
+
+        let lenient = DecodeOptions {
+            allow_undefined: true,
+            ..Default::default()
+        };
+        let ipld = DagCborCodec.decode_with(&bytes, &lenient)?;
+        assert_eq!(ipld, Ipld::List(vec![Ipld::Null, Ipld::Null]));
+
+        let strict = DecodeOptions {
+            allow_undefined: true,
+            ..DecodeOptions::strict()
+        };
+        DagCborCodec
+            .decode_with(&bytes, &strict)
+            .expect_err("should have failed to decode undefined in strict mode")
+            .downcast::<Undefined>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn simple_values() -> Result<()> {
+        let err = DagCborCodec
+            .decode::<Ipld>(&[0xe5])
+            .expect_err("should have failed to decode simple value 5")
+            .downcast::<UnsupportedSimpleValue>()
+            .unwrap();
+        assert_eq!(err.0, 5);
+
+        // The payload byte is consumed, the next item can be read.
+        let bytes = [0xf8, 0x20, 0x01];
+        let mut r = Cursor::new(&bytes);
+        let err = Ipld::decode(DagCbor, &mut r)
+            .expect_err("should have failed to decode simple value 32")
+            .downcast::<UnsupportedSimpleValue>()
+            .unwrap();
+        assert_eq!(err.0, 0x20);
+        assert_eq!(r.position(), 2);
+        assert_eq!(u64::decode(DagCbor, &mut r)?, 1);
+
+        let mut r = Cursor::new(&bytes);
+        skip_value(&mut r)
+            .expect_err("should have failed to skip simple value 32")
+            .downcast::<UnsupportedSimpleValue>()
+            .unwrap();
+        assert_eq!(r.position(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn huge_lengths() {
+        // Headers claiming u32::MAX items followed by EOF must fail without allocating memory
+        // for all of them.
+        let bytes = [0x5a, 0xff, 0xff, 0xff, 0xff];
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a truncated byte string")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        DagCborCodec
+            .decode::<Box<[u8]>>(&bytes)
+            .expect_err("should have failed to decode a truncated byte string");
+        let bytes = [0x7a, 0xff, 0xff, 0xff, 0xff];
+        DagCborCodec
+            .decode::<String>(&bytes)
+            .expect_err("should have failed to decode a truncated text string");
+        let bytes = [0x9a, 0xff, 0xff, 0xff, 0xff];
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a truncated list");
+        DagCborCodec
+            .decode::<Vec<u64>>(&bytes)
+            .expect_err("should have failed to decode a truncated list");
+        let bytes = [0xba, 0xff, 0xff, 0xff, 0xff];
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a truncated map");
+        DagCborCodec
+            .decode::<BTreeMap<String, u64>>(&bytes)
+            .expect_err("should have failed to decode a truncated map");
+
+        // Zero sized types.
+        let list = DagCborCodec.decode::<Vec<()>>(&[0x82, 0xf6, 0xf6]).unwrap();
+        assert_eq!(list.len(), 2);
+        DagCborCodec
+            .decode::<Vec<()>>(&[0x9a, 0xff, 0xff, 0xff, 0xff])
+            .expect_err("should have failed to decode a truncated list");
+    }
+
+    #[test]
+    fn bignum() {
+        // u64::MAX + 1
+        let bytes = [0xc2, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        assert_eq!(ipld, Ipld::Integer(u64::MAX as i128 + 1));
+
+        // -u64::MAX - 2
+        let bytes = [0xc3, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        assert_eq!(ipld, Ipld::Integer(-(u64::MAX as i128) - 2));
+
+        // i128::MIN, with leading zeros.
+        let mut bytes = vec![0xc3, 0x51, 0x00, 0x7f];
+        bytes.extend_from_slice(&[0xff; 15]);
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        assert_eq!(ipld, Ipld::Integer(i128::MIN));
+
+        // i128::MAX + 1
+        let mut bytes = vec![0xc2, 0x50, 0x80];
+        bytes.extend_from_slice(&[0x00; 15]);
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode i128::MAX + 1")
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+
+        // More than 16 bytes.
+        let mut bytes = vec![0xc2, 0x51];
+        bytes.extend_from_slice(&[0x01; 17]);
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode an over-long bignum")
+            .downcast::<LengthOutOfRange>()
+            .unwrap();
+
+        // Bignums aren't allowed in strict mode.
+        let bytes = [0xc2, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0];
+        let err = read_ipld(&mut Cursor::new(&bytes), &DecodeOptions::strict())
+            .expect_err("should have rejected a bignum")
+            .downcast::<UnknownTag>()
+            .unwrap();
+        assert_eq!(err.0, 2);
+
+        // The byte string counts towards the maximum length of byte strings.
+        let opts = DecodeOptions {
+            max_bytes_len: 4,
+            ..Default::default()
+        };
+        let ipld = read_ipld(&mut Cursor::new(&[0xc2, 0x44, 0x01, 0, 0, 0]), &opts).unwrap();
+        assert_eq!(ipld, Ipld::Integer(1 << 24));
+        for bytes in [
+            &bytes[..],
+            &[0xc3, 0x5f, 0x43, 0x01, 0, 0, 0x42, 0, 0, 0xff],
+        ] {
+            let err = read_ipld(&mut Cursor::new(bytes), &opts)
+                .expect_err("should have failed to decode a bignum that is too long")
+                .downcast::<MaxStringLenExceeded>()
+                .unwrap();
+            assert_eq!(err.0, 4);
+        }
+        // A huge length fails without reading the payload.
+        let mut r = Cursor::new([0xc2, 0x5b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        read_ipld(&mut r, &opts)
+            .expect_err("should have failed to decode a huge bignum")
+            .downcast::<MaxStringLenExceeded>()
+            .unwrap();
+        assert_eq!(r.position(), 10);
+    }
+
+    #[test]
+    fn il_map_missing_value() {
+        let bytes = [
+            0xbf, // Start indefinite-length map
+            0x61, 0x61, // "a"
+            0x01, // 1
+            0x61, 0x62, // "b"
+            0xff, // "break"
+        ];
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a key without a value")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        assert_eq!(err.code, 0xff);
+        DagCborCodec
+            .decode::<BTreeMap<String, u8>>(&bytes)
+            .expect_err("should have failed to decode a key without a value")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+    }
+
+    #[test]
+    fn il_bytes() {
+        let bytes = [
+            0x5f, // Start indefinite-length byte string
+            0x42, 0x01, 0x02, // First chunk, two bytes
+            0x40, // Empty chunk
+            0x41, 0x03, // Last chunk, one byte
+            0xff, // "break"
+        ];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        assert_eq!(ipld, Ipld::Bytes(vec![1, 2, 3]));
+        let boxed = DagCborCodec.decode::<Box<[u8]>>(&bytes).unwrap();
+        assert_eq!(&boxed[..], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn il_bytes_empty() {
+        let bytes = [0x5f, 0xff];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        assert_eq!(ipld, Ipld::Bytes(vec![]));
+        let boxed = DagCborCodec.decode::<Box<[u8]>>(&bytes).unwrap();
+        assert!(boxed.is_empty());
+    }
+
+    #[test]
+    fn il_bytes_bad_chunk() {
+        // A text string chunk within a byte string.
+        let bytes = [0x5f, 0x41, 0x01, 0x61, 0x61, 0xff];
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a text chunk")
+            .downcast::<UnexpectedCode>()
+            .expect("expected an unexpected code");
+        // A nested indefinite-length byte string.
+        let bytes = [0x5f, 0x5f, 0xff, 0xff];
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a nested chunk");
+        // A missing "break".
+        let bytes = [0x5f, 0x41, 0x01];
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a truncated byte string");
+    }
+
+    #[test]
+    fn il_string_references() -> Result<()> {
+        // The identity hashed CIDv1 of "abc".
+        let link = [
+            0xd8, 0x2a, 0x48, 0x00, 0x01, 0x55, 0x00, 0x03, 0x61, 0x62, 0x63,
+        ];
+        let cid = DagCborCodec.decode::<Cid>(&link)?;
+        let bytes = [
+            &[0x83][..],                           // List of three items
+            &[0x5f, 0x42, 0x01, 0x02, 0x40, 0xff], // Indefinite-length byte string
+            &[0x7f, 0x61, 0x61, 0x60, 0xff],       // Indefinite-length text string
+            &link,
+        ]
+        .concat();
+        let mut set = Vec::new();
+        DagCborCodec.references::<Ipld, _>(&bytes, &mut set)?;
+        assert_eq!(set, [cid]);
+
+        // A text string chunk within a byte string.
+        DagCborCodec
+            .references::<Ipld, _>(&[0x5f, 0x61, 0x61, 0xff], &mut Vec::new())
+            .expect_err("should have failed on a text chunk")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn il_text() {
+        let bytes = [
+            0x7f, // Start indefinite-length text string
+            0x62, 0x68, 0x65, // First chunk, "he"
+            0x60, // Empty chunk
+            0x63, 0x6c, 0x6c, 0x6f, // Last chunk, "llo"
+            0xff, // "break"
+        ];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        assert_eq!(ipld, Ipld::String("hello".into()));
+        let string = DagCborCodec.decode::<String>(&bytes).unwrap();
+        assert_eq!(string, "hello");
+
+        let bytes = [0x7f, 0xff];
+        let string = DagCborCodec.decode::<String>(&bytes).unwrap();
+        assert_eq!(string, "");
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{error::UnexpectedEof, DagCborCodec};
-    use libipld_core::codec::Codec;
+    #[test]
+    fn il_text_split_code_point() {
+        // "€" is encoded as 0xe2 0x82 0xac, here it's split across two chunks.
+        let bytes = [0x7f, 0x61, 0xe2, 0x62, 0x82, 0xac, 0xff];
+        let string = DagCborCodec.decode::<String>(&bytes).unwrap();
+        assert_eq!(string, "€");
+    }
 
     #[test]
-    fn il_map() {
+    fn il_text_bad() {
+        // A byte string chunk within a text string.
+        let bytes = [0x7f, 0x41, 0x61, 0xff];
+        DagCborCodec
+            .decode::<String>(&bytes)
+            .expect_err("should have failed to decode a byte chunk");
+        // Invalid UTF-8 after concatenation.
+        let bytes = [0x7f, 0x61, 0xe2, 0x61, 0x82, 0xff];
+        let err = DagCborCodec
+            .decode::<String>(&bytes)
+            .expect_err("should have failed to decode invalid UTF-8")
+            .downcast::<InvalidUtf8>()
+            .unwrap();
+        assert_eq!(err.offset, 0);
+    }
+
+    #[test]
+    fn il_list() {
+        let bytes = [0x9f, 0xff];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        assert_eq!(ipld, Ipld::List(vec![]));
+        let list = DagCborCodec.decode::<Vec<u8>>(&bytes).unwrap();
+        assert!(list.is_empty());
+
         let bytes = [
-            0xBF, // Start indefinite-length map
-            0x63, // First key, UTF-8 string length 3
-            0x46, 0x75, 0x6e, // "Fun"
-            0xF5, // First value, true
-            0x63, // Second key, UTF-8 string length 3
-            0x41, 0x6d, 0x74, // "Amt"
-            0x21, // Second value, -2
-            0xFF, // "break"
+            0x9f, // Start indefinite-length list
+            0x01, // 1
+            0x82, 0x02, 0x03, // [2, 3]
+            0x9f, 0x04, 0x05, 0xff, // [4, 5]
+            0xff, // "break"
+        ];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        assert_eq!(
+            ipld,
+            Ipld::List(vec![
+                Ipld::Integer(1),
+                Ipld::List(vec![Ipld::Integer(2), Ipld::Integer(3)]),
+                Ipld::List(vec![Ipld::Integer(4), Ipld::Integer(5)]),
+            ])
+        );
+
+        let bytes = [0x9f, 0x9f, 0x01, 0xff, 0x9f, 0xff, 0xff];
+        let list = DagCborCodec.decode::<Vec<Vec<u8>>>(&bytes).unwrap();
+        assert_eq!(list, vec![vec![1], vec![]]);
+    }
+
+    #[test]
+    fn il_limits() {
+        let list = [0x9f, 0x01, 0x02, 0x03, 0xff];
+        let items: Vec<u8> = read_list_il(&mut Cursor::new(&list[1..]), 3).unwrap();
+        assert_eq!(items, [1, 2, 3]);
+        let err = read_list_il::<_, u8>(&mut Cursor::new(&list[1..]), 2)
+            .expect_err("should have failed on too many items")
+            .downcast::<MaxCollectionLenExceeded>()
+            .unwrap();
+        assert_eq!(err.0, 2);
+
+        let map = [0xbf, 0x61, 0x61, 0x01, 0x61, 0x62, 0x02, 0xff];
+        let entries: BTreeMap<String, u8> = read_map_il(&mut Cursor::new(&map[1..]), 2).unwrap();
+        assert_eq!(entries.len(), 2);
+        read_map_il::<_, String, u8>(&mut Cursor::new(&map[1..]), 1)
+            .expect_err("should have failed on too many entries")
+            .downcast::<MaxCollectionLenExceeded>()
+            .unwrap();
+
+        // The limit applies while reading, a list without a break fails before the end.
+        read_list_il::<_, u8>(&mut Cursor::new(vec![0x01; 1000]), 100)
+            .expect_err("should have stopped reading a list without an end")
+            .downcast::<MaxCollectionLenExceeded>()
+            .unwrap();
+
+        // Indefinite-length items count towards the maximum depth of `Ipld`.
+        for start in [0x9f, 0xbf] {
+            let mut bytes = vec![start];
+            for _ in 0..10_000 {
+                if start == 0xbf {
+                    bytes.push(0x60);
+                }
+                bytes.push(start);
+            }
+            let err = DagCborCodec
+                .decode::<Ipld>(&bytes)
+                .expect_err("should have failed to decode deeply nested items")
+                .downcast::<MaxDepthExceeded>()
+                .unwrap();
+            assert_eq!(err.0, 128);
+        }
+        let opts = DecodeOptions {
+            max_collection_len: 2,
+            ..Default::default()
+        };
+        read_ipld(&mut Cursor::new(&[0x81, 0x9f, 0x01, 0x02, 0x03]), &opts)
+            .expect_err("should have failed before the end of the list")
+            .downcast::<MaxCollectionLenExceeded>()
+            .unwrap();
+    }
+
+    #[test]
+    fn il_references() -> Result<()> {
+        // The identity hashed CIDv1 of "abc".
+        let link = [
+            0xd8, 0x2a, 0x48, 0x00, 0x01, 0x55, 0x00, 0x03, 0x61, 0x62, 0x63,
         ];
+        let cid = DagCborCodec.decode::<Cid>(&link)?;
+        let items: [&[u8]; 4] = [
+            &[0x9f],                         // Start indefinite-length list
+            &[0x5f, 0x41, 0x01, 0xff],       // Indefinite-length byte string
+            &[0xbf, 0x7f, 0x61, 0x61, 0xff], // Indefinite-length map, indefinite-length key "a"
+            &[0x82, 0x01, 0x9f, 0xff],       // Value [1, []]
+        ];
+        let bytes = [
+            &items.concat()[..],
+            &[0x61, 0x62], // Key "b"
+            &link,
+            &[0xff], // "break" of the map
+            &link,
+            &[0xff], // "break" of the list
+        ]
+        .concat();
+        DagCborCodec.decode::<Ipld>(&bytes)?;
+        let mut set = Vec::new();
+        DagCborCodec.references::<Ipld, _>(&bytes, &mut set)?;
+        assert_eq!(set, [cid, cid]);
+
+        // A link after the end isn't a reference.
+        let mut set = Vec::new();
+        DagCborCodec.references::<Ipld, _>(&[&[0x9f, 0xff][..], &link].concat(), &mut set)?;
+        assert!(set.is_empty());
+
+        // A key without a value.
+        let bytes = [0xbf, 0x61, 0x61, 0xff];
+        let err = DagCborCodec
+            .references::<Ipld, _>(&bytes, &mut Vec::new())
+            .expect_err("should have failed on a key without a value")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        assert_eq!(err.code, 0xff);
+        // A list without a break.
+        DagCborCodec
+            .references::<Ipld, _>(&[0x9f, 0x01], &mut Vec::new())
+            .expect_err("should have failed on a list without a break")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn il_list_truncated() {
+        let bytes = [0x9f, 0x01, 0x02];
         DagCborCodec
             .decode::<Ipld>(&bytes)
-            .expect_err("should have failed to decode indefinit length map");
+            .expect_err("should have failed to decode a list without a break")
+            .downcast::<UnexpectedEof>()
+            .expect("expected an unexpected eof");
+        DagCborCodec
+            .decode::<Vec<u8>>(&bytes)
+            .expect_err("should have failed to decode a list without a break");
+    }
+
+    #[test]
+    fn truncated() -> Result<()> {
+        fn eof<T: Decode<DagCbor> + std::fmt::Debug>(bytes: &[u8]) -> UnexpectedEof {
+            DagCborCodec
+                .decode::<T>(bytes)
+                .expect_err("should have failed to decode a truncated item")
+                .downcast::<UnexpectedEof>()
+                .unwrap()
+        }
+
+        // A text string claiming 10 bytes followed by 5.
+        let bytes = [0x6a, 0x68, 0x65, 0x6c, 0x6c, 0x6f];
+        let err = eof::<String>(&bytes);
+        assert_eq!((err.expected, err.got), (10, 5));
+        eof::<Ipld>(&bytes);
+
+        let bytes = [0x44, 0x01, 0x02];
+        let err = eof::<ByteBuf>(&bytes);
+        assert_eq!((err.expected, err.got), (4, 2));
+        eof::<Box<[u8]>>(&bytes);
+        eof::<Ipld>(&bytes);
+
+        let cid = Cid::try_from("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL")?;
+        let bytes = DagCborCodec.encode(&cid)?;
+        for len in [4, 5, bytes.len() - 1] {
+            let err = eof::<Cid>(&bytes[..len]);
+            assert_eq!(err.expected, 35);
+            eof::<Ipld>(&bytes[..len]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_at_every_offset() -> Result<()> {
+        let cid = Cid::try_from("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL")?;
+        let mut map = IpldMap::new();
+        map.insert("float".to_string(), Ipld::Float(1.5));
+        map.insert("int".to_string(), Ipld::Integer(-70_000));
+        map.insert("link".to_string(), Ipld::Link(cid));
+        let ipld = Ipld::List(vec![
+            Ipld::Map(map),
+            Ipld::Bytes(vec![1, 2, 3]),
+            Ipld::Integer(u64::MAX.into()),
+        ]);
+        let bytes = DagCborCodec.encode(&ipld)?;
+        for len in 0..bytes.len() {
+            let err = DagCborCodec
+                .decode::<Ipld>(&bytes[..len])
+                .expect_err("should have failed to decode a truncated item");
+            assert!(
+                err.downcast_ref::<UnexpectedEof>().is_some(),
+                "cut at {}: {:#}",
+                len,
+                err
+            );
+            let err = skip_value(&mut &bytes[..len])
+                .expect_err("should have failed to skip a truncated item");
+            assert!(err.downcast_ref::<UnexpectedEof>().is_some());
+        }
+
+        let err = DagCborCodec
+            .decode::<u32>(&[0x1a, 0x00, 0x01])
+            .expect_err("should have failed to decode a truncated number")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        assert_eq!((err.expected, err.got), (4, 2));
+        Ok(())
     }
 
     #[test]
@@ -601,12 +2889,345 @@ mod tests {
     }
 
     #[test]
-    #[allow(clippy::let_unit_value)]
-    fn tuples() -> Result<()> {
-        let data = ();
+    fn link_header() {
+        // An identity hashed CIDv1 with a three byte digest, its byte string uses the compact
+        // header.
+        let bytes = [
+            0xd8, 0x2a, // Tag 42
+            0x48, // Byte string of length 8
+            0x00, // Identity multibase prefix
+            0x01, 0x55, 0x00, 0x03, 0x61, 0x62, 0x63, // CIDv1, raw, identity, "abc"
+        ];
+        let ipld = DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        let cid = match ipld {
+            Ipld::Link(cid) => cid,
+            ipld => panic!("expected a link, got {:?}", ipld),
+        };
+        assert_eq!(cid.hash().digest(), b"abc");
+        assert_eq!(DagCborCodec.encode(&cid).unwrap(), bytes);
+
+        // An identity hashed CIDv1 embedding 300 bytes, which uses a two byte length. The digest
+        // is larger than a `Cid` can hold, hence it is rejected with an error.
+        let mut bytes = vec![
+            0xd8, 0x2a, 0x59, 0x01, 0x32, 0x00, 0x01, 0x55, 0x00, 0xac, 0x02,
+        ];
+        bytes.extend_from_slice(&[0x61; 300]);
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a digest larger than 64 bytes")
+            .downcast::<libipld_core::cid::Error>()
+            .unwrap();
+        assert!(matches!(err, libipld_core::cid::Error::ParsingError));
+        // The whole byte string was read using its two byte length before the CID was parsed.
+        let mut r = Cursor::new(&bytes[2..]);
+        read_link(&mut r)
+            .expect_err("should have failed to decode a digest larger than 64 bytes")
+            .downcast::<libipld_core::cid::Error>()
+            .unwrap();
+        assert_eq!(r.position(), bytes.len() as u64 - 2);
+    }
+
+    #[test]
+    fn link_tag_heads() -> Result<()> {
+        // The identity hashed CIDv1 of "abc".
+        let link = [0x48, 0x00, 0x01, 0x55, 0x00, 0x03, 0x61, 0x62, 0x63];
+        let heads: [&[u8]; 4] = [
+            &[0xd8, 0x2a],
+            &[0xd9, 0x00, 0x2a],
+            &[0xda, 0x00, 0x00, 0x00, 0x2a],
+            &[0xdb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2a],
+        ];
+        for head in heads {
+            let bytes = [head, &link].concat();
+            let cid = DagCborCodec.decode::<Cid>(&bytes)?;
+            assert_eq!(cid.hash().digest(), b"abc");
+            assert_eq!(DagCborCodec.decode::<Ipld>(&bytes)?, Ipld::Link(cid));
+
+            let mut set = Vec::new();
+            DagCborCodec.references::<Ipld, _>(&bytes, &mut set)?;
+            assert_eq!(set, [cid]);
+            let mut r = Cursor::new(&bytes);
+            skip_value(&mut r)?;
+            assert_eq!(r.position() as usize, bytes.len());
+
+            if head.len() == 2 {
+                assert_eq!(DagCborCodec.decode_strict(&bytes)?, Ipld::Link(cid));
+            } else {
+                DagCborCodec
+                    .decode_strict(&bytes)
+                    .expect_err("should have failed to decode a non-minimal link tag")
+                    .downcast::<NumberNotMinimal>()
+                    .unwrap();
+            }
+        }
+
+        // Other tags still need a minimal head.
+        let bytes = [0xd9, 0x00, 0x01, 0x05];
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a non-minimal tag")
+            .downcast::<NumberNotMinimal>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn tags() {
+        // Tag 1 (epoch timestamp).
+        let bytes = [0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0];
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode an unknown tag")
+            .downcast::<UnknownTag>()
+            .unwrap();
+        assert_eq!(err.0, 1);
+        let err = DagCborCodec
+            .decode::<Cid>(&bytes)
+            .expect_err("should have failed to decode an unknown tag")
+            .downcast::<UnknownTag>()
+            .unwrap();
+        assert_eq!(err.0, 1);
+
+        // Multi-byte tag heads.
+        for (bytes, tag) in [
+            (&[0xd9, 0x01, 0x00, 0xf6][..], 256),
+            (&[0xda, 0x00, 0x01, 0x00, 0x00, 0xf6][..], 65536),
+            (&[0xdb, 0, 0, 0, 1, 0, 0, 0, 0, 0xf6][..], 1 << 32),
+        ] {
+            let err = DagCborCodec
+                .decode::<Ipld>(bytes)
+                .expect_err("should have failed to decode an unknown tag")
+                .downcast::<UnknownTag>()
+                .unwrap();
+            assert_eq!(err.0, tag);
+        }
+
+        // A link that isn't a byte string.
+        let bytes = [0xd8, 0x2a, 0x63, 0x66, 0x6f, 0x6f];
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a link that isn't a byte string")
+            .downcast::<InvalidLinkType>()
+            .unwrap();
+        assert_eq!(err.0, 0x63);
+        DagCborCodec
+            .decode::<Cid>(&bytes)
+            .expect_err("should have failed to decode a link that isn't a byte string")
+            .downcast::<InvalidLinkType>()
+            .unwrap();
+    }
+
+    #[test]
+    fn byte_buf() -> Result<()> {
+        let data = ByteBuf(vec![1, 2, 3]);
+        let bytes = DagCborCodec.encode(&data)?;
+        assert_eq!(bytes, [0x43, 0x01, 0x02, 0x03]);
+        let data2: ByteBuf = DagCborCodec.decode(&bytes)?;
+        assert_eq!(data, data2);
+        let ipld: Ipld = DagCborCodec.decode(&bytes)?;
+        assert_eq!(ipld, Ipld::Bytes(vec![1, 2, 3]));
+
+        let data = ByteBuf(vec![0; 300]);
+        let bytes = DagCborCodec.encode(&data)?;
+        let data2: ByteBuf = DagCborCodec.decode(&bytes)?;
+        assert_eq!(data, data2);
+
+        // A plain `Vec<u8>` is a list.
+        let bytes = DagCborCodec.encode(&vec![1u8, 2, 3])?;
+        DagCborCodec
+            .decode::<ByteBuf>(&bytes)
+            .expect_err("should have failed to decode a list")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn option() -> Result<()> {
+        fn roundtrip<T: Encode<DagCbor> + Decode<DagCbor> + PartialEq + std::fmt::Debug>(
+            data: T,
+        ) -> Result<()> {
+            let bytes = DagCborCodec.encode(&data)?;
+            let data2: T = DagCborCodec.decode(&bytes)?;
+            assert_eq!(data, data2);
+            Ok(())
+        }
+        roundtrip(Some(42u64))?;
+        roundtrip(None::<u64>)?;
+        roundtrip(Some("hello".to_string()))?;
+        roundtrip(None::<String>)?;
+        roundtrip(Some(vec![1u8, 2, 3]))?;
+        roundtrip(None::<Vec<u8>>)?;
+        roundtrip(Some(Some(42u64)))?;
+        roundtrip(None::<Option<u64>>)?;
+
+        // Both `Some(None)` and `None` are encoded as null, hence they can't be told apart.
+        let bytes = DagCborCodec.encode(&Some(None::<u64>))?;
+        assert_eq!(bytes, [0xf6]);
+        let data: Option<Option<u64>> = DagCborCodec.decode(&bytes)?;
+        assert_eq!(data, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn smart_pointers() -> Result<()> {
+        #[derive(Debug, PartialEq)]
+        enum Tree {
+            Leaf(u64),
+            Node(Box<Tree>, Box<Tree>),
+        }
+
+        impl Encode<DagCbor> for Tree {
+            fn encode<W: std::io::Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+                match self {
+                    Self::Leaf(value) => value.encode(c, w),
+                    Self::Node(left, right) => (left, right).encode(c, w),
+                }
+            }
+        }
+
+        impl Decode<DagCbor> for Tree {
+            fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+                let major = read_major(r)?;
+                match major.kind() {
+                    MajorKind::UnsignedInt => Ok(Self::Leaf(read_uint(r, major)?)),
+                    MajorKind::Array if read_uint(r, major)? == 2 => {
+                        Ok(Self::Node(Box::decode(c, r)?, Box::decode(c, r)?))
+                    }
+                    _ => Err(UnexpectedCode::new::<Self>(major.into()).into()),
+                }
+            }
+        }
+
+        let tree = Tree::Node(
+            Box::new(Tree::Leaf(1)),
+            Box::new(Tree::Node(Box::new(Tree::Leaf(2)), Box::new(Tree::Leaf(3)))),
+        );
+        let bytes = DagCborCodec.encode(&tree)?;
+        assert_eq!(bytes, [0x82, 0x01, 0x82, 0x02, 0x03]);
+        assert_eq!(DagCborCodec.decode::<Tree>(&bytes)?, tree);
+
+        let data = Rc::new("hello".to_string());
         let bytes = DagCborCodec.encode(&data)?;
-        let _data2: () = DagCborCodec.decode(&bytes)?;
+        assert_eq!(bytes, DagCborCodec.encode(&*data)?);
+        assert_eq!(bytes, DagCborCodec.encode(&&data)?);
+        assert_eq!(DagCborCodec.decode::<Rc<String>>(&bytes)?, data);
+        assert_eq!(
+            DagCborCodec.decode::<Arc<String>>(&bytes)?.as_str(),
+            "hello"
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::let_unit_value)]
+    fn unit() -> Result<()> {
+        let bytes = DagCborCodec.encode(&())?;
+        assert_eq!(bytes, [0xf6]);
+        let _data: () = DagCborCodec.decode(&bytes)?;
+        DagCborCodec
+            .decode::<()>(&[0xf7])
+            .expect_err("should have failed to decode undefined")
+            .downcast::<Undefined>()
+            .unwrap();
+        DagCborCodec
+            .decode::<Option<()>>(&[0xf7])
+            .expect_err("should have failed to decode undefined")
+            .downcast::<Undefined>()
+            .unwrap();
+        DagCborCodec
+            .decode::<()>(&[0x80])
+            .expect_err("should have failed to decode an empty list")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn cows() -> Result<()> {
+        let borrowed: Cow<str> = Cow::Borrowed("hello");
+        let owned: Cow<str> = Cow::Owned("hello".to_string());
+        let bytes = DagCborCodec.encode(&borrowed)?;
+        assert_eq!(bytes, DagCborCodec.encode(&owned)?);
+        assert_eq!(bytes, DagCborCodec.encode("hello")?);
+        assert_eq!(DagCborCodec.decode::<Cow<str>>(&bytes)?, borrowed);
+
+        let borrowed: Cow<[u8]> = Cow::Borrowed(&[1, 2, 3]);
+        let owned: Cow<[u8]> = Cow::Owned(vec![1, 2, 3]);
+        let bytes = DagCborCodec.encode(&borrowed)?;
+        assert_eq!(bytes, [0x43, 0x01, 0x02, 0x03]);
+        assert_eq!(bytes, DagCborCodec.encode(&owned)?);
+        assert_eq!(DagCborCodec.decode::<Cow<[u8]>>(&bytes)?, borrowed);
+
+        DagCborCodec
+            .decode::<Cow<str>>(&[0x62, 0xff, 0xfe])
+            .expect_err("should have failed to decode invalid UTF-8")
+            .downcast::<InvalidUtf8>()
+            .unwrap();
+        DagCborCodec
+            .decode::<Cow<[u8]>>(&[0x63, 0x61, 0x62, 0x63])
+            .expect_err("should have failed to decode a text string as bytes")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn chars() -> Result<()> {
+        for ch in ['a', 'ß', '€', '🦀'] {
+            let bytes = DagCborCodec.encode(&ch)?;
+            assert_eq!(bytes, DagCborCodec.encode(&ch.to_string())?);
+            assert_eq!(DagCborCodec.decode::<char>(&bytes)?, ch);
+        }
+        assert_eq!(DagCborCodec.encode(&'€')?, [0x63, 0xe2, 0x82, 0xac]);
+
+        for string in ["", "ab", "€€"] {
+            let err = DagCborCodec
+                .decode::<char>(&DagCborCodec.encode(string)?)
+                .expect_err("should have failed to decode a string that isn't a single char")
+                .downcast::<LengthMismatch>()
+                .unwrap();
+            assert_eq!(err.len, string.chars().count() as u64);
+        }
+        DagCborCodec
+            .decode::<char>(&[0x01])
+            .expect_err("should have failed to decode an integer")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bytes() -> Result<()> {
+        use bytes::Bytes;
+
+        for data in [
+            Bytes::new(),
+            Bytes::from_static(&[1, 2, 3]),
+            vec![7; 300].into(),
+        ] {
+            let bytes = DagCborCodec.encode(&data)?;
+            assert_eq!(bytes, DagCborCodec.encode(&data[..])?);
+            assert_eq!(DagCborCodec.decode::<Bytes>(&bytes)?, data);
+        }
+        let mut map = BTreeMap::new();
+        map.insert("data".to_string(), Bytes::from_static(b"payload"));
+        assert_eq!(
+            DagCborCodec.decode::<Ipld>(&DagCborCodec.encode(&map)?)?,
+            ipld!({ "data": b"payload".to_vec() })
+        );
+        DagCborCodec
+            .decode::<Bytes>(&DagCborCodec.encode("text")?)
+            .expect_err("should have failed to decode a text string as bytes")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        Ok(())
+    }
 
+    #[test]
+    fn tuples() -> Result<()> {
         let data = ("hello".to_string(),);
         let bytes = DagCborCodec.encode(&data)?;
         let data2: (String,) = DagCborCodec.decode(&bytes)?;
@@ -629,4 +3250,334 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn tuples_mixed() -> Result<()> {
+        let cid = Cid::try_from("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL")?;
+        let data = (42u64, "hello".to_string(), Some(cid));
+        let bytes = DagCborCodec.encode(&data)?;
+        let data2: (u64, String, Option<Cid>) = DagCborCodec.decode(&bytes)?;
+        assert_eq!(data, data2);
+
+        let data = (
+            1u8,
+            2u16,
+            3u32,
+            4u64,
+            -5i64,
+            true,
+            None::<Cid>,
+            "eight".to_string(),
+        );
+        let bytes = DagCborCodec.encode(&data)?;
+        assert_eq!(bytes[0], 0x88);
+        let data2: (u8, u16, u32, u64, i64, bool, Option<Cid>, String) =
+            DagCborCodec.decode(&bytes)?;
+        assert_eq!(data, data2);
+
+        let bytes = DagCborCodec.encode(&(1u8, 2u8, 3u8))?;
+        let err = DagCborCodec
+            .decode::<(u8, u8)>(&bytes)
+            .expect_err("should have failed to decode a list of the wrong length")
+            .downcast::<LengthMismatch>()
+            .unwrap();
+        assert_eq!((err.expected, err.len), (2, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn arrays() -> Result<()> {
+        let data = [1u64, 2, 3];
+        let bytes = DagCborCodec.encode(&data)?;
+        assert_eq!(bytes, [0x83, 0x01, 0x02, 0x03]);
+        let data2: [u64; 3] = DagCborCodec.decode(&bytes)?;
+        assert_eq!(data, data2);
+
+        let data: [String; 0] = [];
+        let bytes = DagCborCodec.encode(&data)?;
+        assert_eq!(bytes, [0x80]);
+        let data2: [String; 0] = DagCborCodec.decode(&bytes)?;
+        assert_eq!(data, data2);
+
+        let data = [[0u8; 32]; 2];
+        let bytes = DagCborCodec.encode(&data)?;
+        let data2: [[u8; 32]; 2] = DagCborCodec.decode(&bytes)?;
+        assert_eq!(data, data2);
+
+        let cid = Cid::try_from("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL")?;
+        let data = [cid; 4];
+        let bytes = DagCborCodec.encode(&data)?;
+        let data2: [Cid; 4] = DagCborCodec.decode(&bytes)?;
+        assert_eq!(data, data2);
+
+        let err = DagCborCodec
+            .decode::<[u64; 2]>(&[0x83, 0x01, 0x02, 0x03])
+            .expect_err("should have failed to decode a list of the wrong length")
+            .downcast::<LengthMismatch>()
+            .unwrap();
+        assert_eq!((err.expected, err.len), (2, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn map_keys() -> Result<()> {
+        let bytes = [
+            0xa2, // Map of length 2
+            0x01, 0x61, 0x61, // 1: "a"
+            0x18, 0x2a, 0x61, 0x62, // 42: "b"
+        ];
+        let map: BTreeMap<u64, String> = DagCborCodec.decode(&bytes)?;
+        assert_eq!(map[&1], "a");
+        assert_eq!(map[&42], "b");
+
+        let bytes = [
+            0xa2, // Map of length 2
+            0x61, 0x61, 0x01, // "a": 1
+            0x02, 0x03, // 2: 3
+        ];
+        DagCborCodec
+            .decode::<BTreeMap<String, u64>>(&bytes)
+            .expect_err("should have failed to decode an integer key as string")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        DagCborCodec
+            .decode::<BTreeMap<u64, u64>>(&bytes)
+            .expect_err("should have failed to decode a string key as integer")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode an integer key as string")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn hash_map() -> Result<()> {
+        let mut data = HashMap::new();
+        data.insert("hello".to_string(), 1u64);
+        data.insert("a".to_string(), 2);
+        data.insert("world".to_string(), 3);
+        let bytes = DagCborCodec.encode(&data)?;
+        let data2: HashMap<String, u64> = DagCborCodec.decode(&bytes)?;
+        assert_eq!(data, data2);
+
+        // The keys are sorted like the ones of a `BTreeMap`.
+        let data: BTreeMap<_, _> = data.into_iter().collect();
+        assert_eq!(bytes, DagCborCodec.encode(&data)?);
+
+        let bytes = [0xbf, 0x61, 0x61, 0x01, 0x61, 0x62, 0x02, 0xff];
+        let data: HashMap<String, u64, std::collections::hash_map::RandomState> =
+            DagCborCodec.decode(&bytes)?;
+        assert_eq!(data.len(), 2);
+
+        // If a key occurs more than once, the last value wins.
+        let bytes = [0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02];
+        let data: HashMap<String, u64> = DagCborCodec.decode(&bytes)?;
+        assert_eq!(data["a"], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn skip() -> Result<()> {
+        let cid = Cid::try_from("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL")?;
+        let mut map = IpldMap::new();
+        map.insert("bytes".to_string(), Ipld::Bytes(vec![0; 100_000]));
+        map.insert("link".to_string(), Ipld::Link(cid));
+        map.insert("float".to_string(), Ipld::Float(1.5));
+        map.insert("list".to_string(), Ipld::List(vec![Ipld::Null; 3]));
+        let ipld = Ipld::List(vec![Ipld::Map(map), Ipld::Integer(-1000)]);
+        let mut bytes = DagCborCodec.encode(&ipld)?;
+        let len = bytes.len();
+        bytes.push(0x01);
+        let mut r = Cursor::new(&bytes);
+        skip_value(&mut r)?;
+        assert_eq!(r.position(), len as u64);
+
+        // Indefinite-length items.
+        let bytes = [
+            0xbf, // Start indefinite-length map
+            0x7f, 0x61, 0x61, 0xff, // "a"
+            0x9f, 0x5f, 0x41, 0x01, 0xff, 0xf9, 0x3c, 0x00, 0xff, // [h'01', 1.0]
+            0xff, // "break"
+            0x01,
+        ];
+        let mut r = Cursor::new(&bytes);
+        skip_value(&mut r)?;
+        assert_eq!(r.position(), 15);
+
+        skip_value(&mut Cursor::new(&[0xbf, 0x61, 0x61, 0xff]))
+            .expect_err("should have failed to skip a key without a value")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        skip_value(&mut Cursor::new(&[0x5a, 0xff, 0xff, 0xff, 0xff]))
+            .expect_err("should have failed to skip a truncated byte string")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        skip_value(&mut Cursor::new(vec![0x81; 10_000]))
+            .expect_err("should have failed to skip deeply nested lists")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+        skip_value(&mut Cursor::new(vec![0xc1; 10_000]))
+            .expect_err("should have failed to skip deeply nested tags")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn slice_decoder() -> Result<()> {
+        let mut bytes = DagCborCodec.encode(&("hello".to_string(), 1u8))?;
+        let len = bytes.len();
+        bytes.extend(DagCborCodec.encode(&vec![1u64, 2, 3])?);
+        bytes.extend([0x82, 0x01, 0xfc]);
+
+        let mut decoder = SliceDecoder::new(&bytes);
+        let data: (String, u8) = decoder.decode()?;
+        assert_eq!(data, ("hello".to_string(), 1));
+        assert_eq!(decoder.position(), len);
+        let data: Ipld = decoder.decode()?;
+        assert_eq!(data.get(2)?, &Ipld::Integer(3));
+        assert_eq!(decoder.position(), len + 4);
+        assert_eq!(decoder.remaining(), [0x82, 0x01, 0xfc]);
+
+        let err = decoder
+            .decode::<Ipld>()
+            .expect_err("should have failed to decode an invalid list");
+        let location = err.downcast_ref::<Location>().unwrap();
+        assert_eq!(location.offset, len as u64 + 6);
+        assert_eq!(location.path, ["1"]);
+        assert_eq!(err.downcast_ref::<UnexpectedCode>().unwrap().code, 0xfc);
+        // The position isn't advanced on error.
+        assert_eq!(decoder.position(), len + 4);
+
+        let (data, len) = decode_prefix::<Vec<u64>>(&[0x82, 0x01, 0x02, 0x03])?;
+        assert_eq!((data, len), (vec![1, 2], 3));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_prefix_len() -> Result<()> {
+        /// Checks that the count is the length of the encoding, whatever follows it.
+        fn check<T>(value: T) -> Result<()>
+        where
+            T: Decode<DagCbor> + Encode<DagCbor> + PartialEq + std::fmt::Debug,
+        {
+            let mut bytes = DagCborCodec.encode(&value)?;
+            let len = bytes.len();
+            bytes.extend_from_slice(&[0x82, 0x01, 0x02]);
+            assert_eq!(decode_prefix::<T>(&bytes)?, (value, len));
+            Ok(())
+        }
+
+        let cid = Cid::try_from("bafyreibvjvcv745gig4mvqs4hctx4zfkono4rjejm2ta6gtyzkqxfjeily")?;
+        check(ipld!([1, [2, [3, [4, []]]], { "a": { "b": { "c": null } } }]))?;
+        check(ipld!({ "link": cid, "links": [cid, cid], "bytes": vec![0u8; 300] }))?;
+        check(Ipld::Integer(-(u64::MAX as i128) - 1))?;
+        check(vec![Some(cid), None])?;
+        check(("text".to_string(), -1_000_000i64, 1.5f64))?;
+        check(BTreeMap::from([("key".to_string(), vec![u64::MAX; 3])]))?;
+        Ok(())
+    }
+
+    #[test]
+    fn location() {
+        let bytes = [
+            0xa2, // Map of length 2
+            0x61, 0x61, 0x01, // "a": 1
+            0x67, 0x70, 0x61, 0x79, 0x6c, 0x6f, 0x61, 0x64, // "payload"
+            0x84, 0x01, 0x02, 0x03, // [1, 2, 3, ..
+            0xc1, 0x01, // .. tag 1]
+        ];
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode an unknown tag");
+        let location = err.downcast_ref::<Location>().unwrap();
+        assert_eq!(location.offset, 16);
+        assert_eq!(location.path, ["payload", "3"]);
+        assert_eq!(err.downcast_ref::<UnknownTag>().unwrap().0, 1);
+        assert_eq!(
+            format!("{:#}", err),
+            "Decoding failed at offset 16 under `payload/3`: Unkown cbor tag `1`."
+        );
+
+        // An invalid key.
+        let bytes = [0xa2, 0x61, 0x61, 0x01, 0x01, 0x02];
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode an integer key");
+        let location = err.downcast_ref::<Location>().unwrap();
+        assert_eq!(location.offset, 4);
+        assert!(location.path.is_empty());
+        assert_eq!(err.downcast_ref::<UnexpectedCode>().unwrap().code, 0x01);
+
+        // A truncated item.
+        let bytes = [0x82, 0x01, 0x5a, 0xff, 0xff, 0xff, 0xff, 0x01];
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a truncated byte string");
+        let location = err.downcast_ref::<Location>().unwrap();
+        assert_eq!(location.offset, 2);
+        assert_eq!(location.path, ["1"]);
+        assert!(err.downcast_ref::<UnexpectedEof>().is_some());
+    }
+
+    #[test]
+    fn strict() {
+        let strict = |bytes: &[u8]| read_ipld(&mut Cursor::new(bytes), &DecodeOptions::strict());
+
+        // Indefinite-length items.
+        for bytes in [
+            &[0x5f, 0x41, 0x01, 0xff][..],
+            &[0x7f, 0x61, 0x61, 0xff][..],
+            &[0x9f, 0xff][..],
+            &[0xbf, 0xff][..],
+            &[0x81, 0x9f, 0xff][..],
+        ] {
+            DagCborCodec.decode::<Ipld>(bytes).unwrap();
+            strict(bytes)
+                .expect_err("should have rejected an indefinite-length item")
+                .downcast::<IndefiniteLength>()
+                .unwrap();
+        }
+
+        // Floats that aren't 64 bit.
+        for bytes in [&[0xf9, 0x3e, 0x00][..], &[0xfa, 0x3f, 0xc0, 0x00, 0x00][..]] {
+            DagCborCodec.decode::<Ipld>(bytes).unwrap();
+            strict(bytes)
+                .expect_err("should have rejected a float that isn't 64 bit")
+                .downcast::<FloatNotF64>()
+                .unwrap();
+        }
+        let bytes = [0xfb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0];
+        assert_eq!(strict(&bytes).unwrap(), Ipld::Float(1.5));
+
+        // Duplicate keys.
+        let bytes = [0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02];
+        let err = strict(&bytes)
+            .expect_err("should have rejected a duplicate key")
+            .downcast::<DuplicateKey>()
+            .unwrap();
+        assert_eq!(err.0, "a");
+
+        // Unordered keys.
+        let bytes = [0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02];
+        DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+        let err = strict(&bytes)
+            .expect_err("should have rejected unordered keys")
+            .downcast::<UnorderedKeys>()
+            .unwrap();
+        assert_eq!(err.0, "a");
+
+        // Integers with a longer head than needed.
+        assert_eq!(
+            DagCborCodec.decode::<Ipld>(&[0x18, 0x05]).unwrap(),
+            Ipld::Integer(5)
+        );
+        strict(&[0x18, 0x05])
+            .expect_err("should have rejected a non-minimal integer")
+            .downcast::<NumberNotMinimal>()
+            .unwrap();
+    }
 }
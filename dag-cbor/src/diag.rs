@@ -0,0 +1,262 @@
+//! Diagnostic notation, the human readable form of cbor defined in RFC 8949, section 8.
+//!
+//! Byte strings are written as `h'00ff'`, links as the tagged byte string they are encoded as,
+//! `42(h'00...')`, and lists and maps of indefinite length with a leading underscore, `[_ 1, 2]`.
+//!
+//! # Example
+//!
+//! ```
+//! use libipld_cbor::diag::{diag_from_slice, to_diag};
+//! use libipld_macro::ipld;
+//!
+//! let ipld = ipld!({ "a": 1, "b": [true, null, 1.5], "c": vec![0u8, 255] });
+//! assert_eq!(to_diag(&ipld), r#"{"a": 1, "b": [true, null, 1.5], "c": h'00ff'}"#);
+//!
+//! assert_eq!(diag_from_slice(&[0x9f, 0x01, 0x02, 0xff])?, "[_ 1, 2]");
+//! # Ok::<(), libipld_core::error::Error>(())
+//! ```
+use crate::error::TrailingBytes;
+use crate::token::{Token, Tokenizer};
+use libipld_core::cid::Cid;
+use libipld_core::error::Result;
+use libipld_core::ipld::Ipld;
+use std::fmt::Write;
+
+/// Returns the diagnostic notation of `Ipld`.
+///
+/// Map keys are written in the order of the map, not in the order they are encoded in.
+pub fn to_diag(ipld: &Ipld) -> String {
+    let mut out = String::new();
+    write_ipld(&mut out, ipld);
+    out
+}
+
+fn write_ipld(out: &mut String, ipld: &Ipld) {
+    match ipld {
+        Ipld::Null => out.push_str("null"),
+        Ipld::Bool(b) => write!(out, "{}", b).unwrap(),
+        Ipld::Integer(i) => write!(out, "{}", i).unwrap(),
+        Ipld::Float(f) => write_float(out, *f),
+        Ipld::String(s) => write_string(out, s),
+        Ipld::Bytes(b) => write_bytes(out, b),
+        Ipld::Link(cid) => write_link(out, cid),
+        Ipld::List(l) => {
+            out.push('[');
+            for (i, value) in l.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_ipld(out, value);
+            }
+            out.push(']');
+        }
+        Ipld::Map(m) => {
+            out.push('{');
+            for (i, (key, value)) in m.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_string(out, key);
+                out.push_str(": ");
+                write_ipld(out, value);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// Returns the diagnostic notation of a single cbor encoded item.
+///
+/// Unlike [`to_diag`] this shows the item as it is encoded, including the order of map keys and
+/// indefinite lengths. It accepts the same input as decoding `Ipld` with the default options.
+pub fn diag_from_slice(bytes: &[u8]) -> Result<String> {
+    let mut out = String::new();
+    // Whether each open list or map is a map, and the number of items in it so far.
+    let mut stack: Vec<(bool, u64)> = Vec::new();
+    let mut tokenizer = Tokenizer::new(bytes);
+    while let Some(token) = tokenizer.next_token()? {
+        if token == Token::End {
+            let (is_map, _) = stack.pop().expect("the tokenizer balances the ends");
+            out.push(if is_map { '}' } else { ']' });
+            continue;
+        }
+        if let Some((is_map, items)) = stack.last_mut() {
+            if *is_map && *items % 2 == 1 {
+                out.push_str(": ");
+            } else if *items > 0 {
+                out.push_str(", ");
+            }
+            *items += 1;
+        }
+        match token {
+            Token::Null => out.push_str("null"),
+            Token::Bool(b) => write!(out, "{}", b).unwrap(),
+            Token::Integer(i) => write!(out, "{}", i).unwrap(),
+            Token::Float(f) => write_float(&mut out, f),
+            Token::String(s) => write_string(&mut out, &s),
+            Token::Bytes(b) => write_bytes(&mut out, &b),
+            Token::Link(cid) => write_link(&mut out, &cid),
+            Token::StartList(len) => {
+                out.push_str(if len.is_some() { "[" } else { "[_ " });
+                stack.push((false, 0));
+            }
+            Token::StartMap(len) => {
+                out.push_str(if len.is_some() { "{" } else { "{_ " });
+                stack.push((true, 0));
+            }
+            Token::End => unreachable!(),
+        }
+    }
+    let remaining = tokenizer.into_inner().len();
+    if remaining > 0 {
+        return Err(TrailingBytes(remaining).into());
+    }
+    Ok(out)
+}
+
+/// Writes a float with a decimal point or an exponent, so that it can't be mistaken for an
+/// integer, e.g. `1.0` or `1.0e+300`.
+fn write_float(out: &mut String, value: f64) {
+    if value.is_nan() {
+        out.push_str("NaN");
+    } else if value.is_infinite() {
+        out.push_str(if value > 0.0 { "Infinity" } else { "-Infinity" });
+    } else {
+        let s = format!("{:?}", value);
+        match s.split_once('e') {
+            Some((mantissa, exponent)) => {
+                out.push_str(mantissa);
+                if !mantissa.contains('.') {
+                    out.push_str(".0");
+                }
+                out.push('e');
+                if !exponent.starts_with('-') {
+                    out.push('+');
+                }
+                out.push_str(exponent);
+            }
+            None => out.push_str(&s),
+        }
+    }
+}
+
+/// Writes a string in double quotes, escaped like in JSON.
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Writes a byte string in base16, e.g. `h'00ff'`.
+fn write_bytes(out: &mut String, bytes: &[u8]) {
+    out.push_str("h'");
+    for byte in bytes {
+        write!(out, "{:02x}", byte).unwrap();
+    }
+    out.push('\'');
+}
+
+/// Writes a link as it is encoded, tag 42 with the binary CID prefixed by a zero byte.
+fn write_link(out: &mut String, cid: &Cid) {
+    out.push_str("42(h'00");
+    for byte in cid.to_bytes() {
+        write!(out, "{:02x}", byte).unwrap();
+    }
+    out.push_str("')");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DagCborCodec;
+    use libipld_core::codec::Codec;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_macro::ipld;
+
+    fn diag(hex: &str) -> String {
+        diag_from_slice(&hex::decode(hex).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rfc_examples() {
+        // Examples from RFC 8949, appendix A.
+        assert_eq!(diag("00"), "0");
+        assert_eq!(diag("1bffffffffffffffff"), "18446744073709551615");
+        assert_eq!(diag("3bffffffffffffffff"), "-18446744073709551616");
+        assert_eq!(diag("c249010000000000000000"), "18446744073709551616");
+        assert_eq!(diag("fb3ff199999999999a"), "1.1");
+        assert_eq!(diag("f93c00"), "1.0");
+        assert_eq!(diag("f9c400"), "-4.0");
+        assert_eq!(diag("fb7e37e43c8800759c"), "1.0e+300");
+        assert_eq!(diag("f90001"), "5.960464477539063e-8");
+        assert_eq!(diag("f97c00"), "Infinity");
+        assert_eq!(diag("f97e00"), "NaN");
+        assert_eq!(diag("fbfff0000000000000"), "-Infinity");
+        assert_eq!(diag("f4"), "false");
+        assert_eq!(diag("f6"), "null");
+        assert_eq!(diag("40"), "h''");
+        assert_eq!(diag("4401020304"), "h'01020304'");
+        assert_eq!(diag("60"), r#""""#);
+        assert_eq!(diag("62225c"), r#""\"\\""#);
+        assert_eq!(diag("63e6b0b4"), "\"\u{6c34}\"");
+        assert_eq!(diag("80"), "[]");
+        assert_eq!(diag("8301820203820405"), "[1, [2, 3], [4, 5]]");
+        assert_eq!(diag("a0"), "{}");
+        assert_eq!(diag("a201020304"), "{1: 2, 3: 4}");
+        assert_eq!(diag("a26161016162820203"), r#"{"a": 1, "b": [2, 3]}"#);
+        assert_eq!(diag("5f42010243030405ff"), "h'0102030405'");
+        assert_eq!(diag("9fff"), "[_ ]");
+        assert_eq!(diag("9f018202039f0405ffff"), "[_ 1, [2, 3], [_ 4, 5]]");
+        assert_eq!(
+            diag("bf61610161629f0203ffff"),
+            r#"{_ "a": 1, "b": [_ 2, 3]}"#
+        );
+    }
+
+    #[test]
+    fn ipld() {
+        let cid = Cid::new_v1(0x71, Code::Sha2_256.digest(b"diag"));
+        let ipld = ipld!({
+            "bytes": vec![0u8, 1, 255],
+            "float": 100000.0,
+            "link": cid,
+            "list": [-1, "tab\there", { "nested": null }],
+        });
+        let expected = format!(
+            r#"{{"bytes": h'0001ff', "float": 100000.0, "link": 42(h'00{}'), "list": [-1, "tab\there", {{"nested": null}}]}}"#,
+            hex::encode(cid.to_bytes())
+        );
+        assert_eq!(to_diag(&ipld), expected);
+
+        // The encoded map is in canonical order.
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        let expected = format!(
+            r#"{{"link": 42(h'00{}'), "list": [-1, "tab\there", {{"nested": null}}], "bytes": h'0001ff', "float": 100000.0}}"#,
+            hex::encode(cid.to_bytes())
+        );
+        assert_eq!(diag_from_slice(&bytes).unwrap(), expected);
+    }
+
+    #[test]
+    fn invalid() {
+        diag_from_slice(&[0x82, 0x01])
+            .expect_err("should have failed on a truncated list")
+            .downcast::<crate::error::UnexpectedEof>()
+            .unwrap();
+        let err = diag_from_slice(&[0x01, 0x02])
+            .expect_err("should have failed on trailing bytes")
+            .downcast::<TrailingBytes>()
+            .unwrap();
+        assert_eq!(err.0, 1);
+    }
+}
@@ -1,20 +1,25 @@
 //! CBOR encoder.
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::Write;
 use std::iter::FromIterator;
 use std::ops::Deref;
+use std::rc::Rc;
 use std::sync::Arc;
 
 use byteorder::{BigEndian, ByteOrder};
-use libipld_core::cid::Cid;
-use libipld_core::codec::Encode;
+use libipld_core::cid::{Cid, Version};
+use libipld_core::codec::{CountingWriter, Encode};
 use libipld_core::error::Result;
 use libipld_core::ipld::Ipld;
 
-use crate::cbor::{MajorKind, FALSE, TRUE};
-use crate::error::NumberOutOfRange;
+use crate::cbor::{ByteBuf, KeyOrder, List, MajorKind, BREAK, FALSE, TRUE};
+use crate::error::{
+    DuplicateKey, InvalidEncoderState, ItemCountMismatch, NonFiniteFloat, NumberOutOfRange,
+    UnclosedContainers, UnorderedKeys,
+};
 use crate::DagCborCodec as DagCbor;
 
 /// Writes a null byte to a cbor encoded byte stream.
@@ -23,52 +28,77 @@ pub fn write_null<W: Write>(w: &mut W) -> Result<()> {
     Ok(())
 }
 
+/// Returns the size of `value` encoded, by encoding it into a [`CountingWriter`].
+///
+/// Unlike [`Encode::encoded_len`] this never relies on a size computed without encoding, so it
+/// also measures custom types whose `encoded_len` is inexact.
+pub fn cbor_size<T: Encode<DagCbor> + ?Sized>(value: &T) -> Result<usize> {
+    let mut w = CountingWriter::new();
+    value.encode(DagCbor, &mut w)?;
+    Ok(w.count())
+}
+
+/// The first bytes of an item, its major type and argument. For floats this is the whole item.
+///
+/// Computing the head is separate from writing it, so that the sync and async encoders share it.
+pub(crate) struct Head {
+    buf: [u8; 9],
+    len: usize,
+}
+
+impl Deref for Head {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.buf[..self.len]
+    }
+}
+
+/// Returns the minimal head of an item with the given argument.
+pub(crate) fn head(major: MajorKind, value: u64) -> Head {
+    let major = (major as u8) << 5;
+    let mut buf = [0; 9];
+    let len = head_len(value);
+    buf[0] = match len {
+        1 => major | value as u8,
+        2 => major | 24,
+        3 => major | 25,
+        5 => major | 26,
+        _ => major | 27,
+    };
+    buf[1..len].copy_from_slice(&value.to_be_bytes()[9 - len..]);
+    Head { buf, len }
+}
+
+/// Returns the head of an integer, which is written without anything following it.
+pub(crate) fn int_head(value: i128) -> Result<Head> {
+    let (major, value) = if value < 0 {
+        (MajorKind::NegativeInt, -(value + 1))
+    } else {
+        (MajorKind::UnsignedInt, value)
+    };
+    let value = u64::try_from(value).map_err(|_| NumberOutOfRange::new::<i128>())?;
+    Ok(head(major, value))
+}
+
 /// Writes a u8 to a cbor encoded byte stream.
 pub fn write_u8<W: Write>(w: &mut W, major: MajorKind, value: u8) -> Result<()> {
-    let major = major as u8;
-    if value <= 0x17 {
-        let buf = [major << 5 | value];
-        w.write_all(&buf)?;
-    } else {
-        let buf = [major << 5 | 24, value];
-        w.write_all(&buf)?;
-    }
-    Ok(())
+    write_u64(w, major, value.into())
 }
 
 /// Writes a u16 to a cbor encoded byte stream.
 pub fn write_u16<W: Write>(w: &mut W, major: MajorKind, value: u16) -> Result<()> {
-    if value <= u16::from(u8::max_value()) {
-        write_u8(w, major, value as u8)?;
-    } else {
-        let mut buf = [(major as u8) << 5 | 25, 0, 0];
-        BigEndian::write_u16(&mut buf[1..], value);
-        w.write_all(&buf)?;
-    }
-    Ok(())
+    write_u64(w, major, value.into())
 }
 
 /// Writes a u32 to a cbor encoded byte stream.
 pub fn write_u32<W: Write>(w: &mut W, major: MajorKind, value: u32) -> Result<()> {
-    if value <= u32::from(u16::max_value()) {
-        write_u16(w, major, value as u16)?;
-    } else {
-        let mut buf = [(major as u8) << 5 | 26, 0, 0, 0, 0];
-        BigEndian::write_u32(&mut buf[1..], value);
-        w.write_all(&buf)?;
-    }
-    Ok(())
+    write_u64(w, major, value.into())
 }
 
 /// Writes a u64 to a cbor encoded byte stream.
 pub fn write_u64<W: Write>(w: &mut W, major: MajorKind, value: u64) -> Result<()> {
-    if value <= u64::from(u32::max_value()) {
-        write_u32(w, major, value as u32)?;
-    } else {
-        let mut buf = [(major as u8) << 5 | 27, 0, 0, 0, 0, 0, 0, 0, 0];
-        BigEndian::write_u64(&mut buf[1..], value);
-        w.write_all(&buf)?;
-    }
+    w.write_all(&head(major, value))?;
     Ok(())
 }
 
@@ -77,36 +107,82 @@ pub fn write_tag<W: Write>(w: &mut W, tag: u64) -> Result<()> {
     write_u64(w, MajorKind::Tag, tag)
 }
 
+/// Returns the length of a major with the given argument, as written by [`write_u64`].
+pub fn head_len(value: u64) -> usize {
+    match value {
+        0..=0x17 => 1,
+        0x18..=0xff => 2,
+        0x100..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Returns the length of an unsigned varint.
+fn varint_len(value: u64) -> usize {
+    (64 - value.leading_zeros() as usize).max(1).div_ceil(7)
+}
+
+/// Returns the length of the binary form of a CID.
+fn cid_len(cid: &Cid) -> usize {
+    let hash = cid.hash();
+    let hash_len = varint_len(hash.code()) + varint_len(hash.size().into()) + hash.size() as usize;
+    match cid.version() {
+        Version::V0 => hash_len,
+        Version::V1 => varint_len(1) + varint_len(cid.codec()) + hash_len,
+    }
+}
+
 impl Encode<DagCbor> for bool {
     fn encode<W: Write>(&self, _: DagCbor, w: &mut W) -> Result<()> {
         let buf = if *self { [TRUE.into()] } else { [FALSE.into()] };
         w.write_all(&buf)?;
         Ok(())
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        Ok(1)
+    }
 }
 
 impl Encode<DagCbor> for u8 {
     fn encode<W: Write>(&self, _: DagCbor, w: &mut W) -> Result<()> {
         write_u8(w, MajorKind::UnsignedInt, *self)
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        Ok(head_len((*self).into()))
+    }
 }
 
 impl Encode<DagCbor> for u16 {
     fn encode<W: Write>(&self, _: DagCbor, w: &mut W) -> Result<()> {
         write_u16(w, MajorKind::UnsignedInt, *self)
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        Ok(head_len((*self).into()))
+    }
 }
 
 impl Encode<DagCbor> for u32 {
     fn encode<W: Write>(&self, _: DagCbor, w: &mut W) -> Result<()> {
         write_u32(w, MajorKind::UnsignedInt, *self)
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        Ok(head_len((*self).into()))
+    }
 }
 
 impl Encode<DagCbor> for u64 {
     fn encode<W: Write>(&self, _: DagCbor, w: &mut W) -> Result<()> {
         write_u64(w, MajorKind::UnsignedInt, *self)
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        Ok(head_len(*self))
+    }
 }
 
 impl Encode<DagCbor> for i8 {
@@ -117,6 +193,15 @@ impl Encode<DagCbor> for i8 {
             (*self as u8).encode(c, w)
         }
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        let value = if self.is_negative() {
+            -(*self + 1)
+        } else {
+            *self
+        };
+        Ok(head_len(value as u64))
+    }
 }
 
 impl Encode<DagCbor> for i16 {
@@ -127,6 +212,15 @@ impl Encode<DagCbor> for i16 {
             (*self as u16).encode(c, w)
         }
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        let value = if self.is_negative() {
+            -(*self + 1)
+        } else {
+            *self
+        };
+        Ok(head_len(value as u64))
+    }
 }
 
 impl Encode<DagCbor> for i32 {
@@ -137,6 +231,15 @@ impl Encode<DagCbor> for i32 {
             (*self as u32).encode(c, w)
         }
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        let value = if self.is_negative() {
+            -(*self + 1)
+        } else {
+            *self
+        };
+        Ok(head_len(value as u64))
+    }
 }
 
 impl Encode<DagCbor> for i64 {
@@ -147,6 +250,15 @@ impl Encode<DagCbor> for i64 {
             (*self as u64).encode(c, w)
         }
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        let value = if self.is_negative() {
+            -(*self + 1)
+        } else {
+            *self
+        };
+        Ok(head_len(value as u64))
+    }
 }
 
 impl Encode<DagCbor> for f32 {
@@ -154,18 +266,103 @@ impl Encode<DagCbor> for f32 {
         // IPLD maximally encodes floats.
         f64::from(*self).encode(c, w)
     }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        f64::from(*self).encoded_len(c)
+    }
 }
 
 impl Encode<DagCbor> for f64 {
     fn encode<W: Write>(&self, _: DagCbor, w: &mut W) -> Result<()> {
+        write_float(w, *self, &EncodeOptions::default())
+    }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
         // IPLD forbids nan, infinities, etc.
         if !self.is_finite() {
-            return Err(NumberOutOfRange::new::<f64>().into());
+            return Err(NonFiniteFloat(*self).into());
         }
-        let mut buf = [0xfb, 0, 0, 0, 0, 0, 0, 0, 0];
-        BigEndian::write_f64(&mut buf[1..], *self);
-        w.write_all(&buf)?;
-        Ok(())
+        Ok(9)
+    }
+}
+
+/// Options for encoding `Ipld`, see [`write_ipld`].
+///
+/// The default options produce canonical DAG-CBOR, which is what `Ipld::encode` and all typed
+/// encoders write.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct EncodeOptions {
+    /// Write floats in the shortest form that keeps their value, as half, single or double
+    /// precision, and allow NaN and infinities. This is valid cbor, but not DAG-CBOR, which
+    /// requires all floats to be finite and encoded as 64 bit.
+    pub shortest_floats: bool,
+    /// Write integers outside the range of cbor integers, -2^64 to 2^64 - 1, as bignums (tags 2
+    /// and 3) instead of returning [`NumberOutOfRange`]. This is valid cbor, but not DAG-CBOR.
+    pub bignums: bool,
+    /// The order of map keys. Any order but [`KeyOrder::DagCbor`] is valid cbor, but not
+    /// DAG-CBOR.
+    pub key_order: KeyOrder,
+}
+
+/// Writes a float to a cbor encoded byte stream using the given options.
+///
+/// Returns [`NonFiniteFloat`] for NaN and infinities, unless writing the shortest floats.
+pub fn write_float<W: Write>(w: &mut W, value: f64, opts: &EncodeOptions) -> Result<()> {
+    w.write_all(&float_head(value, opts)?)?;
+    Ok(())
+}
+
+/// Returns the encoded float, see [`write_float`].
+pub(crate) fn float_head(value: f64, opts: &EncodeOptions) -> Result<Head> {
+    let mut buf = [0; 9];
+    let single = value as f32;
+    let len = if !opts.shortest_floats || (f64::from(single) != value && !value.is_nan()) {
+        // IPLD forbids nan, infinities, etc.
+        if !opts.shortest_floats && !value.is_finite() {
+            return Err(NonFiniteFloat(value).into());
+        }
+        buf[0] = 0xfb;
+        BigEndian::write_f64(&mut buf[1..], value);
+        9
+    } else if let Some(half) = to_f16(single) {
+        buf[0] = 0xf9;
+        BigEndian::write_u16(&mut buf[1..3], half);
+        3
+    } else {
+        buf[0] = 0xfa;
+        BigEndian::write_f32(&mut buf[1..5], single);
+        5
+    };
+    Ok(Head { buf, len })
+}
+
+/// Returns the bits of `value` as half-precision float, if it can be converted without loss. All
+/// NaNs become the same quiet NaN.
+fn to_f16(value: f32) -> Option<u16> {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32 - 127;
+    let mant = bits & 0x7f_ffff;
+    if value.is_nan() {
+        return Some(0x7e00);
+    }
+    if value.is_infinite() {
+        return Some(sign | 0x7c00);
+    }
+    if value == 0.0 {
+        return Some(sign);
+    }
+    match exp {
+        -14..=15 if mant & 0x1fff == 0 => {
+            Some(sign | ((exp + 15) as u16) << 10 | (mant >> 13) as u16)
+        }
+        // Subnormals, the implicit leading bit becomes part of the mantissa.
+        -24..=-15 => {
+            let mant = mant | 0x80_0000;
+            let shift = -1 - exp;
+            (mant & ((1 << shift) - 1) == 0).then(|| sign | (mant >> shift) as u16)
+        }
+        _ => None,
     }
 }
 
@@ -175,12 +372,42 @@ impl Encode<DagCbor> for [u8] {
         w.write_all(self)?;
         Ok(())
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        Ok(head_len(self.len() as u64) + self.len())
+    }
 }
 
 impl Encode<DagCbor> for Box<[u8]> {
     fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
         self[..].encode(c, w)
     }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        self[..].encoded_len(c)
+    }
+}
+
+impl Encode<DagCbor> for ByteBuf {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self[..].encode(c, w)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        self[..].encoded_len(c)
+    }
+}
+
+/// Encoded as a byte string, without copying the payload.
+#[cfg(feature = "bytes")]
+impl Encode<DagCbor> for bytes::Bytes {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self[..].encode(c, w)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        self[..].encoded_len(c)
+    }
 }
 
 impl Encode<DagCbor> for str {
@@ -189,29 +416,77 @@ impl Encode<DagCbor> for str {
         w.write_all(self.as_bytes())?;
         Ok(())
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        Ok(head_len(self.len() as u64) + self.len())
+    }
+}
+
+impl Encode<DagCbor> for Cow<'_, str> {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self.as_ref().encode(c, w)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        self.as_ref().encoded_len(c)
+    }
+}
+
+/// Encoded as a byte string, just like `Box<[u8]>`.
+impl Encode<DagCbor> for Cow<'_, [u8]> {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self.as_ref().encode(c, w)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        self.as_ref().encoded_len(c)
+    }
+}
+
+impl Encode<DagCbor> for char {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self.encode_utf8(&mut [0; 4]).encode(c, w)
+    }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        Ok(1 + self.len_utf8())
+    }
 }
 
 impl Encode<DagCbor> for String {
     fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
         self.as_str().encode(c, w)
     }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        self.as_str().encoded_len(c)
+    }
 }
 
 impl Encode<DagCbor> for i128 {
     fn encode<W: Write>(&self, _: DagCbor, w: &mut W) -> Result<()> {
-        if *self < 0 {
-            if -(*self + 1) > u64::max_value() as i128 {
-                return Err(NumberOutOfRange::new::<i128>().into());
-            }
-            write_u64(w, MajorKind::NegativeInt, -(*self + 1) as u64)?;
-        } else {
-            if *self > u64::max_value() as i128 {
-                return Err(NumberOutOfRange::new::<i128>().into());
-            }
-            write_u64(w, MajorKind::UnsignedInt, *self as u64)?;
-        }
+        w.write_all(&int_head(*self)?)?;
         Ok(())
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        let value = if *self < 0 { -(*self + 1) } else { *self };
+        let value = u64::try_from(value).map_err(|_| NumberOutOfRange::new::<i128>())?;
+        Ok(head_len(value))
+    }
+}
+
+impl Encode<DagCbor> for u128 {
+    fn encode<W: Write>(&self, _: DagCbor, w: &mut W) -> Result<()> {
+        let value = u64::try_from(*self).map_err(|_| NumberOutOfRange::new::<u128>())?;
+        write_u64(w, MajorKind::UnsignedInt, value)?;
+        Ok(())
+    }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        let value = u64::try_from(*self).map_err(|_| NumberOutOfRange::new::<u128>())?;
+        Ok(head_len(value))
+    }
 }
 
 impl Encode<DagCbor> for Cid {
@@ -226,8 +501,16 @@ impl Encode<DagCbor> for Cid {
         w.write_all(&buf[..len])?;
         Ok(())
     }
+
+    fn encoded_len(&self, _: DagCbor) -> Result<usize> {
+        // The tag, and the byte string with the zero byte prefix.
+        let len = cid_len(self) + 1;
+        Ok(head_len(42) + head_len(len as u64) + len)
+    }
 }
 
+/// `None` is written as null and `Some` as the value itself. Hence `Some(None)` and `None` of an
+/// `Option<Option<T>>` have the same encoding, both decode to `None`.
 impl<T: Encode<DagCbor>> Encode<DagCbor> for Option<T> {
     fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
         if let Some(value) = self {
@@ -237,40 +520,199 @@ impl<T: Encode<DagCbor>> Encode<DagCbor> for Option<T> {
         }
         Ok(())
     }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        match self {
+            Some(value) => value.encoded_len(c),
+            None => Ok(1),
+        }
+    }
 }
 
 impl<T: Encode<DagCbor>> Encode<DagCbor> for Vec<T> {
     fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
-        write_u64(w, MajorKind::Array, self.len() as u64)?;
-        for value in self {
-            value.encode(c, w)?;
-        }
-        Ok(())
+        write_list(w, c, self)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        list_len(c, self)
+    }
+}
+
+/// Arrays are encoded as lists, just like a `Vec<T>`. This includes `[u8; N]`, use a `&[u8]` to
+/// encode a byte string.
+impl<T: Encode<DagCbor>, const N: usize> Encode<DagCbor> for [T; N] {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        write_list(w, c, self)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        list_len(c, self)
+    }
+}
+
+impl<T: Encode<DagCbor>> Encode<DagCbor> for List<'_, T> {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        write_list(w, c, self.0)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        list_len(c, self.0)
+    }
+}
+
+/// Writes a slice as a cbor list.
+pub fn write_list<W: Write, T: Encode<DagCbor>>(w: &mut W, c: DagCbor, values: &[T]) -> Result<()> {
+    write_u64(w, MajorKind::Array, values.len() as u64)?;
+    for value in values {
+        value.encode(c, w)?;
+    }
+    Ok(())
+}
+
+/// Writes a map with string keys to a cbor encoded byte stream. The entries are sorted into
+/// canonical order, hence they can be in any order.
+pub fn write_map<'a, W, K, T, I>(w: &mut W, c: DagCbor, entries: I) -> Result<()>
+where
+    W: Write,
+    K: AsRef<str> + 'a,
+    T: Encode<DagCbor> + 'a,
+    I: IntoIterator<Item = (&'a K, &'a T)>,
+{
+    let entries = ordered_entries(entries, KeyOrder::DagCbor);
+    write_u64(w, MajorKind::Map, entries.len() as u64)?;
+    for (k, v) in entries {
+        k.as_ref().encode(c, w)?;
+        v.encode(c, w)?;
+    }
+    Ok(())
+}
+
+/// Sorts map entries into the given key order.
+pub(crate) fn ordered_entries<'a, K, T, I>(entries: I, order: KeyOrder) -> Vec<(&'a K, &'a T)>
+where
+    K: AsRef<str> + 'a,
+    T: 'a,
+    I: IntoIterator<Item = (&'a K, &'a T)>,
+{
+    // CBOR RFC-7049 specifies a canonical sort order, where keys are sorted by length first.
+    // This was later revised with RFC-8949, but we need to stick to the original order to stay
+    // compatible with existing data.
+    let mut entries = Vec::from_iter(entries);
+    if order != KeyOrder::Insertion {
+        entries.sort_unstable_by(|&(key_a, _), &(key_b, _)| {
+            order
+                .compare(key_a.as_ref(), key_b.as_ref())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+    entries
+}
+
+/// Returns the length of a list, as written for a `Vec<T>`.
+fn list_len<'a, T, I>(c: DagCbor, values: I) -> Result<usize>
+where
+    T: Encode<DagCbor> + 'a,
+    I: IntoIterator<Item = &'a T>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let values = values.into_iter();
+    let mut len = head_len(values.len() as u64);
+    for value in values {
+        len += value.encoded_len(c)?;
     }
+    Ok(len)
+}
+
+/// Returns the length of a map, as written by [`write_map`].
+fn map_len<'a, K, T, I>(c: DagCbor, entries: I) -> Result<usize>
+where
+    K: AsRef<str> + 'a,
+    T: Encode<DagCbor> + 'a,
+    I: IntoIterator<Item = (&'a K, &'a T)>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let entries = entries.into_iter();
+    let mut len = head_len(entries.len() as u64);
+    for (key, value) in entries {
+        len += key.as_ref().encoded_len(c)? + value.encoded_len(c)?;
+    }
+    Ok(len)
 }
 
 impl<T: Encode<DagCbor> + 'static> Encode<DagCbor> for BTreeMap<String, T> {
     fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
-        write_u64(w, MajorKind::Map, self.len() as u64)?;
-        // CBOR RFC-7049 specifies a canonical sort order, where keys are sorted by length first.
-        // This was later revised with RFC-8949, but we need to stick to the original order to stay
-        // compatible with existing data.
-        let mut cbor_order = Vec::from_iter(self);
-        cbor_order.sort_unstable_by(|&(key_a, _), &(key_b, _)| {
-            match key_a.len().cmp(&key_b.len()) {
-                Ordering::Greater => Ordering::Greater,
-                Ordering::Less => Ordering::Less,
-                Ordering::Equal => key_a.cmp(key_b),
+        write_map(w, c, self)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        map_len(c, self)
+    }
+}
+
+impl<T: Encode<DagCbor>, S> Encode<DagCbor> for HashMap<String, T, S> {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        write_map(w, c, self)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        map_len(c, self)
+    }
+}
+
+impl<T: Encode<DagCbor>, S> Encode<DagCbor> for HashMap<&str, T, S> {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        write_map(w, c, self)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        map_len(c, self)
+    }
+}
+
+/// Writes `Ipld` to a cbor encoded byte stream using the given options.
+///
+/// This is what `Ipld::encode` does with the default options.
+pub fn write_ipld<W: Write>(w: &mut W, ipld: &Ipld, opts: &EncodeOptions) -> Result<()> {
+    match ipld {
+        Ipld::Integer(i) if opts.bignums && int_head(*i).is_err() => write_bignum(w, *i),
+        Ipld::Float(f) => write_float(w, *f, opts),
+        Ipld::List(l) => {
+            write_u64(w, MajorKind::Array, l.len() as u64)?;
+            for value in l {
+                write_ipld(w, value, opts)?;
             }
-        });
-        for (k, v) in cbor_order {
-            k.encode(c, w)?;
-            v.encode(c, w)?;
+            Ok(())
         }
-        Ok(())
+        Ipld::Map(m) => {
+            let entries = ordered_entries(m, opts.key_order);
+            write_u64(w, MajorKind::Map, entries.len() as u64)?;
+            for (key, value) in entries {
+                key.encode(DagCbor, w)?;
+                write_ipld(w, value, opts)?;
+            }
+            Ok(())
+        }
+        _ => ipld.encode(DagCbor, w),
     }
 }
 
+/// Writes an integer as a bignum, i.e. tag 2 or 3 followed by its big-endian bytes.
+fn write_bignum<W: Write>(w: &mut W, value: i128) -> Result<()> {
+    let (tag, value) = if value < 0 {
+        (3, -(value + 1))
+    } else {
+        (2, value)
+    };
+    let bytes = value.to_be_bytes();
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    write_tag(w, tag)?;
+    bytes[start..].encode(DagCbor, w)
+}
+
 impl Encode<DagCbor> for Ipld {
     fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
         match self {
@@ -281,61 +723,338 @@ impl Encode<DagCbor> for Ipld {
             Self::Bytes(b) => b.as_slice().encode(c, w),
             Self::String(s) => s.encode(c, w),
             Self::List(l) => l.encode(c, w),
-            Self::Map(m) => m.encode(c, w),
+            Self::Map(m) => write_map(w, c, m),
             Self::Link(cid) => cid.encode(c, w),
         }
     }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        match self {
+            Self::Null => Ok(1),
+            Self::Bool(b) => b.encoded_len(c),
+            Self::Integer(i) => i.encoded_len(c),
+            Self::Float(f) => f.encoded_len(c),
+            Self::Bytes(b) => b.as_slice().encoded_len(c),
+            Self::String(s) => s.encoded_len(c),
+            Self::List(l) => l.encoded_len(c),
+            Self::Map(m) => map_len(c, m),
+            Self::Link(cid) => cid.encoded_len(c),
+        }
+    }
+}
+
+impl<T: Encode<DagCbor>> Encode<DagCbor> for Rc<T> {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self.deref().encode(c, w)
+    }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        self.deref().encoded_len(c)
+    }
 }
 
 impl<T: Encode<DagCbor>> Encode<DagCbor> for Arc<T> {
     fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
         self.deref().encode(c, w)
     }
+
+    fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+        self.deref().encoded_len(c)
+    }
 }
 
 impl Encode<DagCbor> for () {
     fn encode<W: Write>(&self, _c: DagCbor, w: &mut W) -> Result<()> {
-        write_u8(w, MajorKind::Array, 0)?;
-        Ok(())
+        write_null(w)
+    }
+
+    fn encoded_len(&self, _c: DagCbor) -> Result<usize> {
+        Ok(1)
     }
 }
 
-impl<A: Encode<DagCbor>> Encode<DagCbor> for (A,) {
-    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
-        write_u8(w, MajorKind::Array, 1)?;
-        self.0.encode(c, w)?;
+macro_rules! impl_tuple {
+    ($len:expr, $($t:ident $i:tt),+) => {
+        impl<$($t: Encode<DagCbor>),+> Encode<DagCbor> for ($($t,)+) {
+            fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+                write_u8(w, MajorKind::Array, $len)?;
+                $(self.$i.encode(c, w)?;)+
+                Ok(())
+            }
+
+            fn encoded_len(&self, c: DagCbor) -> Result<usize> {
+                Ok(1 $(+ self.$i.encoded_len(c)?)+)
+            }
+        }
+    };
+}
+
+impl_tuple!(1, A 0);
+impl_tuple!(2, A 0, B 1);
+impl_tuple!(3, A 0, B 1, C 2);
+impl_tuple!(4, A 0, B 1, C 2, D 3);
+impl_tuple!(5, A 0, B 1, C 2, D 3, E 4);
+impl_tuple!(6, A 0, B 1, C 2, D 3, E 4, F 5);
+impl_tuple!(7, A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_tuple!(8, A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// Encodes lists and maps item by item, without building them in memory first.
+///
+/// Lists and maps are opened with [`Encoder::begin_list`] and [`Encoder::begin_map`], and closed
+/// with [`Encoder::end`]. In between, items are added with [`Encoder::push`] or by opening a
+/// nested list or map. Every map entry starts with [`Encoder::push_key`]. The keys have to be in
+/// canonical order, as the entries can't be sorted once they are written.
+///
+/// Lists and maps of unknown length are written with indefinite length. That's valid cbor, but
+/// not DAG-CBOR, so pass the length whenever it's known.
+///
+/// # Example
+///
+/// ```
+/// use libipld_cbor::encode::Encoder;
+/// use libipld_cbor::DagCborCodec;
+/// use libipld_core::codec::Codec;
+/// use libipld_core::ipld::Ipld;
+///
+/// let mut encoder = Encoder::new(Vec::new());
+/// encoder.begin_map(Some(1))?;
+/// encoder.push_key("list")?;
+/// encoder.begin_list(Some(3))?;
+/// for i in 1..=3 {
+///     encoder.push(&i)?;
+/// }
+/// encoder.end()?;
+/// encoder.end()?;
+/// let bytes = encoder.finish()?;
+///
+/// let ipld: Ipld = DagCborCodec.decode(&bytes)?;
+/// assert_eq!(ipld.get("list")?.get(2)?, &Ipld::Integer(3));
+/// # Ok::<(), libipld_core::error::Error>(())
+/// ```
+pub struct Encoder<W: Write> {
+    w: W,
+    stack: Vec<Container>,
+}
+
+/// A list or map the [`Encoder`] is within.
+struct Container {
+    map: bool,
+    len: Option<u64>,
+    /// The number of items or map entries so far.
+    items: u64,
+    /// The previous map key.
+    key: Option<String>,
+    /// Whether a map key was written, but not its value.
+    key_pending: bool,
+}
+
+impl Container {
+    /// Counts another item or entry, unless the container is full.
+    fn count(&mut self) -> Result<()> {
+        if Some(self.items) == self.len {
+            return Err(ItemCountMismatch {
+                expected: self.items,
+                got: self.items + 1,
+            }
+            .into());
+        }
+        self.items += 1;
         Ok(())
     }
 }
 
-impl<A: Encode<DagCbor>, B: Encode<DagCbor>> Encode<DagCbor> for (A, B) {
-    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
-        write_u8(w, MajorKind::Array, 2)?;
-        self.0.encode(c, w)?;
-        self.1.encode(c, w)?;
+impl<W: Write> Encoder<W> {
+    /// Creates an encoder that writes to `w`.
+    pub fn new(w: W) -> Self {
+        Self {
+            w,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Checks that an item can be written at the current position, and counts it.
+    fn start_item(&mut self) -> Result<()> {
+        match self.stack.last_mut() {
+            Some(container) if container.map => {
+                if !container.key_pending {
+                    return Err(InvalidEncoderState("expected a map key").into());
+                }
+                container.key_pending = false;
+                Ok(())
+            }
+            Some(container) => container.count(),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes a single item.
+    pub fn push<T: Encode<DagCbor> + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.start_item()?;
+        value.encode(DagCbor, &mut self.w)
+    }
+
+    /// Writes the key of the next map entry, its value is the next item.
+    ///
+    /// Returns [`UnorderedKeys`] or [`DuplicateKey`] if the key doesn't come after the previous
+    /// one in canonical order, i.e. sorted by length first and then bytewise.
+    pub fn push_key(&mut self, key: &str) -> Result<()> {
+        let container = match self.stack.last_mut() {
+            Some(container) if container.map && !container.key_pending => container,
+            Some(container) if container.map => {
+                return Err(InvalidEncoderState("expected a map value").into())
+            }
+            _ => return Err(InvalidEncoderState("map key outside of a map").into()),
+        };
+        if let Some(prev) = &container.key {
+            match (prev.len(), prev.as_str()).cmp(&(key.len(), key)) {
+                Ordering::Less => {}
+                Ordering::Equal => return Err(DuplicateKey(key.to_string()).into()),
+                Ordering::Greater => return Err(UnorderedKeys(key.to_string()).into()),
+            }
+        }
+        container.count()?;
+        container.key = Some(key.to_string());
+        container.key_pending = true;
+        key.encode(DagCbor, &mut self.w)
+    }
+
+    /// Opens a list of `len` items, or of indefinite length if `None`.
+    pub fn begin_list(&mut self, len: Option<u64>) -> Result<()> {
+        self.begin(MajorKind::Array, len)
+    }
+
+    /// Opens a map of `len` entries, or of indefinite length if `None`.
+    pub fn begin_map(&mut self, len: Option<u64>) -> Result<()> {
+        self.begin(MajorKind::Map, len)
+    }
+
+    fn begin(&mut self, kind: MajorKind, len: Option<u64>) -> Result<()> {
+        self.start_item()?;
+        match len {
+            Some(len) => write_u64(&mut self.w, kind, len)?,
+            None => self.w.write_all(&[(kind as u8) << 5 | 31])?,
+        }
+        self.stack.push(Container {
+            map: kind == MajorKind::Map,
+            len,
+            items: 0,
+            key: None,
+            key_pending: false,
+        });
         Ok(())
     }
+
+    /// Closes the innermost list or map.
+    ///
+    /// Returns [`ItemCountMismatch`] if it was opened with a different length.
+    pub fn end(&mut self) -> Result<()> {
+        let container = self
+            .stack
+            .pop()
+            .ok_or(InvalidEncoderState("no list or map to end"))?;
+        if container.key_pending {
+            return Err(InvalidEncoderState("map key without a value").into());
+        }
+        match container.len {
+            Some(len) if len != container.items => Err(ItemCountMismatch {
+                expected: len,
+                got: container.items,
+            }
+            .into()),
+            Some(_) => Ok(()),
+            None => {
+                self.w.write_all(&[BREAK.into()])?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the writer, once all lists and maps are closed.
+    pub fn finish(self) -> Result<W> {
+        if !self.stack.is_empty() {
+            return Err(UnclosedContainers(self.stack.len()).into());
+        }
+        Ok(self.w)
+    }
 }
 
-impl<A: Encode<DagCbor>, B: Encode<DagCbor>, C: Encode<DagCbor>> Encode<DagCbor> for (A, B, C) {
-    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
-        write_u8(w, MajorKind::Array, 3)?;
-        self.0.encode(c, w)?;
-        self.1.encode(c, w)?;
-        self.2.encode(c, w)?;
+/// Writes items back to back, also known as a CBOR sequence. It is read back with
+/// [`SeqDecoder`](crate::decode::SeqDecoder).
+///
+/// Each item is encoded in full before it is written, so an item that fails to encode doesn't
+/// leave a partial item in the stream.
+///
+/// # Example
+///
+/// ```
+/// use libipld_cbor::decode::SeqDecoder;
+/// use libipld_cbor::encode::SeqEncoder;
+/// use std::io::Cursor;
+///
+/// let mut encoder = SeqEncoder::new(Vec::new());
+/// for i in [1u64, 2, 42] {
+///     encoder.push(&i)?;
+/// }
+/// assert_eq!(encoder.count(), 3);
+/// let bytes = encoder.finish()?;
+/// assert_eq!(bytes, [0x01, 0x02, 0x18, 0x2a]);
+///
+/// let items = SeqDecoder::<_, u64>::new(Cursor::new(bytes));
+/// assert_eq!(items.collect::<Result<Vec<_>, _>>()?, vec![1, 2, 42]);
+/// # Ok::<(), libipld_core::error::Error>(())
+/// ```
+pub struct SeqEncoder<W: Write> {
+    w: W,
+    buf: Vec<u8>,
+    count: u64,
+    bytes_written: u64,
+}
+
+impl<W: Write> SeqEncoder<W> {
+    /// Creates a new encoder, appending to `w`.
+    pub fn new(w: W) -> Self {
+        Self {
+            w,
+            buf: Vec::new(),
+            count: 0,
+            bytes_written: 0,
+        }
+    }
+
+    /// Encodes an item and appends it to the sequence.
+    pub fn push<T: Encode<DagCbor> + ?Sized>(&mut self, item: &T) -> Result<()> {
+        self.buf.clear();
+        item.encode(DagCbor, &mut self.buf)?;
+        self.w.write_all(&self.buf)?;
+        self.count += 1;
+        self.bytes_written += self.buf.len() as u64;
         Ok(())
     }
-}
 
-impl<A: Encode<DagCbor>, B: Encode<DagCbor>, C: Encode<DagCbor>, D: Encode<DagCbor>> Encode<DagCbor>
-    for (A, B, C, D)
-{
-    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
-        write_u8(w, MajorKind::Array, 4)?;
-        self.0.encode(c, w)?;
-        self.1.encode(c, w)?;
-        self.2.encode(c, w)?;
-        self.3.encode(c, w)?;
+    /// Returns the number of items written.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the number of bytes written.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Returns a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.w
+    }
+
+    /// Flushes the inner writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.w.flush()?;
         Ok(())
     }
+
+    /// Flushes and returns the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.flush()?;
+        Ok(self.w)
+    }
 }
@@ -1,5 +1,7 @@
 //! CBOR error types.
 use std::any::type_name;
+use std::fmt;
+use std::str::Utf8Error;
 use thiserror::Error;
 
 /// Number larger than u64.
@@ -24,6 +26,36 @@ impl NumberOutOfRange {
 #[error("Number not minimally encoded.")]
 pub struct NumberNotMinimal;
 
+/// Float not encoded as 64 bit, which is the only float encoding canonical DAG-CBOR allows.
+#[derive(Debug, Error)]
+#[error("Float not encoded as 64 bit.")]
+pub struct FloatNotF64;
+
+/// NaN or infinite float, which DAG-CBOR doesn't allow.
+#[derive(Debug, Error)]
+#[error("Float `{0}` is not allowed.")]
+pub struct NonFiniteFloat(pub f64);
+
+/// Indefinite-length item, which canonical DAG-CBOR doesn't allow.
+#[derive(Debug, Error)]
+#[error("Indefinite-length items are not allowed.")]
+pub struct IndefiniteLength;
+
+/// Lists and maps nested deeper than the configured maximum depth.
+#[derive(Debug, Error)]
+#[error("Maximum nesting depth of {0} exceeded.")]
+pub struct MaxDepthExceeded(pub usize);
+
+/// Byte string or text string longer than the configured maximum length.
+#[derive(Debug, Error)]
+#[error("Maximum string length of {0} bytes exceeded.")]
+pub struct MaxStringLenExceeded(pub usize);
+
+/// List or map with more items than the configured maximum length.
+#[derive(Debug, Error)]
+#[error("Maximum collection length of {0} exceeded.")]
+pub struct MaxCollectionLenExceeded(pub usize);
+
 /// Length larger than usize or too small, for example zero length cid field.
 #[derive(Debug, Error)]
 #[error("Length out of range when decoding {ty}.")]
@@ -41,6 +73,29 @@ impl LengthOutOfRange {
     }
 }
 
+/// List with a different length than expected.
+#[derive(Debug, Error)]
+#[error("Expected a list of length {expected} but got length {len} when decoding `{ty}`.")]
+pub struct LengthMismatch {
+    /// Expected length.
+    pub expected: u64,
+    /// Actual length.
+    pub len: u64,
+    /// Type.
+    pub ty: &'static str,
+}
+
+impl LengthMismatch {
+    /// Creates a new `LengthMismatch` error.
+    pub fn new<T>(expected: u64, len: u64) -> Self {
+        Self {
+            expected,
+            len,
+            ty: type_name::<T>(),
+        }
+    }
+}
+
 /// Unexpected cbor code.
 #[derive(Debug, Error)]
 #[error("Unexpected cbor code `0x{code:x}` when decoding `{ty}`.")]
@@ -81,6 +136,17 @@ impl UnexpectedKey {
     }
 }
 
+/// Duplicate map key.
+#[derive(Debug, Error)]
+#[error("Duplicate map key `{0}`.")]
+pub struct DuplicateKey(pub String);
+
+/// Map key that isn't in canonical order, i.e. sorted by length first and then bytewise, or in
+/// the key order required by the decode options.
+#[derive(Debug, Error)]
+#[error("Map key `{0}` is not in canonical order.")]
+pub struct UnorderedKeys(pub String);
+
 /// Missing key.
 #[derive(Debug, Error)]
 #[error("Missing key `{key}` for decoding `{ty}`.")]
@@ -106,10 +172,106 @@ impl MissingKey {
 #[error("Unkown cbor tag `{0}`.")]
 pub struct UnknownTag(pub u64);
 
+/// The item following a link tag (42) isn't a byte string.
+#[derive(Debug, Error)]
+#[error("Expected a byte string after tag 42, found cbor code `0x{0:x}`.")]
+pub struct InvalidLinkType(pub u8);
+
+/// A text string that isn't valid UTF-8.
+#[derive(Debug, Error)]
+#[error("Invalid UTF-8 at byte {offset} of a text string.")]
+pub struct InvalidUtf8 {
+    /// The offset of the first invalid byte within the text string.
+    pub offset: usize,
+    /// The underlying error.
+    #[source]
+    pub source: Utf8Error,
+}
+
+impl From<Utf8Error> for InvalidUtf8 {
+    fn from(source: Utf8Error) -> Self {
+        Self {
+            offset: source.valid_up_to(),
+            source,
+        }
+    }
+}
+
+/// A simple value other than false, true and null.
+#[derive(Debug, Error)]
+#[error("Unsupported cbor simple value `{0}`.")]
+pub struct UnsupportedSimpleValue(pub u8);
+
+/// Undefined isn't part of the DAG-CBOR data model.
+#[derive(Debug, Error)]
+#[error("Undefined is not allowed.")]
+pub struct Undefined;
+
 /// Unexpected eof.
 #[derive(Debug, Error)]
-#[error("Unexpected end of file.")]
-pub struct UnexpectedEof;
+#[error("Unexpected end of file, expected {expected} bytes but got {got}.")]
+pub struct UnexpectedEof {
+    /// The number of bytes that should have been read.
+    pub expected: u64,
+    /// The number of bytes that were available.
+    pub got: u64,
+}
+
+/// The input continues after the decoded item.
+#[derive(Debug, Error)]
+#[error("Found {0} trailing bytes after the decoded item.")]
+pub struct TrailingBytes(pub usize);
+
+/// A call to the [`Encoder`](crate::encode::Encoder) that doesn't fit the lists and maps opened so
+/// far, e.g. closing a list that wasn't opened.
+#[derive(Debug, Error)]
+#[error("Invalid encoder call: {0}.")]
+pub struct InvalidEncoderState(pub &'static str);
+
+/// A list or map of known length got a different number of items or entries.
+#[derive(Debug, Error)]
+#[error("Expected {expected} items in a list or map but got {got}.")]
+pub struct ItemCountMismatch {
+    /// The length the list or map was opened with.
+    pub expected: u64,
+    /// The number of items or entries it got.
+    pub got: u64,
+}
+
+/// The [`Encoder`](crate::encode::Encoder) was finished with lists or maps still open.
+#[derive(Debug, Error)]
+#[error("{0} lists or maps were not closed.")]
+pub struct UnclosedContainers(pub usize);
+
+/// Re-encoding decoded `Ipld` didn't reproduce the input.
+#[derive(Debug, Error)]
+#[error("Re-encoding differs from the input at byte {offset}.")]
+pub struct NotCanonical {
+    /// The offset of the first byte that differs.
+    pub offset: usize,
+}
+
+/// Where decoding failed.
+///
+/// This is attached as context to the error that caused decoding to fail, which can still be
+/// downcast to. Use the alternate format (`{:#}`) to display both.
+#[derive(Debug, Error)]
+pub struct Location {
+    /// The offset of the item that failed to decode.
+    pub offset: u64,
+    /// The map keys and list indices leading to the item that failed to decode.
+    pub path: Vec<String>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Decoding failed at offset {}", self.offset)?;
+        if !self.path.is_empty() {
+            write!(f, " under `{}`", self.path.join("/"))?;
+        }
+        Ok(())
+    }
+}
 
 /// The byte before Cid was not multibase identity prefix.
 #[derive(Debug, Error)]
@@ -3,23 +3,224 @@
 #![deny(warnings)]
 
 use core::convert::TryFrom;
-use libipld_core::codec::{Codec, Decode, Encode};
+use libipld_core::cid::Cid;
+use libipld_core::codec::{Codec, Decode, Encode, FixedCodec, Multicodec};
 pub use libipld_core::error::{Result, UnsupportedCodec};
+use libipld_core::ipld::Ipld;
+use libipld_core::multihash::MultihashDigest;
+use std::io::Cursor;
 
+#[cfg(feature = "async")]
+pub mod async_decode;
+#[cfg(feature = "async")]
+pub mod async_encode;
+pub mod borrowed;
+mod buffered;
 pub mod cbor;
 pub mod decode;
+pub mod diag;
 pub mod encode;
 pub mod error;
+#[cfg(feature = "serde")]
+pub mod serde;
+#[cfg(feature = "bytes")]
+pub mod shared;
+pub mod token;
+
+#[cfg(feature = "bytes")]
+pub use bytes;
 
 /// CBOR codec.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DagCborCodec;
 
-impl Codec for DagCborCodec {}
+/// DAG-CBOR encoding and decoding as methods of `Ipld`.
+///
+/// These are shorthands for [`DagCborCodec`]. `Ipld` is defined in libipld-core, which doesn't
+/// depend on any codec, hence they are trait methods. The trait is part of the libipld prelude.
+pub trait IpldCborExt: Sized {
+    /// Encodes into canonical DAG-CBOR.
+    ///
+    /// ```
+    /// use libipld_cbor::IpldCborExt;
+    /// use libipld_core::ipld::Ipld;
+    ///
+    /// assert_eq!(Ipld::Integer(1).to_cbor()?, [0x01]);
+    /// # Ok::<(), libipld_core::error::Error>(())
+    /// ```
+    fn to_cbor(&self) -> Result<Vec<u8>>;
+
+    /// Encodes into canonical DAG-CBOR, writing to a byte stream.
+    ///
+    /// ```
+    /// use libipld_cbor::IpldCborExt;
+    /// use libipld_core::ipld::Ipld;
+    ///
+    /// let mut bytes = Vec::new();
+    /// Ipld::Bool(true).to_cbor_writer(&mut bytes)?;
+    /// assert_eq!(bytes, [0xf5]);
+    /// # Ok::<(), libipld_core::error::Error>(())
+    /// ```
+    fn to_cbor_writer<W: std::io::Write>(&self, w: W) -> Result<()>;
+
+    /// Decodes DAG-CBOR, failing if there are bytes after the item.
+    ///
+    /// ```
+    /// use libipld_cbor::IpldCborExt;
+    /// use libipld_core::ipld::Ipld;
+    ///
+    /// assert_eq!(Ipld::from_cbor(&[0x63, 0x61, 0x62, 0x63])?, Ipld::String("abc".into()));
+    /// assert!(Ipld::from_cbor(&[0x01, 0x02]).is_err());
+    /// # Ok::<(), libipld_core::error::Error>(())
+    /// ```
+    fn from_cbor(bytes: &[u8]) -> Result<Self>;
+}
+
+impl IpldCborExt for Ipld {
+    fn to_cbor(&self) -> Result<Vec<u8>> {
+        DagCborCodec.encode(self)
+    }
+
+    fn to_cbor_writer<W: std::io::Write>(&self, mut w: W) -> Result<()> {
+        self.encode(DagCborCodec, &mut w)
+    }
+
+    fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        DagCborCodec.decode(bytes)
+    }
+}
+
+impl DagCborCodec {
+    /// Decodes `Ipld`, only accepting canonical DAG-CBOR.
+    ///
+    /// Unlike [`Codec::decode`] this guarantees that re-encoding the result yields the same bytes,
+    /// see [`decode::DecodeOptions::strict`].
+    pub fn decode_strict(&self, bytes: &[u8]) -> Result<Ipld> {
+        self.decode_with(bytes, &decode::DecodeOptions::strict())
+    }
+
+    /// Decodes `Ipld` strictly and checks that re-encoding it yields the same bytes.
+    ///
+    /// Strict decoding alone already rejects non-canonical input, so a difference points to a bug
+    /// in the decoder or the encoder. It is reported as [`error::NotCanonical`] with the offset of
+    /// the first differing byte.
+    pub fn verify_canonical(&self, bytes: &[u8]) -> Result<Ipld> {
+        let ipld = self.decode_strict(bytes)?;
+        let encoded = self.encode(&ipld)?;
+        if encoded != bytes {
+            let offset = encoded
+                .iter()
+                .zip(bytes)
+                .position(|(a, b)| a != b)
+                .unwrap_or_else(|| encoded.len().min(bytes.len()));
+            return Err(error::NotCanonical { offset }.into());
+        }
+        Ok(ipld)
+    }
+
+    /// Decodes `Ipld` using the given options. Like [`Codec::decode`] it rejects any bytes after
+    /// the decoded item.
+    pub fn decode_with(&self, bytes: &[u8], opts: &decode::DecodeOptions) -> Result<Ipld> {
+        let mut r = Cursor::new(bytes);
+        let ipld = decode::read_ipld_unbuffered(&mut r, opts)?;
+        let remaining = bytes.len() - r.position() as usize;
+        if remaining > 0 {
+            return Err(error::TrailingBytes(remaining).into());
+        }
+        Ok(ipld)
+    }
+
+    /// Encodes `Ipld` using the given options.
+    ///
+    /// With the default options this is the same as [`Codec::encode`], see
+    /// [`encode::EncodeOptions`] for the other options, which write cbor that isn't DAG-CBOR.
+    pub fn encode_with(&self, ipld: &Ipld, opts: &encode::EncodeOptions) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        encode::write_ipld(&mut buf, ipld, opts)?;
+        Ok(buf)
+    }
+
+    /// Decodes many blocks into `Ipld`, in parallel if the `parallel` feature is enabled.
+    ///
+    /// The results are in the same order as the blocks. A block that fails to decode doesn't
+    /// stop the others from being decoded.
+    pub fn decode_batch<I>(&self, blocks: I) -> Vec<Result<Ipld>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]> + Send,
+    {
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            let blocks: Vec<_> = blocks.into_iter().collect();
+            blocks
+                .into_par_iter()
+                .map(|block| self.decode(block.as_ref()))
+                .collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            blocks
+                .into_iter()
+                .map(|block| self.decode(block.as_ref()))
+                .collect()
+        }
+    }
+
+    /// Encodes and hashes many nodes into blocks, in parallel if the `parallel` feature is
+    /// enabled.
+    ///
+    /// Each block is a CIDv1 with the DAG-CBOR codec and a `hcode` multihash of its bytes, the
+    /// same as encoding the nodes one at a time. The results are in the same order as the nodes.
+    pub fn encode_batch<T, H>(&self, hcode: H, nodes: &[T]) -> Vec<Result<(Cid, Vec<u8>)>>
+    where
+        T: Encode<Self> + Sync,
+        H: MultihashDigest<64> + Sync,
+    {
+        let encode = |node: &T| {
+            let bytes = self.encode(node)?;
+            let cid = Cid::new_v1((*self).into(), hcode.digest(&bytes));
+            Ok((cid, bytes))
+        };
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            nodes.par_iter().map(encode).collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            nodes.iter().map(encode).collect()
+        }
+    }
+}
+
+impl Codec for DagCborCodec {
+    /// Encodes an encodable type into a buffer of exactly the encoded length.
+    fn encode<T: Encode<Self> + ?Sized>(&self, obj: &T) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(obj.encoded_len(*self)?);
+        obj.encode(*self, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Encodes an encodable type, appending it to `buf`, which grows at most once.
+    fn encode_into<T: Encode<Self> + ?Sized>(&self, obj: &T, buf: &mut Vec<u8>) -> Result<()> {
+        buf.reserve(obj.encoded_len(*self)?);
+        obj.encode(*self, buf)
+    }
+
+    /// Decodes a decodable type, rejecting any bytes after the decoded item.
+    fn decode<T: Decode<Self>>(&self, bytes: &[u8]) -> Result<T> {
+        decode::decode(bytes)
+    }
+}
+
+impl FixedCodec for DagCborCodec {
+    const CODE: u64 = Multicodec::DagCbor.code();
+}
 
 impl From<DagCborCodec> for u64 {
     fn from(_: DagCborCodec) -> Self {
-        0x71
+        DagCborCodec::CODE
     }
 }
 
@@ -32,7 +233,28 @@ impl TryFrom<u64> for DagCborCodec {
 }
 
 /// Marker trait for types supporting the `DagCborCodec`.
-pub trait DagCbor: Encode<DagCborCodec> + Decode<DagCborCodec> {}
+pub trait DagCbor: Encode<DagCborCodec> + Decode<DagCborCodec> {
+    /// Decodes a single item that has to span all of `bytes`, see [`decode::decode`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use libipld_cbor::DagCbor;
+    /// use libipld_core::ipld::Ipld;
+    ///
+    /// // {"a": 1, "b": [true]}
+    /// let bytes = [0xa2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x81, 0xf5];
+    /// let ipld = Ipld::from_slice(&bytes).unwrap();
+    /// assert_eq!(ipld.get("a").unwrap(), &Ipld::Integer(1));
+    /// assert_eq!(ipld.get("b").unwrap(), &Ipld::List(vec![Ipld::Bool(true)]));
+    ///
+    /// // Trailing bytes are an error.
+    /// assert!(Ipld::from_slice(&[0x01, 0x02]).is_err());
+    /// ```
+    fn from_slice(bytes: &[u8]) -> Result<Self> {
+        decode::decode(bytes)
+    }
+}
 
 impl<T: Encode<DagCborCodec> + Decode<DagCborCodec>> DagCbor for T {}
 
@@ -41,7 +263,6 @@ mod tests {
     use super::*;
     use libipld_core::cid::Cid;
     use libipld_core::codec::assert_roundtrip;
-    use libipld_core::ipld::Ipld;
     use libipld_core::multihash::{Code, MultihashDigest};
     use libipld_macro::ipld;
     use std::collections::HashSet;
@@ -98,4 +319,910 @@ mod tests {
         assert_roundtrip(DagCborCodec, &u32::MIN, &Ipld::Integer(u32::MIN as i128));
         assert_roundtrip(DagCborCodec, &u64::MIN, &Ipld::Integer(u64::MIN as i128));
     }
+
+    #[test]
+    fn test_option() {
+        let cid = Cid::new_v1(0, Code::Blake3_256.digest(&b"cid"[..]));
+        assert_roundtrip(DagCborCodec, &Some(7u64), &Ipld::Integer(7));
+        assert_roundtrip(DagCborCodec, &None::<u64>, &Ipld::Null);
+        assert_roundtrip(
+            DagCborCodec,
+            &Some("text".to_string()),
+            &Ipld::String("text".into()),
+        );
+        assert_roundtrip(DagCborCodec, &None::<String>, &Ipld::Null);
+        assert_roundtrip(DagCborCodec, &Some(cid), &Ipld::Link(cid));
+        assert_roundtrip(DagCborCodec, &None::<Cid>, &Ipld::Null);
+        assert_eq!(DagCborCodec.encode(&Some(7u64)).unwrap(), [0x07]);
+        assert_eq!(DagCborCodec.encode(&None::<u64>).unwrap(), [0xf6]);
+
+        // Nested options are ambiguous, `Some(None)` decodes as `None`.
+        let bytes = DagCborCodec.encode(&Some(None::<u64>)).unwrap();
+        assert_eq!(bytes, [0xf6]);
+        assert_eq!(
+            DagCborCodec.decode::<Option<Option<u64>>>(&bytes).unwrap(),
+            None
+        );
+        let bytes = DagCborCodec.encode(&Some(Some(7u64))).unwrap();
+        assert_eq!(
+            DagCborCodec.decode::<Option<Option<u64>>>(&bytes).unwrap(),
+            Some(Some(7))
+        );
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        fn check<T: Encode<DagCborCodec> + ?Sized>(value: &T) {
+            let bytes = DagCborCodec.encode(value).unwrap();
+            assert_eq!(value.encoded_len(DagCborCodec).unwrap(), bytes.len());
+            assert_eq!(bytes.capacity(), bytes.len());
+        }
+
+        let v0 = Cid::try_from("QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n").unwrap();
+        let v1 = Cid::new_v1(0x71, Code::Blake3_256.digest(&b"cid"[..]));
+        // The boundaries of every head length.
+        for n in [
+            0u64,
+            23,
+            24,
+            255,
+            256,
+            65535,
+            65536,
+            u32::MAX as u64,
+            u64::MAX,
+        ] {
+            check(&n);
+            check(&Ipld::Integer(n.into()));
+            check(&Ipld::Integer(-1 - i128::from(n)));
+        }
+        for n in [0usize, 23, 24, 255, 256, 65535, 65536] {
+            check(&vec![0u8; n][..]);
+            check(&"a".repeat(n)[..]);
+            check(&vec![1u8; n]);
+            check(&Ipld::List(vec![Ipld::Null; n]));
+        }
+        check(&i8::MIN);
+        check(&i64::MIN);
+        check(&1.5f32);
+        check(&'€');
+        check(&true);
+        check(&());
+        check(&(1u8, "two".to_string(), [3u16; 3]));
+        check(&Some(v1));
+        check(&None::<Cid>);
+        check(&v0);
+        check(&ipld!({
+            "number": -300,
+            "float": 0.5,
+            "list": [true, null, "string", vec![4, 5]],
+            "map": { "v0": v0, "v1": v1, "": {} },
+        }));
+
+        // Fails like encode would.
+        f64::NAN
+            .encoded_len(DagCborCodec)
+            .expect_err("should have failed on a non-finite float");
+        Ipld::Integer(i128::MAX)
+            .encoded_len(DagCborCodec)
+            .expect_err("should have failed on an integer out of range");
+    }
+
+    #[test]
+    fn test_encode_into() {
+        let mut buf = vec![0xff];
+        DagCborCodec
+            .encode_into(&ipld!([1, "a"]), &mut buf)
+            .unwrap();
+        DagCborCodec.encode_into("b", &mut buf).unwrap();
+        assert_eq!(buf, [0xff, 0x82, 0x01, 0x61, 0x61, 0x61, 0x62]);
+
+        // Doesn't allocate once the buffer is large enough.
+        let ptr = buf.as_ptr();
+        buf.clear();
+        DagCborCodec
+            .encode_into(&ipld!({ "a": [1, 2] }), &mut buf)
+            .unwrap();
+        assert_eq!(buf.as_ptr(), ptr);
+        assert_eq!(buf, DagCborCodec.encode(&ipld!({ "a": [1, 2] })).unwrap());
+    }
+
+    #[test]
+    fn test_borrowed_encode() {
+        use crate::cbor::{ByteBuf, List};
+
+        // Encodes the borrowed value the same as the owned one, and decodes it as the owned one.
+        fn check<B, O>(borrowed: &B, owned: &O)
+        where
+            B: Encode<DagCborCodec> + ?Sized,
+            O: Encode<DagCborCodec> + Decode<DagCborCodec> + PartialEq + core::fmt::Debug,
+        {
+            let bytes = DagCborCodec.encode(borrowed).unwrap();
+            assert_eq!(bytes, DagCborCodec.encode(owned).unwrap());
+            assert_eq!(&DagCborCodec.decode::<O>(&bytes).unwrap(), owned);
+        }
+
+        let text = "hello".to_string();
+        check("hello", &text);
+        check(&text.as_str(), &text);
+        check(&&text.as_str(), &text);
+
+        let bytes = vec![1u8, 2, 3];
+        check(&bytes[..], &ByteBuf(bytes.clone()));
+        check(&&bytes[..], &ByteBuf(bytes.clone()));
+
+        let list = vec![1u64, 1000, 1_000_000];
+        check(&List(&list[..]), &list);
+        check(&List(&bytes[..]), &bytes);
+        let strings = ["a", "b"];
+        check(&List(&strings[..]), &vec!["a".to_string(), "b".to_string()]);
+        check(&List::<u64>(&[]), &Vec::<u64>::new());
+
+        check(&[7u8, 8], &[7u8, 8]);
+        check(&["a", "b"], &["a".to_string(), "b".to_string()]);
+        check(&&&[1i32, -1], &[1i32, -1]);
+
+        // Containers of references.
+        check(&vec![&text, &text], &vec![text.clone(), text.clone()]);
+        check(
+            &(&text, &bytes[..], &7u8),
+            &(text.clone(), ByteBuf(bytes.clone()), 7u8),
+        );
+    }
+
+    #[test]
+    fn test_float_encoding() {
+        use crate::decode::{read_ipld, DecodeOptions};
+        use crate::encode::{write_ipld, EncodeOptions};
+        use crate::error::NonFiniteFloat;
+
+        let half = [0xfb, 0x3f, 0xe0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(DagCborCodec.encode(&0.5f32).unwrap(), half);
+        assert_eq!(DagCborCodec.encode(&0.5f64).unwrap(), half);
+        assert_eq!(DagCborCodec.encode(&Ipld::Float(0.5)).unwrap(), half);
+
+        for num in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            DagCborCodec
+                .encode(&num)
+                .expect_err("should have failed on a non-finite float")
+                .downcast::<NonFiniteFloat>()
+                .unwrap();
+            DagCborCodec
+                .encode(&(num as f32))
+                .expect_err("should have failed on a non-finite float")
+                .downcast::<NonFiniteFloat>()
+                .unwrap();
+            DagCborCodec
+                .encode(&ipld!({ "list": [Ipld::Float(num)] }))
+                .expect_err("should have failed on a non-finite float")
+                .downcast::<NonFiniteFloat>()
+                .unwrap();
+        }
+
+        // Examples from RFC 8949, appendix A.
+        let opts = EncodeOptions {
+            shortest_floats: true,
+            ..Default::default()
+        };
+        for (num, bytes) in [
+            (0.0, &[0xf9, 0x00, 0x00][..]),
+            (-0.0, &[0xf9, 0x80, 0x00]),
+            (1.0, &[0xf9, 0x3c, 0x00]),
+            (1.1, &[0xfb, 0x3f, 0xf1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a]),
+            (1.5, &[0xf9, 0x3e, 0x00]),
+            (65504.0, &[0xf9, 0x7b, 0xff]),
+            (100000.0, &[0xfa, 0x47, 0xc3, 0x50, 0x00]),
+            (3.4028234663852886e+38, &[0xfa, 0x7f, 0x7f, 0xff, 0xff]),
+            (
+                1.0e+300,
+                &[0xfb, 0x7e, 0x37, 0xe4, 0x3c, 0x88, 0x00, 0x75, 0x9c],
+            ),
+            (5.960464477539063e-8, &[0xf9, 0x00, 0x01]),
+            (0.00006103515625, &[0xf9, 0x04, 0x00]),
+            (-4.0, &[0xf9, 0xc4, 0x00]),
+            (
+                -4.1,
+                &[0xfb, 0xc0, 0x10, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66],
+            ),
+            (f64::INFINITY, &[0xf9, 0x7c, 0x00]),
+            (f64::NAN, &[0xf9, 0x7e, 0x00]),
+            (f64::NEG_INFINITY, &[0xf9, 0xfc, 0x00]),
+        ] {
+            let mut buf = vec![];
+            write_ipld(&mut buf, &Ipld::Float(num), &opts).unwrap();
+            assert_eq!(buf, bytes, "{}", num);
+            let ipld = read_ipld(&mut Cursor::new(&buf), &DecodeOptions::default()).unwrap();
+            match ipld {
+                Ipld::Float(f) if num.is_nan() => assert!(f.is_nan()),
+                Ipld::Float(f) => assert_eq!(f.to_bits(), num.to_bits()),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+
+        // Everything else is encoded like DAG-CBOR.
+        let ipld = ipld!({ "a": [1, "b", 0.5], "bb": null });
+        let mut buf = vec![];
+        write_ipld(&mut buf, &ipld, &EncodeOptions::default()).unwrap();
+        assert_eq!(buf, DagCborCodec.encode(&ipld).unwrap());
+        buf.clear();
+        write_ipld(&mut buf, &ipld, &opts).unwrap();
+        assert_eq!(DagCborCodec.decode::<Ipld>(&buf).unwrap(), ipld);
+        assert_eq!(buf.len(), DagCborCodec.encode(&ipld).unwrap().len() - 6);
+    }
+
+    #[test]
+    fn test_minimal_heads() {
+        use crate::cbor::MajorKind;
+        use crate::decode::{read_ipld, read_major, read_uint, DecodeOptions};
+        use crate::encode::write_u64;
+        use quickcheck::QuickCheck;
+
+        fn minimal_len(n: u64) -> usize {
+            if n < 24 {
+                1
+            } else if n <= u8::MAX.into() {
+                2
+            } else if n <= u16::MAX.into() {
+                3
+            } else if n <= u32::MAX.into() {
+                5
+            } else {
+                9
+            }
+        }
+
+        fn strict_roundtrip(ipld: Ipld) -> bool {
+            let bytes = DagCborCodec.encode(&ipld).unwrap();
+            read_ipld(&mut Cursor::new(&bytes), &DecodeOptions::strict()).unwrap() == ipld
+        }
+
+        // Shifting spreads the random numbers over all head lengths.
+        fn prop(value: u64, shift: u8) -> bool {
+            let n = value >> (shift % 64);
+            let len = minimal_len(n);
+            let mut ok = true;
+            for int in [i128::from(n), -i128::from(n) - 1] {
+                ok &= DagCborCodec.encode(&Ipld::Integer(int)).unwrap().len() == len;
+                ok &= strict_roundtrip(Ipld::Integer(int));
+            }
+            ok &= DagCborCodec.encode(&n).unwrap().len() == len;
+            if let Ok(n) = u32::try_from(n) {
+                ok &= DagCborCodec.encode(&n).unwrap().len() == len;
+                ok &= DagCborCodec.encode(&-i64::from(n)).unwrap().len()
+                    == minimal_len(u64::from(n).saturating_sub(1));
+            }
+            for kind in [
+                MajorKind::ByteString,
+                MajorKind::TextString,
+                MajorKind::Array,
+                MajorKind::Map,
+                MajorKind::Tag,
+            ] {
+                let mut bytes = vec![];
+                write_u64(&mut bytes, kind, n).unwrap();
+                let mut r = Cursor::new(&bytes);
+                let major = read_major(&mut r).unwrap();
+                ok &= bytes.len() == len;
+                ok &= major.kind() == kind && read_uint(&mut r, major).unwrap() == n;
+            }
+            // Lengths of actual items, up to a few thousand.
+            let short = (n % 5000) as usize;
+            ok &= strict_roundtrip(Ipld::Bytes(vec![0; short]));
+            ok &= strict_roundtrip(Ipld::String("a".repeat(short)));
+            ok &= strict_roundtrip(Ipld::List(vec![Ipld::Null; short]));
+            ok &= strict_roundtrip(Ipld::Map(
+                (0..short % 300)
+                    .map(|i| (i.to_string(), Ipld::Null))
+                    .collect(),
+            ));
+            ok
+        }
+
+        QuickCheck::new().quickcheck(prop as fn(u64, u8) -> bool);
+    }
+
+    #[test]
+    fn test_encoder() {
+        use crate::encode::Encoder;
+
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.begin_map(Some(2)).unwrap();
+        encoder.push_key("a").unwrap();
+        encoder.push(&1u64).unwrap();
+        encoder.push_key("bb").unwrap();
+        encoder.begin_list(Some(2)).unwrap();
+        encoder.push("x").unwrap();
+        encoder.begin_map(Some(0)).unwrap();
+        encoder.end().unwrap();
+        encoder.end().unwrap();
+        encoder.end().unwrap();
+        let bytes = encoder.finish().unwrap();
+        let ipld = ipld!({ "a": 1, "bb": ["x", {}] });
+        assert_eq!(bytes, DagCborCodec.encode(&ipld).unwrap());
+
+        // Unknown lengths are written as indefinite-length items.
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.begin_list(None).unwrap();
+        encoder.push(&1u8).unwrap();
+        encoder.begin_map(None).unwrap();
+        encoder.push_key("a").unwrap();
+        encoder.push(&true).unwrap();
+        encoder.end().unwrap();
+        encoder.end().unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(bytes, [0x9f, 0x01, 0xbf, 0x61, 0x61, 0xf5, 0xff, 0xff]);
+        assert_eq!(
+            DagCborCodec.decode::<Ipld>(&bytes).unwrap(),
+            ipld!([1, { "a": true }])
+        );
+    }
+
+    #[test]
+    fn test_encoder_errors() {
+        use crate::encode::Encoder;
+        use crate::error::{
+            DuplicateKey, InvalidEncoderState, ItemCountMismatch, UnclosedContainers, UnorderedKeys,
+        };
+
+        // Unbalanced begin and end.
+        let mut encoder = Encoder::new(Vec::new());
+        encoder
+            .end()
+            .expect_err("should have failed to end without a list")
+            .downcast::<InvalidEncoderState>()
+            .unwrap();
+        encoder.begin_list(Some(1)).unwrap();
+        encoder.begin_list(None).unwrap();
+        let err = encoder
+            .finish()
+            .expect_err("should have failed to finish with open lists")
+            .downcast::<UnclosedContainers>()
+            .unwrap();
+        assert_eq!(err.0, 2);
+
+        // Lengths that don't match.
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.begin_list(Some(1)).unwrap();
+        encoder.push(&1u8).unwrap();
+        encoder
+            .push(&2u8)
+            .expect_err("should have failed to push too many items")
+            .downcast::<ItemCountMismatch>()
+            .unwrap();
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.begin_map(Some(2)).unwrap();
+        encoder.push_key("a").unwrap();
+        encoder.push(&1u8).unwrap();
+        let err = encoder
+            .end()
+            .expect_err("should have failed to end a map early")
+            .downcast::<ItemCountMismatch>()
+            .unwrap();
+        assert_eq!((err.expected, err.got), (2, 1));
+
+        // Map keys and values out of place.
+        let mut encoder = Encoder::new(Vec::new());
+        encoder
+            .push_key("a")
+            .expect_err("should have failed to push a key outside of a map")
+            .downcast::<InvalidEncoderState>()
+            .unwrap();
+        encoder.begin_map(None).unwrap();
+        encoder
+            .push(&1u8)
+            .expect_err("should have failed to push a value without a key")
+            .downcast::<InvalidEncoderState>()
+            .unwrap();
+        encoder.push_key("b").unwrap();
+        encoder
+            .end()
+            .expect_err("should have failed to end a map after a key")
+            .downcast::<InvalidEncoderState>()
+            .unwrap();
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.begin_map(None).unwrap();
+        encoder.push_key("b").unwrap();
+        encoder.push(&1u8).unwrap();
+        encoder
+            .push_key("b")
+            .expect_err("should have failed to push a key twice")
+            .downcast::<DuplicateKey>()
+            .unwrap();
+        encoder
+            .push_key("a")
+            .expect_err("should have failed to push keys out of order")
+            .downcast::<UnorderedKeys>()
+            .unwrap();
+        encoder.push_key("aa").unwrap();
+    }
+
+    #[test]
+    fn test_integer_range() {
+        use crate::decode::{read_ipld, DecodeOptions};
+        use crate::encode::{write_ipld, EncodeOptions};
+        use crate::error::NumberOutOfRange;
+
+        let max = i128::from(u64::MAX);
+        for int in [max, -max - 1] {
+            assert_roundtrip(DagCborCodec, &Ipld::Integer(int), &Ipld::Integer(int));
+        }
+        for int in [max + 1, -max - 2, i128::MAX, i128::MIN] {
+            DagCborCodec
+                .encode(&Ipld::Integer(int))
+                .expect_err("should have failed to encode an integer out of range")
+                .downcast::<NumberOutOfRange>()
+                .unwrap();
+            DagCborCodec
+                .encode(&ipld!({ "nested": [Ipld::Integer(int)] }))
+                .expect_err("should have failed to encode an integer out of range")
+                .downcast::<NumberOutOfRange>()
+                .unwrap();
+            DagCborCodec
+                .encode(&int)
+                .expect_err("should have failed to encode an integer out of range")
+                .downcast::<NumberOutOfRange>()
+                .unwrap();
+        }
+
+        // Bignums, which only lenient decoding accepts.
+        let opts = EncodeOptions {
+            bignums: true,
+            ..Default::default()
+        };
+        for (int, bytes) in [
+            (
+                max,
+                &[0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff][..],
+            ),
+            (max + 1, &[0xc2, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0]),
+            (-max - 2, &[0xc3, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0]),
+            (
+                i128::MAX,
+                &[
+                    0xc2, 0x50, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                    0xff, 0xff, 0xff, 0xff, 0xff,
+                ],
+            ),
+            (
+                i128::MIN,
+                &[
+                    0xc3, 0x50, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                    0xff, 0xff, 0xff, 0xff, 0xff,
+                ],
+            ),
+        ] {
+            let mut buf = vec![];
+            write_ipld(&mut buf, &Ipld::Integer(int), &opts).unwrap();
+            assert_eq!(buf, bytes, "{}", int);
+            let ipld = read_ipld(&mut Cursor::new(&buf), &DecodeOptions::default()).unwrap();
+            assert_eq!(ipld, Ipld::Integer(int));
+        }
+    }
+
+    #[test]
+    fn test_verify_canonical() {
+        use crate::error::{TrailingBytes, UnorderedKeys};
+        use proptest::prelude::*;
+        use proptest::test_runner::{Config, TestRunner};
+
+        TestRunner::new(Config::with_cases(500))
+            .run(&any::<Ipld>(), |ipld| {
+                let bytes = DagCborCodec.encode(&ipld).unwrap();
+                prop_assert_eq!(DagCborCodec.verify_canonical(&bytes).unwrap(), ipld);
+                Ok(())
+            })
+            .unwrap();
+
+        // Lenient input is rejected by the strict decoding.
+        DagCborCodec
+            .verify_canonical(&[0xa2, 0x62, 0x61, 0x61, 0x01, 0x61, 0x62, 0x02])
+            .expect_err("should have failed to verify unordered keys")
+            .downcast::<UnorderedKeys>()
+            .unwrap();
+        DagCborCodec
+            .verify_canonical(&[0x01, 0x02])
+            .expect_err("should have failed to verify trailing bytes")
+            .downcast::<TrailingBytes>()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_encode_to_slice() {
+        use libipld_core::error::BufferTooSmall;
+
+        let ipld = ipld!({ "list": [1, "two", vec![3u8]], "float": 0.5 });
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+
+        let mut buf = vec![0xaa; bytes.len()];
+        assert_eq!(
+            DagCborCodec.encode_to_slice(&ipld, &mut buf).unwrap(),
+            bytes.len()
+        );
+        assert_eq!(buf, bytes);
+        // Larger buffers are only written to the start.
+        let mut buf = [0xaa; 100];
+        assert_eq!(
+            DagCborCodec.encode_to_slice(&ipld, &mut buf).unwrap(),
+            bytes.len()
+        );
+        assert_eq!(buf[..bytes.len()], bytes);
+        assert!(buf[bytes.len()..].iter().all(|byte| *byte == 0xaa));
+
+        let mut buf = vec![0; bytes.len() - 1];
+        let err = DagCborCodec
+            .encode_to_slice(&ipld, &mut buf)
+            .expect_err("should have failed to encode into a buffer that is too small")
+            .downcast::<BufferTooSmall>()
+            .unwrap();
+        assert_eq!((err.needed, err.len), (bytes.len(), bytes.len() - 1));
+        DagCborCodec
+            .encode_to_slice(&1u8, &mut [])
+            .expect_err("should have failed to encode into an empty buffer")
+            .downcast::<BufferTooSmall>()
+            .unwrap();
+
+        // Other errors are passed on.
+        DagCborCodec
+            .encode_to_slice(&f64::NAN, &mut [0; 100])
+            .expect_err("should have failed to encode NaN")
+            .downcast::<crate::error::NonFiniteFloat>()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_map_key_order() {
+        // Keys are sorted by length first, then bytewise, as in RFC 7049's canonical CBOR.
+        let expected = [
+            0xa6, // Map of length 6
+            0x61, 0x61, 0x03, // "a": 3
+            0x61, 0x62, 0x01, // "b": 1
+            0x62, 0x61, 0x61, 0x02, // "aa": 2
+            0x62, 0x61, 0x62, 0x04, // "ab": 4
+            0x62, 0x7a, 0x7a, 0x06, // "zz": 6
+            0x62, 0xc3, 0xa9, 0x07, // "é": 7, two bytes that sort after "zz"
+        ];
+        let entries = [
+            ("b", 1),
+            ("aa", 2),
+            ("é", 7),
+            ("a", 3),
+            ("zz", 6),
+            ("ab", 4),
+        ];
+        let ipld = Ipld::Map(
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), Ipld::Integer(*value)))
+                .collect(),
+        );
+        assert_eq!(DagCborCodec.encode(&ipld).unwrap(), expected);
+        let map: std::collections::BTreeMap<String, u8> = entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), *value as u8))
+            .collect();
+        assert_eq!(DagCborCodec.encode(&map).unwrap(), expected);
+        let map: std::collections::HashMap<String, u8> = map.into_iter().collect();
+        assert_eq!(DagCborCodec.encode(&map).unwrap(), expected);
+        assert_eq!(DagCborCodec.decode_strict(&expected).unwrap(), ipld);
+
+        // The same map in plain lexicographic order isn't canonical.
+        let lexicographic = [
+            0xa2, // Map of length 2
+            0x62, 0x61, 0x61, 0x02, // "aa": 2
+            0x61, 0x62, 0x01, // "b": 1
+        ];
+        DagCborCodec
+            .decode_strict(&lexicographic)
+            .expect_err("should have failed to decode unordered keys")
+            .downcast::<error::UnorderedKeys>()
+            .unwrap();
+        let ipld = DagCborCodec.decode::<Ipld>(&lexicographic).unwrap();
+        assert_eq!(
+            DagCborCodec.encode(&ipld).unwrap(),
+            [0xa2, 0x61, 0x62, 0x01, 0x62, 0x61, 0x61, 0x02]
+        );
+    }
+
+    #[test]
+    fn test_decode_strict() {
+        let cid = Cid::new_v1(0, Code::Blake3_256.digest(&b"cid"[..]));
+        let ipld = ipld!({
+          "a": 1,
+          "bb": [true, null, -1.5],
+          "ab": vec![0, 1, 2, 3],
+          "link": cid,
+        });
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        assert_eq!(DagCborCodec.decode_strict(&bytes).unwrap(), ipld);
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let ipld = ipld!({ "a": [1, 2] });
+        let mut bytes = DagCborCodec.encode(&ipld).unwrap();
+        assert_eq!(DagCborCodec.decode::<Ipld>(&bytes).unwrap(), ipld);
+
+        bytes.push(0x00);
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed to decode a block with trailing bytes")
+            .downcast::<error::TrailingBytes>()
+            .unwrap();
+        assert_eq!(err.0, 1);
+        DagCborCodec
+            .decode_strict(&bytes)
+            .expect_err("should have failed to decode a block with trailing bytes")
+            .downcast::<error::TrailingBytes>()
+            .unwrap();
+        let (ipld2, len) = decode::decode_prefix::<Ipld>(&bytes).unwrap();
+        assert_eq!((ipld2, len), (ipld, bytes.len() - 1));
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let data = vec![(1u8, "a".to_string()), (2, "b".to_string())];
+        let bytes = DagCborCodec.encode(&data).unwrap();
+        assert_eq!(Vec::<(u8, String)>::from_slice(&bytes).unwrap(), data);
+
+        // Errors know where they happened.
+        let err = Vec::<(u8, String)>::from_slice(&bytes[..bytes.len() - 1])
+            .expect_err("should have failed to decode a truncated list");
+        assert!(err.downcast_ref::<error::Location>().is_some());
+        assert!(err.downcast_ref::<error::UnexpectedEof>().is_some());
+    }
+
+    #[test]
+    fn test_decode_batch() {
+        let mut blocks: Vec<Vec<u8>> = (0..100u64)
+            .map(|i| DagCborCodec.encode(&ipld!({ "i": i })).unwrap())
+            .collect();
+        blocks[10].pop();
+        blocks[20].push(0x00);
+        let results = DagCborCodec.decode_batch(&blocks);
+        assert_eq!(results.len(), blocks.len());
+        for (i, result) in results.into_iter().enumerate() {
+            match i {
+                10 => assert!(result
+                    .expect_err("should have failed to decode a truncated block")
+                    .downcast_ref::<error::UnexpectedEof>()
+                    .is_some()),
+                20 => assert!(result
+                    .expect_err("should have failed to decode trailing bytes")
+                    .downcast_ref::<error::TrailingBytes>()
+                    .is_some()),
+                _ => assert_eq!(result.unwrap(), ipld!({ "i": i })),
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_batch() {
+        let nodes: Vec<Ipld> = (0..100u64).map(|i| ipld!({ "i": i })).collect();
+        let results = DagCborCodec.encode_batch(Code::Sha2_256, &nodes);
+        assert_eq!(results.len(), nodes.len());
+        for (node, result) in nodes.iter().zip(results) {
+            let (cid, bytes) = result.unwrap();
+            assert_eq!(bytes, DagCborCodec.encode(node).unwrap());
+            assert_eq!(cid, Cid::new_v1(0x71, Code::Sha2_256.digest(&bytes)));
+        }
+
+        let results = DagCborCodec.encode_batch(Code::Sha2_256, &[Ipld::Float(f64::NAN)]);
+        results[0]
+            .as_ref()
+            .expect_err("should have failed to encode NaN")
+            .downcast_ref::<error::NonFiniteFloat>()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_seq_encoder() {
+        use crate::decode::SeqDecoder;
+        use crate::encode::SeqEncoder;
+
+        let items: Vec<Ipld> = (0..1000u64)
+            .map(|i| match i % 5 {
+                0 => Ipld::Integer(i as i128),
+                1 => Ipld::String(format!("item {}", i)),
+                2 => ipld!({ "i": i, "even": i % 2 == 0 }),
+                3 => ipld!([i, null, [1.5]]),
+                _ => Ipld::Bytes(vec![i as u8; (i % 64) as usize]),
+            })
+            .collect();
+        let mut encoder = SeqEncoder::new(Vec::new());
+        for item in &items {
+            encoder.push(item).unwrap();
+        }
+        // Failing items aren't written.
+        encoder.push(&Ipld::Float(f64::NAN)).unwrap_err();
+        assert_eq!(encoder.count(), 1000);
+        let len = encoder.bytes_written();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(bytes.len() as u64, len);
+
+        let decoded = SeqDecoder::<_, Ipld>::new(Cursor::new(&bytes))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(decoded, items);
+    }
+
+    #[test]
+    fn test_cbor_size() {
+        use crate::encode::cbor_size;
+
+        /// Only implements `encode`, so `encoded_len` falls back to counting.
+        struct Custom(Vec<u32>);
+
+        impl Encode<DagCborCodec> for Custom {
+            fn encode<W: std::io::Write>(&self, c: DagCborCodec, w: &mut W) -> Result<()> {
+                self.0.encode(c, w)
+            }
+        }
+
+        let cid = Cid::new_v1(0x71, Code::Sha2_256.digest(b"cbor_size"));
+        let values = [
+            Ipld::Null,
+            Ipld::Integer(-1),
+            Ipld::Integer(u64::MAX as i128),
+            Ipld::Float(1.5),
+            Ipld::String("a".repeat(300)),
+            Ipld::Bytes(vec![0; 70_000]),
+            Ipld::Link(cid),
+            ipld!({ "list": [1, "two", [3.0]], "map": { "link": cid } }),
+        ];
+        for value in &values {
+            assert_eq!(
+                cbor_size(value).unwrap(),
+                DagCborCodec.encode(value).unwrap().len()
+            );
+        }
+        let custom = Custom((0..1000).collect());
+        let len = DagCborCodec.encode(&custom).unwrap().len();
+        assert_eq!(cbor_size(&custom).unwrap(), len);
+        assert_eq!(custom.encoded_len(DagCborCodec).unwrap(), len);
+        cbor_size(&Ipld::Float(f64::NAN)).unwrap_err();
+    }
+
+    #[test]
+    fn test_encode_counted() {
+        let cid = Cid::new_v1(0x71, Code::Sha2_256.digest(b"encode_counted"));
+        let ipld = ipld!({ "list": [1, "two", [3.0]], "bytes": vec![0u8; 1000], "link": cid });
+        let len = DagCborCodec.encode(&ipld).unwrap().len();
+        let n = ipld
+            .encode_counted(DagCborCodec, &mut std::io::sink())
+            .unwrap();
+        assert_eq!(n, len);
+        assert_eq!(
+            [ipld.clone(), ipld]
+                .encode_counted(DagCborCodec, &mut std::io::sink())
+                .unwrap(),
+            2 * len + 1
+        );
+    }
+
+    #[test]
+    fn test_key_order() {
+        use crate::cbor::KeyOrder;
+        use crate::decode::{read_ipld, DecodeOptions};
+        use crate::encode::{write_ipld, EncodeOptions};
+
+        let map = Ipld::Map(
+            [("c", 1), ("aa", 2), ("b", 3)]
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), Ipld::Integer(value)))
+                .collect(),
+        );
+        let encode = |key_order| {
+            let mut bytes = Vec::new();
+            let opts = EncodeOptions {
+                key_order,
+                ..Default::default()
+            };
+            write_ipld(&mut bytes, &map, &opts).unwrap();
+            bytes
+        };
+        let dag_cbor = encode(KeyOrder::DagCbor);
+        let bytewise = encode(KeyOrder::Bytewise);
+        let insertion = encode(KeyOrder::Insertion);
+        assert_eq!(dag_cbor, DagCborCodec.encode(&map).unwrap());
+        assert_eq!(
+            dag_cbor,
+            [0xa3, 0x61, 0x62, 0x03, 0x61, 0x63, 0x01, 0x62, 0x61, 0x61, 0x02]
+        );
+        assert_eq!(
+            bytewise,
+            [0xa3, 0x62, 0x61, 0x61, 0x02, 0x61, 0x62, 0x03, 0x61, 0x63, 0x01]
+        );
+        // `IpldMap` is an `IndexMap` if libipld-core's `indexmap` feature is enabled.
+        let keeps_insertion_order =
+            matches!(&map, Ipld::Map(m) if m.keys().next().map(String::as_str) == Some("c"));
+        if keeps_insertion_order {
+            assert_eq!(
+                insertion,
+                [0xa3, 0x61, 0x63, 0x01, 0x62, 0x61, 0x61, 0x02, 0x61, 0x62, 0x03]
+            );
+        } else {
+            // A `BTreeMap` yields its keys in bytewise order.
+            assert_eq!(insertion, bytewise);
+        }
+
+        // Strict decoding accepts the keys in the matching order only.
+        let decode = |bytes: &[u8], key_order| {
+            let opts = DecodeOptions {
+                key_order,
+                ..DecodeOptions::strict()
+            };
+            read_ipld(&mut Cursor::new(bytes), &opts)
+        };
+        assert_eq!(decode(&dag_cbor, KeyOrder::DagCbor).unwrap(), map);
+        assert_eq!(decode(&bytewise, KeyOrder::Bytewise).unwrap(), map);
+        assert_eq!(decode(&insertion, KeyOrder::Insertion).unwrap(), map);
+        assert_eq!(decode(&dag_cbor, KeyOrder::Insertion).unwrap(), map);
+        decode(&bytewise, KeyOrder::DagCbor)
+            .expect_err("should have rejected keys in bytewise order")
+            .downcast::<error::UnorderedKeys>()
+            .unwrap();
+        decode(&dag_cbor, KeyOrder::Bytewise)
+            .expect_err("should have rejected keys in DAG-CBOR order")
+            .downcast::<error::UnorderedKeys>()
+            .unwrap();
+        // Duplicates are rejected in any order.
+        decode(
+            &[0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02],
+            KeyOrder::Insertion,
+        )
+        .expect_err("should have rejected duplicate keys")
+        .downcast::<error::DuplicateKey>()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_hash_map_deterministic() {
+        use std::collections::{BTreeMap, HashMap};
+
+        let entries: Vec<(String, Vec<u64>)> = (0..100u64)
+            .map(|i| (format!("key{}", i * 7919 % 1000), vec![i; (i % 3) as usize]))
+            .collect();
+        let forward: HashMap<String, Vec<u64>> = entries.iter().cloned().collect();
+        let backward: HashMap<String, Vec<u64>> = entries.iter().rev().cloned().collect();
+        let bytes = DagCborCodec.encode(&forward).unwrap();
+        assert_eq!(bytes, DagCborCodec.encode(&backward).unwrap());
+        assert_eq!(bytes.len(), forward.encoded_len(DagCborCodec).unwrap());
+        let sorted: BTreeMap<String, Vec<u64>> = entries.iter().cloned().collect();
+        assert_eq!(bytes, DagCborCodec.encode(&sorted).unwrap());
+        let decoded: HashMap<String, Vec<u64>> = DagCborCodec.decode(&bytes).unwrap();
+        assert_eq!(decoded, forward);
+
+        // Borrowed keys encode the same.
+        let borrowed: HashMap<&str, &Vec<u64>> = entries
+            .iter()
+            .rev()
+            .map(|(key, value)| (key.as_str(), value))
+            .collect();
+        assert_eq!(DagCborCodec.encode(&borrowed).unwrap(), bytes);
+        assert_eq!(borrowed.encoded_len(DagCborCodec).unwrap(), bytes.len());
+    }
+
+    #[test]
+    fn test_encode_with() {
+        use crate::encode::EncodeOptions;
+
+        let ipld = ipld!({ "float": 1.5, "list": [1, "a"], "bb": null });
+        let bytes = DagCborCodec
+            .encode_with(&ipld, &EncodeOptions::default())
+            .unwrap();
+        assert_eq!(bytes, DagCborCodec.encode(&ipld).unwrap());
+        assert_eq!(DagCborCodec.decode_strict(&bytes).unwrap(), ipld);
+
+        let opts = EncodeOptions {
+            shortest_floats: true,
+            ..Default::default()
+        };
+        let bytes = DagCborCodec.encode_with(&Ipld::Float(1.5), &opts).unwrap();
+        assert_eq!(bytes, [0xf9, 0x3e, 0x00]);
+        DagCborCodec
+            .decode_strict(&bytes)
+            .expect_err("should have failed on a half precision float")
+            .downcast::<error::FloatNotF64>()
+            .unwrap();
+    }
 }
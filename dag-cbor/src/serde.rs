@@ -0,0 +1,1232 @@
+//! Serde serializer and deserializer for DAG-CBOR.
+//!
+//! This encodes types deriving `serde::Serialize` directly into DAG-CBOR and decodes DAG-CBOR
+//! directly into types deriving `serde::Deserialize`, without going through `Ipld`. `Cid`s are
+//! encoded as links and links are deserialized into `Cid`s.
+use crate::cbor::{KeyOrder, Major, MajorKind, BREAK, F16, F32, F64, FALSE, NULL, TRUE, UNDEFINED};
+use crate::decode::{
+    read_bignum, read_byte_string, read_f16, read_f32, read_f64, read_len, read_link, read_major,
+    read_tag, read_text_string, read_uint, DecodeOptions,
+};
+use crate::encode::{write_null, write_u64};
+use crate::error::{
+    DuplicateKey, MaxDepthExceeded, TrailingBytes, Undefined, UnexpectedCode, UnknownTag,
+};
+use crate::DagCborCodec as DagCbor;
+use core::convert::TryFrom;
+use libipld_core::cid::serde::CID_SERDE_PRIVATE_IDENTIFIER;
+use libipld_core::cid::Cid;
+use libipld_core::codec::Encode;
+use libipld_core::error::Result;
+use serde::de::value::BytesDeserializer;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde::ser::{self, Serialize};
+use std::fmt;
+use std::io::{Read, Write};
+
+/// Serializes a value into canonical DAG-CBOR.
+///
+/// # Example
+///
+/// ```
+/// use libipld_cbor::serde::to_vec;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Person {
+///     name: String,
+///     age: u8,
+/// }
+///
+/// let bytes = to_vec(&Person { name: "Alice".into(), age: 52 }).unwrap();
+/// // {"age": 52, "name": "Alice"}
+/// assert_eq!(
+///     bytes,
+///     [
+///         0xa2, 0x63, 0x61, 0x67, 0x65, 0x18, 0x34, 0x64, 0x6e, 0x61, 0x6d, 0x65, 0x65, 0x41,
+///         0x6c, 0x69, 0x63, 0x65,
+///     ]
+/// );
+/// ```
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    to_writer(&mut bytes, value)?;
+    Ok(bytes)
+}
+
+/// Serializes a value into canonical DAG-CBOR, writing it to a byte stream.
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(w: W, value: &T) -> Result<()> {
+    value
+        .serialize(&mut Serializer::new(w))
+        .map_err(|err| err.0)
+}
+
+/// Deserializes a single item that has to span all of `bytes`.
+///
+/// # Example
+///
+/// ```
+/// use libipld_cbor::serde::from_slice;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Person {
+///     name: String,
+///     age: u8,
+/// }
+///
+/// // {"age": 52, "name": "Alice"}
+/// let bytes = [
+///     0xa2, 0x63, 0x61, 0x67, 0x65, 0x18, 0x34, 0x64, 0x6e, 0x61, 0x6d, 0x65, 0x65, 0x41, 0x6c,
+///     0x69, 0x63, 0x65,
+/// ];
+/// let person: Person = from_slice(&bytes).unwrap();
+/// assert_eq!(person.name, "Alice");
+/// assert_eq!(person.age, 52);
+/// ```
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let mut de = Deserializer::new(bytes);
+    let value = T::deserialize(&mut de).map_err(|err| err.0)?;
+    let remaining = de.r.len() + usize::from(de.peeked.is_some());
+    if remaining > 0 {
+        return Err(TrailingBytes(remaining).into());
+    }
+    Ok(value)
+}
+
+/// Deserializes a single item from a byte stream. Anything after the item isn't read.
+pub fn from_reader<T: DeserializeOwned, R: Read>(r: R) -> Result<T> {
+    let mut de = Deserializer::new(r);
+    T::deserialize(&mut de).map_err(|err| err.0)
+}
+
+/// The error of the [`Serializer`] and [`Deserializer`], which wraps the encoding or decoding
+/// error.
+#[derive(Debug)]
+pub struct Error(pub libipld_core::error::Error);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(libipld_core::error::Error::msg(msg.to_string()))
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(libipld_core::error::Error::msg(msg.to_string()))
+    }
+}
+
+impl From<libipld_core::error::Error> for Error {
+    fn from(err: libipld_core::error::Error) -> Self {
+        Self(err)
+    }
+}
+
+/// Deserializes DAG-CBOR from a byte stream.
+///
+/// It accepts the same input as decoding `Ipld` with the default options, including the nesting
+/// limit.
+pub struct Deserializer<R> {
+    r: R,
+    /// A major that was read ahead to decide how to continue.
+    peeked: Option<Major>,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<R: Read> Deserializer<R> {
+    /// Creates a new deserializer.
+    pub fn new(r: R) -> Self {
+        Self {
+            r,
+            peeked: None,
+            depth: 0,
+            max_depth: DecodeOptions::default().max_depth,
+        }
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    fn peek(&mut self) -> Result<Major> {
+        match self.peeked {
+            Some(major) => Ok(major),
+            None => {
+                let major = read_major(&mut self.r)?;
+                self.peeked = Some(major);
+                Ok(major)
+            }
+        }
+    }
+
+    fn next_major(&mut self) -> Result<Major> {
+        match self.peeked.take() {
+            Some(major) => Ok(major),
+            None => read_major(&mut self.r),
+        }
+    }
+
+    /// Returns whether another item of a list or map follows, see [`crate::decode::read_next`].
+    /// The "break" stop code of an indefinite-length list or map is consumed.
+    fn has_next(&mut self, remaining: &mut Option<u64>) -> Result<bool> {
+        match remaining {
+            Some(0) => Ok(false),
+            Some(len) => {
+                *len -= 1;
+                Ok(true)
+            }
+            None if self.peek()? == BREAK => {
+                self.peeked = None;
+                *remaining = Some(0);
+                Ok(false)
+            }
+            None => Ok(true),
+        }
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        if self.depth >= self.max_depth {
+            return Err(MaxDepthExceeded(self.max_depth).into());
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn visit_major<'de, V: Visitor<'de>>(
+        &mut self,
+        major: Major,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match major.kind() {
+            MajorKind::UnsignedInt => visitor.visit_u64(read_uint(&mut self.r, major)?),
+            MajorKind::NegativeInt => {
+                let value = -1 - read_uint(&mut self.r, major)? as i128;
+                match i64::try_from(value) {
+                    Ok(value) => visitor.visit_i64(value),
+                    Err(_) => visitor.visit_i128(value),
+                }
+            }
+            MajorKind::ByteString => visitor.visit_byte_buf(read_byte_string(&mut self.r, major)?),
+            MajorKind::TextString => visitor.visit_string(read_text_string(&mut self.r, major)?),
+            MajorKind::Array => {
+                let mut remaining = read_len(&mut self.r, major)?;
+                self.enter()?;
+                let value = visitor.visit_seq(Seq {
+                    de: self,
+                    remaining: &mut remaining,
+                })?;
+                self.depth -= 1;
+                if remaining != Some(0) {
+                    return Err(de::Error::custom("list has more items than expected"));
+                }
+                Ok(value)
+            }
+            MajorKind::Map => {
+                let mut remaining = read_len(&mut self.r, major)?;
+                self.enter()?;
+                let value = visitor.visit_map(Seq {
+                    de: self,
+                    remaining: &mut remaining,
+                })?;
+                self.depth -= 1;
+                if remaining != Some(0) {
+                    return Err(de::Error::custom("map has more entries than expected"));
+                }
+                Ok(value)
+            }
+            MajorKind::Tag => match read_tag(&mut self.r, major, false)? {
+                42 => {
+                    let cid = read_link(&mut self.r)?;
+                    visitor.visit_newtype_struct(BytesDeserializer::new(&cid.to_bytes()))
+                }
+                2 => visitor.visit_i128(read_bignum(&mut self.r)?),
+                3 => visitor.visit_i128(-1 - read_bignum(&mut self.r)?),
+                value => Err(Error(UnknownTag(value).into())),
+            },
+            MajorKind::Other => match major {
+                FALSE => visitor.visit_bool(false),
+                TRUE => visitor.visit_bool(true),
+                NULL => visitor.visit_none(),
+                UNDEFINED => Err(Error(Undefined.into())),
+                F16 => visitor.visit_f64(read_f16(&mut self.r)?),
+                F32 => visitor.visit_f64(read_f32(&mut self.r)? as f64),
+                F64 => visitor.visit_f64(read_f64(&mut self.r)?),
+                m => Err(Error(UnexpectedCode::new::<V::Value>(m.into()).into())),
+            },
+        }
+    }
+}
+
+impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let major = self.next_major()?;
+        self.visit_major(major, visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.peek()? == NULL {
+            self.peeked = None;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.next_major()? {
+            NULL => visitor.visit_unit(),
+            major => self.visit_major(major, visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if name != CID_SERDE_PRIVATE_IDENTIFIER {
+            return visitor.visit_newtype_struct(self);
+        }
+        let major = self.next_major()?;
+        if major.kind() != MajorKind::Tag {
+            return Err(Error(UnexpectedCode::new::<V::Value>(major.into()).into()));
+        }
+        match read_tag(&mut self.r, major, false)? {
+            42 => {
+                let cid = read_link(&mut self.r)?;
+                visitor.visit_newtype_struct(BytesDeserializer::new(&cid.to_bytes()))
+            }
+            value => Err(Error(UnknownTag(value).into())),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let major = self.next_major()?;
+        match major.kind() {
+            // Unit variants.
+            MajorKind::TextString => {
+                let variant = read_text_string(&mut self.r, major)?;
+                visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(variant))
+            }
+            // Other variants are a map with a single entry.
+            MajorKind::Map => {
+                let mut remaining = read_len(&mut self.r, major)?;
+                if !self.has_next(&mut remaining)? {
+                    return Err(de::Error::custom("expected a map with a single entry"));
+                }
+                self.enter()?;
+                let value = visitor.visit_enum(Variant { de: self })?;
+                self.depth -= 1;
+                if self.has_next(&mut remaining)? {
+                    return Err(de::Error::custom("expected a map with a single entry"));
+                }
+                Ok(value)
+            }
+            _ => Err(Error(UnexpectedCode::new::<V::Value>(major.into()).into())),
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf seq
+        tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// The items of a list or the entries of a map.
+struct Seq<'a, R> {
+    de: &'a mut Deserializer<R>,
+    remaining: &'a mut Option<u64>,
+}
+
+impl<'de, R: Read> de::SeqAccess<'de> for Seq<'_, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if !self.de.has_next(self.remaining)? {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining.and_then(|len| usize::try_from(len).ok())
+    }
+}
+
+impl<'de, R: Read> de::MapAccess<'de> for Seq<'_, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if !self.de.has_next(self.remaining)? {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining.and_then(|len| usize::try_from(len).ok())
+    }
+}
+
+/// The single entry of a map that represents an enum variant.
+struct Variant<'a, R> {
+    de: &'a mut Deserializer<R>,
+}
+
+impl<'de, R: Read> de::EnumAccess<'de> for Variant<'_, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant = seed.deserialize(&mut *self.de)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, R: Read> de::VariantAccess<'de> for Variant<'_, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
+
+/// Serializes into canonical DAG-CBOR, writing to a byte stream.
+///
+/// Values are mapped like serializing into `Ipld`: structs become maps, sequences and tuples
+/// become lists, unit enum variants become strings and other enum variants a map with a single
+/// entry. Map keys have to be strings. They are written in canonical order, hence the entries of
+/// a map are buffered until it ends.
+pub struct Serializer<W> {
+    w: W,
+}
+
+impl<W: Write> Serializer<W> {
+    /// Creates a new serializer.
+    pub fn new(w: W) -> Self {
+        Self { w }
+    }
+
+    /// Returns the inner writer.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+
+    fn write<T: Encode<DagCbor> + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        Ok(value.encode(DagCbor, &mut self.w)?)
+    }
+
+    /// Writes bytes that are encoded already.
+    fn write_raw(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.w
+            .write_all(bytes)
+            .map_err(libipld_core::error::Error::from)?;
+        Ok(())
+    }
+
+    /// Writes the start of an enum variant that isn't a unit variant, a map with a single entry.
+    fn write_variant(&mut self, variant: &str) -> Result<(), Error> {
+        write_u64(&mut self.w, MajorKind::Map, 1)?;
+        self.write(variant)
+    }
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = SerializeList<'a, W>;
+    type SerializeTuple = SerializeList<'a, W>;
+    type SerializeTupleStruct = SerializeList<'a, W>;
+    type SerializeTupleVariant = SerializeList<'a, W>;
+    type SerializeMap = SerializeMap<'a, W>;
+    type SerializeStruct = SerializeMap<'a, W>;
+    type SerializeStructVariant = SerializeMap<'a, W>;
+
+    fn serialize_bool(self, value: bool) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), Error> {
+        self.write(&value)
+    }
+
+    fn serialize_char(self, value: char) -> Result<(), Error> {
+        self.write(value.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        self.write(value)
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), Error> {
+        self.write(value)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(write_null(&mut self.w)?)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Err(ser::Error::custom("Unit is not supported"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Err(ser::Error::custom("Unit structs are not supported"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.write(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        if name != CID_SERDE_PRIVATE_IDENTIFIER {
+            return value.serialize(self);
+        }
+        // A `Cid` serializes as its bytes.
+        let bytes = to_vec(value)?;
+        let mut r = &bytes[..];
+        let major = read_major(&mut r)?;
+        if major.kind() != MajorKind::ByteString {
+            return Err(ser::Error::custom("Invalid CID: expected bytes"));
+        }
+        let cid = Cid::try_from(read_byte_string(&mut r, major)?)
+            .map_err(|err| <Error as ser::Error>::custom(format!("Invalid CID: {}", err)))?;
+        self.write(&cid)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.write_variant(variant)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList<'a, W>, Error> {
+        // Lists of unknown length are buffered, as DAG-CBOR doesn't allow indefinite lengths.
+        let buf = match len {
+            Some(len) => {
+                write_u64(&mut self.w, MajorKind::Array, len as u64)?;
+                None
+            }
+            None => Some(Serializer::new(Vec::new())),
+        };
+        Ok(SerializeList {
+            ser: self,
+            buf,
+            expected: len.map(|len| len as u64),
+            len: 0,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList<'a, W>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeList<'a, W>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeList<'a, W>, Error> {
+        self.write_variant(variant)?;
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap<'a, W>, Error> {
+        Ok(SerializeMap {
+            ser: self,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap<'a, W>, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap<'a, W>, Error> {
+        self.write_variant(variant)?;
+        self.serialize_map(Some(len))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Serializes the items of a list, tuple or tuple variant.
+pub struct SerializeList<'a, W> {
+    ser: &'a mut Serializer<W>,
+    /// The items of a list of unknown length, which are written once the length is known.
+    buf: Option<Serializer<Vec<u8>>>,
+    /// The length that was written as the header of a list of known length.
+    expected: Option<u64>,
+    len: u64,
+}
+
+impl<W: Write> SerializeList<'_, W> {
+    fn item<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        // Fail before writing an item that isn't covered by the header.
+        if Some(self.len) == self.expected {
+            return Err(ser::Error::custom("list has more items than its length"));
+        }
+        self.len += 1;
+        match &mut self.buf {
+            Some(buf) => value.serialize(buf),
+            None => value.serialize(&mut *self.ser),
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if let Some(expected) = self.expected {
+            if self.len != expected {
+                return Err(ser::Error::custom(format!(
+                    "list has {} items but a length of {}",
+                    self.len, expected
+                )));
+            }
+        }
+        if let Some(buf) = self.buf {
+            write_u64(&mut self.ser.w, MajorKind::Array, self.len)?;
+            self.ser.write_raw(&buf.w)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeSeq for SerializeList<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTuple for SerializeList<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleStruct for SerializeList<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleVariant for SerializeList<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+/// Serializes the entries of a map, struct or struct variant.
+pub struct SerializeMap<'a, W> {
+    ser: &'a mut Serializer<W>,
+    /// The keys with their encoded values, which are written in canonical order once the map
+    /// ends.
+    entries: Vec<(String, Vec<u8>)>,
+    next_key: Option<String>,
+}
+
+impl<W: Write> SerializeMap<'_, W> {
+    fn entry<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        self.entries.push((key, to_vec(value)?));
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), Error> {
+        self.entries.sort_unstable_by(|(a, _), (b, _)| {
+            KeyOrder::DagCbor
+                .compare(a, b)
+                .expect("DAG-CBOR order compares keys")
+        });
+        if let Some(pair) = self.entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(Error(DuplicateKey(pair[0].0.clone()).into()));
+        }
+        write_u64(&mut self.ser.w, MajorKind::Map, self.entries.len() as u64)?;
+        for (key, value) in &self.entries {
+            self.ser.write(key.as_str())?;
+            self.ser.write_raw(value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeMap for SerializeMap<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.next_key.take().ok_or_else(|| {
+            <Error as ser::Error>::custom("serialize_value called before serialize_key")
+        })?;
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeStruct for SerializeMap<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.entry(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeStructVariant for SerializeMap<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.entry(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+/// Serializes a map key, which has to be a string.
+struct KeySerializer;
+
+impl KeySerializer {
+    fn unsupported() -> Error {
+        ser::Error::custom("Map keys must be strings")
+    }
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<String, Error>;
+    type SerializeTuple = ser::Impossible<String, Error>;
+    type SerializeTupleStruct = ser::Impossible<String, Error>;
+    type SerializeTupleVariant = ser::Impossible<String, Error>;
+    type SerializeMap = ser::Impossible<String, Error>;
+    type SerializeStruct = ser::Impossible<String, Error>;
+    type SerializeStructVariant = ser::Impossible<String, Error>;
+
+    fn serialize_str(self, value: &str) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_char(self, value: char) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<String, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Self::unsupported())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{NonFiniteFloat, UnexpectedEof};
+    use crate::DagCborCodec;
+    use libipld_core::cid::Cid;
+    use libipld_core::codec::Codec;
+    use libipld_core::ipld::Ipld;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_core::serde::to_ipld;
+    use libipld_macro::ipld;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Block {
+        name: String,
+        link: Cid,
+        parent: Option<Cid>,
+        size: Option<u64>,
+        counts: HashMap<String, i64>,
+        kind: Kind,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    enum Kind {
+        File,
+        Dir(u32),
+        Symlink { target: String },
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(b"block"));
+        let bytes = DagCborCodec.encode(&ipld!({
+            "name": "block",
+            "link": cid,
+            "parent": null,
+            "size": 42,
+            "counts": { "a": 1, "b": -2 },
+            "kind": { "Symlink": { "target": "a" } },
+        }))?;
+        let block: Block = from_slice(&bytes)?;
+        let expected = Block {
+            name: "block".into(),
+            link: cid,
+            parent: None,
+            size: Some(42),
+            counts: HashMap::from([("a".into(), 1), ("b".into(), -2)]),
+            kind: Kind::Symlink { target: "a".into() },
+        };
+        assert_eq!(block, expected);
+        assert_eq!(DagCborCodec.encode(&to_ipld(&block)?)?, bytes);
+
+        for kind in [Kind::File, Kind::Dir(3)] {
+            let bytes = DagCborCodec.encode(&to_ipld(&kind)?)?;
+            assert_eq!(from_slice::<Kind>(&bytes)?, kind);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn serialize() -> Result<()> {
+        let cid = Cid::new_v1(0x71, Code::Sha2_256.digest(b"block"));
+        let parent = Cid::new_v0(Code::Sha2_256.digest(b"parent")).unwrap();
+        let block = Block {
+            name: "block".into(),
+            link: cid,
+            parent: Some(parent),
+            size: None,
+            counts: HashMap::from([("bb".into(), 1), ("a".into(), -2), ("c".into(), 3)]),
+            kind: Kind::Symlink { target: "a".into() },
+        };
+        let bytes = to_vec(&block)?;
+        assert_eq!(from_slice::<Block>(&bytes)?, block);
+
+        // The same bytes, and hence the same CID, as encoding the equivalent `Ipld`.
+        let ipld = to_ipld(&block)?;
+        assert_eq!(DagCborCodec.decode::<Ipld>(&bytes)?, ipld);
+        let ipld_bytes = DagCborCodec.encode(&ipld)?;
+        assert_eq!(bytes, ipld_bytes);
+        assert_eq!(
+            Cid::new_v1(0x71, Code::Sha2_256.digest(&bytes)),
+            Cid::new_v1(0x71, Code::Sha2_256.digest(&ipld_bytes))
+        );
+
+        for kind in [Kind::File, Kind::Dir(3)] {
+            let bytes = to_vec(&kind)?;
+            assert_eq!(bytes, DagCborCodec.encode(&to_ipld(&kind)?)?);
+            assert_eq!(from_slice::<Kind>(&bytes)?, kind);
+        }
+
+        let ipld = ipld!({
+            "bytes": vec![1u8, 2, 3],
+            "list": [1, -1, 1.5, true, null, "a", u64::MAX],
+            "link": cid,
+        });
+        let mut bytes = Vec::new();
+        to_writer(&mut bytes, &ipld)?;
+        assert_eq!(bytes, DagCborCodec.encode(&ipld)?);
+
+        // A sequence of unknown length.
+        struct Evens;
+        impl Serialize for Evens {
+            fn serialize<S: ser::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                s.collect_seq((0..10u64).filter(|i| i % 2 == 0))
+            }
+        }
+        assert_eq!(
+            to_vec(&Evens)?,
+            DagCborCodec.encode(&vec![0u64, 2, 4, 6, 8])?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_errors() {
+        to_vec(&f64::NAN)
+            .expect_err("should have failed to serialize NaN")
+            .downcast::<NonFiniteFloat>()
+            .unwrap();
+        to_vec(&HashMap::from([(1u32, 1u32)]))
+            .expect_err("should have failed to serialize an integer map key");
+        to_vec(&()).expect_err("should have failed to serialize unit");
+
+        /// Serializes `items` items as a list of length `len`.
+        struct Mislabeled {
+            len: usize,
+            items: u64,
+        }
+
+        impl Serialize for Mislabeled {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeSeq;
+                let mut seq = s.serialize_seq(Some(self.len))?;
+                for item in 0..self.items {
+                    seq.serialize_element(&item)?;
+                }
+                seq.end()
+            }
+        }
+
+        assert_eq!(
+            to_vec(&Mislabeled { len: 2, items: 2 }).unwrap(),
+            [0x82, 0x00, 0x01]
+        );
+        to_vec(&Mislabeled { len: 2, items: 1 })
+            .expect_err("should have failed to serialize too few items");
+        to_vec(&Mislabeled { len: 2, items: 3 })
+            .expect_err("should have failed to serialize too many items");
+    }
+
+    #[test]
+    fn ipld() -> Result<()> {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(b"block"));
+        let ipld = ipld!({
+            "bytes": vec![1u8, 2, 3],
+            "list": [1, -1, 1.5, true, null, "a"],
+            "link": cid,
+        });
+        let bytes = DagCborCodec.encode(&ipld)?;
+        assert_eq!(from_slice::<Ipld>(&bytes)?, ipld);
+        assert_eq!(from_reader::<Ipld, _>(&bytes[..])?, ipld);
+
+        // Indefinite-length lists and maps.
+        let bytes = [0xbf, 0x61, 0x61, 0x9f, 0x01, 0xff, 0xff];
+        let map: HashMap<String, Vec<u8>> = from_slice(&bytes)?;
+        assert_eq!(map, HashMap::from([("a".into(), vec![1])]));
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        from_slice::<u64>(&[0x01, 0x02])
+            .expect_err("should have failed to decode trailing bytes")
+            .downcast::<TrailingBytes>()
+            .unwrap();
+        from_slice::<Vec<u64>>(&[0x82, 0x01])
+            .expect_err("should have failed to decode a truncated list")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        from_slice::<(u64,)>(&[0x82, 0x01, 0x02])
+            .expect_err("should have failed to decode a list that is too long");
+        from_slice::<Cid>(&[0x01])
+            .expect_err("should have failed to decode an integer as a link")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        from_slice::<Ipld>(&vec![0x81; 10_000])
+            .expect_err("should have failed to decode deeply nested lists")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+//! Decoding into `Ipld` whose byte strings share the decoded buffer.
+use crate::borrowed::IpldRef;
+use bytes::Bytes;
+use libipld_core::cid::Cid;
+use libipld_core::error::{Result, TypeError, TypeErrorType};
+use libipld_core::ipld::{Ipld, IpldIndex};
+use std::collections::BTreeMap;
+
+/// Version of [`Ipld`] whose byte strings are slices of a shared [`Bytes`] buffer.
+///
+/// Decoding a block that arrived as [`Bytes`] doesn't copy the payload of its byte strings, and
+/// neither does cloning the result. The buffer stays alive as long as any of its slices does.
+/// Strings, keys and links are still copied, like for [`Ipld`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SharedIpld {
+    /// Represents the absence of a value or the value undefined.
+    Null,
+    /// Represents a boolean value.
+    Bool(bool),
+    /// Represents an integer.
+    Integer(i128),
+    /// Represents a floating point value.
+    Float(f64),
+    /// Represents an UTF-8 string.
+    String(String),
+    /// Represents a sequence of bytes.
+    Bytes(Bytes),
+    /// Represents a list.
+    List(Vec<SharedIpld>),
+    /// Represents a map of strings.
+    Map(BTreeMap<String, SharedIpld>),
+    /// Represents a link.
+    Link(Cid),
+}
+
+impl SharedIpld {
+    /// Decodes a single item that has to span all of `bytes`.
+    ///
+    /// Accepts the same input as [`IpldRef::decode`], i.e. indefinite-length strings are
+    /// rejected as they can't be sliced out of the buffer.
+    pub fn decode(bytes: &Bytes) -> Result<Self> {
+        let ipld = IpldRef::decode(bytes)?;
+        Ok(Self::from_ref(bytes, ipld))
+    }
+
+    /// Converts borrowed `Ipld` that points into `bytes`.
+    fn from_ref(bytes: &Bytes, ipld: IpldRef<'_>) -> Self {
+        match ipld {
+            IpldRef::Null => Self::Null,
+            IpldRef::Bool(b) => Self::Bool(b),
+            IpldRef::Integer(i) => Self::Integer(i),
+            IpldRef::Float(f) => Self::Float(f),
+            IpldRef::String(s) => Self::String(s.to_string()),
+            IpldRef::Bytes(b) => Self::Bytes(bytes.slice_ref(b)),
+            IpldRef::List(l) => Self::List(
+                l.into_iter()
+                    .map(|value| Self::from_ref(bytes, value))
+                    .collect(),
+            ),
+            IpldRef::Map(m) => Self::Map(
+                m.into_iter()
+                    .map(|(key, value)| (key.to_string(), Self::from_ref(bytes, value)))
+                    .collect(),
+            ),
+            IpldRef::Link(cid) => Self::Link(cid),
+        }
+    }
+
+    /// Indexes into a list or map.
+    pub fn get<'a, T: Into<IpldIndex<'a>>>(&self, index: T) -> Result<&Self, TypeError> {
+        let index = index.into();
+        let ipld = match self {
+            Self::List(l) => match index {
+                IpldIndex::List(i) => Some(i),
+                IpldIndex::Map(ref key) => key.parse().ok(),
+                IpldIndex::MapRef(key) => key.parse().ok(),
+            }
+            .map(|i| l.get(i)),
+            Self::Map(m) => match index {
+                IpldIndex::Map(ref key) => Some(m.get(key)),
+                IpldIndex::MapRef(key) => Some(m.get(key)),
+                IpldIndex::List(i) => Some(m.get(&i.to_string())),
+            },
+            _ => None,
+        };
+        ipld.unwrap_or_default()
+            .ok_or_else(|| TypeError::new(index, self))
+    }
+
+    /// Copies the byte strings into an owned [`Ipld`].
+    pub fn to_ipld(&self) -> Ipld {
+        match self {
+            Self::Null => Ipld::Null,
+            Self::Bool(b) => Ipld::Bool(*b),
+            Self::Integer(i) => Ipld::Integer(*i),
+            Self::Float(f) => Ipld::Float(*f),
+            Self::String(s) => Ipld::String(s.clone()),
+            Self::Bytes(b) => Ipld::Bytes(b.to_vec()),
+            Self::List(l) => Ipld::List(l.iter().map(Self::to_ipld).collect()),
+            Self::Map(m) => Ipld::Map(
+                m.iter()
+                    .map(|(key, value)| (key.clone(), value.to_ipld()))
+                    .collect(),
+            ),
+            Self::Link(cid) => Ipld::Link(*cid),
+        }
+    }
+}
+
+impl From<&SharedIpld> for TypeErrorType {
+    fn from(ipld: &SharedIpld) -> Self {
+        match ipld {
+            SharedIpld::Null => Self::Null,
+            SharedIpld::Bool(_) => Self::Bool,
+            SharedIpld::Integer(_) => Self::Integer,
+            SharedIpld::Float(_) => Self::Float,
+            SharedIpld::String(_) => Self::String,
+            SharedIpld::Bytes(_) => Self::Bytes,
+            SharedIpld::List(_) => Self::List,
+            SharedIpld::Map(_) => Self::Map,
+            SharedIpld::Link(_) => Self::Link,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::IndefiniteLength;
+    use crate::DagCborCodec;
+    use libipld_core::codec::Codec;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_macro::ipld;
+
+    #[test]
+    fn roundtrip() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(&b"cid"[..]));
+        let ipld = ipld!({
+          "number": -1,
+          "list": [true, null, 1.5, vec![4, 5]],
+          "bytes": vec![0, 1, 2, 3],
+          "empty": Vec::<u8>::new(),
+          "map": { "string": "hello", "link": cid },
+        });
+        let bytes = Bytes::from(DagCborCodec.encode(&ipld).unwrap());
+        let shared = SharedIpld::decode(&bytes).unwrap();
+        assert_eq!(shared.to_ipld(), ipld);
+        assert_eq!(
+            shared.get("map").unwrap().get("link").unwrap(),
+            &SharedIpld::Link(cid)
+        );
+        shared
+            .get("number")
+            .unwrap()
+            .get(0)
+            .expect_err("should have failed to index into an integer");
+    }
+
+    #[test]
+    fn no_copies() {
+        let ipld = ipld!({ "data": vec![7u8; 1000] });
+        let bytes = Bytes::from(DagCborCodec.encode(&ipld).unwrap());
+        assert!(bytes.is_unique());
+
+        let shared = SharedIpld::decode(&bytes).unwrap();
+        // The leaf keeps a reference to the input instead of a copy.
+        assert!(!bytes.is_unique());
+        let data = match shared.get("data").unwrap() {
+            SharedIpld::Bytes(data) => data.clone(),
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(data, vec![7u8; 1000]);
+        assert!(bytes.as_ptr_range().contains(&data.as_ptr()));
+
+        // Cloning shares the payload as well.
+        let cloned = shared.clone();
+        match cloned.get("data").unwrap() {
+            SharedIpld::Bytes(cloned) => assert_eq!(cloned.as_ptr(), data.as_ptr()),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        drop(shared);
+        drop(cloned);
+        drop(data);
+        assert!(bytes.is_unique());
+    }
+
+    #[test]
+    fn indefinite_length() {
+        SharedIpld::decode(&Bytes::from_static(&[0x5f, 0x41, 0x01, 0xff]))
+            .expect_err("should have failed to share an indefinite-length string")
+            .downcast::<IndefiniteLength>()
+            .unwrap();
+    }
+}
@@ -0,0 +1,320 @@
+//! Pull based CBOR tokenizer.
+use crate::cbor::{Major, MajorKind, BREAK, F16, F32, F64, FALSE, NULL, TRUE};
+use crate::decode::{
+    read_bignum, read_byte_string, read_f16, read_f32, read_f64, read_link, read_major, read_tag,
+    read_text_string, read_uint, DecodeOptions,
+};
+use crate::error::{LengthOutOfRange, MaxDepthExceeded, UnexpectedCode, UnknownTag};
+use libipld_core::cid::Cid;
+use libipld_core::error::Result;
+use std::io::Read;
+
+/// A single token of a cbor encoded item.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// Represents a null value.
+    Null,
+    /// Represents a boolean.
+    Bool(bool),
+    /// Represents an integer.
+    Integer(i128),
+    /// Represents a floating point value.
+    Float(f64),
+    /// Represents an UTF-8 string.
+    String(String),
+    /// Represents a sequence of bytes.
+    Bytes(Vec<u8>),
+    /// Represents a link.
+    Link(Cid),
+    /// Starts a list with the given number of items, `None` if it is of indefinite length.
+    StartList(Option<u64>),
+    /// Starts a map with the given number of entries, `None` if it is of indefinite length. Each
+    /// entry is a key followed by a value.
+    StartMap(Option<u64>),
+    /// Ends the most recently started list or map.
+    End,
+}
+
+/// A list or map that hasn't ended yet.
+struct Frame {
+    /// The number of remaining items, `None` if it is of indefinite length. A map entry counts as
+    /// two items.
+    remaining: Option<u64>,
+    /// Whether it's a map.
+    is_map: bool,
+    /// The number of items read so far.
+    items: u64,
+}
+
+/// Reads a single cbor encoded item one token at a time.
+///
+/// Unlike decoding `Ipld`, the tokenizer doesn't build the whole tree in memory. It accepts the
+/// same input as decoding `Ipld` with the default options, including the nesting limit.
+///
+/// # Example
+///
+/// Extracting all links:
+///
+/// ```
+/// use libipld_cbor::token::{Token, Tokenizer};
+/// use libipld_cbor::DagCborCodec;
+/// use libipld_core::cid::Cid;
+/// use libipld_core::codec::Codec;
+/// use libipld_core::ipld::Ipld;
+///
+/// let cid = Cid::try_from("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL").unwrap();
+/// let ipld = Ipld::List(vec![Ipld::Link(cid), Ipld::Integer(1), Ipld::Link(cid)]);
+/// let bytes = DagCborCodec.encode(&ipld).unwrap();
+///
+/// let mut links = Vec::new();
+/// for token in Tokenizer::new(&bytes[..]) {
+///     if let Token::Link(cid) = token.unwrap() {
+///         links.push(cid);
+///     }
+/// }
+/// assert_eq!(links, vec![cid, cid]);
+/// ```
+pub struct Tokenizer<R> {
+    r: R,
+    stack: Vec<Frame>,
+    max_depth: usize,
+    done: bool,
+}
+
+impl<R: Read> Tokenizer<R> {
+    /// Creates a new tokenizer.
+    pub fn new(r: R) -> Self {
+        Self {
+            r,
+            stack: Vec::new(),
+            max_depth: DecodeOptions::default().max_depth,
+            done: false,
+        }
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    /// Returns the next token, or `None` once the item was read completely.
+    pub fn next_token(&mut self) -> Result<Option<Token>> {
+        if self.done {
+            return Ok(None);
+        }
+        let result = self.read_token();
+        if result.is_err() {
+            self.done = true;
+        }
+        result.map(Some)
+    }
+
+    fn read_token(&mut self) -> Result<Token> {
+        // Definite-length lists and maps end after their last item.
+        if let Some(Frame {
+            remaining: Some(0), ..
+        }) = self.stack.last()
+        {
+            return Ok(self.end());
+        }
+
+        let major = read_major(&mut self.r)?;
+        if major == BREAK {
+            return match self.stack.last() {
+                // A map needs to end after a value, not after a key.
+                Some(frame)
+                    if frame.remaining.is_none() && !(frame.is_map && frame.items % 2 == 1) =>
+                {
+                    Ok(self.end())
+                }
+                _ => Err(UnexpectedCode::new::<Token>(major.into()).into()),
+            };
+        }
+        if let Some(frame) = self.stack.last_mut() {
+            if let Some(remaining) = &mut frame.remaining {
+                *remaining -= 1;
+            }
+            frame.items += 1;
+        }
+
+        let token = match major.kind() {
+            MajorKind::Array | MajorKind::Map => return self.start(major),
+            MajorKind::UnsignedInt => Token::Integer(read_uint(&mut self.r, major)? as i128),
+            MajorKind::NegativeInt => Token::Integer(-1 - read_uint(&mut self.r, major)? as i128),
+            MajorKind::ByteString => Token::Bytes(read_byte_string(&mut self.r, major)?),
+            MajorKind::TextString => Token::String(read_text_string(&mut self.r, major)?),
+            MajorKind::Tag => match read_tag(&mut self.r, major, false)? {
+                42 => Token::Link(read_link(&mut self.r)?),
+                2 => Token::Integer(read_bignum(&mut self.r)?),
+                3 => Token::Integer(-1 - read_bignum(&mut self.r)?),
+                value => return Err(UnknownTag(value).into()),
+            },
+            MajorKind::Other => match major {
+                FALSE => Token::Bool(false),
+                TRUE => Token::Bool(true),
+                NULL => Token::Null,
+                F16 => Token::Float(read_f16(&mut self.r)?),
+                F32 => Token::Float(read_f32(&mut self.r)? as f64),
+                F64 => Token::Float(read_f64(&mut self.r)?),
+                m => return Err(UnexpectedCode::new::<Token>(m.into()).into()),
+            },
+        };
+        self.done = self.stack.is_empty();
+        Ok(token)
+    }
+
+    fn start(&mut self, major: Major) -> Result<Token> {
+        if self.stack.len() >= self.max_depth {
+            return Err(MaxDepthExceeded(self.max_depth).into());
+        }
+        let is_map = major.kind() == MajorKind::Map;
+        let len = if major.is_indefinite() {
+            None
+        } else {
+            Some(read_uint(&mut self.r, major)?)
+        };
+        let remaining = match len {
+            Some(len) if is_map => Some(
+                len.checked_mul(2)
+                    .ok_or_else(LengthOutOfRange::new::<Token>)?,
+            ),
+            len => len,
+        };
+        self.stack.push(Frame {
+            remaining,
+            is_map,
+            items: 0,
+        });
+        Ok(if is_map {
+            Token::StartMap(len)
+        } else {
+            Token::StartList(len)
+        })
+    }
+
+    fn end(&mut self) -> Token {
+        self.stack.pop();
+        self.done = self.stack.is_empty();
+        Token::End
+    }
+}
+
+impl<R: Read> Iterator for Tokenizer<R> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::UnexpectedEof;
+    use crate::DagCborCodec;
+    use libipld_core::codec::Codec;
+    use libipld_core::ipld::{Ipld, IpldMap};
+
+    fn tokens(bytes: &[u8]) -> Result<Vec<Token>> {
+        Tokenizer::new(bytes).collect()
+    }
+
+    #[test]
+    fn scalar() {
+        assert_eq!(tokens(&[0x01, 0x02]).unwrap(), vec![Token::Integer(1)]);
+        assert_eq!(tokens(&[0xf6]).unwrap(), vec![Token::Null]);
+        assert_eq!(
+            tokens(&[0x63, 0x66, 0x6f, 0x6f]).unwrap(),
+            vec![Token::String("foo".into())]
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let mut map = IpldMap::new();
+        map.insert(
+            "a".to_string(),
+            Ipld::List(vec![Ipld::Bool(true), Ipld::Float(1.5)]),
+        );
+        map.insert("b".to_string(), Ipld::Map(IpldMap::new()));
+        let ipld = Ipld::List(vec![Ipld::Map(map), Ipld::Bytes(vec![1])]);
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        assert_eq!(
+            tokens(&bytes).unwrap(),
+            vec![
+                Token::StartList(Some(2)),
+                Token::StartMap(Some(2)),
+                Token::String("a".into()),
+                Token::StartList(Some(2)),
+                Token::Bool(true),
+                Token::Float(1.5),
+                Token::End,
+                Token::String("b".into()),
+                Token::StartMap(Some(0)),
+                Token::End,
+                Token::End,
+                Token::Bytes(vec![1]),
+                Token::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn indefinite_length() {
+        let bytes = [
+            0x9f, // Start indefinite-length list
+            0xbf, 0x61, 0x61, 0x80, 0xff, // {"a": []}
+            0x5f, 0x41, 0x01, 0xff, // h'01'
+            0xff, // "break"
+        ];
+        assert_eq!(
+            tokens(&bytes).unwrap(),
+            vec![
+                Token::StartList(None),
+                Token::StartMap(None),
+                Token::String("a".into()),
+                Token::StartList(Some(0)),
+                Token::End,
+                Token::End,
+                Token::Bytes(vec![1]),
+                Token::End,
+            ]
+        );
+
+        // A key without a value.
+        tokens(&[0xbf, 0x61, 0x61, 0xff])
+            .expect_err("should have failed to read a key without a value")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+        // A break outside of an indefinite-length item.
+        tokens(&[0x81, 0xff])
+            .expect_err("should have failed to read a stray break")
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+    }
+
+    #[test]
+    fn errors() {
+        tokens(&[0x82, 0x01])
+            .expect_err("should have failed to read a truncated list")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        tokens(&[0x5a, 0xff, 0xff, 0xff, 0xff])
+            .expect_err("should have failed to read a truncated byte string")
+            .downcast::<UnexpectedEof>()
+            .unwrap();
+        tokens(&vec![0x81; 10_000])
+            .expect_err("should have failed to read deeply nested lists")
+            .downcast::<MaxDepthExceeded>()
+            .unwrap();
+
+        // The tokenizer stops after an error.
+        let mut tokenizer = Tokenizer::new(&[0x82, 0xf8, 0x01][..]);
+        assert_eq!(
+            tokenizer.next_token().unwrap(),
+            Some(Token::StartList(Some(2)))
+        );
+        assert!(tokenizer.next_token().is_err());
+        assert_eq!(tokenizer.next_token().unwrap(), None);
+    }
+}
@@ -1,9 +1,14 @@
-use libipld_cbor::DagCborCodec;
+use libipld_cbor::error::{InvalidCidPrefix, NonFiniteFloat, NumberOutOfRange};
+use libipld_cbor::{DagCborCodec, IpldCborExt};
 use libipld_core::{
+    cid::Cid,
     codec::{assert_roundtrip, Codec, Decode, Encode},
-    ipld::Ipld,
+    ipld::{Ipld, IpldMap},
+    multihash::{Code, MultihashDigest},
+    prop::IpldParams,
     raw_value::{IgnoredAny, RawValue, SkipOne},
 };
+use proptest::prelude::*;
 use std::{io::Cursor, result};
 
 #[test]
@@ -22,12 +27,36 @@ fn roundtrip_with_cid() {
         "a163666f6fd82a582300122031c3d57080d8463a3c63b2923df5a1d40ad7a73eae5a14af584213e5f504ac33";
     let input = hex::decode(input).unwrap();
 
-    let ipld: Ipld = DagCborCodec.decode(&input).unwrap();
-    let bytes = DagCborCodec.encode(&ipld).unwrap().to_vec();
+    let ipld = Ipld::from_cbor(&input).unwrap();
+    let bytes = ipld.to_cbor().unwrap();
 
     assert_eq!(input, bytes);
 }
 
+#[test]
+fn link_fixture_cid() {
+    // The block from `roundtrip_with_cid`, built from scratch instead of decoded.
+    let link = Cid::try_from("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL").unwrap();
+    let ipld = Ipld::Map(IpldMap::from_iter([("foo".to_string(), Ipld::Link(link))]));
+    let bytes = ipld.to_cbor().unwrap();
+    assert_eq!(
+        hex::encode(&bytes),
+        "a163666f6fd82a582300122031c3d57080d8463a3c63b2923df5a1d40ad7a73eae5a14af584213e5f504ac33"
+    );
+    // Tag 42, a byte string of 35 bytes, the identity multibase prefix and then the CID.
+    assert_eq!(
+        DagCborCodec.encode(&link).unwrap()[..5],
+        [0xd8, 0x2a, 0x58, 0x23, 0x00]
+    );
+
+    // Reproduces the CID go-ipfs assigned to the block.
+    let cid = Cid::new_v1(0x71, Code::Sha2_256.digest(&bytes));
+    assert_eq!(
+        cid.to_string(),
+        "bafyreibvjvcv745gig4mvqs4hctx4zfkono4rjejm2ta6gtyzkqxfjeily"
+    );
+}
+
 #[test]
 #[should_panic]
 fn invalid_cid_prefix() {
@@ -37,6 +66,38 @@ fn invalid_cid_prefix() {
     let _: Ipld = DagCborCodec.decode(&input).unwrap();
 }
 
+#[test]
+fn cid_prefix_is_stripped() {
+    // The block from `roundtrip_with_cid`.
+    let input =
+        "a163666f6fd82a582300122031c3d57080d8463a3c63b2923df5a1d40ad7a73eae5a14af584213e5f504ac33";
+    let input = hex::decode(input).unwrap();
+
+    let ipld: Ipld = DagCborCodec.decode(&input).unwrap();
+    let cid = match ipld.get("foo").unwrap() {
+        Ipld::Link(cid) => *cid,
+        ipld => panic!("expected a link, got {:?}", ipld),
+    };
+    assert_eq!(
+        cid.to_string(),
+        "QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL"
+    );
+}
+
+#[test]
+fn missing_cid_prefix() {
+    // The CID from `roundtrip_with_cid` without the leading identity multibase prefix.
+    let input =
+        "a163666f6fd82a5822122031c3d57080d8463a3c63b2923df5a1d40ad7a73eae5a14af584213e5f504ac33";
+    let input = hex::decode(input).unwrap();
+    let err = DagCborCodec
+        .decode::<Ipld>(&input)
+        .expect_err("should have failed to decode a CID without prefix")
+        .downcast::<InvalidCidPrefix>()
+        .unwrap();
+    assert_eq!(err.0, 0x12);
+}
+
 #[test]
 #[should_panic]
 fn zero_length_cid() {
@@ -108,6 +169,23 @@ fn raw_value() {
     assert!(r.is_err());
 }
 
+proptest! {
+    #[test]
+    fn ipld_roundtrip(ipld in any::<Ipld>()) {
+        let bytes = ipld.to_cbor().unwrap();
+        prop_assert_eq!(Ipld::from_cbor(&bytes).unwrap(), ipld);
+    }
+
+    #[test]
+    fn any_numbers(ipld in any_with::<Ipld>(IpldParams { any_numbers: true, ..Default::default() })) {
+        // Fails cleanly on numbers that can't be encoded.
+        match ipld.to_cbor() {
+            Ok(bytes) => prop_assert_eq!(Ipld::from_cbor(&bytes).unwrap(), ipld),
+            Err(err) => prop_assert!(err.is::<NumberOutOfRange>() || err.is::<NonFiniteFloat>()),
+        }
+    }
+}
+
 #[test]
 #[should_panic]
 fn test_assert_roundtrip() {
@@ -41,8 +41,7 @@ impl Arbitrary for ValueArb {
                     .into_iter()
                     .map(|x| {
                         let slf = Self(x);
-                        let shrunk = slf.shrink().map(|x| x.0).collect::<Vec<_>>();
-                        shrunk
+                        slf.shrink().map(|x| x.0).collect::<Vec<_>>()
                     })
                     .map(Value::Array)
                     .map(Self),
@@ -119,3 +118,31 @@ fn gen_map(g: &mut Gen, depth: u16) -> BTreeMap<Value, Value> {
         .map(|_| (gen_value(g, depth), gen_value(g, depth)))
         .collect()
 }
+
+#[test]
+fn value_conversion_roundtrip() {
+    use libipld_core::cid::Cid;
+    use libipld_core::ipld::Ipld;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_macro::ipld;
+    use std::convert::TryFrom;
+
+    let cid = Cid::new_v1(0x71, Code::Sha2_256.digest(b"serde_cbor"));
+    let ipld = ipld!({
+        "name": "value",
+        "numbers": [0, -1, 2.5, u64::MAX],
+        "flags": { "null": null, "yes": true },
+        "bytes": vec![1u8, 2, 3],
+        "links": [cid, { "nested": cid }],
+    });
+
+    // Values converted from Ipld are what serde_cbor reads from the DAG-CBOR encoding.
+    let bytes = DagCborCodec.encode(&ipld).unwrap();
+    let value: Value = serde_cbor::from_slice(&bytes).unwrap();
+    assert_eq!(value, Value::from(ipld.clone()));
+
+    // Ipld converted from values encodes the same as the original.
+    let converted = Ipld::try_from(value).unwrap();
+    assert_eq!(converted, ipld);
+    assert_eq!(DagCborCodec.encode(&converted).unwrap(), bytes);
+}
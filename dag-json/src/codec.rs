@@ -1,5 +1,5 @@
 use core::convert::TryFrom;
-use libipld_core::cid::Cid;
+use libipld_core::cid_string::{cid_to_string_base32, parse_cid_any_base};
 use libipld_core::ipld::Ipld;
 use libipld_core::multibase::Base;
 use serde::de::Error as SerdeError;
@@ -28,7 +28,19 @@ fn serialize<S: ser::Serializer>(ipld: &Ipld, ser: S) -> Result<S::Ok, S::Error>
     match &ipld {
         Ipld::Null => ser.serialize_none(),
         Ipld::Bool(bool) => ser.serialize_bool(*bool),
+        // Larger integers would be read back as floats.
+        Ipld::Integer(i128) if i64::try_from(*i128).is_err() && u64::try_from(*i128).is_err() => {
+            Err(ser::Error::custom(format_args!(
+                "integer {} is outside of the range of 64 bit integers",
+                i128
+            )))
+        }
         Ipld::Integer(i128) => ser.serialize_i128(*i128),
+        // serde_json would write them as null.
+        Ipld::Float(f64) if !f64.is_finite() => Err(ser::Error::custom(format_args!(
+            "{} can't be encoded as DAG-JSON",
+            f64
+        ))),
         Ipld::Float(f64) => ser.serialize_f64(*f64),
         Ipld::String(string) => ser.serialize_str(string),
         Ipld::Bytes(bytes) => {
@@ -42,12 +54,21 @@ fn serialize<S: ser::Serializer>(ipld: &Ipld, ser: S) -> Result<S::Ok, S::Error>
             ser.collect_seq(wrapped)
         }
         Ipld::Map(map) => {
-            let wrapped = map.iter().map(|(key, ipld)| (key, Wrapper(ipld)));
+            // Such a map would be read back as a link or bytes, or not at all.
+            if map.contains_key(RESERVED_KEY) {
+                return Err(ser::Error::custom(
+                    "maps with a \"/\" key can't be encoded as DAG-JSON",
+                ));
+            }
+            // DAG-JSON sorts keys bytewise, whatever order the map keeps them in.
+            let mut entries = Vec::from_iter(map);
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            let wrapped = entries.into_iter().map(|(key, ipld)| (key, Wrapper(ipld)));
             ser.collect_map(wrapped)
         }
         Ipld::Link(link) => {
             let mut map = BTreeMap::new();
-            map.insert("/", link.to_string());
+            map.insert("/", cid_to_string_base32(link));
 
             ser.collect_map(map)
         }
@@ -174,28 +195,30 @@ impl<'de> de::Visitor<'de> for JsonVisitor {
             values.push((key, value));
         }
 
-        // JSON Object represents an IPLD Link if it is a slash, followed by a string
-        // (`{ "/": "...." }`) therefore we validate if that is the case here.
-        if let Some((key, WrapperOwned(Ipld::String(value)))) = values.first() {
-            if key == RESERVED_KEY && values.len() == 1 {
-                let cid = Cid::try_from(value.clone()).map_err(SerdeError::custom)?;
-                return Ok(Ipld::Link(cid));
-            }
-        }
-        // JSON Object represents IPLD bytes if it is a slash, followed by an object which contains
-        // only a single key called "bytes", where the value is a string.
-        if let Some((key, WrapperOwned(Ipld::Map(map)))) = values.first() {
-            // Object with a slash
-            if key == RESERVED_KEY && values.len() == 1 {
-                if let Some((bytes_key, Ipld::String(bytes_value))) = map.iter().next() {
-                    if bytes_key == BYTES_KEY && values.len() == 1 {
-                        let decoded_bytes = Base::Base64.decode(bytes_value).map_err(|_| {
-                            SerdeError::custom("bytes kind must be base-64 encoded")
-                        })?;
-                        return Ok(Ipld::Bytes(decoded_bytes));
+        // The slash key is reserved, an object with it is either a link, a slash followed by a
+        // string (`{ "/": "...." }`), or bytes, a slash followed by an object which contains only
+        // a single key called "bytes", where the value is a string. Anything else is invalid.
+        if values.iter().any(|(key, _)| key == RESERVED_KEY) {
+            if let [(_, WrapperOwned(value))] = &values[..] {
+                match value {
+                    Ipld::String(value) => {
+                        let cid = parse_cid_any_base(value).map_err(SerdeError::custom)?;
+                        return Ok(Ipld::Link(cid));
+                    }
+                    Ipld::Map(map) if map.len() == 1 => {
+                        if let Some(Ipld::String(bytes_value)) = map.get(BYTES_KEY) {
+                            let decoded_bytes = Base::Base64.decode(bytes_value).map_err(|_| {
+                                SerdeError::custom("bytes kind must be base-64 encoded")
+                            })?;
+                            return Ok(Ipld::Bytes(decoded_bytes));
+                        }
                     }
+                    _ => {}
                 }
             }
+            return Err(SerdeError::custom(
+                "the \"/\" key is reserved for links and bytes",
+            ));
         }
 
         let unwrapped = values
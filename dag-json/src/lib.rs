@@ -1,10 +1,20 @@
 //! Json codec.
+//!
+//! # Numbers
+//!
+//! Integers from `i64::MIN` to `u64::MAX` are written exactly and read back as integers, larger
+//! ones can't be encoded. Note that many JSON implementations, e.g. JavaScript's, read integers
+//! beyond ±2^53 as floats and lose precision, so such blocks may not round-trip elsewhere.
+//!
+//! Floats are always written with a fraction or an exponent, so that they are read back as
+//! floats, `-0.0` keeps its sign. NaN and infinities can't be encoded, and numbers that don't fit
+//! into a 64 bit float, like `1e999`, can't be decoded.
 #![deny(missing_docs)]
 #![deny(warnings)]
 
 use core::convert::TryFrom;
 use libipld_core::cid::Cid;
-use libipld_core::codec::{Codec, Decode, Encode, References};
+use libipld_core::codec::{Codec, Decode, Encode, FixedCodec, Multicodec, References};
 use libipld_core::error::{Result, UnsupportedCodec};
 use libipld_core::ipld::Ipld;
 // TODO vmx 2020-05-28: Don't expose the `serde_json` error directly, but wrap it in a custom one
@@ -12,6 +22,8 @@ pub use serde_json::Error;
 use std::io::{Read, Seek, Write};
 
 mod codec;
+#[cfg(feature = "plain-json")]
+pub mod plain_json;
 
 /// Json codec.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -19,9 +31,13 @@ pub struct DagJsonCodec;
 
 impl Codec for DagJsonCodec {}
 
+impl FixedCodec for DagJsonCodec {
+    const CODE: u64 = Multicodec::DagJson.code();
+}
+
 impl From<DagJsonCodec> for u64 {
     fn from(_: DagJsonCodec) -> Self {
-        0x0129
+        DagJsonCodec::CODE
     }
 }
 
@@ -60,8 +76,8 @@ impl References<DagJsonCodec> for Ipld {
 mod tests {
     use super::*;
     use libipld_core::cid::Cid;
+    use libipld_core::ipld::IpldMap;
     use libipld_core::multihash::{Code, MultihashDigest};
-    use std::collections::BTreeMap;
 
     #[test]
     fn encode_struct() {
@@ -70,7 +86,7 @@ mod tests {
 
         // Create a contact object that looks like:
         // Contact { name: "Hello World", details: CID }
-        let mut map = BTreeMap::new();
+        let mut map = IpldMap::new();
         map.insert("name".to_string(), Ipld::String("Hello World!".to_string()));
         map.insert("details".to_string(), Ipld::Link(cid));
         let contact = Ipld::Map(map);
@@ -90,4 +106,178 @@ mod tests {
         let contact_decoded: Ipld = DagJsonCodec.decode(&contact_encoded).unwrap();
         assert_eq!(contact_decoded, contact);
     }
+
+    #[test]
+    fn reserved_key_decode() {
+        let cid = Cid::new_v1(0x55, Code::Blake3_256.digest(&b"block"[..]));
+        let valid = [
+            (format!(r#"{{"/":"{}"}}"#, cid), Ipld::Link(cid)),
+            (
+                r#"{"/":{"bytes":"AQID"}}"#.into(),
+                Ipld::Bytes(vec![1, 2, 3]),
+            ),
+            (r#"{"/":{"bytes":""}}"#.into(), Ipld::Bytes(vec![])),
+            (
+                format!(r#"{{"a":[{{"/":"{}"}}]}}"#, cid),
+                ipld_map([("a", Ipld::List(vec![Ipld::Link(cid)]))]),
+            ),
+            (
+                r#"{"bytes":"AQID","//":1,"/a":2}"#.into(),
+                ipld_map([
+                    ("bytes", Ipld::String("AQID".into())),
+                    ("//", Ipld::Integer(1)),
+                    ("/a", Ipld::Integer(2)),
+                ]),
+            ),
+        ];
+        for (json, expected) in valid {
+            let decoded: Ipld = DagJsonCodec.decode(json.as_bytes()).unwrap();
+            assert_eq!(decoded, expected, "{}", json);
+        }
+
+        let invalid = [
+            r#"{"/":42}"#.to_string(),
+            r#"{"/":null}"#.into(),
+            r#"{"/":true}"#.into(),
+            r#"{"/":[]}"#.into(),
+            r#"{"/":{}}"#.into(),
+            r#"{"/":{"bytes":1}}"#.into(),
+            r#"{"/":{"bytes":"!!!"}}"#.into(),
+            r#"{"/":{"bytes":"AQID","other":1}}"#.into(),
+            r#"{"/":{"other":"AQID"}}"#.into(),
+            format!(r#"{{"/":{{"/":"{}"}}}}"#, cid),
+            r#"{"/":"not a cid"}"#.into(),
+            format!(r#"{{"/":"{}","other":1}}"#, cid),
+            format!(r#"{{"other":1,"/":"{}"}}"#, cid),
+            r#"{"a":{"/":42}}"#.into(),
+        ];
+        for json in invalid {
+            DagJsonCodec
+                .decode::<Ipld>(json.as_bytes())
+                .expect_err(&format!("should have failed to decode {}", json))
+                .downcast::<Error>()
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn reserved_key_encode() {
+        let cid = Cid::new_v1(0x55, Code::Blake3_256.digest(&b"block"[..]));
+        let invalid = [
+            ipld_map([("/", Ipld::String(cid.to_string()))]),
+            ipld_map([("/", Ipld::Link(cid))]),
+            ipld_map([("/", ipld_map([("bytes", Ipld::String("AQID".into()))]))]),
+            ipld_map([("/", Ipld::Integer(42)), ("other", Ipld::Null)]),
+            Ipld::List(vec![ipld_map([("/", Ipld::Null)])]),
+        ];
+        for ipld in invalid {
+            DagJsonCodec
+                .encode(&ipld)
+                .expect_err("should have failed to encode a map with a \"/\" key")
+                .downcast::<Error>()
+                .unwrap();
+        }
+
+        // Links and bytes use the reserved key and read back as what they were.
+        let ipld = ipld_map([
+            ("link", Ipld::Link(cid)),
+            ("bytes", Ipld::Bytes(vec![1, 2, 3])),
+            ("map", ipld_map([("bytes", Ipld::String("AQID".into()))])),
+        ]);
+        let encoded = DagJsonCodec.encode(&ipld).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&encoded).unwrap(),
+            format!(
+                r#"{{"bytes":{{"/":{{"bytes":"AQID"}}}},"link":{{"/":"{}"}},"map":{{"bytes":"AQID"}}}}"#,
+                cid
+            )
+        );
+        let decoded: Ipld = DagJsonCodec.decode(&encoded).unwrap();
+        assert_eq!(decoded, ipld);
+    }
+
+    fn ipld_map<const N: usize>(entries: [(&str, Ipld); N]) -> Ipld {
+        Ipld::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn numbers() {
+        let valid = [
+            (Ipld::Integer(0), "0"),
+            (Ipld::Integer(-1), "-1"),
+            (Ipld::Integer(1 << 53), "9007199254740992"),
+            (Ipld::Integer((1 << 53) + 1), "9007199254740993"),
+            (Ipld::Integer(-(1 << 53) - 1), "-9007199254740993"),
+            (Ipld::Integer(i64::MIN.into()), "-9223372036854775808"),
+            (Ipld::Integer(u64::MAX.into()), "18446744073709551615"),
+            (Ipld::Float(0.0), "0.0"),
+            (Ipld::Float(-0.0), "-0.0"),
+            (Ipld::Float(1.0), "1.0"),
+            (Ipld::Float(0.1), "0.1"),
+            (Ipld::Float(1e300), "1e+300"),
+            (Ipld::Float(f64::MAX), "1.7976931348623157e+308"),
+            (Ipld::Float(f64::MIN_POSITIVE), "2.2250738585072014e-308"),
+        ];
+        for (ipld, json) in valid {
+            let encoded = DagJsonCodec.encode(&ipld).unwrap();
+            assert_eq!(std::str::from_utf8(&encoded).unwrap(), json);
+            let decoded: Ipld = DagJsonCodec.decode(&encoded).unwrap();
+            assert_eq!(decoded, ipld, "{}", json);
+            if let (Ipld::Float(a), Ipld::Float(b)) = (&decoded, &ipld) {
+                assert_eq!(a.is_sign_negative(), b.is_sign_negative(), "{}", json);
+            }
+        }
+
+        let unencodable = [
+            Ipld::Float(f64::NAN),
+            Ipld::Float(f64::INFINITY),
+            Ipld::Float(f64::NEG_INFINITY),
+            Ipld::Integer(i128::from(u64::MAX) + 1),
+            Ipld::Integer(i128::from(i64::MIN) - 1),
+            Ipld::List(vec![Ipld::Float(f64::NAN)]),
+        ];
+        for ipld in unencodable {
+            DagJsonCodec
+                .encode(&ipld)
+                .expect_err(&format!("should have failed to encode {:?}", ipld))
+                .downcast::<Error>()
+                .unwrap();
+        }
+
+        for json in ["1e999", "-1e999", "NaN", "Infinity", "[1e400]"] {
+            DagJsonCodec
+                .decode::<Ipld>(json.as_bytes())
+                .expect_err(&format!("should have failed to decode {}", json))
+                .downcast::<Error>()
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn link_bases() {
+        let cid = Cid::new_v1(0x71, Code::Sha2_256.digest(&b"block"[..]));
+        let v0 = Cid::new_v0(Code::Sha2_256.digest(&b"block"[..])).unwrap();
+        let ipld = Ipld::List(vec![Ipld::Link(cid), Ipld::Link(v0)]);
+        let encoded = DagJsonCodec.encode(&ipld).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&encoded).unwrap(),
+            format!(r#"[{{"/":"{}"}},{{"/":"{}"}}]"#, cid, v0)
+        );
+
+        // Links in other bases are read, but written in the canonical base again.
+        let base58 = cid.to_string_of_base(libipld_core::multibase::Base::Base58Btc);
+        let json = format!(r#"{{"/":"{}"}}"#, base58.unwrap());
+        let decoded: Ipld = DagJsonCodec.decode(json.as_bytes()).unwrap();
+        assert_eq!(decoded, Ipld::Link(cid));
+
+        let json = format!(r#"{{"/":"/ipfs/{}"}}"#, cid);
+        DagJsonCodec
+            .decode::<Ipld>(json.as_bytes())
+            .expect_err("should have failed on a path instead of a CID");
+    }
 }
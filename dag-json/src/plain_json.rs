@@ -0,0 +1,165 @@
+//! Conversion between `Ipld` and ordinary JSON, e.g. for web APIs.
+//!
+//! Unlike DAG-JSON this doesn't reserve the `"/"` key, so links and bytes can't be told apart from
+//! strings once converted. The conversion is lossy and deterministic:
+//!
+//! - Links are written as the string form of the CID, base32 for CIDv1 and base58btc for CIDv0.
+//! - Bytes are written as padded standard base64 strings.
+//! - Integers outside of `i64::MIN..=u64::MAX` are written as decimal strings.
+//! - Map keys are written in bytewise order, whatever order the map keeps them in.
+//! - Floats that aren't finite can't be represented and are an error.
+//!
+//! Reading JSON never produces links or bytes, every string stays a string. Numbers without a
+//! fraction or exponent that fit in an `i64` or `u64` become integers, all other numbers become
+//! floats.
+//!
+//! # Example
+//!
+//! ```
+//! use libipld_json::plain_json::{from_json, to_json};
+//! use libipld_macro::ipld;
+//!
+//! let ipld = ipld!({ "name": "block", "data": vec![1u8, 2, 3], "size": 3 });
+//! let json = to_json(&ipld)?;
+//! assert_eq!(json, r#"{"data":"AQID","name":"block","size":3}"#);
+//! assert_eq!(from_json(&json)?, ipld!({ "data": "AQID", "name": "block", "size": 3 }));
+//! # Ok::<(), serde_json::Error>(())
+//! ```
+use libipld_core::cid_string::cid_to_string_base32;
+use libipld_core::ipld::Ipld;
+use libipld_core::multibase::Base;
+use serde::ser::{self, Error as _, Serialize};
+use serde_json::{Error, Value};
+
+/// Writes `Ipld` as plain JSON.
+///
+/// Fails on floats that aren't finite.
+pub fn to_json(ipld: &Ipld) -> Result<String, Error> {
+    serde_json::to_string(&Plain(ipld))
+}
+
+/// Reads plain JSON into `Ipld`.
+pub fn from_json(json: &str) -> Result<Ipld, Error> {
+    let value: Value = serde_json::from_str(json)?;
+    Ok(Ipld::from(value))
+}
+
+struct Plain<'a>(&'a Ipld);
+
+impl Serialize for Plain<'_> {
+    fn serialize<S: ser::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Ipld::Null => ser.serialize_none(),
+            Ipld::Bool(bool) => ser.serialize_bool(*bool),
+            Ipld::Integer(int) => {
+                if let Ok(int) = i64::try_from(*int) {
+                    ser.serialize_i64(int)
+                } else if let Ok(int) = u64::try_from(*int) {
+                    ser.serialize_u64(int)
+                } else {
+                    ser.collect_str(int)
+                }
+            }
+            Ipld::Float(float) if float.is_finite() => ser.serialize_f64(*float),
+            Ipld::Float(float) => Err(S::Error::custom(format_args!(
+                "{} can't be represented in JSON",
+                float
+            ))),
+            Ipld::String(string) => ser.serialize_str(string),
+            Ipld::Bytes(bytes) => ser.serialize_str(&Base::Base64Pad.encode(bytes)),
+            Ipld::List(list) => ser.collect_seq(list.iter().map(Plain)),
+            Ipld::Map(map) => {
+                let mut entries = Vec::from_iter(map);
+                entries.sort_unstable_by_key(|(key, _)| *key);
+                ser.collect_map(entries.into_iter().map(|(key, ipld)| (key, Plain(ipld))))
+            }
+            Ipld::Link(cid) => ser.serialize_str(&cid_to_string_base32(cid)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld_core::cid::Cid;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_macro::ipld;
+
+    #[test]
+    fn roundtrip() {
+        let ipld = ipld!({
+            "null": null,
+            "bool": true,
+            "ints": [0, -1, i64::MIN, u64::MAX],
+            "floats": [0.5, -1.5, 1e300],
+            "string": "tab\tand \"quotes\"",
+            "nested": { "list": [[], {}] },
+        });
+        let json = to_json(&ipld).unwrap();
+        assert_eq!(from_json(&json).unwrap(), ipld);
+    }
+
+    #[test]
+    fn lossy() {
+        let cid = Cid::new_v1(0x71, Code::Sha2_256.digest(b"plain json"));
+        let v0 = Cid::new_v0(Code::Sha2_256.digest(b"plain json")).unwrap();
+        let ipld = ipld!({
+            "link": cid,
+            "v0": v0,
+            "bytes": vec![0u8, 255, 1, 2],
+            "big": Ipld::Integer(i128::MAX),
+            "small": Ipld::Integer(i128::from(i64::MIN) - 1),
+        });
+        let json = to_json(&ipld).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"big":"{}","bytes":"AP8BAg==","link":"{}","small":"-9223372036854775809","v0":"{}"}}"#,
+                i128::MAX,
+                cid,
+                v0
+            )
+        );
+        assert!(cid.to_string().starts_with('b'));
+        assert!(v0.to_string().starts_with("Qm"));
+        assert_eq!(
+            from_json(&json).unwrap(),
+            ipld!({
+                "link": cid.to_string(),
+                "v0": v0.to_string(),
+                "bytes": "AP8BAg==",
+                "big": i128::MAX.to_string(),
+                "small": "-9223372036854775809",
+            })
+        );
+
+        // Integral floats stay floats, numbers too big for an integer become floats.
+        assert_eq!(to_json(&Ipld::Float(1.0)).unwrap(), "1.0");
+        assert_eq!(from_json("1.0").unwrap(), Ipld::Float(1.0));
+        assert_eq!(
+            from_json("18446744073709551616").unwrap(),
+            Ipld::Float(18446744073709551616.0)
+        );
+    }
+
+    #[test]
+    fn deterministic() {
+        let mut map = libipld_core::ipld::IpldMap::new();
+        for key in ["b", "a", "aa", "B"] {
+            map.insert(key.to_string(), Ipld::Null);
+        }
+        assert_eq!(
+            to_json(&Ipld::Map(map)).unwrap(),
+            r#"{"B":null,"a":null,"aa":null,"b":null}"#
+        );
+    }
+
+    #[test]
+    fn unrepresentable() {
+        for float in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            to_json(&ipld!([Ipld::Float(float)]))
+                .expect_err("should have failed on a float that isn't finite");
+        }
+        from_json("{\"a\": 1").expect_err("should have failed on invalid JSON");
+    }
+}
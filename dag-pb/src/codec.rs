@@ -2,9 +2,15 @@ use crate::dag_pb;
 use core::convert::{TryFrom, TryInto};
 use libipld_core::cid::Cid;
 use libipld_core::error::{Result, TypeError, TypeErrorType};
-use libipld_core::ipld::Ipld;
+use libipld_core::ipld::{Ipld, IpldMap};
 use prost::bytes::{Buf, Bytes};
-use std::collections::BTreeMap;
+use prost::encoding::WireType;
+use thiserror::Error;
+
+/// Ipld that doesn't have the form of a DAG-PB node, or a DAG-PB link without a hash.
+#[derive(Debug, Error)]
+#[error("Invalid DAG-PB form: {0}.")]
+pub struct InvalidPbForm(pub String);
 
 /// A protobuf ipld link.
 #[derive(Debug)]
@@ -32,53 +38,218 @@ impl PbNode {
     pub(crate) fn links(bytes: Bytes, links: &mut impl Extend<Cid>) -> Result<()> {
         let proto = dag_pb::PbNode::decode(bytes)?;
         for link in proto.links {
-            let cid = Cid::try_from(link.hash.as_ref())?;
-            links.extend(Some(cid));
+            links.extend(Some(link_cid(&link)?));
         }
         Ok(())
     }
 
-    /// Deserializes a `PbNode` from bytes.
+    /// Deserializes a `PbNode` from bytes. Absent fields are empty.
     pub fn from_bytes(bytes: impl Buf) -> Result<Self> {
         let proto: dag_pb::PbNode = dag_pb::PbNode::decode(bytes)?;
-        let data = proto.data.to_vec().into_boxed_slice();
+        let data = proto.data.unwrap_or_default().to_vec().into_boxed_slice();
         let mut links = Vec::new();
         for link in proto.links {
-            let cid = Cid::try_from(link.hash.as_ref())?;
-            let name = link.name;
-            let size = link.tsize;
+            let cid = link_cid(&link)?;
+            let name = link.name.unwrap_or_default();
+            let size = link.tsize.unwrap_or_default();
             links.push(PbLink { cid, name, size });
         }
         Ok(PbNode { links, data })
     }
 
-    /// Serializes a `PbNode` to bytes.
+    /// Serializes a `PbNode` to bytes. Empty fields are left out.
     pub fn into_bytes(self) -> Box<[u8]> {
         let links = self
             .links
             .into_iter()
             .map(|link| dag_pb::PbLink {
-                hash: link.cid.to_bytes().into(),
-                name: link.name,
-                tsize: link.size,
+                hash: Some(link.cid.to_bytes().into()),
+                name: Some(link.name).filter(|name| !name.is_empty()),
+                tsize: Some(link.size).filter(|size| *size != 0),
             })
             .collect::<Vec<_>>();
         let proto = dag_pb::PbNode {
-            data: self.data.into(),
+            data: Some(self.data.into()).filter(|data: &Bytes| !data.is_empty()),
             links,
         };
+        encode_node(proto).into_boxed_slice()
+    }
+}
 
-        let mut res = Vec::with_capacity(proto.encoded_len());
-        proto
-            .encode(&mut res)
+/// Returns the CID of a link, which is required.
+fn link_cid(link: &dag_pb::PbLink) -> Result<Cid> {
+    match &link.hash {
+        Some(hash) => Ok(Cid::try_from(hash.as_ref())?),
+        None => Err(InvalidPbForm("link without a Hash".into()).into()),
+    }
+}
+
+/// Encodes a node with the links before the data, as the DAG-PB spec requires. Prost writes the
+/// fields in the order of their tags, which would put the data first and change the CID.
+fn encode_node(node: dag_pb::PbNode) -> Vec<u8> {
+    let links = dag_pb::PbNode {
+        links: node.links,
+        data: None,
+    };
+    let data = dag_pb::PbNode {
+        links: Vec::new(),
+        data: node.data,
+    };
+    let mut bytes = Vec::with_capacity(links.encoded_len() + data.encoded_len());
+    // Concatenated protobuf messages decode as one message with the fields of both.
+    for part in [links, data] {
+        part.encode(&mut bytes)
             .expect("there is no situation in which the protobuf message can be invalid");
-        res.into_boxed_slice()
+    }
+    bytes
+}
+
+/// Decodes a node into the IPLD form of the DAG-PB spec,
+/// `{"Data": bytes, "Links": [{"Hash": link, "Name": string, "Tsize": int}]}`. Absent optional
+/// fields are left out, so that encoding the result reproduces the bytes. Fields that aren't in
+/// the order of the spec are rejected, as they would encode to different bytes.
+pub(crate) fn decode_ipld(bytes: Bytes) -> Result<Ipld> {
+    let proto = dag_pb::PbNode::decode(bytes.clone())?;
+    check_node_fields(&bytes)?;
+    let mut links: Vec<Ipld> = Vec::with_capacity(proto.links.len());
+    for link in proto.links {
+        let mut map = IpldMap::new();
+        map.insert("Hash".to_string(), link_cid(&link)?.into());
+        if let Some(name) = link.name {
+            map.insert("Name".to_string(), name.into());
+        }
+        if let Some(size) = link.tsize {
+            map.insert("Tsize".to_string(), size.into());
+        }
+        links.push(map.into());
+    }
+    let mut map = IpldMap::new();
+    if let Some(data) = proto.data {
+        map.insert("Data".to_string(), data.to_vec().into());
+    }
+    map.insert("Links".to_string(), links.into());
+    Ok(map.into())
+}
+
+/// Checks that an encoded node only has the fields of the spec, with all links before the data.
+fn check_node_fields(mut bytes: &[u8]) -> Result<()> {
+    let mut data = false;
+    while !bytes.is_empty() {
+        match read_field(&mut bytes)? {
+            (2, _) if data => return Err(InvalidPbForm("Links after Data".into()).into()),
+            (2, link) => check_link_fields(link)?,
+            (1, _) if data => return Err(InvalidPbForm("repeated Data".into()).into()),
+            (1, _) => data = true,
+            (tag, _) => return Err(InvalidPbForm(format!("unexpected field {}", tag)).into()),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that an encoded link only has the fields of the spec, each at most once and in the
+/// order Hash, Name, Tsize.
+fn check_link_fields(mut bytes: &[u8]) -> Result<()> {
+    let mut prev = 0;
+    while !bytes.is_empty() {
+        let (tag, _) = read_field(&mut bytes)?;
+        if tag > 3 {
+            return Err(InvalidPbForm(format!("unexpected link field {}", tag)).into());
+        }
+        if tag <= prev {
+            return Err(InvalidPbForm("link fields out of order".into()).into());
+        }
+        prev = tag;
+    }
+    Ok(())
+}
+
+/// Reads the tag and the payload of a protobuf field. The payload of a varint field is empty.
+fn read_field<'a>(bytes: &mut &'a [u8]) -> Result<(u32, &'a [u8])> {
+    let (tag, wire_type) = prost::encoding::decode_key(bytes)?;
+    let len = match wire_type {
+        WireType::Varint => {
+            prost::encoding::decode_varint(bytes)?;
+            return Ok((tag, &[]));
+        }
+        WireType::LengthDelimited => prost::encoding::decode_varint(bytes)?,
+        _ => return Err(InvalidPbForm(format!("unexpected field {}", tag)).into()),
+    };
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|len| *len <= bytes.len())
+        .ok_or_else(|| InvalidPbForm(format!("truncated field {}", tag)))?;
+    let (value, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok((tag, value))
+}
+
+/// Encodes Ipld of the form returned by [`decode_ipld`].
+pub(crate) fn encode_ipld(ipld: &Ipld) -> Result<Vec<u8>> {
+    let node = as_map(ipld, &["Data", "Links"])?;
+    let data = match node.get("Data") {
+        Some(Ipld::Bytes(data)) => Some(data.clone().into()),
+        Some(other) => return Err(TypeError::new(TypeErrorType::Bytes, other.clone()).into()),
+        None => None,
+    };
+    let links = match node.get("Links") {
+        Some(Ipld::List(links)) => links,
+        Some(other) => return Err(TypeError::new(TypeErrorType::List, other.clone()).into()),
+        None => return Err(InvalidPbForm("node without Links".into()).into()),
+    };
+    let links = links
+        .iter()
+        .map(|link| {
+            let link = as_map(link, &["Hash", "Name", "Tsize"])?;
+            let hash = match link.get("Hash") {
+                Some(Ipld::Link(cid)) => cid.to_bytes().into(),
+                Some(other) => {
+                    return Err(TypeError::new(TypeErrorType::Link, other.clone()).into())
+                }
+                None => return Err(InvalidPbForm("link without a Hash".into()).into()),
+            };
+            let name = match link.get("Name") {
+                Some(Ipld::String(name)) => Some(name.clone()),
+                Some(other) => {
+                    return Err(TypeError::new(TypeErrorType::String, other.clone()).into())
+                }
+                None => None,
+            };
+            let tsize = match link.get("Tsize") {
+                Some(Ipld::Integer(size)) => Some(
+                    u64::try_from(*size)
+                        .map_err(|_| InvalidPbForm(format!("Tsize {} out of range", size)))?,
+                ),
+                Some(other) => {
+                    return Err(TypeError::new(TypeErrorType::Integer, other.clone()).into())
+                }
+                None => None,
+            };
+            Ok(dag_pb::PbLink {
+                hash: Some(hash),
+                name,
+                tsize,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(encode_node(dag_pb::PbNode { links, data }))
+}
+
+/// Returns the map of a node or link, which may only contain the given keys.
+fn as_map<'a>(ipld: &'a Ipld, keys: &[&str]) -> Result<&'a IpldMap> {
+    match ipld {
+        Ipld::Map(map) => {
+            if let Some(key) = map.keys().find(|key| !keys.contains(&key.as_str())) {
+                return Err(InvalidPbForm(format!("unexpected key `{}`", key)).into());
+            }
+            Ok(map)
+        }
+        other => Err(TypeError::new(TypeErrorType::Map, other.clone()).into()),
     }
 }
 
 impl From<PbNode> for Ipld {
     fn from(node: PbNode) -> Self {
-        let mut map = BTreeMap::<String, Ipld>::new();
+        let mut map = IpldMap::new();
         let links = node
             .links
             .into_iter()
@@ -92,7 +263,7 @@ impl From<PbNode> for Ipld {
 
 impl From<PbLink> for Ipld {
     fn from(link: PbLink) -> Self {
-        let mut map = BTreeMap::<String, Ipld>::new();
+        let mut map = IpldMap::new();
         map.insert("Hash".to_string(), link.cid.into());
         map.insert("Name".to_string(), link.name.into());
         map.insert("Tsize".to_string(), link.size.into());
@@ -100,6 +271,8 @@ impl From<PbLink> for Ipld {
     }
 }
 
+/// Accepts the form returned by decoding `Ipld`, where `Data` may be absent. An absent `Data` is
+/// empty.
 impl TryFrom<&Ipld> for PbNode {
     type Error = TypeError;
 
@@ -112,15 +285,17 @@ impl TryFrom<&Ipld> for PbNode {
         } else {
             return Err(TypeError::new(TypeErrorType::List, ipld));
         };
-        let data = if let Ipld::Bytes(data) = ipld.get("Data")? {
-            data.clone().into_boxed_slice()
-        } else {
-            return Err(TypeError::new(TypeErrorType::Bytes, ipld));
+        let data = match ipld.get("Data") {
+            Ok(Ipld::Bytes(data)) => data.clone().into_boxed_slice(),
+            Ok(_) => return Err(TypeError::new(TypeErrorType::Bytes, ipld)),
+            Err(_) => Box::default(),
         };
         Ok(PbNode { links, data })
     }
 }
 
+/// `Name` and `Tsize` may be absent, like in the form returned by decoding `Ipld`. An absent
+/// `Name` is empty and an absent `Tsize` is zero.
 impl TryFrom<&Ipld> for PbLink {
     type Error = TypeError;
 
@@ -130,15 +305,15 @@ impl TryFrom<&Ipld> for PbLink {
         } else {
             return Err(TypeError::new(TypeErrorType::Link, ipld));
         };
-        let name = if let Ipld::String(name) = ipld.get("Name")? {
-            name.clone()
-        } else {
-            return Err(TypeError::new(TypeErrorType::String, ipld));
+        let name = match ipld.get("Name") {
+            Ok(Ipld::String(name)) => name.clone(),
+            Ok(_) => return Err(TypeError::new(TypeErrorType::String, ipld)),
+            Err(_) => String::new(),
         };
-        let size = if let Ipld::Integer(size) = ipld.get("Tsize")? {
-            *size as u64
-        } else {
-            return Err(TypeError::new(TypeErrorType::Integer, ipld));
+        let size = match ipld.get("Tsize") {
+            Ok(Ipld::Integer(size)) => *size as u64,
+            Ok(_) => return Err(TypeError::new(TypeErrorType::Integer, ipld)),
+            Err(_) => 0,
         };
         Ok(PbLink { cid, name, size })
     }
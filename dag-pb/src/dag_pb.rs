@@ -2,14 +2,14 @@
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PbLink {
     /// binary CID (with no multibase prefix) of the target object
-    #[prost(bytes = "bytes", tag = "1")]
-    pub hash: ::prost::bytes::Bytes,
+    #[prost(bytes = "bytes", optional, tag = "1")]
+    pub hash: ::core::option::Option<::prost::bytes::Bytes>,
     /// UTF-8 string name
-    #[prost(string, tag = "2")]
-    pub name: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "2")]
+    pub name: ::core::option::Option<::prost::alloc::string::String>,
     /// cumulative size of target object
-    #[prost(uint64, tag = "3")]
-    pub tsize: u64,
+    #[prost(uint64, optional, tag = "3")]
+    pub tsize: ::core::option::Option<u64>,
 }
 /// An IPFS MerkleDAG Node
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -18,6 +18,6 @@ pub struct PbNode {
     #[prost(message, repeated, tag = "2")]
     pub links: ::prost::alloc::vec::Vec<PbLink>,
     /// opaque user data
-    #[prost(bytes = "bytes", tag = "1")]
-    pub data: ::prost::bytes::Bytes,
+    #[prost(bytes = "bytes", optional, tag = "1")]
+    pub data: ::core::option::Option<::prost::bytes::Bytes>,
 }
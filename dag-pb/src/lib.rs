@@ -3,10 +3,10 @@
 #![deny(warnings)]
 #![allow(clippy::derive_partial_eq_without_eq)]
 
-pub use crate::codec::{PbLink, PbNode};
-use core::convert::{TryFrom, TryInto};
+pub use crate::codec::{InvalidPbForm, PbLink, PbNode};
+use core::convert::TryFrom;
 use libipld_core::cid::Cid;
-use libipld_core::codec::{Codec, Decode, Encode, References};
+use libipld_core::codec::{Codec, Decode, Encode, FixedCodec, Multicodec, References};
 use libipld_core::error::{Result, UnsupportedCodec};
 use libipld_core::ipld::Ipld;
 use prost::bytes::Bytes;
@@ -14,6 +14,8 @@ use std::io::{Read, Seek, Write};
 
 mod codec;
 mod dag_pb;
+pub mod unixfs;
+mod unixfs_pb;
 
 /// Protobuf codec.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -21,9 +23,13 @@ pub struct DagPbCodec;
 
 impl Codec for DagPbCodec {}
 
+impl FixedCodec for DagPbCodec {
+    const CODE: u64 = Multicodec::DagPb.code();
+}
+
 impl From<DagPbCodec> for u64 {
     fn from(_: DagPbCodec) -> Self {
-        0x70
+        DagPbCodec::CODE
     }
 }
 
@@ -37,9 +43,7 @@ impl TryFrom<u64> for DagPbCodec {
 
 impl Encode<DagPbCodec> for Ipld {
     fn encode<W: Write>(&self, _: DagPbCodec, w: &mut W) -> Result<()> {
-        let pb_node: PbNode = self.try_into()?;
-        let bytes = pb_node.into_bytes();
-        w.write_all(&bytes)?;
+        w.write_all(&codec::encode_ipld(self)?)?;
         Ok(())
     }
 }
@@ -48,7 +52,7 @@ impl Decode<DagPbCodec> for Ipld {
     fn decode<R: Read + Seek>(_: DagPbCodec, r: &mut R) -> Result<Self> {
         let mut bytes = Vec::new();
         r.read_to_end(&mut bytes)?;
-        Ok(PbNode::from_bytes(Bytes::from(bytes))?.into())
+        codec::decode_ipld(Bytes::from(bytes))
     }
 }
 
@@ -68,20 +72,21 @@ impl References<DagPbCodec> for Ipld {
 mod tests {
     use super::*;
     use libipld_core::cid::Cid;
+    use libipld_core::ipld::IpldMap;
     use libipld_core::multihash::{Code, MultihashDigest};
-    use std::collections::BTreeMap;
+    use libipld_macro::ipld;
 
     #[test]
     fn test_encode_decode() {
         let digest = Code::Blake3_256.digest(&b"cid"[..]);
         let cid = Cid::new_v1(0x55, digest);
-        let mut pb_link = BTreeMap::<String, Ipld>::new();
+        let mut pb_link = IpldMap::new();
         pb_link.insert("Hash".to_string(), cid.into());
         pb_link.insert("Name".to_string(), "block".to_string().into());
         pb_link.insert("Tsize".to_string(), 13.into());
 
         let links: Vec<Ipld> = vec![pb_link.into()];
-        let mut pb_node = BTreeMap::<String, Ipld>::new();
+        let mut pb_node = IpldMap::new();
         pb_node.insert("Data".to_string(), b"Here is some data\n".to_vec().into());
         pb_node.insert("Links".to_string(), links.into());
         let data: Ipld = pb_node.into();
@@ -90,4 +95,170 @@ mod tests {
         let data2 = DagPbCodec.decode(&bytes).unwrap();
         assert_eq!(data, data2);
     }
+
+    /// The empty UnixFS directory.
+    const EMPTY_DIR: &[u8] = &[0x0a, 0x02, 0x08, 0x01];
+    /// The empty UnixFS file.
+    const EMPTY_FILE: &[u8] = &[0x0a, 0x04, 0x08, 0x02, 0x18, 0x00];
+
+    fn cid_v0(bytes: &[u8]) -> Cid {
+        Cid::new_v0(Code::Sha2_256.digest(bytes)).unwrap()
+    }
+
+    #[test]
+    fn test_unixfs_fixtures() {
+        let cases = [
+            (EMPTY_DIR, "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn"),
+            (EMPTY_FILE, "QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH"),
+        ];
+        for (bytes, expected) in cases {
+            let ipld: Ipld = DagPbCodec.decode(bytes).unwrap();
+            assert_eq!(ipld.get("Links").unwrap(), &Ipld::List(vec![]));
+            assert_eq!(DagPbCodec.encode(&ipld).unwrap(), bytes);
+            assert_eq!(cid_v0(bytes).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_links_before_data() {
+        let dir = cid_v0(EMPTY_DIR);
+        let file = cid_v0(EMPTY_FILE);
+        let ipld = ipld!({
+            "Data": vec![0x08u8, 0x01],
+            "Links": [
+                { "Hash": dir, "Name": "dir", "Tsize": EMPTY_DIR.len() },
+                { "Hash": file, "Name": "file", "Tsize": EMPTY_FILE.len() },
+            ],
+        });
+        let bytes = DagPbCodec.encode(&ipld).unwrap();
+
+        let mut expected = Vec::new();
+        for (cid, name, size) in [(dir, "dir", 4), (file, "file", 6)] {
+            let cid = cid.to_bytes();
+            expected.extend([
+                0x12,
+                (cid.len() + name.len() + 6) as u8,
+                0x0a,
+                cid.len() as u8,
+            ]);
+            expected.extend(cid);
+            expected.extend([0x12, name.len() as u8]);
+            expected.extend(name.as_bytes());
+            expected.extend([0x18, size]);
+        }
+        expected.extend([0x0a, 0x02, 0x08, 0x01]);
+        assert_eq!(bytes, expected);
+
+        let decoded: Ipld = DagPbCodec.decode(&bytes).unwrap();
+        assert_eq!(decoded, ipld);
+        let hash = decoded.get("Links").unwrap().get(1).unwrap().get("Hash");
+        assert_eq!(hash.unwrap(), &Ipld::Link(file));
+        assert_eq!(
+            PbNode::from_bytes(&bytes[..]).unwrap().into_bytes(),
+            bytes.into()
+        );
+    }
+
+    #[test]
+    fn test_optional_fields() {
+        let cid = cid_v0(EMPTY_DIR);
+        let ipld = ipld!({ "Links": [{ "Hash": cid }] });
+        let bytes = DagPbCodec.encode(&ipld).unwrap();
+        let decoded: Ipld = DagPbCodec.decode(&bytes).unwrap();
+        assert_eq!(decoded, ipld);
+
+        // Present but empty fields are kept.
+        let ipld =
+            ipld!({ "Data": Vec::<u8>::new(), "Links": [{ "Hash": cid, "Name": "", "Tsize": 0 }] });
+        let bytes = DagPbCodec.encode(&ipld).unwrap();
+        let decoded: Ipld = DagPbCodec.decode(&bytes).unwrap();
+        assert_eq!(decoded, ipld);
+    }
+
+    #[test]
+    fn test_try_from_optional_fields() {
+        let cid = cid_v0(EMPTY_DIR);
+        let node = PbNode::try_from(&ipld!({ "Links": [{ "Hash": cid }] })).unwrap();
+        assert!(node.data.is_empty());
+        assert_eq!(node.links[0].name, "");
+        assert_eq!(node.links[0].size, 0);
+        PbNode::try_from(&ipld!({ "Data": Vec::<u8>::new() }))
+            .expect_err("should have failed on a node without links");
+        PbLink::try_from(&ipld!({ "Name": "a" }))
+            .expect_err("should have failed on a link without a hash");
+    }
+
+    #[test]
+    fn test_non_canonical_order() {
+        let cid = cid_v0(EMPTY_DIR).to_bytes();
+        let mut link = vec![0x0a, cid.len() as u8];
+        link.extend(&cid);
+        link.extend([0x12, 0x01, b'a']);
+
+        // Data before links.
+        let mut bytes = vec![0x0a, 0x02, 0x08, 0x01, 0x12, link.len() as u8];
+        bytes.extend(&link);
+        PbNode::from_bytes(&bytes[..]).unwrap();
+        DagPbCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed on data before links")
+            .downcast::<InvalidPbForm>()
+            .unwrap();
+
+        // Data twice.
+        let bytes = [0x0a, 0x00, 0x0a, 0x00];
+        DagPbCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed on repeated data")
+            .downcast::<InvalidPbForm>()
+            .unwrap();
+
+        // Name before hash.
+        let mut swapped = vec![0x12, 0x01, b'a', 0x0a, cid.len() as u8];
+        swapped.extend(&cid);
+        let mut bytes = vec![0x12, swapped.len() as u8];
+        bytes.extend(&swapped);
+        DagPbCodec
+            .decode::<Ipld>(&bytes)
+            .expect_err("should have failed on link fields out of order")
+            .downcast::<InvalidPbForm>()
+            .unwrap();
+
+        // An unknown field.
+        DagPbCodec
+            .decode::<Ipld>(&[0x18, 0x01])
+            .expect_err("should have failed on an unknown field")
+            .downcast::<InvalidPbForm>()
+            .unwrap();
+
+        // The canonical order still decodes.
+        let mut bytes = vec![0x12, link.len() as u8];
+        bytes.extend(&link);
+        bytes.extend([0x0a, 0x02, 0x08, 0x01]);
+        let ipld: Ipld = DagPbCodec.decode(&bytes).unwrap();
+        assert_eq!(DagPbCodec.encode(&ipld).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_form() {
+        let cid = cid_v0(EMPTY_DIR);
+        let invalid = [
+            ipld!({ "Data": Vec::<u8>::new() }),
+            ipld!({ "Links": [], "Extra": 1 }),
+            ipld!({ "Links": [{ "Name": "a" }] }),
+            ipld!({ "Links": [{ "Hash": cid, "Tsize": -1 }] }),
+        ];
+        for ipld in &invalid {
+            DagPbCodec
+                .encode(ipld)
+                .expect_err("should have failed on an invalid form")
+                .downcast::<InvalidPbForm>()
+                .unwrap();
+        }
+        DagPbCodec
+            .encode(&ipld!({ "Links": [{ "Hash": "not a link" }] }))
+            .expect_err("should have failed on a hash that isn't a link")
+            .downcast::<libipld_core::error::TypeError>()
+            .unwrap();
+    }
 }
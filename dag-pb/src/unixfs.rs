@@ -0,0 +1,371 @@
+//! UnixFS, the format of files and directories in IPFS.
+//!
+//! UnixFS nodes are DAG-PB nodes whose `Data` field holds a UnixFS message, see
+//! <https://github.com/ipfs/specs/blob/main/UNIXFS.md>. Files may also be split into raw leaves,
+//! which hold nothing but the file contents.
+use crate::codec::PbNode;
+use crate::unixfs_pb::{self, data::DataType};
+use libipld_core::cid::Cid;
+use libipld_core::codec::Multicodec;
+use libipld_core::error::{Result, UnsupportedCodec};
+use prost::Message;
+use thiserror::Error;
+
+/// The type of a UnixFS node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnixFsType {
+    /// Raw file contents, an alternative to raw leaves.
+    Raw,
+    /// A directory, its entries are the links of the node.
+    Directory,
+    /// A file, its contents are the data of the node followed by the contents of its links.
+    File,
+    /// Metadata of another node.
+    Metadata,
+    /// A symbolic link, its target is the data of the node.
+    Symlink,
+    /// A shard of a directory that is split into a hash array mapped trie.
+    HamtShard,
+}
+
+impl From<DataType> for UnixFsType {
+    fn from(kind: DataType) -> Self {
+        match kind {
+            DataType::Raw => Self::Raw,
+            DataType::Directory => Self::Directory,
+            DataType::File => Self::File,
+            DataType::Metadata => Self::Metadata,
+            DataType::Symlink => Self::Symlink,
+            DataType::HamtShard => Self::HamtShard,
+        }
+    }
+}
+
+/// The UnixFS data of a DAG-PB node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnixFsData {
+    /// The type of the node.
+    pub kind: UnixFsType,
+    /// File contents or the target of a symbolic link.
+    pub data: Vec<u8>,
+    /// The size of the whole file.
+    pub filesize: Option<u64>,
+    /// The sizes of the file contents of each link.
+    pub blocksizes: Vec<u64>,
+    /// The hash function of a HAMT shard.
+    pub hash_type: Option<u64>,
+    /// The number of buckets of a HAMT shard.
+    pub fanout: Option<u64>,
+    /// The unix permissions.
+    pub mode: Option<u32>,
+}
+
+/// The type of a UnixFS node is unknown.
+#[derive(Debug, Error)]
+#[error("Unknown UnixFS type {0}.")]
+pub struct UnknownUnixFsType(pub i32);
+
+/// The UnixFS node has the wrong type.
+#[derive(Debug, Error)]
+#[error("Expected a UnixFS {expected:?} but found a {found:?}.")]
+pub struct UnexpectedUnixFsType {
+    /// The expected type.
+    pub expected: UnixFsType,
+    /// The actual type.
+    pub found: UnixFsType,
+}
+
+/// Parses the UnixFS data in the `Data` field of a DAG-PB node.
+pub fn parse_unixfs(data: &[u8]) -> Result<UnixFsData> {
+    let proto = unixfs_pb::Data::decode(data)?;
+    let kind = DataType::from_i32(proto.r#type).ok_or(UnknownUnixFsType(proto.r#type))?;
+    Ok(UnixFsData {
+        kind: kind.into(),
+        data: proto.data.unwrap_or_default(),
+        filesize: proto.filesize,
+        blocksizes: proto.blocksizes,
+        hash_type: proto.hash_type,
+        fanout: proto.fanout,
+        mode: proto.mode,
+    })
+}
+
+/// Parses an encoded DAG-PB node and its UnixFS data.
+fn parse_node(bytes: &[u8]) -> Result<(PbNode, UnixFsData)> {
+    let node = PbNode::from_bytes(bytes)?;
+    let data = parse_unixfs(&node.data)?;
+    Ok((node, data))
+}
+
+/// Returns the contents of a file.
+///
+/// `get` returns the encoded block of a CID. The blocks of the file are either DAG-PB nodes of
+/// type file or raw, or raw leaves. Verifying that a block matches its CID is up to `get`.
+pub fn cat<F>(root: &Cid, mut get: F) -> Result<Vec<u8>>
+where
+    F: FnMut(&Cid) -> Result<Vec<u8>>,
+{
+    let mut contents = Vec::new();
+    // The links still to visit, in reverse order.
+    let mut stack = vec![*root];
+    while let Some(cid) = stack.pop() {
+        let bytes = get(&cid)?;
+        match Multicodec::try_from(cid.codec()) {
+            Ok(Multicodec::Raw) => contents.extend(bytes),
+            Ok(Multicodec::DagPb) => {
+                let (node, data) = parse_node(&bytes)?;
+                if data.kind != UnixFsType::File && data.kind != UnixFsType::Raw {
+                    return Err(UnexpectedUnixFsType {
+                        expected: UnixFsType::File,
+                        found: data.kind,
+                    }
+                    .into());
+                }
+                contents.extend(data.data);
+                stack.extend(node.links.iter().rev().map(|link| link.cid));
+            }
+            _ => return Err(UnsupportedCodec(cid.codec()).into()),
+        }
+    }
+    Ok(contents)
+}
+
+/// Returns the entries of a directory, given its encoded DAG-PB node.
+///
+/// Directories that are split into HAMT shards aren't supported.
+pub fn ls(bytes: &[u8]) -> Result<Vec<crate::PbLink>> {
+    let (node, data) = parse_node(bytes)?;
+    if data.kind != UnixFsType::Directory {
+        return Err(UnexpectedUnixFsType {
+            expected: UnixFsType::Directory,
+            found: data.kind,
+        }
+        .into());
+    }
+    Ok(node.links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DagPbCodec, PbLink};
+    use libipld_core::codec::Codec;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use std::collections::HashMap;
+
+    fn unixfs(kind: DataType, data: &[u8], blocksizes: Vec<u64>) -> Vec<u8> {
+        unixfs_pb::Data {
+            r#type: kind as i32,
+            data: Some(data.to_vec()).filter(|data| !data.is_empty()),
+            filesize: Some(data.len() as u64 + blocksizes.iter().sum::<u64>())
+                .filter(|_| kind == DataType::File),
+            blocksizes,
+            ..Default::default()
+        }
+        .encode_to_vec()
+    }
+
+    fn node(data: Vec<u8>, links: Vec<(Cid, &str, u64)>) -> Vec<u8> {
+        let links = links
+            .into_iter()
+            .map(|(cid, name, size)| PbLink {
+                cid,
+                name: name.into(),
+                size,
+            })
+            .collect();
+        PbNode {
+            links,
+            data: data.into(),
+        }
+        .into_bytes()
+        .into()
+    }
+
+    #[derive(Default)]
+    struct Blocks(HashMap<Cid, Vec<u8>>);
+
+    impl Blocks {
+        fn insert(&mut self, codec: Multicodec, bytes: Vec<u8>) -> Cid {
+            let digest = Code::Sha2_256.digest(&bytes);
+            let cid = if codec == Multicodec::DagPb {
+                Cid::new_v0(digest).unwrap()
+            } else {
+                Cid::new_v1(codec.code(), digest)
+            };
+            self.0.insert(cid, bytes);
+            cid
+        }
+
+        fn get(&self, cid: &Cid) -> Result<Vec<u8>> {
+            Ok(self.0[cid].clone())
+        }
+    }
+
+    #[test]
+    fn test_hello_world() {
+        // `echo "hello world" | ipfs add`
+        let bytes = node(unixfs(DataType::File, b"hello world\n", vec![]), vec![]);
+        let mut blocks = Blocks::default();
+        let cid = blocks.insert(Multicodec::DagPb, bytes.clone());
+        assert_eq!(
+            cid.to_string(),
+            "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o"
+        );
+
+        let data = parse_unixfs(&PbNode::from_bytes(&bytes[..]).unwrap().data).unwrap();
+        assert_eq!(data.kind, UnixFsType::File);
+        assert_eq!(data.data, b"hello world\n");
+        assert_eq!(data.filesize, Some(12));
+        assert_eq!(cat(&cid, |cid| blocks.get(cid)).unwrap(), b"hello world\n");
+    }
+
+    #[test]
+    fn test_chunked_file() {
+        let mut blocks = Blocks::default();
+        let chunks: [&[u8]; 3] = [b"first chunk, ", b"second chunk, ", b"third chunk"];
+        let pb_leaf = node(unixfs(DataType::File, chunks[2], vec![]), vec![]);
+        let pb_leaf_len = pb_leaf.len() as u64;
+        let pb_leaf = blocks.insert(Multicodec::DagPb, pb_leaf);
+        let links = vec![
+            (blocks.insert(Multicodec::Raw, chunks[0].to_vec()), "", 13),
+            (blocks.insert(Multicodec::Raw, chunks[1].to_vec()), "", 14),
+            (pb_leaf, "", pb_leaf_len),
+        ];
+        let blocksizes = chunks.iter().map(|chunk| chunk.len() as u64).collect();
+        let root = blocks.insert(
+            Multicodec::DagPb,
+            node(unixfs(DataType::File, b"", blocksizes), links),
+        );
+        assert_eq!(
+            cat(&root, |cid| blocks.get(cid)).unwrap(),
+            b"first chunk, second chunk, third chunk"
+        );
+
+        let bytes = blocks.get(&root).unwrap();
+        let data = parse_unixfs(&PbNode::from_bytes(&bytes[..]).unwrap().data).unwrap();
+        assert_eq!(data.blocksizes, [13, 14, 11]);
+        assert_eq!(data.filesize, Some(38));
+        // The block is valid DAG-PB, the codec reads the same links.
+        let ipld: libipld_core::ipld::Ipld = DagPbCodec.decode(&bytes).unwrap();
+        let hash = ipld.get("Links").unwrap().get(2).unwrap().get("Hash");
+        assert_eq!(hash.unwrap(), &libipld_core::ipld::Ipld::Link(pb_leaf));
+    }
+
+    #[test]
+    fn test_directory() {
+        let mut blocks = Blocks::default();
+        // `ipfs object new unixfs-dir`
+        let empty = blocks.insert(
+            Multicodec::DagPb,
+            node(unixfs(DataType::Directory, b"", vec![]), vec![]),
+        );
+        assert_eq!(
+            empty.to_string(),
+            "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn"
+        );
+        assert!(ls(&blocks.get(&empty).unwrap()).unwrap().is_empty());
+
+        let file = node(unixfs(DataType::File, b"hello world\n", vec![]), vec![]);
+        let file_len = file.len() as u64;
+        let file = blocks.insert(Multicodec::DagPb, file);
+        let dir = node(
+            unixfs(DataType::Directory, b"", vec![]),
+            vec![(file, "hello.txt", file_len), (empty, "sub", 4)],
+        );
+        let entries = ls(&dir).unwrap();
+        let names: Vec<_> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["hello.txt", "sub"]);
+        assert_eq!(entries[0].cid, file);
+        assert_eq!(entries[0].size, file_len);
+        assert_eq!(
+            cat(&entries[0].cid, |cid| blocks.get(cid)).unwrap(),
+            b"hello world\n"
+        );
+
+        let dir = blocks.insert(Multicodec::DagPb, dir);
+        let err = cat(&dir, |cid| blocks.get(cid))
+            .expect_err("should have failed to cat a directory")
+            .downcast::<UnexpectedUnixFsType>()
+            .unwrap();
+        assert_eq!(err.found, UnixFsType::Directory);
+        ls(&blocks.get(&file).unwrap())
+            .expect_err("should have failed to list a file")
+            .downcast::<UnexpectedUnixFsType>()
+            .unwrap();
+    }
+
+    // The blocks of `ipfs add -r planets` from go-ipfs' sharness test t0040-add-and-cat, with
+    // `planets/mars.txt` containing "Hello Mars!\n" and `planets/venus.txt` "Hello Venus!\n".
+    const MARS: &[u8] = &[
+        0x0a, 0x12, 0x08, 0x02, 0x12, 0x0c, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x4d, 0x61, 0x72,
+        0x73, 0x21, 0x0a, 0x18, 0x0c,
+    ];
+    const VENUS: &[u8] = &[
+        0x0a, 0x13, 0x08, 0x02, 0x12, 0x0d, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x56, 0x65, 0x6e,
+        0x75, 0x73, 0x21, 0x0a, 0x18, 0x0d,
+    ];
+    const PLANETS: &[u8] = &[
+        0x12, 0x30, 0x0a, 0x22, 0x12, 0x20, 0x16, 0x98, 0x34, 0xd9, 0x8d, 0x92, 0xc6, 0xb8, 0x08,
+        0xbb, 0x7d, 0xb6, 0xce, 0xe6, 0x65, 0x7c, 0x02, 0xbf, 0x2a, 0xc7, 0x72, 0x47, 0xd7, 0xf3,
+        0x12, 0x55, 0x87, 0x09, 0x13, 0x2f, 0x18, 0x27, 0x12, 0x08, 0x6d, 0x61, 0x72, 0x73, 0x2e,
+        0x74, 0x78, 0x74, 0x18, 0x14, 0x12, 0x31, 0x0a, 0x22, 0x12, 0x20, 0x55, 0x55, 0x4f, 0x2d,
+        0x16, 0x3f, 0xee, 0x1a, 0x40, 0xcc, 0x69, 0x83, 0xcc, 0xce, 0x1b, 0x45, 0x17, 0xb6, 0x00,
+        0xc0, 0x2c, 0xc4, 0xa8, 0xfb, 0x3d, 0x82, 0x4a, 0xf8, 0x6b, 0xfd, 0x99, 0x8b, 0x12, 0x09,
+        0x76, 0x65, 0x6e, 0x75, 0x73, 0x2e, 0x74, 0x78, 0x74, 0x18, 0x15, 0x0a, 0x02, 0x08, 0x01,
+    ];
+
+    #[test]
+    fn test_go_ipfs_directory() {
+        let mut blocks = Blocks::default();
+        let mars = blocks.insert(Multicodec::DagPb, MARS.to_vec());
+        let venus = blocks.insert(Multicodec::DagPb, VENUS.to_vec());
+        let planets = blocks.insert(Multicodec::DagPb, PLANETS.to_vec());
+        // The CIDs go-ipfs prints.
+        assert_eq!(
+            mars.to_string(),
+            "QmPrrHqJzto9m7SyiRzarwkqPcCSsKR2EB1AyqJfe8L8tN"
+        );
+        assert_eq!(
+            venus.to_string(),
+            "QmU5kp3BH3B8tnWUU2Pikdb2maksBNkb92FHRr56hyghh4"
+        );
+        assert_eq!(
+            planets.to_string(),
+            "QmWSgS32xQEcXMeqd3YPJLrNBLSdsfYCep2U7CFkyrjXwY"
+        );
+
+        let entries = ls(PLANETS).unwrap();
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.cid, entry.size))
+            .collect();
+        assert_eq!(entries, [("mars.txt", mars, 20), ("venus.txt", venus, 21)]);
+        assert_eq!(cat(&mars, |cid| blocks.get(cid)).unwrap(), b"Hello Mars!\n");
+        assert_eq!(
+            cat(&venus, |cid| blocks.get(cid)).unwrap(),
+            b"Hello Venus!\n"
+        );
+
+        // Decoding and encoding reproduces the bytes go-ipfs wrote.
+        for bytes in [MARS, VENUS, PLANETS] {
+            let ipld: libipld_core::ipld::Ipld = DagPbCodec.decode(bytes).unwrap();
+            assert_eq!(DagPbCodec.encode(&ipld).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let err = parse_unixfs(&[0x08, 0x09])
+            .expect_err("should have failed on an unknown type")
+            .downcast::<UnknownUnixFsType>()
+            .unwrap();
+        assert_eq!(err.0, 9);
+
+        let cid = Cid::new_v1(Multicodec::DagCbor.code(), Code::Sha2_256.digest(b"cbor"));
+        cat(&cid, |_| Ok(vec![0xa0]))
+            .expect_err("should have failed on a dag-cbor block")
+            .downcast::<UnsupportedCodec>()
+            .unwrap();
+    }
+}
@@ -0,0 +1,31 @@
+/// The UnixFS data of a DAG-PB node
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Data {
+    #[prost(enumeration = "data::DataType", required, tag = "1")]
+    pub r#type: i32,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub data: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint64, optional, tag = "3")]
+    pub filesize: ::core::option::Option<u64>,
+    #[prost(uint64, repeated, packed = "false", tag = "4")]
+    pub blocksizes: ::prost::alloc::vec::Vec<u64>,
+    #[prost(uint64, optional, tag = "5")]
+    pub hash_type: ::core::option::Option<u64>,
+    #[prost(uint64, optional, tag = "6")]
+    pub fanout: ::core::option::Option<u64>,
+    #[prost(uint32, optional, tag = "7")]
+    pub mode: ::core::option::Option<u32>,
+}
+/// Nested message and enum types in `Data`.
+pub mod data {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum DataType {
+        Raw = 0,
+        Directory = 1,
+        File = 2,
+        Metadata = 3,
+        Symlink = 4,
+        HamtShard = 5,
+    }
+}
@@ -1,4 +1,5 @@
 //! `ipld!` macro.
+use libipld_core::cid::{self, Cid};
 /// Construct an `Ipld` from a literal.
 ///
 /// ```edition2018
@@ -16,12 +17,11 @@
 /// });
 /// ```
 ///
-/// Variables or expressions can be interpolated into the JSON literal. Any type
-/// interpolated into an array element or object value must implement Serde's
-/// `Serialize` trait, while any type interpolated into a object key must
-/// implement `Into<String>`. If the `Serialize` implementation of the
-/// interpolated type decides to fail, or if the interpolated type contains a
-/// map with non-string keys, the `json!` macro will panic.
+/// Variables or expressions can be interpolated into the literal. Any type
+/// interpolated into a list element or map value must implement `Into<Ipld>`,
+/// including `Ipld` itself, e.g. from a nested `ipld!`. Any type interpolated
+/// into a map key must implement `Into<String>`. Expressions that span several
+/// tokens can be wrapped in parentheses.
 ///
 /// ```edition2018
 /// # use libipld_macro::ipld;
@@ -34,11 +34,36 @@
 ///     "success": code == 200,
 ///     "payload": {
 ///         features[0]: features[1]
-///     }
+///     },
+///     (format!("{}s", "header")): ipld!([null, (1 + 2)]),
 /// });
 /// ```
 ///
-/// Trailing commas are allowed inside both arrays and objects.
+/// Map keys have to be strings.
+///
+/// ```compile_fail
+/// # use libipld_macro::ipld;
+/// #
+/// let value = ipld!({ (1 + 2): "three" });
+/// ```
+///
+/// Links can be written as `link` followed by a CID string literal. The CID is
+/// parsed when the expression is evaluated, which panics if it is invalid. Use
+/// [`try_link`] to handle invalid CIDs instead. Expressions of type `Cid` or
+/// `&Cid` are interpolated as links as well.
+///
+/// ```edition2018
+/// # use libipld_macro::ipld;
+/// # use libipld_core::ipld::Ipld;
+/// #
+/// let value = ipld!({
+///     "parent": link "QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL",
+///     "children": [link "bafyreibvjvcv745gig4mvqs4hctx4zfkono4rjejm2ta6gtyzkqxfjeily"],
+/// });
+/// assert!(matches!(value.get("parent"), Ok(Ipld::Link(_))));
+/// ```
+///
+/// Trailing commas are allowed inside both lists and maps.
 ///
 /// ```edition2018
 /// # use libipld_macro::ipld;
@@ -50,7 +75,47 @@
 ///     "comma -->",
 /// ]);
 /// ```
-pub use libipld_core::ipld::Ipld;
+pub use libipld_core::ipld::{Ipld, IpldMap};
+
+/// Parses a CID string into an `Ipld::Link`.
+///
+/// This is the fallible counterpart of the `link` syntax of [`ipld!`], and can be interpolated
+/// into it with `?`.
+///
+/// ```edition2018
+/// # use libipld_macro::{ipld, try_link};
+/// #
+/// fn node(cid: &str) -> Result<libipld_core::ipld::Ipld, libipld_core::cid::Error> {
+///     Ok(ipld!({ "child": (try_link(cid)?) }))
+/// }
+/// assert!(node("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL").is_ok());
+/// assert!(node("not a cid").is_err());
+/// ```
+pub fn try_link(cid: &str) -> Result<Ipld, cid::Error> {
+    Ok(Ipld::Link(Cid::try_from(cid)?))
+}
+
+/// Parses a CID string, panicking if it is invalid. Used by the `link` syntax of [`ipld!`].
+#[doc(hidden)]
+pub fn parse_link(cid: &str) -> Ipld {
+    try_link(cid).unwrap_or_else(|err| panic!("invalid CID `{}`: {}", cid, err))
+}
+
+/// Construct an `Ipld::Link` from a CID string, panicking if it is invalid, see [`ipld!`].
+///
+/// ```edition2018
+/// # use libipld_macro::link;
+/// # use libipld_core::ipld::Ipld;
+/// #
+/// let value = link!("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL");
+/// assert!(matches!(value, Ipld::Link(_)));
+/// ```
+#[macro_export]
+macro_rules! link {
+    ($cid:expr) => {
+        $crate::parse_link($cid)
+    };
+}
 
 #[macro_export(local_inner_macros)]
 macro_rules! ipld {
@@ -80,6 +145,11 @@ macro_rules! ipld_internal {
         ipld_internal_vec![$($elems),*]
     };
 
+    // Next element is a link.
+    (@array [$($elems:expr,)*] link $cid:literal $($rest:tt)*) => {
+        ipld_internal!(@array [$($elems,)* link!($cid)] $($rest)*)
+    };
+
     // Next element is `null`.
     (@array [$($elems:expr,)*] null $($rest:tt)*) => {
         ipld_internal!(@array [$($elems,)* ipld_internal!(null)] $($rest)*)
@@ -154,6 +224,11 @@ macro_rules! ipld_internal {
         let _ = $object.insert(($($key)+).into(), $value);
     };
 
+    // Next value is a link.
+    (@object $object:ident ($($key:tt)+) (: link $cid:literal $($rest:tt)*) $copy:tt) => {
+        ipld_internal!(@object $object [$($key)+] (link!($cid)) $($rest)*);
+    };
+
     // Next value is `null`.
     (@object $object:ident ($($key:tt)+) (: null $($rest:tt)*) $copy:tt) => {
         ipld_internal!(@object $object [$($key)+] (ipld_internal!(null)) $($rest)*);
@@ -235,6 +310,10 @@ macro_rules! ipld_internal {
         $crate::Ipld::Null
     };
 
+    (link $cid:literal) => {
+        link!($cid)
+    };
+
     (true) => {
         $crate::Ipld::Bool(true)
     };
@@ -252,12 +331,12 @@ macro_rules! ipld_internal {
     };
 
     ({}) => {
-        $crate::Ipld::Map(std::collections::BTreeMap::new())
+        $crate::Ipld::Map($crate::IpldMap::new())
     };
 
     ({ $($tt:tt)+ }) => {
         $crate::Ipld::Map({
-            let mut object = std::collections::BTreeMap::new();
+            let mut object = $crate::IpldMap::new();
             ipld_internal!(@object object () ($($tt)+) ($($tt)+));
             object
         })
@@ -314,4 +393,116 @@ mod tests {
         let mh = Code::Blake3_256.digest(&b"cid"[..]);
         let _: Ipld = ipld!(Cid::new_v1(0, mh));
     }
+
+    fn map<const N: usize>(entries: [(&str, Ipld); N]) -> Ipld {
+        Ipld::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_null() {
+        assert_eq!(ipld!(null), Ipld::Null);
+        assert_eq!(
+            ipld!([null, 1]),
+            Ipld::List(vec![Ipld::Null, Ipld::Integer(1)])
+        );
+        assert_eq!(ipld!({ "a": null }), map([("a", Ipld::Null)]));
+    }
+
+    #[test]
+    fn test_expressions() {
+        let n = 2;
+        let s = "s";
+        assert_eq!(ipld!((n + 1)), Ipld::Integer(3));
+        assert_eq!(
+            ipld!([(n * 2), s]),
+            Ipld::List(vec![Ipld::Integer(4), Ipld::from("s")])
+        );
+        assert_eq!(
+            ipld!({ "a": (n - 2), "b": vec![1u8] }),
+            map([("a", Ipld::Integer(0)), ("b", Ipld::Bytes(vec![1]))])
+        );
+        // Keys are any expression of a type that converts into a string.
+        assert_eq!(
+            ipld!({ (format!("{}{}", s, n)): 1, s: 2 }),
+            map([("s2", Ipld::Integer(1)), ("s", Ipld::Integer(2))])
+        );
+    }
+
+    #[test]
+    fn test_nested() {
+        let inner = ipld!([1, 2]);
+        assert_eq!(
+            ipld!({ "inner": inner.clone(), "again": ipld!({ "x": [true] }) }),
+            map([
+                ("inner", inner),
+                ("again", map([("x", Ipld::List(vec![Ipld::Bool(true)]))])),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        assert_eq!(ipld!([1,]), ipld!([1]));
+        assert_eq!(
+            ipld!([null, true, false, [], {},]),
+            ipld!([null, true, false, [], {}])
+        );
+        assert_eq!(ipld!({ "a": 1, }), ipld!({ "a": 1 }));
+        assert_eq!(
+            ipld!({ "a": [1, [2,],], "b": { "c": null, }, "d": (3), }),
+            ipld!({ "a": [1, [2]], "b": { "c": null }, "d": 3 })
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        const V0: &str = "QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL";
+        const V1: &str = "bafyreibvjvcv745gig4mvqs4hctx4zfkono4rjejm2ta6gtyzkqxfjeily";
+        let v0 = Cid::try_from(V0).unwrap();
+        let v1 = Cid::try_from(V1).unwrap();
+
+        assert_eq!(link!(V0), Ipld::Link(v0));
+        assert_eq!(
+            ipld!(link "bafyreibvjvcv745gig4mvqs4hctx4zfkono4rjejm2ta6gtyzkqxfjeily"),
+            Ipld::Link(v1)
+        );
+
+        // A small dag: a root linking to a directory that links to two files.
+        let root = ipld!({
+            "name": "root",
+            "dir": link "bafyreibvjvcv745gig4mvqs4hctx4zfkono4rjejm2ta6gtyzkqxfjeily",
+            "files": [
+                link "QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL",
+                { "file": link "QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL", "size": 3 },
+                link "bafyreibvjvcv745gig4mvqs4hctx4zfkono4rjejm2ta6gtyzkqxfjeily",
+            ],
+        });
+        let mut refs = Vec::new();
+        root.references(&mut refs);
+        assert_eq!(refs, vec![v1, v0, v0, v1]);
+
+        // Expressions of type `Cid` and `&Cid` are links too.
+        assert_eq!(
+            ipld!([v0, &v1]),
+            Ipld::List(vec![Ipld::Link(v0), Ipld::Link(v1)])
+        );
+
+        // A variable called `link` is still an expression.
+        let link = 1;
+        assert_eq!(ipld!({ "link": link }), ipld!({ "link": 1 }));
+
+        assert_eq!(try_link(V1).unwrap(), Ipld::Link(v1));
+        try_link("not a cid").expect_err("should have failed to parse an invalid CID");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid CID `not a cid`")]
+    fn test_invalid_link() {
+        let _ = ipld!([link "not a cid"]);
+    }
 }
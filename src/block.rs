@@ -1,7 +1,9 @@
 //! Block validation
 use crate::cid::Cid;
 use crate::codec::{Codec, Decode, Encode, References};
-use crate::error::{BlockTooLarge, InvalidMultihash, Result, UnsupportedMultihash};
+use crate::error::{
+    BlockTooLarge, InvalidMultihash, Result, UnsupportedCodec, UnsupportedMultihash,
+};
 use crate::ipld::Ipld;
 use crate::multihash::MultihashDigest;
 use crate::store::StoreParams;
@@ -69,8 +71,17 @@ impl<S> AsRef<[u8]> for Block<S> {
     }
 }
 
+/// The multihash code of the identity hash, its digest is the data itself.
+const IDENTITY: u64 = 0x00;
+
 // TODO: move to tiny_cid
 fn verify_cid<M: MultihashDigest<S>, const S: usize>(cid: &Cid, payload: &[u8]) -> Result<()> {
+    if cid.hash().code() == IDENTITY {
+        if cid.hash().digest() != payload {
+            return Err(InvalidMultihash(cid.hash().to_bytes()).into());
+        }
+        return Ok(());
+    }
     let mh = M::try_from(cid.hash().code())
         .map_err(|_| UnsupportedMultihash(cid.hash().code()))?
         .digest(payload);
@@ -98,6 +109,17 @@ impl<S: StoreParams> Block<S> {
         }
     }
 
+    /// Returns the block inlined in a cid with the identity hash, the data is the digest.
+    ///
+    /// Such blocks are never written to a store, so they can be read without one. Returns `None`
+    /// for cids with any other hash.
+    pub fn inline(cid: &Cid) -> Option<Self> {
+        if cid.hash().code() != IDENTITY {
+            return None;
+        }
+        Some(Self::new_unchecked(*cid, cid.hash().digest().to_vec()))
+    }
+
     /// Returns the cid.
     pub fn cid(&self) -> &Cid {
         &self.cid
@@ -113,14 +135,20 @@ impl<S: StoreParams> Block<S> {
         (self.cid, self.data)
     }
 
+    /// Hashes the data again and checks that it matches the cid, e.g. for a block created with
+    /// [`Block::new_unchecked`] from untrusted data.
+    pub fn validate(&self) -> Result<()> {
+        verify_cid::<S::Hashes, 64>(&self.cid, &self.data)
+    }
+
     /// Encode a block.`
-    pub fn encode<CE: Codec, T: Encode<CE> + ?Sized>(
+    pub fn encode<CE, T: Encode<CE> + ?Sized>(
         codec: CE,
         hcode: S::Hashes,
         payload: &T,
     ) -> Result<Self>
     where
-        CE: Into<S::Codecs>,
+        CE: Codec + Into<S::Codecs>,
     {
         debug_assert_eq!(
             Into::<u64>::into(codec),
@@ -162,14 +190,35 @@ impl<S: StoreParams> Block<S> {
     where
         S::Codecs: Into<CD>,
     {
-        debug_assert_eq!(
-            Into::<u64>::into(CD::try_from(self.cid.codec()).unwrap()),
-            Into::<u64>::into(S::Codecs::try_from(self.cid.codec()).unwrap()),
-        );
-        CD::try_from(self.cid.codec())?.decode(&self.data)
+        // Codecs convert from any code, decoding e.g. a raw block as dag-cbor has to be refused here.
+        let codec = CD::try_from(self.cid.codec())?;
+        if Into::<u64>::into(codec) != self.cid.codec() {
+            return Err(UnsupportedCodec(self.cid.codec()).into());
+        }
+        codec.decode(&self.data)
     }
 
     /// Returns the decoded ipld.
+    ///
+    /// The block is decoded with the codec its CID names, an unknown codec is an
+    /// [`UnsupportedCodec`] error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use libipld::block::Block;
+    /// use libipld::cbor::DagCborCodec;
+    /// use libipld::ipld::Ipld;
+    /// use libipld::multihash::Code;
+    /// use libipld::raw::RawCodec;
+    /// use libipld::store::DefaultParams;
+    ///
+    /// let raw = Block::<DefaultParams>::encode(RawCodec, Code::Sha2_256, &b"raw"[..])?;
+    /// let cbor = Block::<DefaultParams>::encode(DagCborCodec, Code::Sha2_256, "cbor")?;
+    /// assert_eq!(raw.ipld()?, Ipld::Bytes(b"raw".to_vec()));
+    /// assert_eq!(cbor.ipld()?, Ipld::String("cbor".into()));
+    /// # Ok::<(), libipld::error::Error>(())
+    /// ```
     pub fn ipld(&self) -> Result<Ipld>
     where
         Ipld: Decode<S::Codecs>,
@@ -190,11 +239,16 @@ impl<S: StoreParams> Block<S> {
 mod tests {
     use super::*;
     use crate::cbor::DagCborCodec;
+    use crate::codec::FixedCodec;
+    use crate::codec_impl::test_codes::{PRIVATE, UNREGISTERED};
     use crate::codec_impl::IpldCodec;
     use crate::ipld;
     use crate::ipld::Ipld;
-    use crate::multihash::Code;
+    use crate::multihash::{Code, MultihashDigest};
+    use crate::path::{resolve, DagPath};
+    use crate::raw::RawCodec;
     use crate::store::DefaultParams;
+    use crate::Multihash;
     use fnv::FnvHashSet;
 
     type IpldBlock = Block<DefaultParams>;
@@ -235,4 +289,238 @@ mod tests {
         let b1 = IpldBlock::encode(DagCborCodec, Code::Blake3_256, &42).unwrap();
         assert_eq!(b1.cid.codec(), 0x71);
     }
+
+    #[test]
+    fn test_encode_error() {
+        let payload = ipld!({ "big": Ipld::Integer(i128::MAX) });
+        IpldBlock::encode(IpldCodec::DagCbor, Code::Blake3_256, &payload)
+            .expect_err("should have failed to encode an integer out of range")
+            .downcast::<crate::cbor::error::NumberOutOfRange>()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_validate() {
+        let payload = ipld!({ "name": "block", "list": [1, 2, 3] });
+        let block = IpldBlock::encode(IpldCodec::DagCbor, Code::Sha2_256, &payload).unwrap();
+        // The same as encoding and hashing by hand.
+        let data = DagCborCodec.encode(&payload).unwrap();
+        assert_eq!(block.cid, Cid::new_v1(0x71, Code::Sha2_256.digest(&data)));
+        assert_eq!(block.data, data);
+        block.validate().unwrap();
+        assert_eq!(block.ipld().unwrap(), payload);
+
+        let (cid, mut data) = block.into_inner();
+        data.push(0);
+        IpldBlock::new_unchecked(cid, data.clone())
+            .validate()
+            .expect_err("should have failed to validate modified data")
+            .downcast::<crate::error::InvalidMultihash>()
+            .unwrap();
+        IpldBlock::new(cid, data).expect_err("should have failed to create a modified block");
+    }
+
+    #[test]
+    fn test_raw_leaf() {
+        let leaf = IpldBlock::encode(RawCodec, Code::Sha2_256, &b"leaf bytes"[..]).unwrap();
+        assert_eq!(leaf.cid.codec(), 0x55);
+        assert_eq!(leaf.data(), b"leaf bytes");
+        let parent = IpldBlock::encode(
+            DagCborCodec,
+            Code::Sha2_256,
+            &ipld!({ "chunks": [&leaf.cid] }),
+        )
+        .unwrap();
+
+        // Follow the link and decode with the codec named by the CID.
+        let cid = match parent
+            .ipld()
+            .unwrap()
+            .get("chunks")
+            .unwrap()
+            .get(0)
+            .unwrap()
+        {
+            Ipld::Link(cid) => *cid,
+            other => panic!("expected a link, got {:?}", other),
+        };
+        let block = IpldBlock::new(cid, leaf.data().to_vec()).unwrap();
+        assert_eq!(block.ipld().unwrap(), Ipld::Bytes(b"leaf bytes".to_vec()));
+        let mut refs = FnvHashSet::default();
+        block.references(&mut refs).unwrap();
+        assert!(refs.is_empty());
+
+        block
+            .decode::<DagCborCodec, Ipld>()
+            .expect_err("should have refused to decode raw bytes as dag-cbor")
+            .downcast::<crate::error::UnsupportedCodec>()
+            .unwrap();
+        IpldBlock::encode(RawCodec, Code::Sha2_256, &ipld!({ "not": "bytes" }))
+            .expect_err("should have failed to encode a map with the raw codec")
+            .downcast::<crate::error::TypeError>()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_codecs() {
+        let payload = ipld!({ "Links": [] });
+        let cbor = IpldBlock::encode(DagCborCodec, Code::Sha2_256, &payload).unwrap();
+        let json = IpldBlock::encode(IpldCodec::DagJson, Code::Sha2_256, &payload).unwrap();
+        let pb = IpldBlock::encode(IpldCodec::DagPb, Code::Sha2_256, &payload).unwrap();
+        assert_eq!(cbor.cid.codec(), DagCborCodec::CODE);
+        assert_eq!(json.cid.codec(), crate::json::DagJsonCodec::CODE);
+        assert_eq!(pb.cid.codec(), crate::pb::DagPbCodec::CODE);
+        assert_ne!(cbor.cid, json.cid);
+        assert_ne!(cbor.cid, pb.cid);
+        for block in [&cbor, &json, &pb] {
+            let block = IpldBlock::new(block.cid, block.data.clone()).unwrap();
+            assert_eq!(block.ipld().unwrap(), payload);
+        }
+
+        let bytes = ipld!(&b"bytes"[..]);
+        let raw = IpldBlock::encode(RawCodec, Code::Sha2_256, &bytes).unwrap();
+        let cbor = IpldBlock::encode(DagCborCodec, Code::Sha2_256, &bytes).unwrap();
+        assert_eq!(raw.cid.codec(), RawCodec::CODE);
+        assert_eq!(raw.cid.hash(), &Code::Sha2_256.digest(b"bytes"));
+        assert_ne!(raw.cid, cbor.cid);
+        assert_eq!(raw.ipld().unwrap(), bytes);
+        assert_eq!(cbor.ipld().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_traverse_mixed_codecs() {
+        let leaf = IpldBlock::encode(RawCodec, Code::Sha2_256, &b"leaf"[..]).unwrap();
+        let node = IpldBlock::encode(
+            IpldCodec::DagJson,
+            Code::Sha2_256,
+            &ipld!({ "name": "node", "leaf": &leaf.cid }),
+        )
+        .unwrap();
+        let root = IpldBlock::encode(
+            DagCborCodec,
+            Code::Sha2_256,
+            &ipld!({ "children": [&node.cid, &leaf.cid] }),
+        )
+        .unwrap();
+        let blocks: std::collections::HashMap<Cid, IpldBlock> = [leaf, node, root.clone()]
+            .into_iter()
+            .map(|b| (b.cid, b))
+            .collect();
+
+        // Every block is decoded with the codec its CID names.
+        let resolve_path = |path: DagPath| resolve(&path, |cid| blocks.get(cid).cloned());
+        let leaf = Ipld::Bytes(b"leaf".to_vec());
+        assert_eq!(
+            resolve_path(DagPath::new(&root.cid, "children/1")).unwrap(),
+            leaf
+        );
+        assert_eq!(
+            resolve_path(DagPath::new(&root.cid, "children/0/leaf")).unwrap(),
+            leaf
+        );
+        assert_eq!(
+            resolve_path(DagPath::new(&root.cid, "children/0/name")).unwrap(),
+            ipld!("node")
+        );
+
+        let cid = Cid::new_v1(UNREGISTERED, Code::Sha2_256.digest(b"unknown"));
+        let err = IpldBlock::new_unchecked(cid, b"unknown".to_vec())
+            .ipld()
+            .expect_err("should have failed on an unknown codec")
+            .downcast::<crate::error::UnsupportedCodec>()
+            .unwrap();
+        assert_eq!(err.0, UNREGISTERED);
+    }
+
+    /// A private codec, which prefixes DAG-CBOR with a magic byte.
+    struct PrivateCodec;
+
+    /// A block of the private codec without the magic byte.
+    #[derive(Debug, thiserror::Error)]
+    #[error("Missing magic byte.")]
+    struct MissingMagic;
+
+    impl crate::DynCodec for PrivateCodec {
+        fn encode_ipld(&self, ipld: &Ipld) -> Result<Vec<u8>> {
+            let mut bytes = b"P".to_vec();
+            DagCborCodec.encode_into(ipld, &mut bytes)?;
+            Ok(bytes)
+        }
+
+        fn decode_ipld(&self, bytes: &[u8]) -> Result<Ipld> {
+            match bytes.split_first() {
+                Some((b'P', bytes)) => DagCborCodec.decode(bytes),
+                _ => Err(MissingMagic.into()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_codec() {
+        crate::register_codec(PRIVATE, Box::new(PrivateCodec)).unwrap();
+        let codec = IpldCodec::try_from(PRIVATE).unwrap();
+
+        let private =
+            IpldBlock::encode(codec, Code::Sha2_256, &ipld!({ "secret": [1, 2, 3] })).unwrap();
+        assert_eq!(private.cid().codec(), PRIVATE);
+        assert_eq!(private.data()[0], b'P');
+        let root = IpldBlock::encode(
+            DagCborCodec,
+            Code::Sha2_256,
+            &ipld!({ "private": &private.cid }),
+        )
+        .unwrap();
+        let mut refs = FnvHashSet::default();
+        root.references(&mut refs).unwrap();
+        assert!(refs.contains(private.cid()));
+        let blocks: std::collections::HashMap<Cid, IpldBlock> = [root.clone(), private.clone()]
+            .into_iter()
+            .map(|b| (b.cid, b))
+            .collect();
+
+        // Follows the link into the private block.
+        let path = DagPath::new(&root.cid, "private/secret/2");
+        let ipld = resolve(&path, |cid| blocks.get(cid).cloned()).unwrap();
+        assert_eq!(ipld, Ipld::Integer(3));
+
+        let data = DagCborCodec.encode(&ipld!({ "secret": [] })).unwrap();
+        let cid = Cid::new_v1(PRIVATE, Code::Sha2_256.digest(&data));
+        IpldBlock::new(cid, data)
+            .unwrap()
+            .ipld()
+            .expect_err("should have failed on a block without the magic byte")
+            .downcast::<MissingMagic>()
+            .unwrap();
+
+        let cid = Cid::new_v1(UNREGISTERED, Code::Sha2_256.digest(b"unknown"));
+        IpldBlock::new_unchecked(cid, b"unknown".to_vec())
+            .ipld()
+            .expect_err("should have failed on an unknown codec")
+            .downcast::<UnsupportedCodec>()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_inline() {
+        let data = DagCborCodec.encode(&ipld!({ "name": "inline" })).unwrap();
+        let cid = Cid::new_v1(0x71, Multihash::wrap(0x00, &data).unwrap());
+        let child = IpldBlock::inline(&cid).unwrap();
+        assert_eq!(child.data(), &data[..]);
+        child.validate().unwrap();
+        IpldBlock::new(cid, data.clone()).unwrap();
+        IpldBlock::new(cid, b"other".to_vec())
+            .expect_err("should have failed on data that isn't the digest")
+            .downcast::<crate::error::InvalidMultihash>()
+            .unwrap();
+
+        // Only the parent is stored, the inline child is read from its cid.
+        let parent =
+            IpldBlock::encode(DagCborCodec, Code::Sha2_256, &ipld!({ "child": cid })).unwrap();
+        assert!(IpldBlock::inline(parent.cid()).is_none());
+        let blocks: std::collections::HashMap<Cid, IpldBlock> =
+            [(parent.cid, parent.clone())].into_iter().collect();
+        let path = DagPath::new(&parent.cid, "child/name");
+        let ipld = resolve(&path, |cid| blocks.get(cid).cloned()).unwrap();
+        assert_eq!(ipld, ipld!("inline"));
+    }
 }
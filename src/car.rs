@@ -0,0 +1,604 @@
+//! CAR, the content addressable archive format.
+//!
+//! A CAR v1 file starts with a header that names the roots of the archived dags, followed by the
+//! blocks. The header and every block are a section, prefixed by its length as an unsigned varint.
+//! The header is a DAG-CBOR map `{ "roots": [cid], "version": 1 }`, a block section is its cid
+//! followed by its data. See <https://ipld.io/specs/transport/car/carv1/>.
+//!
+//! [`CarReader`] and [`load_car`] read CAR files, [`CarWriter`] and [`export_car`] write them.
+use crate::block::Block;
+use crate::cbor::DagCborCodec;
+use crate::cid::Cid;
+use crate::codec::{Codec, References};
+use crate::error::{BlockNotFound, Result};
+use crate::ipld::Ipld;
+use crate::store::StoreParams;
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use thiserror::Error;
+
+/// The header of a CAR file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CarHeader {
+    /// The version of the format, always 1.
+    pub version: u64,
+    /// The roots of the archived dags.
+    pub roots: Vec<Cid>,
+}
+
+/// A varint is longer than 64 bits.
+#[derive(Debug, Error)]
+#[error("Invalid varint.")]
+pub struct InvalidVarint;
+
+/// The file ends in the middle of a section.
+#[derive(Debug, Error)]
+#[error("Truncated CAR section, expected {expected} bytes but read {read}.")]
+pub struct TruncatedSection {
+    /// The length of the section.
+    pub expected: u64,
+    /// The number of bytes read before the end of the file.
+    pub read: u64,
+}
+
+/// The header isn't a DAG-CBOR map with roots and a version.
+#[derive(Debug, Error)]
+#[error("Invalid CAR header: {0}.")]
+pub struct InvalidCarHeader(pub String);
+
+/// The version of the CAR file isn't 1.
+#[derive(Debug, Error)]
+#[error("Unsupported CAR version {0}.")]
+pub struct UnsupportedCarVersion(pub u64);
+
+/// Reads the blocks of a CAR v1 file.
+///
+/// Iterating yields the cid and data of each block in the order of the file. The data isn't
+/// checked against the cid, see [`load_car`] for that. Iteration stops after the first error.
+pub struct CarReader<R> {
+    reader: R,
+    header: CarHeader,
+    done: bool,
+}
+
+impl<R: Read> CarReader<R> {
+    /// Reads the header of a CAR file.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let bytes = read_section(&mut reader)?.ok_or(TruncatedSection {
+            expected: 1,
+            read: 0,
+        })?;
+        let header = decode_header(&bytes)?;
+        Ok(Self {
+            reader,
+            header,
+            done: false,
+        })
+    }
+
+    /// Returns the header.
+    pub fn header(&self) -> &CarHeader {
+        &self.header
+    }
+
+    fn read_block(&mut self) -> Result<Option<(Cid, Vec<u8>)>> {
+        let bytes = match read_section(&mut self.reader)? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        let mut data = &bytes[..];
+        let cid = Cid::read_bytes(&mut data)?;
+        Ok(Some((cid, data.to_vec())))
+    }
+}
+
+impl<R: Read> Iterator for CarReader<R> {
+    type Item = Result<(Cid, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let block = self.read_block().transpose();
+        self.done = !matches!(block, Some(Ok(_)));
+        block
+    }
+}
+
+/// Reads a CAR file into `blocks`, returns its roots.
+///
+/// With `verify` every block is hashed and checked against its cid, which fails on hash
+/// functions that `S` doesn't support.
+pub fn load_car<S, R, E>(reader: R, blocks: &mut E, verify: bool) -> Result<Vec<Cid>>
+where
+    S: StoreParams,
+    R: Read,
+    E: Extend<Block<S>>,
+{
+    let mut reader = CarReader::new(reader)?;
+    let roots = reader.header().roots.clone();
+    for block in &mut reader {
+        let (cid, data) = block?;
+        let block = if verify {
+            Block::new(cid, data)?
+        } else {
+            Block::new_unchecked(cid, data)
+        };
+        blocks.extend(Some(block));
+    }
+    Ok(roots)
+}
+
+/// Writes a CAR v1 file, the header first and then the blocks as they are written.
+pub struct CarWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> CarWriter<W> {
+    /// Writes the header with the given roots.
+    pub fn new(mut writer: W, roots: Vec<Cid>) -> Result<Self> {
+        let header = Ipld::Map(
+            [
+                (
+                    "roots".to_string(),
+                    Ipld::List(roots.into_iter().map(Ipld::Link).collect()),
+                ),
+                ("version".to_string(), Ipld::Integer(1)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let bytes = DagCborCodec.encode(&header)?;
+        write_varint(&mut writer, bytes.len() as u64)?;
+        writer.write_all(&bytes)?;
+        Ok(Self { writer })
+    }
+
+    /// Writes a block.
+    pub fn write(&mut self, cid: &Cid, data: &[u8]) -> Result<()> {
+        let cid = cid.to_bytes();
+        write_varint(&mut self.writer, (cid.len() + data.len()) as u64)?;
+        self.writer.write_all(&cid)?;
+        self.writer.write_all(data)?;
+        Ok(())
+    }
+
+    /// Returns the writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Writes the dag of `root` as a CAR file with that root, returns the writer.
+///
+/// `get` returns the block of a cid, blocks inlined in identity cids are read from the cid instead,
+/// see [`Block::inline`]. Blocks are written in depth first order, following the links of each
+/// block in the order they are encoded, and every block is written once. A missing block is a
+/// [`BlockNotFound`] error, unless `skip_missing` is set, in which case neither it nor its links
+/// are written.
+pub fn export_car<S, W, F>(root: &Cid, mut get: F, writer: W, skip_missing: bool) -> Result<W>
+where
+    S: StoreParams,
+    W: Write,
+    F: FnMut(&Cid) -> Option<Block<S>>,
+    Ipld: References<S::Codecs>,
+{
+    let mut car = CarWriter::new(writer, vec![*root])?;
+    let mut seen = HashSet::new();
+    // The links still to visit, in reverse order.
+    let mut stack = vec![*root];
+    while let Some(cid) = stack.pop() {
+        if !seen.insert(cid) {
+            continue;
+        }
+        let block = match Block::inline(&cid).or_else(|| get(&cid)) {
+            Some(block) => block,
+            None if skip_missing => continue,
+            None => return Err(BlockNotFound(cid).into()),
+        };
+        car.write(&cid, block.data())?;
+        let mut links = Vec::new();
+        block.references(&mut links)?;
+        stack.extend(links.into_iter().rev());
+    }
+    Ok(car.into_inner())
+}
+
+fn decode_header(bytes: &[u8]) -> Result<CarHeader> {
+    let header: Ipld = DagCborCodec.decode(bytes)?;
+    let version = match header.get("version") {
+        Ok(Ipld::Integer(version)) => {
+            u64::try_from(*version).map_err(|_| InvalidCarHeader("invalid version".into()))?
+        }
+        _ => return Err(InvalidCarHeader("expected a version".into()).into()),
+    };
+    if version != 1 {
+        return Err(UnsupportedCarVersion(version).into());
+    }
+    let roots = match header.get("roots") {
+        Ok(Ipld::List(roots)) => roots
+            .iter()
+            .map(|root| match root {
+                Ipld::Link(cid) => Ok(*cid),
+                _ => Err(InvalidCarHeader("expected the roots to be links".into())),
+            })
+            .collect::<core::result::Result<_, _>>()?,
+        _ => return Err(InvalidCarHeader("expected a list of roots".into()).into()),
+    };
+    Ok(CarHeader { version, roots })
+}
+
+/// Reads a varint, returns `None` at the end of the file.
+fn read_varint<R: Read>(reader: &mut R) -> Result<Option<u64>> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let mut byte = [0u8];
+        if reader.read(&mut byte)? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Err(TruncatedSection {
+                expected: i + 1,
+                read: i,
+            }
+            .into());
+        }
+        let bits = u64::from(byte[0] & 0x7f);
+        if i == 9 && bits > 1 {
+            return Err(InvalidVarint.into());
+        }
+        value |= bits << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(InvalidVarint.into())
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> Result<()> {
+    let mut bytes = Vec::with_capacity(10);
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Reads a length prefixed section, returns `None` at the end of the file.
+fn read_section<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let len = match read_varint(reader)? {
+        Some(len) => len,
+        None => return Ok(None),
+    };
+    // Doesn't allocate more than was read, whatever the length claims.
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(TruncatedSection {
+            expected: len,
+            read: bytes.len() as u64,
+        }
+        .into());
+    }
+    Ok(Some(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec_impl::IpldCodec;
+    use crate::error::InvalidMultihash;
+    use crate::ipld;
+    use crate::multihash::{Code, MultihashDigest};
+    use crate::path::{self, DagPath};
+    use crate::raw::RawCodec;
+    use crate::store::DefaultParams;
+    use std::collections::HashSet;
+
+    type IpldBlock = Block<DefaultParams>;
+
+    /// A root `{ "dir": <empty UnixFS directory>, "leaf": <raw>, "name": "car" }`.
+    const BASIC: &[u8] = include_bytes!("../tests/car/basic.car");
+
+    /// The `carv1-basic.car` fixture of the CAR v1 spec, generated by go-car: two DAG-CBOR roots, a
+    /// CIDv0 DAG-PB tree and raw leaves. Every block hashes to the cid the spec lists for it.
+    const GO_CAR: &[u8] = include_bytes!("../tests/car/carv1-basic.car");
+
+    fn root() -> Cid {
+        "bafyreiea7uc5ochxuihqyrmxmmmhfckkwkdpbu7wgrmtjiur2pddbfsrr4"
+            .parse()
+            .unwrap()
+    }
+
+    /// Resolves a path, following links into `blocks`.
+    fn resolve(blocks: &HashSet<IpldBlock>, path: DagPath) -> Result<Ipld> {
+        path::resolve(&path, |cid| blocks.get(cid).cloned())
+    }
+
+    #[test]
+    fn test_read() {
+        let reader = CarReader::new(BASIC).unwrap();
+        assert_eq!(
+            reader.header(),
+            &CarHeader {
+                version: 1,
+                roots: vec![root()],
+            }
+        );
+        let cids: Vec<_> = reader.map(|block| block.unwrap().0.to_string()).collect();
+        assert_eq!(
+            cids,
+            [
+                "bafyreiea7uc5ochxuihqyrmxmmmhfckkwkdpbu7wgrmtjiur2pddbfsrr4",
+                "bafkreierjdry57u7oxaazzovsy3orxt4irf4q4bkpjvcpstdtnmgaxxjt4",
+                "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_car() {
+        let mut blocks = HashSet::new();
+        let roots = load_car(BASIC, &mut blocks, true).unwrap();
+        assert_eq!(roots, [root()]);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(
+            resolve(&blocks, DagPath::new(&root(), "leaf")).unwrap(),
+            Ipld::Bytes(b"hello car\n".to_vec())
+        );
+        assert_eq!(
+            resolve(&blocks, DagPath::new(&root(), "dir/Data")).unwrap(),
+            Ipld::Bytes(vec![0x08, 0x01])
+        );
+    }
+
+    #[test]
+    fn test_read_go_car() {
+        let reader = CarReader::new(GO_CAR).unwrap();
+        let roots: Vec<_> = reader.header().roots.iter().map(Cid::to_string).collect();
+        assert_eq!(
+            roots,
+            [
+                "bafyreihyrpefhacm6kkp4ql6j6udakdit7g3dmkzfriqfykhjw6cad5lrm",
+                "bafyreidj5idub6mapiupjwjsyyxhyhedxycv4vihfsicm2vt46o7morwlm",
+            ]
+        );
+        let cids: Vec<_> = reader.map(|block| block.unwrap().0.to_string()).collect();
+        assert_eq!(
+            cids,
+            [
+                "bafyreihyrpefhacm6kkp4ql6j6udakdit7g3dmkzfriqfykhjw6cad5lrm",
+                "QmNX6Tffavsya4xgBi2VJQnSuqy9GsxongxZZ9uZBqp16d",
+                "bafkreifw7plhl6mofk6sfvhnfh64qmkq73oeqwl6sloru6rehaoujituke",
+                "QmWXZxVQ9yZfhQxLD35eDR8LiMRsYtHxYqTFCBbJoiJVys",
+                "bafkreiebzrnroamgos2adnbpgw5apo3z4iishhbdx77gldnbk57d4zdio4",
+                "QmdwjhxpxzcMsR3qUuj7vUL8pbA7MgR3GAxWi2GLHjsKCT",
+                "bafkreidbxzk2ryxwwtqxem4l3xyyjvw35yu4tcct4cqeqxwo47zhxgxqwq",
+                "bafyreidj5idub6mapiupjwjsyyxhyhedxycv4vihfsicm2vt46o7morwlm",
+            ]
+        );
+
+        let mut blocks = HashSet::new();
+        let roots = load_car(GO_CAR, &mut blocks, true).unwrap();
+        assert_eq!(blocks.len(), 8);
+        assert_eq!(
+            resolve(&blocks, DagPath::new(&roots[0], "name")).unwrap(),
+            Ipld::String("blip".into())
+        );
+        assert_eq!(
+            resolve(
+                &blocks,
+                DagPath::new(&roots[0], "link/Links/1/Hash/Links/1/Hash/Links/0/Hash")
+            )
+            .unwrap(),
+            Ipld::Bytes(b"aaaa".to_vec())
+        );
+        assert_eq!(
+            resolve(&blocks, DagPath::new(&roots[1], "link")).unwrap(),
+            Ipld::Null
+        );
+    }
+
+    #[test]
+    fn test_hash_mismatch() {
+        let mut car = BASIC.to_vec();
+        // The last byte of the data of the last block.
+        *car.last_mut().unwrap() = 0x02;
+        load_car::<DefaultParams, _, _>(&car[..], &mut HashSet::new(), true)
+            .expect_err("should have failed on data that doesn't match its cid")
+            .downcast::<InvalidMultihash>()
+            .unwrap();
+        let mut blocks = HashSet::<IpldBlock>::new();
+        load_car(&car[..], &mut blocks, false).unwrap();
+        assert_eq!(blocks.len(), 3);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut reader = CarReader::new(&BASIC[..BASIC.len() - 1]).unwrap();
+        reader.next().unwrap().unwrap();
+        reader.next().unwrap().unwrap();
+        let err = reader
+            .next()
+            .unwrap()
+            .expect_err("should have failed on a truncated section")
+            .downcast::<TruncatedSection>()
+            .unwrap();
+        assert_eq!(err.expected, err.read + 1);
+        assert!(reader.next().is_none());
+
+        CarReader::new(&[][..])
+            .err()
+            .expect("should have failed on an empty file")
+            .downcast::<TruncatedSection>()
+            .unwrap();
+        CarReader::new(&[0x80][..])
+            .err()
+            .expect("should have failed in the middle of a varint")
+            .downcast::<TruncatedSection>()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_invalid_varint() {
+        CarReader::new(&[0xff; 11][..])
+            .err()
+            .expect("should have failed on a varint longer than 64 bits")
+            .downcast::<InvalidVarint>()
+            .unwrap();
+        CarReader::new(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02][..])
+            .err()
+            .expect("should have failed on a varint that overflows")
+            .downcast::<InvalidVarint>()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let car = |header: Ipld| {
+            let header = DagCborCodec.encode(&header).unwrap();
+            let mut car = vec![header.len() as u8];
+            car.extend(header);
+            CarReader::new(&car[..]).err().expect("should have failed")
+        };
+        let err = car(ipld!({ "roots": [], "version": 2 }))
+            .downcast::<UnsupportedCarVersion>()
+            .unwrap();
+        assert_eq!(err.0, 2);
+        car(ipld!({ "roots": [] }))
+            .downcast::<InvalidCarHeader>()
+            .unwrap();
+        car(ipld!({ "roots": ["not a link"], "version": 1 }))
+            .downcast::<InvalidCarHeader>()
+            .unwrap();
+        car(ipld!([1])).downcast::<InvalidCarHeader>().unwrap();
+    }
+
+    #[test]
+    fn test_write() {
+        let mut car = CarWriter::new(Vec::new(), vec![root()]).unwrap();
+        for block in CarReader::new(BASIC).unwrap() {
+            let (cid, data) = block.unwrap();
+            car.write(&cid, &data).unwrap();
+        }
+        assert_eq!(car.into_inner(), BASIC);
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value).unwrap();
+            assert_eq!(read_varint(&mut &bytes[..]).unwrap(), Some(value));
+        }
+    }
+
+    /// A dag whose leaf is linked twice, returns the root and the blocks.
+    fn dag() -> (Cid, HashSet<IpldBlock>) {
+        let leaf = IpldBlock::encode(RawCodec, Code::Sha2_256, &b"leaf"[..]).unwrap();
+        let a = IpldBlock::encode(
+            DagCborCodec,
+            Code::Sha2_256,
+            &ipld!({ "name": "a", "leaf": leaf.cid() }),
+        )
+        .unwrap();
+        let b = IpldBlock::encode(
+            IpldCodec::DagJson,
+            Code::Sha2_256,
+            &ipld!({ "name": "b", "a": a.cid(), "leaf": leaf.cid() }),
+        )
+        .unwrap();
+        let root = IpldBlock::encode(
+            DagCborCodec,
+            Code::Sha2_256,
+            &ipld!({ "a": a.cid(), "b": b.cid() }),
+        )
+        .unwrap();
+        (*root.cid(), [leaf, a, b, root].into_iter().collect())
+    }
+
+    #[test]
+    fn test_export_car() {
+        let (root, blocks) = dag();
+        let car = export_car(&root, |cid| blocks.get(cid).cloned(), Vec::new(), false).unwrap();
+        let again = export_car(&root, |cid| blocks.get(cid).cloned(), Vec::new(), false).unwrap();
+        assert_eq!(car, again);
+
+        let reader = CarReader::new(&car[..]).unwrap();
+        assert_eq!(reader.header().roots, [root]);
+        let names: Vec<_> = reader
+            .map(|block| {
+                let (cid, _) = block.unwrap();
+                match blocks.get(&cid).unwrap().ipld().unwrap() {
+                    Ipld::Bytes(bytes) => String::from_utf8(bytes).unwrap(),
+                    ipld => match ipld.take("name") {
+                        Ok(Ipld::String(name)) => name,
+                        _ => "root".into(),
+                    },
+                }
+            })
+            .collect();
+        assert_eq!(names, ["root", "a", "leaf", "b"]);
+
+        let mut imported = HashSet::new();
+        assert_eq!(load_car(&car[..], &mut imported, true).unwrap(), [root]);
+        assert_eq!(imported, blocks);
+        for path in ["a/name", "a/leaf", "b/a/leaf", "b/name"] {
+            assert_eq!(
+                resolve(&imported, DagPath::new(&root, path)).unwrap(),
+                resolve(&blocks, DagPath::new(&root, path)).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_export_inline() {
+        let leaf = IpldBlock::encode(RawCodec, Code::Sha2_256, &b"leaf"[..]).unwrap();
+        let data = DagCborCodec.encode(&ipld!({ "leaf": leaf.cid() })).unwrap();
+        let inline = Cid::new_v1(0x71, crate::Multihash::wrap(0x00, &data).unwrap());
+        let root =
+            IpldBlock::encode(DagCborCodec, Code::Sha2_256, &ipld!({ "inline": inline })).unwrap();
+        // The inline block isn't stored.
+        let blocks: HashSet<_> = [leaf.clone(), root.clone()].into_iter().collect();
+
+        let car = export_car(
+            root.cid(),
+            |cid| blocks.get(cid).cloned(),
+            Vec::new(),
+            false,
+        )
+        .unwrap();
+        let cids: Vec<_> = CarReader::new(&car[..])
+            .unwrap()
+            .map(|block| block.unwrap().0)
+            .collect();
+        assert_eq!(cids, [*root.cid(), inline, *leaf.cid()]);
+
+        let mut imported = HashSet::<IpldBlock>::new();
+        load_car(&car[..], &mut imported, true).unwrap();
+        assert_eq!(imported.len(), 3);
+        assert_eq!(
+            resolve(&imported, DagPath::new(root.cid(), "inline/leaf")).unwrap(),
+            Ipld::Bytes(b"leaf".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_export_missing() {
+        let (root, mut blocks) = dag();
+        let leaf = Cid::new_v1(0x55, Code::Sha2_256.digest(b"leaf"));
+        assert!(blocks.remove(&leaf));
+        let err = export_car(&root, |cid| blocks.get(cid).cloned(), Vec::new(), false)
+            .expect_err("should have failed on a missing block")
+            .downcast::<BlockNotFound>()
+            .unwrap();
+        assert_eq!(err.0, leaf);
+
+        let car = export_car(&root, |cid| blocks.get(cid).cloned(), Vec::new(), true).unwrap();
+        let mut imported = HashSet::new();
+        load_car(&car[..], &mut imported, true).unwrap();
+        assert_eq!(imported, blocks);
+    }
+}
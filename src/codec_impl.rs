@@ -2,7 +2,7 @@
 #[cfg(feature = "dag-cbor")]
 use crate::cbor::DagCborCodec;
 use crate::cid::Cid;
-use crate::codec::{Codec, Decode, Encode, References};
+use crate::codec::{Codec, Decode, Encode, Multicodec, References};
 use crate::error::{Result, UnsupportedCodec};
 use crate::ipld::Ipld;
 #[cfg(feature = "dag-json")]
@@ -11,10 +11,14 @@ use crate::json::DagJsonCodec;
 use crate::pb::DagPbCodec;
 use crate::raw::RawCodec;
 use core::convert::TryFrom;
-use std::io::{Read, Seek, Write};
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Seek, Write};
+use std::sync::{Arc, PoisonError, RwLock};
+use thiserror::Error;
 
 /// Default codecs.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum IpldCodec {
     /// Raw codec.
     Raw,
@@ -27,39 +31,168 @@ pub enum IpldCodec {
     /// Protobuf codec.
     #[cfg(feature = "dag-pb")]
     DagPb,
+    /// A codec added with [`register_codec`].
+    Registered(RegisteredCodec),
+}
+
+/// A codec added with [`register_codec`].
+///
+/// It's only obtained by converting a registered code into an [`IpldCodec`], so it always names a
+/// codec that was registered and isn't built in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegisteredCodec(u64);
+
+impl RegisteredCodec {
+    /// Returns the multicodec code.
+    pub fn code(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The code belongs to a codec built into [`IpldCodec`].
+#[derive(Clone, Copy, Debug, Error)]
+#[error("Codec {0:#x} is built in.")]
+pub struct AlreadyBuiltIn(pub u64);
+
+/// An object-safe codec of `Ipld`, which can be added with [`register_codec`].
+///
+/// Every [`Codec`] that encodes and decodes `Ipld` is one.
+pub trait DynCodec: Send + Sync {
+    /// Encodes `Ipld` into the bytes of a block.
+    fn encode_ipld(&self, ipld: &Ipld) -> Result<Vec<u8>>;
+
+    /// Decodes `Ipld` from the bytes of a block.
+    fn decode_ipld(&self, bytes: &[u8]) -> Result<Ipld>;
+}
+
+impl<C: Codec> DynCodec for C
+where
+    Ipld: Encode<C> + Decode<C>,
+{
+    fn encode_ipld(&self, ipld: &Ipld) -> Result<Vec<u8>> {
+        self.encode(ipld)
+    }
+
+    fn decode_ipld(&self, bytes: &[u8]) -> Result<Ipld> {
+        self.decode(bytes)
+    }
+}
+
+static REGISTRY: RwLock<BTreeMap<u64, Arc<dyn DynCodec>>> = RwLock::new(BTreeMap::new());
+
+/// Adds a codec for the given multicodec code, e.g. one in the private-use range, replacing the
+/// codec registered for it before.
+///
+/// [`IpldCodec`] falls back to the registered codecs for codes that aren't built in, so stores
+/// with the [`DefaultParams`](crate::DefaultParams) read and write blocks of these codes. The
+/// codes of the built-in codecs, those whose features are enabled, can't be registered.
+pub fn register_codec(
+    code: u64,
+    codec: Box<dyn DynCodec>,
+) -> core::result::Result<(), AlreadyBuiltIn> {
+    if built_in(code).is_some() {
+        return Err(AlreadyBuiltIn(code));
+    }
+    REGISTRY
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(code, codec.into());
+    Ok(())
+}
+
+/// Returns the built-in codec of the given code.
+fn built_in(code: u64) -> Option<IpldCodec> {
+    match Multicodec::try_from(code).ok()? {
+        Multicodec::Raw => Some(IpldCodec::Raw),
+        #[cfg(feature = "dag-cbor")]
+        Multicodec::DagCbor => Some(IpldCodec::DagCbor),
+        #[cfg(feature = "dag-json")]
+        Multicodec::DagJson => Some(IpldCodec::DagJson),
+        #[cfg(feature = "dag-pb")]
+        Multicodec::DagPb => Some(IpldCodec::DagPb),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Returns the codec registered for the given code.
+fn registered(code: u64) -> core::result::Result<Arc<dyn DynCodec>, UnsupportedCodec> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&code)
+        .cloned()
+        .ok_or(UnsupportedCodec(code))
 }
 
 impl TryFrom<u64> for IpldCodec {
     type Error = UnsupportedCodec;
 
     fn try_from(ccode: u64) -> core::result::Result<Self, Self::Error> {
-        Ok(match ccode {
-            0x55 => Self::Raw,
-            #[cfg(feature = "dag-cbor")]
-            0x71 => Self::DagCbor,
-            #[cfg(feature = "dag-json")]
-            0x0129 => Self::DagJson,
-            #[cfg(feature = "dag-pb")]
-            0x70 => Self::DagPb,
-            _ => return Err(UnsupportedCodec(ccode)),
-        })
+        if let Some(codec) = built_in(ccode) {
+            return Ok(codec);
+        }
+        registered(ccode)?;
+        Ok(Self::Registered(RegisteredCodec(ccode)))
     }
 }
 
 impl From<IpldCodec> for u64 {
     fn from(mc: IpldCodec) -> Self {
         match mc {
-            IpldCodec::Raw => 0x55,
+            IpldCodec::Raw => Multicodec::Raw.code(),
             #[cfg(feature = "dag-cbor")]
-            IpldCodec::DagCbor => 0x71,
+            IpldCodec::DagCbor => Multicodec::DagCbor.code(),
             #[cfg(feature = "dag-json")]
-            IpldCodec::DagJson => 0x0129,
+            IpldCodec::DagJson => Multicodec::DagJson.code(),
             #[cfg(feature = "dag-pb")]
-            IpldCodec::DagPb => 0x70,
+            IpldCodec::DagPb => Multicodec::DagPb.code(),
+            IpldCodec::Registered(codec) => codec.code(),
         }
     }
 }
 
+/// Codec helpers for CIDs.
+pub trait CidCodecExt {
+    /// Returns the codec of the block the CID points to, if it's one defined by IPLD.
+    fn multicodec(&self) -> core::result::Result<Multicodec, UnsupportedCodec>;
+
+    /// Whether the CID points to a raw block.
+    fn is_raw(&self) -> bool;
+
+    /// Whether the CID points to a DAG-CBOR block.
+    fn is_dag_cbor(&self) -> bool;
+
+    /// Whether the CID points to a DAG-JSON block.
+    fn is_dag_json(&self) -> bool;
+
+    /// Whether the CID points to a DAG-PB block.
+    fn is_dag_pb(&self) -> bool;
+}
+
+// The checks use `Multicodec`, so that they work whether or not a codec's feature is enabled.
+impl CidCodecExt for Cid {
+    fn multicodec(&self) -> core::result::Result<Multicodec, UnsupportedCodec> {
+        Multicodec::try_from(self.codec())
+    }
+
+    fn is_raw(&self) -> bool {
+        self.codec() == Multicodec::Raw.code()
+    }
+
+    fn is_dag_cbor(&self) -> bool {
+        self.codec() == Multicodec::DagCbor.code()
+    }
+
+    fn is_dag_json(&self) -> bool {
+        self.codec() == Multicodec::DagJson.code()
+    }
+
+    fn is_dag_pb(&self) -> bool {
+        self.codec() == Multicodec::DagPb.code()
+    }
+}
+
 impl From<RawCodec> for IpldCodec {
     fn from(_: RawCodec) -> Self {
         Self::Raw
@@ -108,7 +241,21 @@ impl From<IpldCodec> for DagPbCodec {
     }
 }
 
-impl Codec for IpldCodec {}
+impl Codec for IpldCodec {
+    fn decode<T: Decode<Self>>(&self, bytes: &[u8]) -> Result<T> {
+        let mut r = Cursor::new(bytes);
+        let value = T::decode(*self, &mut r)?;
+        // Every byte of a block is covered by its hash, padding is corruption.
+        #[cfg(feature = "dag-cbor")]
+        if *self == Self::DagCbor {
+            let remaining = bytes.len() - r.position() as usize;
+            if remaining > 0 {
+                return Err(crate::cbor::error::TrailingBytes(remaining).into());
+            }
+        }
+        Ok(value)
+    }
+}
 
 impl Encode<IpldCodec> for Ipld {
     fn encode<W: Write>(&self, c: IpldCodec, w: &mut W) -> Result<()> {
@@ -120,6 +267,9 @@ impl Encode<IpldCodec> for Ipld {
             IpldCodec::DagJson => self.encode(DagJsonCodec, w)?,
             #[cfg(feature = "dag-pb")]
             IpldCodec::DagPb => self.encode(DagPbCodec, w)?,
+            IpldCodec::Registered(codec) => {
+                w.write_all(&registered(codec.code())?.encode_ipld(self)?)?
+            }
         };
         Ok(())
     }
@@ -135,6 +285,11 @@ impl Decode<IpldCodec> for Ipld {
             IpldCodec::DagJson => Self::decode(DagJsonCodec, r)?,
             #[cfg(feature = "dag-pb")]
             IpldCodec::DagPb => Self::decode(DagPbCodec, r)?,
+            IpldCodec::Registered(codec) => {
+                let mut bytes = Vec::new();
+                r.read_to_end(&mut bytes)?;
+                registered(codec.code())?.decode_ipld(&bytes)?
+            }
         })
     }
 }
@@ -157,14 +312,28 @@ impl References<IpldCodec> for Ipld {
             }
             #[cfg(feature = "dag-pb")]
             IpldCodec::DagPb => <Self as References<DagPbCodec>>::references(DagPbCodec, r, set)?,
+            IpldCodec::Registered(_) => Self::decode(c, r)?.references(set),
         };
         Ok(())
     }
 }
 
+/// Private-use codes of the tests. The registry is global to the test binary and entries can't be
+/// removed, so every test that registers a codec has a code of its own.
+#[cfg(test)]
+pub(crate) mod test_codes {
+    /// A code that no test registers.
+    pub const UNREGISTERED: u64 = 0x300000;
+    /// Registered by `codec_impl::tests::registered_codec`.
+    pub const REGISTERED_RAW: u64 = 0x300001;
+    /// Registered by `block::tests::test_custom_codec`.
+    pub const PRIVATE: u64 = 0x300002;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::codec::FixedCodec;
 
     #[test]
     fn raw_encode() {
@@ -196,6 +365,17 @@ mod tests {
         assert_eq!(result, Ipld::Bytes(vec![0x22, 0x33, 0x44]));
     }
 
+    #[cfg(feature = "dag-cbor")]
+    #[test]
+    fn dag_cbor_decode_trailing_bytes() {
+        let data = [0x43, 0x22, 0x33, 0x44, 0x00];
+        IpldCodec::DagCbor
+            .decode::<Ipld>(&data)
+            .expect_err("should have failed to decode a block with trailing bytes")
+            .downcast::<crate::cbor::error::TrailingBytes>()
+            .unwrap();
+    }
+
     #[cfg(feature = "dag-json")]
     #[test]
     fn dag_json_encode() {
@@ -215,7 +395,7 @@ mod tests {
     #[cfg(feature = "dag-pb")]
     #[test]
     fn dag_pb_encode() {
-        let mut data_map = std::collections::BTreeMap::<String, Ipld>::new();
+        let mut data_map = crate::ipld::IpldMap::new();
         data_map.insert("Data".to_string(), Ipld::Bytes(b"data".to_vec()));
         data_map.insert("Links".to_string(), Ipld::List(vec![]));
 
@@ -227,7 +407,7 @@ mod tests {
     #[cfg(feature = "dag-pb")]
     #[test]
     fn dag_pb_decode() {
-        let mut data_map = std::collections::BTreeMap::<String, Ipld>::new();
+        let mut data_map = crate::ipld::IpldMap::new();
         data_map.insert("Data".to_string(), Ipld::Bytes(b"data".to_vec()));
         data_map.insert("Links".to_string(), Ipld::List(vec![]));
         let expected = Ipld::Map(data_map);
@@ -236,4 +416,101 @@ mod tests {
         let result: Ipld = IpldCodec::DagPb.decode(&data).unwrap();
         assert_eq!(result, expected);
     }
+
+    #[cfg(all(feature = "dag-cbor", feature = "dag-json", feature = "indexmap"))]
+    #[test]
+    fn map_insertion_order() {
+        // {"b": 1, "a": 2}, which is not in canonical order.
+        let data = [0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02];
+        let result: Ipld = IpldCodec::DagCbor.decode(&data).unwrap();
+        match &result {
+            Ipld::Map(map) => assert!(map.keys().eq(["b", "a"])),
+            _ => panic!("expected a map"),
+        }
+        assert_eq!(result.get("a").unwrap(), &Ipld::Integer(2));
+
+        // Encoding still sorts the keys.
+        let encoded = IpldCodec::DagCbor.encode(&result).unwrap();
+        assert_eq!(encoded, [0xa2, 0x61, 0x61, 0x02, 0x61, 0x62, 0x01]);
+        let json = IpldCodec::DagJson.encode(&result).unwrap();
+        assert_eq!(json, br#"{"a":2,"b":1}"#);
+    }
+
+    #[test]
+    fn registered_codec() {
+        // Raw bytes under a private code, as the raw codec is a `Codec` of `Ipld`.
+        let code = test_codes::REGISTERED_RAW;
+        IpldCodec::try_from(code).unwrap_err();
+        register_codec(code, Box::new(RawCodec)).unwrap();
+        let codec = IpldCodec::try_from(code).unwrap();
+        assert!(matches!(codec, IpldCodec::Registered(registered) if registered.code() == code));
+        assert_eq!(u64::from(codec), code);
+
+        let data = Ipld::Bytes(vec![0x22, 0x33, 0x44]);
+        let bytes = codec.encode(&data).unwrap();
+        assert_eq!(bytes, [0x22, 0x33, 0x44]);
+        assert_eq!(codec.decode::<Ipld>(&bytes).unwrap(), data);
+        codec
+            .encode(&Ipld::Null)
+            .expect_err("should have failed to encode null as raw");
+
+        // Built-in codecs can't be replaced.
+        let err = register_codec(RawCodec::CODE, Box::new(RawCodec)).unwrap_err();
+        assert_eq!(err.0, RawCodec::CODE);
+        assert_eq!(IpldCodec::try_from(RawCodec::CODE).unwrap(), IpldCodec::Raw);
+    }
+
+    #[test]
+    fn codes() {
+        let mut codecs = vec![IpldCodec::Raw];
+        #[cfg(feature = "dag-cbor")]
+        codecs.push(IpldCodec::DagCbor);
+        #[cfg(feature = "dag-json")]
+        codecs.push(IpldCodec::DagJson);
+        #[cfg(feature = "dag-pb")]
+        codecs.push(IpldCodec::DagPb);
+        for codec in codecs {
+            let code: u64 = codec.into();
+            assert_eq!(IpldCodec::try_from(code).unwrap(), codec);
+        }
+        let err = IpldCodec::try_from(test_codes::UNREGISTERED).unwrap_err();
+        assert_eq!(err.0, test_codes::UNREGISTERED);
+
+        assert_eq!(
+            IpldCodec::try_from(RawCodec::CODE).unwrap(),
+            RawCodec.into()
+        );
+        #[cfg(feature = "dag-cbor")]
+        assert_eq!(u64::from(IpldCodec::DagCbor), DagCborCodec::CODE);
+        #[cfg(feature = "dag-json")]
+        assert_eq!(u64::from(IpldCodec::DagJson), DagJsonCodec::CODE);
+        #[cfg(feature = "dag-pb")]
+        assert_eq!(u64::from(IpldCodec::DagPb), DagPbCodec::CODE);
+    }
+
+    #[test]
+    fn cid_codec_ext() {
+        use crate::multihash::{Code, MultihashDigest};
+
+        let digest = Code::Sha2_256.digest(b"cid");
+        let raw = Cid::new_v1(0x55, digest);
+        assert_eq!(raw.multicodec().unwrap(), Multicodec::Raw);
+        assert!(raw.is_raw());
+        assert!(!raw.is_dag_cbor());
+
+        let cbor = Cid::new_v1(0x71, digest);
+        assert!(cbor.is_dag_cbor());
+        assert!(!cbor.is_raw() && !cbor.is_dag_json() && !cbor.is_dag_pb());
+        assert_eq!(cbor.multicodec().unwrap(), Multicodec::DagCbor);
+        // Whether or not the codec's feature is enabled.
+        let json = Cid::new_v1(0x0129, digest);
+        assert!(json.is_dag_json());
+        assert_eq!(json.multicodec().unwrap(), Multicodec::DagJson);
+        let pb = Cid::new_v0(digest).unwrap();
+        assert!(pb.is_dag_pb());
+        assert_eq!(pb.multicodec().unwrap(), Multicodec::DagPb);
+
+        let unknown = Cid::new_v1(0x300000, digest);
+        assert_eq!(unknown.multicodec().unwrap_err().0, 0x300000);
+    }
 }
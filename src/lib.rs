@@ -3,6 +3,8 @@
 #![deny(warnings)]
 
 pub mod block;
+#[cfg(feature = "dag-cbor")]
+pub mod car;
 pub mod codec_impl;
 pub mod path;
 pub mod prelude;
@@ -21,7 +23,7 @@ pub use libipld_pb as pb;
 
 pub use block::Block;
 pub use cid::Cid;
-pub use codec_impl::IpldCodec;
+pub use codec_impl::{register_codec, DynCodec, IpldCodec};
 pub use error::Result;
 pub use ipld::Ipld;
 pub use link::Link;
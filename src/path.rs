@@ -1,5 +1,11 @@
 //! Path
+use crate::block::Block;
 use crate::cid::Cid;
+use crate::cid_string::{cid_to_string_base32, parse_cid_any_base};
+use crate::codec::Decode;
+use crate::error::{BlockNotFound, Result};
+use crate::ipld::Ipld;
+use crate::store::StoreParams;
 
 /// Represents a path in an ipld dag.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
@@ -45,15 +51,17 @@ impl From<String> for Path {
     }
 }
 
-impl ToString for Path {
-    fn to_string(&self) -> String {
-        let mut path = "".to_string();
-        for seg in &self.0 {
-            path.push_str(seg.as_str());
-            path.push('/');
+impl core::fmt::Display for Path {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let mut segments = self.0.iter();
+        if let Some(seg) = segments.next() {
+            f.write_str(seg)?;
         }
-        path.pop();
-        path
+        for seg in segments {
+            f.write_str("/")?;
+            f.write_str(seg)?;
+        }
+        Ok(())
     }
 }
 
@@ -78,12 +86,62 @@ impl<'a> DagPath<'a> {
     }
 }
 
+/// Writes `/ipfs/<cid>/<path>`, the CID in its canonical string form.
+impl core::fmt::Display for DagPath<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "/ipfs/{}", cid_to_string_base32(self.0))?;
+        if self.1 != Path::default() {
+            write!(f, "/{}", self.1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the root and path of a dag path, `/ipfs/<cid>/<path>` or `<cid>/<path>`.
+///
+/// The CID may be in any multibase, see [`parse_cid_any_base`].
+pub fn parse_dag_path(s: &str) -> Result<(Cid, Path)> {
+    let s = s.strip_prefix("/ipfs/").unwrap_or(s);
+    let (cid, path) = s.split_once('/').unwrap_or((s, ""));
+    Ok((parse_cid_any_base(cid)?, Path::from(path)))
+}
+
 impl<'a> From<&'a Cid> for DagPath<'a> {
     fn from(cid: &'a Cid) -> Self {
         Self(cid, Default::default())
     }
 }
 
+/// Resolves a dag path, following links into the blocks returned by `get`.
+///
+/// Every block is decoded with the codec its cid names. Blocks inlined in identity cids are read
+/// from the cid, see [`Block::inline`], so `get` is only called for the others. A path that ends
+/// on a link resolves to the linked block.
+pub fn resolve<S, F>(path: &DagPath, mut get: F) -> Result<Ipld>
+where
+    S: StoreParams,
+    Ipld: Decode<S::Codecs>,
+    F: FnMut(&Cid) -> Option<Block<S>>,
+{
+    let mut load = |cid: &Cid| {
+        Block::<S>::inline(cid)
+            .or_else(|| get(cid))
+            .ok_or(BlockNotFound(*cid))?
+            .ipld()
+    };
+    let mut ipld = load(path.root())?;
+    for segment in path.path().iter() {
+        if let Ipld::Link(cid) = ipld {
+            ipld = load(&cid)?;
+        }
+        ipld = ipld.take(segment)?;
+    }
+    match ipld {
+        Ipld::Link(cid) => load(&cid),
+        ipld => Ok(ipld),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,4 +175,75 @@ mod tests {
     fn test_to_string() {
         assert_eq!(Path::from(vec!["0", "foo", "2"]).to_string(), "0/foo/2");
     }
+
+    #[cfg(feature = "dag-cbor")]
+    #[test]
+    fn test_resolve() {
+        use crate::cbor::DagCborCodec;
+        use crate::multihash::{Code, MultihashDigest};
+        use crate::store::DefaultParams;
+        use crate::{ipld, Multihash};
+
+        type IpldBlock = Block<DefaultParams>;
+
+        let leaf = IpldBlock::encode(DagCborCodec, Code::Sha2_256, &ipld!([1, 2])).unwrap();
+        let inline = Cid::new_v1(
+            0x71,
+            Multihash::wrap(0x00, &[0x63, 0x61, 0x62, 0x63]).unwrap(),
+        );
+        let missing = Cid::new_v1(0x71, Code::Sha2_256.digest(b"missing"));
+        let root = IpldBlock::encode(
+            DagCborCodec,
+            Code::Sha2_256,
+            &ipld!({ "leaf": leaf.cid(), "inline": inline, "missing": missing }),
+        )
+        .unwrap();
+        let get = |cid: &Cid| [&leaf, &root].into_iter().find(|b| b.cid() == cid).cloned();
+
+        let path = DagPath::new(root.cid(), "leaf/1");
+        assert_eq!(resolve(&path, get).unwrap(), ipld!(2));
+        let path = DagPath::new(root.cid(), "leaf");
+        assert_eq!(resolve(&path, get).unwrap(), ipld!([1, 2]));
+        let path = DagPath::new(root.cid(), "inline");
+        assert_eq!(resolve(&path, get).unwrap(), ipld!("abc"));
+        let path = DagPath::new(root.cid(), "missing/0");
+        let err = resolve(&path, get)
+            .expect_err("should have failed on a missing block")
+            .downcast::<BlockNotFound>()
+            .unwrap();
+        assert_eq!(err.0, missing);
+        let path = DagPath::new(root.cid(), "leaf/2");
+        resolve(&path, get).expect_err("should have failed on a missing index");
+    }
+
+    #[test]
+    fn test_dag_path_string() {
+        use crate::multihash::{Code, MultihashDigest};
+
+        let digest = Code::Sha2_256.digest(b"path");
+        for cid in [Cid::new_v1(0x71, digest), Cid::new_v0(digest).unwrap()] {
+            let s = DagPath::new(&cid, "a/0/b").to_string();
+            assert_eq!(s, format!("/ipfs/{}/a/0/b", cid));
+            assert_eq!(parse_dag_path(&s).unwrap(), (cid, Path::from("a/0/b")));
+            assert_eq!(DagPath::from(&cid).to_string(), format!("/ipfs/{}", cid));
+            assert_eq!(
+                parse_dag_path(&cid.to_string()).unwrap(),
+                (cid, Path::default())
+            );
+        }
+
+        let cid = Cid::new_v1(0x71, digest);
+        let base58 = cid
+            .to_string_of_base(crate::multibase::Base::Base58Btc)
+            .unwrap();
+        let (parsed, path) = parse_dag_path(&format!("{}/a/", base58)).unwrap();
+        assert_eq!(parsed, cid);
+        assert_eq!(path, Path::from("a"));
+
+        parse_dag_path("/ipfs/").expect_err("should have failed without a CID");
+        parse_dag_path("/ipfs/!abc/a")
+            .expect_err("should have failed on an unknown multibase")
+            .downcast::<crate::error::UnknownMultibase>()
+            .unwrap();
+    }
 }
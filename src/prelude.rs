@@ -1,3 +1,6 @@
 //! Prelude
-pub use crate::codec::{Codec, Decode, Encode, References};
+#[cfg(feature = "dag-cbor")]
+pub use crate::cbor::IpldCborExt;
+pub use crate::codec::{Codec, Decode, Encode, FixedCodec, Multicodec, References};
+pub use crate::codec_impl::CidCodecExt;
 pub use crate::store::StoreParams;
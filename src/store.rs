@@ -24,6 +24,11 @@ pub trait StoreParams: std::fmt::Debug + Clone + Send + Sync + Unpin + 'static {
     /// The multihash type of the store.
     type Hashes: MultihashDigest<64>;
     /// The codec type of the store.
+    ///
+    /// Blocks are decoded with the codec their CID names, so this decides which codecs a store
+    /// can read. [`IpldCodec`](crate::IpldCodec) reads the built-in codecs and the ones added with
+    /// [`register_codec`](crate::register_codec). A store can also use an enum of its own that
+    /// converts from and to the codes it supports.
     type Codecs: Codec;
     /// The maximum block size supported by the store.
     const MAX_BLOCK_SIZE: usize;
@@ -0,0 +1,203 @@
+//! Runs codec fixtures in the layout of the IPLD codec fixtures,
+//! <https://github.com/ipld/codec-fixtures>.
+//!
+//! Every fixture is a directory with one file per encoding of the same data, named
+//! `<cid>.<codec>`, where the CID may be a CIDv0 for DAG-PB. Each file has to decode, re-encode to
+//! the same bytes and hash to its CID, and all files of a fixture have to decode to the same data.
+//!
+//! The fixtures in `tests/fixtures` are written in this repository. Apart from the well-known CIDs
+//! of empty and UnixFS nodes, they only show that the codecs agree with each other, not that they
+//! agree with other implementations. That is what the upstream fixtures in `tests/codec-fixtures`
+//! are for, a copy of the upstream `fixtures` directory, or a subset of it, along with a `COMMIT`
+//! file naming the upstream commit it was copied from.
+#![cfg(all(feature = "dag-cbor", feature = "dag-json", feature = "dag-pb"))]
+use libipld::cid_string::parse_cid_any_base;
+use libipld::codec::Codec;
+use libipld::multihash::{Code, MultihashDigest};
+use libipld::{Cid, Ipld, IpldCodec};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+/// Returns the codec of a fixture file extension.
+fn codec(extension: &str) -> Option<IpldCodec> {
+    match extension {
+        "dag-cbor" => Some(IpldCodec::DagCbor),
+        "dag-json" => Some(IpldCodec::DagJson),
+        "dag-pb" => Some(IpldCodec::DagPb),
+        "raw" => Some(IpldCodec::Raw),
+        _ => None,
+    }
+}
+
+/// Checks a single fixture file, returns the decoded data.
+fn run_file(path: &Path) -> Result<Ipld, String> {
+    let name = path.file_name().unwrap().to_string_lossy();
+    let (cid, extension) = name
+        .split_once('.')
+        .ok_or_else(|| "expected a file named <cid>.<codec>".to_string())?;
+    let expected = parse_cid_any_base(cid).map_err(|err| format!("invalid CID: {}", err))?;
+    let codec = codec(extension).ok_or_else(|| format!("unknown codec {}", extension))?;
+    if u64::from(codec) != expected.codec() {
+        return Err(format!("the CID has codec {:#x}", expected.codec()));
+    }
+    let bytes = fs::read(path).map_err(|err| err.to_string())?;
+
+    let ipld: Ipld = codec
+        .decode(&bytes)
+        .map_err(|err| format!("failed to decode: {}", err))?;
+    let encoded = codec
+        .encode(&ipld)
+        .map_err(|err| format!("failed to encode: {}", err))?;
+    if encoded != bytes {
+        return Err(format!(
+            "re-encoded to different bytes\n  expected {}\n  actual   {}",
+            hex(&bytes),
+            hex(&encoded)
+        ));
+    }
+    let hash = Code::try_from(expected.hash().code())
+        .map_err(|err| format!("unsupported hash: {}", err))?;
+    let cid = Cid::new(expected.version(), codec.into(), hash.digest(&encoded))
+        .map_err(|err| format!("invalid CID: {}", err))?;
+    if cid != expected {
+        return Err(format!("hashes to {}", cid));
+    }
+    Ok(ipld)
+}
+
+/// Checks every file of a fixture, returns the failures.
+fn run_fixture(dir: &Path) -> Vec<String> {
+    let fixture = dir.file_name().unwrap().to_string_lossy();
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+
+    let mut failures = Vec::new();
+    let mut decoded: Vec<(String, Ipld)> = Vec::new();
+    for path in files {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        match run_file(&path) {
+            Ok(ipld) => decoded.push((name, ipld)),
+            Err(err) => failures.push(format!("{}/{}: {}", fixture, name, err)),
+        }
+    }
+    if let Some(((first, expected), rest)) = decoded.split_first() {
+        for (name, ipld) in rest {
+            if ipld != expected {
+                failures.push(format!(
+                    "{}/{}: decodes to {:?}, but {} decodes to {:?}",
+                    fixture, name, ipld, first, expected
+                ));
+            }
+        }
+    }
+    failures
+}
+
+/// Runs all fixtures in a directory, returns the number of fixtures and the failures.
+fn run_fixtures(dir: &Path) -> (usize, Vec<String>) {
+    let mut fixtures: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    fixtures.sort();
+    let failures = fixtures.iter().flat_map(|dir| run_fixture(dir)).collect();
+    (fixtures.len(), failures)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn local_fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let (count, failures) = run_fixtures(&dir);
+    assert!(count > 0, "no fixtures in {}", dir.display());
+    assert!(
+        failures.is_empty(),
+        "{} fixture files failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+#[ignore = "the upstream codec fixtures aren't vendored in tests/codec-fixtures yet"]
+fn upstream_fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/codec-fixtures");
+    let commit = fs::read_to_string(dir.join("COMMIT"))
+        .unwrap_or_else(|err| panic!("no upstream commit in {}/COMMIT: {}", dir.display(), err));
+    let (count, failures) = run_fixtures(&dir);
+    assert!(count > 0, "no fixtures in {}", dir.display());
+    assert!(
+        failures.is_empty(),
+        "{} fixture files of ipld/codec-fixtures@{} failed:\n{}",
+        failures.len(),
+        commit.trim(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn fixture_failures() {
+    let dir = std::env::temp_dir().join(format!("libipld-fixtures-{}", std::process::id()));
+    let fixture = dir.join("broken");
+    fs::create_dir_all(&fixture).unwrap();
+    let empty_map = "bafyreigbtj4x7ip5legnfznufuopl4sg4knzc2cof6duas4b3q2fy6swua";
+    let empty_list = "bafyreidwx2fvfdiaox32v2mnn6sxu3j4qoxeqcuenhtgrv5qv6litfnmoe";
+    let json_empty_map = "baguqeeraiqjw7i2vwntyuekgvulpp2det2kpwt6cd7tx5ayqybqpmhfk76fa";
+    let json_empty_list = "baguqeeraj5j43immfovaya2uxnpzupwl4xwrfk2nryi3vbz4f4irmeqcxfcq";
+    // The file name, its contents and the expected failure.
+    let files: [(String, &[u8], Option<&str>); 7] = [
+        (format!("{}.dag-cbor", empty_map), b"\xa0", None),
+        (
+            format!("{}.dag-cbor", empty_list),
+            b"\xa0",
+            Some("hashes to"),
+        ),
+        (
+            format!("{}.dag-json", empty_map),
+            b"{}",
+            Some("the CID has codec 0x71"),
+        ),
+        (
+            format!("{}.dag-json", json_empty_map),
+            b"{ }",
+            Some("re-encoded to different bytes"),
+        ),
+        (
+            format!("{}.dag-json", json_empty_list),
+            b"[]",
+            Some("decodes to []"),
+        ),
+        ("not-a-cid.dag-cbor".into(), b"\xa0", Some("invalid CID")),
+        // The CIDv0 of the empty UnixFS directory, the empty node hashes to another CIDv0.
+        (
+            "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn.dag-pb".into(),
+            b"",
+            Some("hashes to QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n"),
+        ),
+    ];
+    for (name, bytes, _) in &files {
+        fs::write(fixture.join(name), bytes).unwrap();
+    }
+
+    let (count, failures) = run_fixtures(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(failures.len(), 6, "{:#?}", failures);
+    for (name, _, expected) in &files {
+        let prefix = format!("broken/{}: ", name);
+        let failure = failures.iter().find(|failure| failure.starts_with(&prefix));
+        match (failure, expected) {
+            (None, None) => {}
+            (Some(failure), Some(expected)) => assert!(failure.contains(expected), "{}", failure),
+            _ => panic!("{}: expected {:?}, got {:?}", name, expected, failure),
+        }
+    }
+}